@@ -0,0 +1,407 @@
+//! Highlight anchoring against the book text
+//!
+//! The reader anchors highlights by DOM path and offset, which breaks whenever
+//! the HTML is regenerated. Each highlight therefore also keeps text
+//! selectors: the quote with a little context either side, and its char range
+//! in the plain text from `text::html_to_book_text`. After the HTML changes,
+//! every highlight is matched back into the new text, exactly if possible and
+//! otherwise by the ends of its quote or by its context. Highlights that
+//! can't be placed are flagged as orphaned rather than dropped.
+
+use crate::db::postgres::DbError;
+use crate::db::{self, Highlight, HighlightFilter, NewHighlight};
+use crate::locator;
+use crate::text::{char_slice, BookText};
+use crate::types::{HighlightId, Tag};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use std::cmp::Reverse;
+
+/// Context kept either side of a quote
+pub const CONTEXT_CHARS: usize = 32;
+
+/// Length of the quote ends and context used for fuzzy matching
+const FUZZY_CHARS: usize = 16;
+
+/// Where a highlight sits in the book text, with the text around it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextSelectors {
+    pub prefix: String,
+    pub suffix: String,
+    /// Char offsets into the book text
+    pub start: usize,
+    pub end: usize,
+}
+
+impl TextSelectors {
+    /// Selectors for the char range `start..end`
+    pub fn at(text: &BookText, start: usize, end: usize) -> Self {
+        let end = end.min(text.char_len());
+        let start = start.min(end);
+        Self {
+            prefix: char_slice(&text.text, start.saturating_sub(CONTEXT_CHARS), start).to_string(),
+            suffix: char_slice(&text.text, end, end + CONTEXT_CHARS).to_string(),
+            start,
+            end,
+        }
+    }
+}
+
+/// Text folded for matching: whitespace runs collapsed, lowercased, and curly
+/// quotes and dashes replaced by their ASCII forms
+struct Folded {
+    text: String,
+    /// Byte offset in `text` and char offset in the source of each char
+    chars: Vec<(usize, usize)>,
+    source_len: usize,
+}
+
+const fn fold_char(c: char) -> Option<char> {
+    match c {
+        '\u{ad}' => None,
+        '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{2032}' => Some('\''),
+        '\u{201c}' | '\u{201d}' | '\u{201e}' | '\u{2033}' => Some('"'),
+        '\u{2010}'..='\u{2015}' => Some('-'),
+        c if c.is_whitespace() => Some(' '),
+        c => Some(c),
+    }
+}
+
+impl Folded {
+    fn new(s: &str) -> Self {
+        let mut folded = Self {
+            text: String::with_capacity(s.len()),
+            chars: Vec::new(),
+            source_len: 0,
+        };
+        for (offset, c) in s.chars().enumerate() {
+            folded.source_len = offset + 1;
+            let Some(c) = fold_char(c) else { continue };
+            if c == ' ' && folded.text.ends_with(' ') {
+                continue;
+            }
+            for lower in c.to_lowercase() {
+                folded.chars.push((folded.text.len(), offset));
+                folded.text.push(lower);
+            }
+        }
+        folded
+    }
+
+    /// Source char offset of the folded char starting at `byte`
+    fn source_offset(&self, byte: usize) -> usize {
+        match self.chars.binary_search_by_key(&byte, |&(b, _)| b) {
+            Ok(i) => self.chars[i].1,
+            Err(i) => self
+                .chars
+                .get(i)
+                .map_or(self.source_len, |&(_, offset)| offset),
+        }
+    }
+
+    /// Byte offsets of every occurrence of `needle`
+    fn find_all(&self, needle: &str) -> Vec<usize> {
+        if needle.is_empty() {
+            return Vec::new();
+        }
+        self.text.match_indices(needle).map(|(i, _)| i).collect()
+    }
+}
+
+fn fold(s: &str) -> String {
+    Folded::new(s).text
+}
+
+fn common_suffix_len(a: &str, b: &str) -> usize {
+    a.chars()
+        .rev()
+        .zip(b.chars().rev())
+        .take_while(|(x, y)| x == y)
+        .count()
+}
+
+fn common_prefix_len(a: &str, b: &str) -> usize {
+    a.chars().zip(b.chars()).take_while(|(x, y)| x == y).count()
+}
+
+fn first_chars(s: &str, n: usize) -> &str {
+    char_slice(s, 0, n)
+}
+
+fn last_chars(s: &str, n: usize) -> &str {
+    let len = s.chars().count();
+    char_slice(s, len.saturating_sub(n), len)
+}
+
+/// Book text folded once, for anchoring many quotes into it
+pub struct Anchorer<'a> {
+    text: &'a BookText,
+    folded: Folded,
+}
+
+impl<'a> Anchorer<'a> {
+    pub fn new(text: &'a BookText) -> Self {
+        Self {
+            text,
+            folded: Folded::new(&text.text),
+        }
+    }
+
+    /// Finds `exact` in the book text. Exact (folded) matches are ranked by how
+    /// well their surroundings match `prefix`/`suffix`, then by closeness to
+    /// `hint`; failing that, the quote's first and last words or its context
+    /// are matched with the text between allowed to have changed.
+    pub fn anchor(
+        &self,
+        exact: &str,
+        prefix: &str,
+        suffix: &str,
+        hint: Option<usize>,
+    ) -> Option<TextSelectors> {
+        let exact = fold(exact.trim());
+        if exact.is_empty() {
+            return None;
+        }
+        let folded = &self.folded;
+        let prefix = fold(prefix);
+        let suffix = fold(suffix);
+        let distance = |byte: usize| hint.map_or(0, |h| folded.source_offset(byte).abs_diff(h));
+
+        let exact_match = folded
+            .find_all(&exact)
+            .into_iter()
+            .map(|start| (start, start + exact.len()))
+            .max_by_key(|&(start, end)| {
+                let score = common_suffix_len(&folded.text[..start], &prefix)
+                    + common_prefix_len(&folded.text[end..], &suffix);
+                (score, Reverse(distance(start)))
+            });
+
+        let range = exact_match
+            .or_else(|| fuzzy_match(folded, &exact, &prefix, &suffix, &distance))
+            .map(|(start, end)| (folded.source_offset(start), folded.source_offset(end)))?;
+        Some(TextSelectors::at(self.text, range.0, range.1))
+    }
+
+    /// Matches one highlight into the text, near its previous position
+    pub fn anchor_highlight(&self, highlight: &Highlight) -> Option<TextSelectors> {
+        self.anchor(
+            &highlight.text,
+            &highlight.quote_prefix,
+            &highlight.quote_suffix,
+            highlight
+                .text_start
+                .and_then(|start| usize::try_from(start).ok()),
+        )
+    }
+}
+
+/// Anchors a single quote; use an `Anchorer` to anchor several into one book
+pub fn anchor(
+    text: &BookText,
+    exact: &str,
+    prefix: &str,
+    suffix: &str,
+    hint: Option<usize>,
+) -> Option<TextSelectors> {
+    Anchorer::new(text).anchor(exact, prefix, suffix, hint)
+}
+
+/// Matches a quote whose middle changed: its first and last `FUZZY_CHARS`, or
+/// failing that the context either side of it, with a plausible length between
+fn fuzzy_match(
+    folded: &Folded,
+    exact: &str,
+    prefix: &str,
+    suffix: &str,
+    distance: &dyn Fn(usize) -> usize,
+) -> Option<(usize, usize)> {
+    let len = exact.len();
+    let plausible = |start: usize, end: usize| end >= start + len / 2 && end <= start + len * 2;
+    let bracket = |open: &str, close: &str, inner: bool| {
+        if open.trim().chars().count() < FUZZY_CHARS / 2
+            || close.trim().chars().count() < FUZZY_CHARS / 2
+        {
+            return None;
+        }
+        let closes = folded.find_all(close);
+        folded
+            .find_all(open)
+            .into_iter()
+            .filter_map(|o| {
+                let after_open = o + open.len();
+                let c = closes.iter().copied().find(|&c| c >= after_open)?;
+                let range = if inner {
+                    (after_open, c)
+                } else {
+                    (o, c + close.len())
+                };
+                plausible(range.0, range.1).then_some(range)
+            })
+            .min_by_key(|&(start, _)| distance(start))
+    };
+
+    if exact.chars().count() >= FUZZY_CHARS * 2 {
+        let found = bracket(
+            first_chars(exact, FUZZY_CHARS),
+            last_chars(exact, FUZZY_CHARS),
+            false,
+        );
+        if found.is_some() {
+            return found;
+        }
+    }
+    let (start, end) = bracket(
+        last_chars(prefix, FUZZY_CHARS),
+        first_chars(suffix, FUZZY_CHARS),
+        true,
+    )?;
+    // The context match includes any whitespace around the quote
+    let start = start + folded.text[start..end].len() - folded.text[start..end].trim_start().len();
+    let end = start + folded.text[start..end].trim().len();
+    Some((start, end))
+}
+
+/// What re-anchoring a book's highlights did
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReanchorReport {
+    pub anchored: usize,
+    /// Anchored at a different place than before
+    pub moved: Vec<HighlightId>,
+    pub orphaned: Vec<HighlightId>,
+}
+
+/// Re-anchors everything in a book after its HTML changed: highlights by their
+/// text selectors, and the reading position and bookmarks by their locators
+pub async fn reanchor_book(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+    text: &BookText,
+) -> Result<ReanchorReport, DbError> {
+    if let Some(position) = db::get_book_position(pool, book_id).await? {
+        db::set_book_position(pool, book_id, &locator::relocate(text, &position.locator)).await?;
+    }
+    for bookmark in db::list_bookmarks(pool, book_id).await? {
+        let moved = locator::relocate(text, &bookmark.locator);
+        db::set_bookmark_locator(pool, bookmark.id.get(), &moved).await?;
+    }
+
+    let anchorer = Anchorer::new(text);
+    let mut report = ReanchorReport::default();
+    for highlight in db::list_highlights(pool, book_id, &HighlightFilter::default()).await? {
+        let selectors = anchorer.anchor_highlight(&highlight);
+        match &selectors {
+            Some(s) => {
+                report.anchored += 1;
+                let start = i64::try_from(s.start).ok();
+                if highlight.text_start.is_some() && highlight.text_start != start {
+                    report.moved.push(highlight.id);
+                }
+            }
+            None => report.orphaned.push(highlight.id),
+        }
+        db::set_highlight_selectors(pool, highlight.id.get(), selectors.as_ref()).await?;
+    }
+    if !report.orphaned.is_empty() {
+        println!(
+            "[Anchoring] Book {book_id}: {} highlights orphaned",
+            report.orphaned.len()
+        );
+    }
+    Ok(report)
+}
+
+/// Creates a highlight with its tags. `highlight.selectors` should already be
+/// set from anchoring its text, so it isn't matched into the book again.
+pub async fn create_highlight(
+    pool: &Pool<Postgres>,
+    highlight: &NewHighlight,
+    tags: &[Tag],
+) -> Result<Highlight, DbError> {
+    let created = db::create_highlight(pool, highlight).await?;
+    if tags.is_empty() {
+        return Ok(created);
+    }
+    for tag in tags {
+        db::add_highlight_tag(pool, created.id.get(), tag).await?;
+    }
+    db::get_highlight(pool, created.id.get()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::html_to_book_text;
+
+    const SCENE: &str = "<html><body><h2>Act III</h2>\
+        <p>HAMLET. To be, or not to be, that is the question: Whether \u{2019}tis nobler \
+        in the mind to suffer the slings and arrows of outrageous fortune.</p>\
+        <p>OPHELIA. Good my lord, how does your honour? To be, or not to be.</p></body></html>";
+
+    fn slice(text: &BookText, s: &TextSelectors) -> String {
+        char_slice(&text.text, s.start, s.end).to_string()
+    }
+
+    #[test]
+    fn test_anchor_picks_occurrence_by_context() {
+        let text = html_to_book_text(SCENE);
+        let anchorer = Anchorer::new(&text);
+        let first = anchorer
+            .anchor("To be, or not to be", "HAMLET. ", ", that", None)
+            .unwrap();
+        let second = anchorer
+            .anchor("To be, or not to be", "your honour? ", ".", None)
+            .unwrap();
+
+        assert!(first.start < second.start);
+        assert_eq!(slice(&text, &first), "To be, or not to be");
+        assert_eq!(slice(&text, &second), "To be, or not to be");
+        assert!(second.prefix.ends_with("your honour? "));
+        assert!(anchor(&text, "Alas, poor Yorick", "", "", None).is_none());
+    }
+
+    #[test]
+    fn test_anchor_folds_quotes_and_whitespace() {
+        let text = html_to_book_text(SCENE);
+        let found = anchor(&text, "WHETHER 'tis  nobler", "", "", None).unwrap();
+        assert_eq!(slice(&text, &found), "Whether \u{2019}tis nobler");
+    }
+
+    #[test]
+    fn test_anchor_fuzzy_after_edit() {
+        let old = html_to_book_text(SCENE);
+        let quote = "the slings and arrows of outrageous fortune";
+        let before = anchor(&old, quote, "", "", None).unwrap();
+
+        // A new edition changes a word inside the quote
+        let new = html_to_book_text(&SCENE.replace("slings and arrows", "slings & arrows"));
+        let after = anchor(
+            &new,
+            quote,
+            &before.prefix,
+            &before.suffix,
+            Some(before.start),
+        )
+        .unwrap();
+        assert_eq!(
+            slice(&new, &after),
+            "the slings & arrows of outrageous fortune"
+        );
+
+        // Only the context survives a rewrite of the whole quote
+        let rewritten =
+            html_to_book_text(&SCENE.replace(quote, "every blow that fortune sends at us"));
+        let context = anchor(
+            &rewritten,
+            quote,
+            &before.prefix,
+            &before.suffix,
+            Some(before.start),
+        )
+        .unwrap();
+        assert_eq!(
+            slice(&rewritten, &context),
+            "every blow that fortune sends at us"
+        );
+    }
+}
@@ -0,0 +1,621 @@
+//! Audiobook export: narrates a chapter range through the TTS server and stitches
+//! the segments into one WAV file with chapter markers.
+//!
+//! Each narrated segment is written to a work directory next to the book assets
+//! before it is concatenated, so an interrupted export picks up where it stopped
+//! when it is started again with the same book, range and voice.
+
+use crate::books::{self, BooksError};
+use crate::normalize::{Normalizer, PartKind};
+use crate::play::{split_speeches, PlayStructure, Speech};
+use crate::text::{collapse_whitespace, BookText};
+use crate::tts::{TtsClient, TtsError};
+use crate::wav::{self, WavAudio, WavError, WavWriter};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter};
+use thiserror::Error;
+
+pub const PROGRESS_EVENT: &str = "audiobook-export-progress";
+
+/// Upper bound for a single TTS request; longer paragraphs are split on sentences
+const MAX_SEGMENT_CHARS: usize = 600;
+
+/// Pause inserted between chapters
+const CHAPTER_PAUSE_SECONDS: f32 = 1.0;
+
+#[derive(Debug, Error)]
+pub enum AudiobookError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Tts(#[from] TtsError),
+
+    #[error(transparent)]
+    Wav(#[from] WavError),
+
+    #[error(transparent)]
+    Books(#[from] BooksError),
+
+    #[error("Invalid chapter range: {0}")]
+    InvalidRange(String),
+
+    #[error("Nothing to narrate in the selected range")]
+    Empty,
+}
+
+/// Output container. Only WAV is produced locally: M4B and Ogg need AAC/Vorbis
+/// encoders that are not available without external tools.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudiobookFormat {
+    #[default]
+    Wav,
+}
+
+impl AudiobookFormat {
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Wav => "wav",
+        }
+    }
+}
+
+/// One TTS request: a run of text spoken by a single voice
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NarrationSegment {
+    pub text: String,
+    pub voice: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlannedChapter {
+    pub index: usize,
+    pub title: String,
+    pub segments: Vec<NarrationSegment>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AudiobookProgress {
+    pub book_id: i64,
+    pub chapter_index: usize,
+    pub chapter_title: String,
+    pub completed_segments: usize,
+    pub total_segments: usize,
+    pub resumed_segments: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AudiobookChapterMarker {
+    pub title: String,
+    pub start_seconds: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AudiobookExport {
+    pub path: String,
+    pub format: AudiobookFormat,
+    pub duration_seconds: f64,
+    pub chapters: Vec<AudiobookChapterMarker>,
+    pub total_segments: usize,
+    pub resumed_segments: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+struct WorkManifest {
+    book_id: i64,
+    chapters: Vec<PlannedChapter>,
+}
+
+pub struct ExportRequest<'a> {
+    pub book_id: i64,
+    pub gutenberg_id: i64,
+    pub book_title: &'a str,
+    pub chapters: Vec<PlannedChapter>,
+    pub format: AudiobookFormat,
+    pub output_path: Option<PathBuf>,
+}
+
+// ============================================================================
+// PLANNING
+// ============================================================================
+
+/// Voices used for narration
+#[derive(Debug, Clone, Default)]
+pub struct NarrationVoices {
+    /// Voice for the text itself
+    pub reader: String,
+    /// Voice for stage directions and, in plays, scene text outside speeches
+    pub narrator: String,
+    /// Per-character voices for plays, keyed by canonical character name
+    pub cast: HashMap<String, String>,
+}
+
+/// Everything needed to turn chapter text into narration segments
+pub struct Narration<'a> {
+    pub voices: &'a NarrationVoices,
+    pub normalizer: &'a Normalizer,
+    /// Set for plays narrated with a cast; speeches switch to the speaker's voice
+    pub play: Option<&'a PlayStructure>,
+}
+
+/// Resolves an inclusive chapter range against the book outline.
+///
+/// Books without headings are narrated as a single chapter named after the book.
+pub fn plan_chapters(
+    book: &BookText,
+    book_title: &str,
+    start_chapter: Option<usize>,
+    end_chapter: Option<usize>,
+    narration: &Narration<'_>,
+) -> Result<Vec<PlannedChapter>, AudiobookError> {
+    if book.chapters.is_empty() {
+        if start_chapter.unwrap_or(0) != 0 || end_chapter.unwrap_or(0) != 0 {
+            return Err(AudiobookError::InvalidRange(
+                "book has no chapter headings".to_string(),
+            ));
+        }
+        return Ok(vec![PlannedChapter {
+            index: 0,
+            title: book_title.to_string(),
+            segments: narration_segments(&book.text, narration),
+        }]);
+    }
+
+    let last = book.chapters.len() - 1;
+    let start = start_chapter.unwrap_or(0);
+    let end = end_chapter.unwrap_or(last);
+    if start > end || end > last {
+        return Err(AudiobookError::InvalidRange(format!(
+            "{start}..={end} (book has chapters 0..={last})"
+        )));
+    }
+
+    Ok(book.chapters[start..=end]
+        .iter()
+        .map(|chapter| PlannedChapter {
+            index: chapter.index,
+            title: chapter.title.clone(),
+            segments: narration_segments(book.chapter_text(chapter.index).unwrap_or(""), narration),
+        })
+        .collect())
+}
+
+/// Normalizes a passage and splits it into segments. Stage directions switch to the
+/// narrator voice; in plays each speech uses its speaker's cast voice.
+fn narration_segments(text: &str, narration: &Narration<'_>) -> Vec<NarrationSegment> {
+    let voices = narration.voices;
+    let speeches = narration.play.map_or_else(
+        || {
+            vec![Speech {
+                speaker: None,
+                text: text.to_string(),
+            }]
+        },
+        |play| split_speeches(text, play),
+    );
+
+    let mut segments = Vec::new();
+    for speech in speeches {
+        let speech_voice = match speech.speaker {
+            Some(ref speaker) => voices.cast.get(speaker).unwrap_or(&voices.reader),
+            None if narration.play.is_some() => &voices.narrator,
+            None => &voices.reader,
+        };
+        for part in narration.normalizer.normalize(&speech.text) {
+            let voice = match part.kind {
+                PartKind::Speech => speech_voice,
+                PartKind::StageDirection => &voices.narrator,
+            };
+            segments.extend(
+                split_for_narration(&part.text, MAX_SEGMENT_CHARS)
+                    .into_iter()
+                    .map(|text| NarrationSegment {
+                        text,
+                        voice: voice.clone(),
+                    }),
+            );
+        }
+    }
+    segments
+}
+
+/// Splits text into TTS-sized chunks, keeping paragraphs together where possible
+pub fn split_for_narration(text: &str, max_chars: usize) -> Vec<String> {
+    let mut chunks: Vec<String> = Vec::new();
+    let mut current = String::new();
+
+    let pieces = text
+        .split("\n\n")
+        .map(collapse_whitespace)
+        .filter(|p| !p.is_empty())
+        .flat_map(|p| split_long(&p, max_chars));
+
+    for piece in pieces {
+        if !current.is_empty() && current.chars().count() + 1 + piece.chars().count() > max_chars {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(&piece);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+fn split_long(paragraph: &str, max_chars: usize) -> Vec<String> {
+    if paragraph.chars().count() <= max_chars {
+        return vec![paragraph.to_string()];
+    }
+
+    let mut sentences: Vec<String> = Vec::new();
+    let mut sentence = String::new();
+    let mut chars = paragraph.chars().peekable();
+    while let Some(ch) = chars.next() {
+        sentence.push(ch);
+        let at_boundary = matches!(ch, '.' | '!' | '?' | ';' | ':')
+            && chars.peek().is_none_or(|next| next.is_whitespace());
+        if at_boundary {
+            sentences.push(sentence.trim().to_string());
+            sentence.clear();
+        }
+    }
+    if !sentence.trim().is_empty() {
+        sentences.push(sentence.trim().to_string());
+    }
+
+    let mut out: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for sentence in sentences
+        .into_iter()
+        .flat_map(|s| split_words(&s, max_chars))
+    {
+        if !current.is_empty() && current.chars().count() + 1 + sentence.chars().count() > max_chars
+        {
+            out.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(&sentence);
+    }
+    if !current.is_empty() {
+        out.push(current);
+    }
+    out
+}
+
+fn split_words(sentence: &str, max_chars: usize) -> Vec<String> {
+    if sentence.chars().count() <= max_chars {
+        return vec![sentence.to_string()];
+    }
+    let mut out = Vec::new();
+    let mut current = String::new();
+    for word in sentence.split_whitespace() {
+        if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > max_chars {
+            out.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        out.push(current);
+    }
+    out
+}
+
+// ============================================================================
+// EXPORT
+// ============================================================================
+
+fn work_dir(
+    app_handle: &AppHandle,
+    request: &ExportRequest<'_>,
+) -> Result<PathBuf, AudiobookError> {
+    let first = request.chapters.first().map_or(0, |c| c.index);
+    let last = request.chapters.last().map_or(0, |c| c.index);
+    let dir = books::books_dir(app_handle)?
+        .join(format!("{}_audiobook_{first}-{last}", request.gutenberg_id));
+    Ok(dir)
+}
+
+fn segment_path(dir: &Path, chapter: usize, segment: usize) -> PathBuf {
+    dir.join(format!("{chapter:04}_{segment:04}.wav"))
+}
+
+/// Reuses the work directory if it was created for the same plan, otherwise resets it
+fn prepare_work_dir(dir: &Path, manifest: &WorkManifest) -> Result<(), AudiobookError> {
+    let manifest_path = dir.join("manifest.json");
+    let matches = fs::read(&manifest_path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<WorkManifest>(&bytes).ok())
+        .is_some_and(|existing| existing == *manifest);
+    if !matches && dir.exists() {
+        fs::remove_dir_all(dir)?;
+    }
+    fs::create_dir_all(dir)?;
+    fs::write(manifest_path, serde_json::to_vec(manifest)?)?;
+    Ok(())
+}
+
+/// Runs file work on the blocking pool so it doesn't stall the async runtime
+async fn blocking<T: Send + 'static>(
+    work: impl FnOnce() -> Result<T, AudiobookError> + Send + 'static,
+) -> Result<T, AudiobookError> {
+    tauri::async_runtime::spawn_blocking(work)
+        .await
+        .map_err(|e| AudiobookError::Io(std::io::Error::other(e.to_string())))?
+}
+
+pub async fn export_audiobook(
+    app_handle: &AppHandle,
+    client: &TtsClient,
+    request: ExportRequest<'_>,
+) -> Result<AudiobookExport, AudiobookError> {
+    let total_segments: usize = request.chapters.iter().map(|c| c.segments.len()).sum();
+    if total_segments == 0 {
+        return Err(AudiobookError::Empty);
+    }
+
+    let dir = work_dir(app_handle, &request)?;
+    let manifest = WorkManifest {
+        book_id: request.book_id,
+        chapters: request.chapters.clone(),
+    };
+    {
+        let dir = dir.clone();
+        blocking(move || prepare_work_dir(&dir, &manifest)).await?;
+    }
+
+    let mut completed = 0usize;
+    let mut resumed = 0usize;
+    for chapter in &request.chapters {
+        for (i, segment) in chapter.segments.iter().enumerate() {
+            let path = segment_path(&dir, chapter.index, i);
+            if path.exists() {
+                resumed += 1;
+            } else {
+                let audio = client.synthesize(&segment.text, &segment.voice).await?;
+                blocking(move || write_segment(&path, &audio)).await?;
+            }
+            completed += 1;
+            let _ = app_handle.emit(
+                PROGRESS_EVENT,
+                AudiobookProgress {
+                    book_id: request.book_id,
+                    chapter_index: chapter.index,
+                    chapter_title: chapter.title.clone(),
+                    completed_segments: completed,
+                    total_segments,
+                    resumed_segments: resumed,
+                },
+            );
+        }
+    }
+
+    let output_path = match request.output_path {
+        Some(ref path) => path.clone(),
+        None => books::books_dir(app_handle)?.join(format!(
+            "{}_audiobook.{}",
+            request.gutenberg_id,
+            request.format.extension()
+        )),
+    };
+
+    let (duration_seconds, chapters) = {
+        let dir = dir.clone();
+        let output_path = output_path.clone();
+        let planned = request.chapters.clone();
+        blocking(move || stitch_segments(&dir, &planned, &output_path)).await?
+    };
+
+    blocking(move || Ok(fs::remove_dir_all(dir)?)).await?;
+    println!(
+        "[Audiobook] Exported {} ({total_segments} segments, {resumed} resumed) to {}",
+        request.book_title,
+        output_path.display()
+    );
+
+    Ok(AudiobookExport {
+        path: output_path.to_string_lossy().to_string(),
+        format: request.format,
+        duration_seconds,
+        chapters,
+        total_segments,
+        resumed_segments: resumed,
+    })
+}
+
+/// Writes a segment atomically so a crash never leaves a truncated file behind
+fn write_segment(path: &Path, audio: &WavAudio) -> Result<(), AudiobookError> {
+    let tmp = path.with_extension("part");
+    let file = BufWriter::new(fs::File::create(&tmp)?);
+    let mut writer = WavWriter::new(file, audio.format)?;
+    writer.append(audio)?;
+    writer.finish()?;
+    fs::rename(tmp, path)?;
+    Ok(())
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn stitch_segments(
+    dir: &Path,
+    chapters: &[PlannedChapter],
+    output_path: &Path,
+) -> Result<(f64, Vec<AudiobookChapterMarker>), AudiobookError> {
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut writer: Option<WavWriter<BufWriter<fs::File>>> = None;
+    let mut markers: Vec<AudiobookChapterMarker> = Vec::new();
+
+    for (n, chapter) in chapters.iter().enumerate() {
+        for i in 0..chapter.segments.len() {
+            let audio = wav::parse_wav(&fs::read(segment_path(dir, chapter.index, i))?)?;
+            let out = match writer {
+                Some(ref mut out) => out,
+                None => writer.insert(WavWriter::new(
+                    BufWriter::new(fs::File::create(output_path)?),
+                    audio.format,
+                )?),
+            };
+            if i == 0 {
+                if n > 0 {
+                    out.append(&WavAudio::silence(out.format(), CHAPTER_PAUSE_SECONDS))?;
+                }
+                markers.push(AudiobookChapterMarker {
+                    title: chapter.title.clone(),
+                    start_seconds: out.frames() as f64 / f64::from(out.format().sample_rate),
+                });
+                out.add_marker(&chapter.title)?;
+            }
+            out.append(&audio)?;
+        }
+    }
+
+    let writer = writer.ok_or(AudiobookError::Empty)?;
+    let duration = writer.frames() as f64 / f64::from(writer.format().sample_rate);
+    writer.finish()?;
+    Ok((duration, markers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::normalize::{NormalizeOptions, DEFAULT_LEXICON};
+    use crate::play::detect_play_structure;
+    use crate::text::html_to_book_text;
+
+    #[test]
+    fn test_split_for_narration_merges_and_splits() {
+        let text = "Short one.\n\nShort two.\n\nThis is a much longer paragraph. It has sentences! Does it split?";
+        let chunks = split_for_narration(text, 40);
+        assert_eq!(chunks[0], "Short one. Short two.");
+        assert!(chunks.iter().all(|c| c.chars().count() <= 40));
+        assert_eq!(
+            chunks.join(" ").split_whitespace().count(),
+            text.split_whitespace().count()
+        );
+    }
+
+    #[test]
+    fn test_split_for_narration_breaks_long_words_runs() {
+        let text = "word ".repeat(50);
+        let chunks = split_for_narration(&text, 24);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.chars().count() <= 24));
+    }
+
+    fn voices() -> NarrationVoices {
+        NarrationVoices {
+            reader: "alba".to_string(),
+            narrator: "jean".to_string(),
+            cast: HashMap::new(),
+        }
+    }
+
+    fn plan_for(
+        book: &BookText,
+        start: Option<usize>,
+        end: Option<usize>,
+        voices: &NarrationVoices,
+        play: Option<&PlayStructure>,
+    ) -> Result<Vec<PlannedChapter>, AudiobookError> {
+        let normalizer = normalizer();
+        let narration = Narration {
+            voices,
+            normalizer: &normalizer,
+            play,
+        };
+        plan_chapters(book, "Play", start, end, &narration)
+    }
+
+    fn normalizer() -> Normalizer {
+        Normalizer::new(DEFAULT_LEXICON.iter().copied(), NormalizeOptions::default())
+    }
+
+    #[test]
+    fn test_plan_chapters_range() {
+        let book = html_to_book_text(
+            "<body><h2>ACT I</h2><p>One.</p><h2>ACT II</h2><p>Two.</p><h2>ACT III</h2><p>Three.</p></body>",
+        );
+        let plan = plan_for(&book, Some(1), Some(2), &voices(), None).unwrap();
+        assert_eq!(plan.len(), 2);
+        assert_eq!(plan[0].title, "ACT II");
+        assert_eq!(plan[0].segments[0].text, "Act 2 Two.");
+        assert!(plan_for(&book, Some(2), Some(1), &voices(), None).is_err());
+        assert!(plan_for(&book, None, Some(3), &voices(), None).is_err());
+    }
+
+    #[test]
+    fn test_plan_chapters_voices_stage_directions() {
+        let book =
+            html_to_book_text("<body><h2>ACT I</h2><p>HAMLET.<br>'Tis here. [_Exit._]</p></body>");
+        let plan = plan_for(&book, None, None, &voices(), None).unwrap();
+        let segments: Vec<(&str, &str)> = plan[0]
+            .segments
+            .iter()
+            .map(|s| (s.text.as_str(), s.voice.as_str()))
+            .collect();
+        assert_eq!(
+            segments,
+            vec![("Act 1 Hamlet. It is here.", "alba"), ("Exit.", "jean")]
+        );
+    }
+
+    #[test]
+    fn test_plan_chapters_without_headings() {
+        let book = html_to_book_text("<body><p>Just prose.</p></body>");
+        let plan = plan_for(&book, None, None, &voices(), None).unwrap();
+        assert_eq!(plan.len(), 1);
+        assert_eq!(plan[0].title, "Play");
+    }
+
+    #[test]
+    fn test_plan_chapters_switches_cast_voices() {
+        let mut html = String::from("<body><h2>ACT I</h2><p>Enter Ghost.</p>");
+        for _ in 0..10 {
+            html.push_str("<p>HAMLET.<br>Speak.</p><p>GHOST.<br>Mark me.</p>");
+        }
+        html.push_str("</body>");
+        let book = html_to_book_text(&html);
+        let structure = detect_play_structure(&book);
+        let mut voices = voices();
+        voices
+            .cast
+            .insert("HAMLET".to_string(), "marius".to_string());
+        voices
+            .cast
+            .insert("GHOST".to_string(), "javert".to_string());
+
+        let plan = plan_for(&book, None, None, &voices, Some(&structure)).unwrap();
+        let segments: Vec<(&str, &str)> = plan[0]
+            .segments
+            .iter()
+            .take(4)
+            .map(|s| (s.text.as_str(), s.voice.as_str()))
+            .collect();
+        assert_eq!(
+            segments,
+            vec![
+                ("Act 1", "jean"),
+                ("Enter Ghost.", "jean"),
+                ("Speak.", "marius"),
+                ("Mark me.", "javert"),
+            ]
+        );
+    }
+}
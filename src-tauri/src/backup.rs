@@ -0,0 +1,542 @@
+//! Library backup and restore
+//!
+//! A backup is a zip archive holding `manifest.json` — every book's metadata,
+//! position, bookmarks, highlights with their messages, chat threads and
+//! messages, plus the settings that aren't credentials — next to each book's
+//! source file and image assets under `books/{id}/`. IDs in the manifest are
+//! the exporting library's. A restore merges into the current library: books
+//! are matched by Gutenberg ID or source hash, everything else is remapped to
+//! the IDs it gets here, and rows already present are skipped.
+
+use crate::db::postgres::DbError;
+use crate::db::{self, BookChatThread, BookMessage, BookPosition, BookRecord, Bookmark};
+use crate::db::{Highlight, HighlightFilter, HighlightMessage};
+use crate::types::SettingKey;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{Read, Seek, Write};
+use std::path::Path;
+use thiserror::Error;
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+/// Bumped when the manifest changes in a way older restores can't read
+pub const BACKUP_VERSION: u32 = 1;
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Debug, Error)]
+pub enum BackupError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Archive error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Backup version {0} is newer than this app supports ({BACKUP_VERSION})")]
+    UnsupportedVersion(u32),
+
+    #[error("Backup thread failed: {0}")]
+    Thread(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub exported_at: String,
+    pub books: Vec<BackupBook>,
+    pub settings: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupBook {
+    #[serde(flatten)]
+    pub record: BookRecord,
+    /// Archive path of the downloaded MOBI file
+    pub source: Option<String>,
+    /// Archive path of the book's HTML, for books without a MOBI file
+    pub html: Option<String>,
+    /// Archive paths of images and covers, restored by file name
+    pub assets: Vec<String>,
+    pub position: Option<BookPosition>,
+    pub bookmarks: Vec<Bookmark>,
+    pub highlights: Vec<BackupHighlight>,
+    pub threads: Vec<BookChatThread>,
+    /// Thread messages and the book's default conversation
+    pub messages: Vec<BookMessage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupHighlight {
+    #[serde(flatten)]
+    pub highlight: Highlight,
+    pub messages: Vec<HighlightMessage>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupSummary {
+    pub path: String,
+    pub books: usize,
+    pub highlights: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RestoreReport {
+    pub books_added: usize,
+    /// Books already in the library, whose annotations were merged
+    pub books_merged: usize,
+    pub highlights_added: usize,
+    pub settings_added: usize,
+    /// IDs of the merged books, whose highlights need re-anchoring here
+    #[serde(skip)]
+    pub merged_book_ids: Vec<i64>,
+}
+
+fn book_dir(book_id: i64) -> String {
+    format!("books/{book_id}")
+}
+
+/// Runs archive and file work on the blocking pool
+async fn blocking<T: Send + 'static>(
+    work: impl FnOnce() -> Result<T, BackupError> + Send + 'static,
+) -> Result<T, BackupError> {
+    tauri::async_runtime::spawn_blocking(work)
+        .await
+        .map_err(|e| BackupError::Thread(e.to_string()))?
+}
+
+fn write_entry<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    name: &str,
+    bytes: &[u8],
+) -> Result<(), BackupError> {
+    zip.start_file(name, SimpleFileOptions::default())?;
+    zip.write_all(bytes)?;
+    Ok(())
+}
+
+fn read_entry<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+) -> Result<Vec<u8>, BackupError> {
+    let mut file = archive.by_name(name)?;
+    let mut bytes = Vec::with_capacity(usize::try_from(file.size()).unwrap_or(0));
+    file.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Reads the manifest, refusing archives from a newer version of the app
+pub fn read_manifest<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<Manifest, BackupError> {
+    #[derive(Deserialize)]
+    struct Version {
+        version: u32,
+    }
+
+    let bytes = read_entry(archive, MANIFEST_FILE)?;
+    let Version { version } = serde_json::from_slice(&bytes)?;
+    if version > BACKUP_VERSION {
+        return Err(BackupError::UnsupportedVersion(version));
+    }
+    Ok(serde_json::from_slice(&bytes)?)
+}
+
+/// Adds the files in a book's asset directory to the archive
+fn write_assets<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    assets_dir: &Path,
+    prefix: &str,
+) -> Result<Vec<String>, BackupError> {
+    let Ok(entries) = fs::read_dir(assets_dir) else {
+        return Ok(Vec::new());
+    };
+    let mut names = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if !path.is_file() {
+            continue;
+        }
+        let name = format!("{prefix}/assets/{file_name}");
+        write_entry(zip, &name, &fs::read(&path)?)?;
+        names.push(name);
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Archive paths of one book's files
+struct BookFiles {
+    source: Option<String>,
+    html: Option<String>,
+    assets: Vec<String>,
+}
+
+/// Adds a book's source file, or its HTML when it has none, and its assets
+fn write_book_files<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    prefix: &str,
+    mobi_data: Option<&[u8]>,
+    html_content: Option<&str>,
+    assets_dir: &Path,
+) -> Result<BookFiles, BackupError> {
+    let source = match mobi_data {
+        Some(bytes) => {
+            let name = format!("{prefix}/source.mobi");
+            write_entry(zip, &name, bytes)?;
+            Some(name)
+        }
+        None => None,
+    };
+    let html = match (&source, html_content) {
+        (None, Some(content)) => {
+            let name = format!("{prefix}/content.html");
+            write_entry(zip, &name, content.as_bytes())?;
+            Some(name)
+        }
+        _ => None,
+    };
+    Ok(BookFiles {
+        assets: write_assets(zip, assets_dir, prefix)?,
+        source,
+        html,
+    })
+}
+
+/// Copies a book's assets out of the archive, keeping files already on disk
+fn restore_assets<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    assets_dir: &Path,
+    names: &[String],
+) -> Result<(), BackupError> {
+    for name in names {
+        let Some(file_name) = Path::new(name).file_name() else {
+            continue;
+        };
+        let target = assets_dir.join(file_name);
+        if !target.exists() {
+            fs::create_dir_all(assets_dir)?;
+            fs::write(&target, read_entry(archive, name)?)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the whole library to a backup archive at `path`. `books_dir` holds
+/// each book's `{gutenberg_id}_assets` directory.
+pub async fn export_library(
+    pool: &Pool<Postgres>,
+    books_dir: &Path,
+    path: &Path,
+) -> Result<BackupSummary, BackupError> {
+    let mut zip = blocking({
+        let path = path.to_path_buf();
+        move || {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            Ok(ZipWriter::new(File::create(&path)?))
+        }
+    })
+    .await?;
+    let mut books = Vec::new();
+    let mut highlight_count = 0;
+
+    for record in db::list_book_records(pool).await? {
+        let id = record.id.get();
+        let prefix = book_dir(id);
+        let book = db::get_book(pool, id).await?;
+        let assets_dir = books_dir.join(format!("{}_assets", record.gutenberg_id.get()));
+        let files;
+        (zip, files) = blocking(move || {
+            let files = write_book_files(
+                &mut zip,
+                &prefix,
+                book.mobi_data.as_deref(),
+                book.html_content.as_deref(),
+                &assets_dir,
+            )?;
+            Ok((zip, files))
+        })
+        .await?;
+
+        let highlights = db::list_highlights(pool, id, &HighlightFilter::default()).await?;
+        let ids: Vec<i64> = highlights.iter().map(|h| h.id.get()).collect();
+        let mut messages: HashMap<i64, Vec<HighlightMessage>> = HashMap::new();
+        for message in db::list_messages_for_highlights(pool, &ids).await? {
+            messages
+                .entry(message.highlight_id.get())
+                .or_default()
+                .push(message);
+        }
+        highlight_count += highlights.len();
+
+        books.push(BackupBook {
+            position: db::get_book_position(pool, id).await?,
+            bookmarks: db::list_bookmarks(pool, id).await?,
+            highlights: highlights
+                .into_iter()
+                .map(|highlight| BackupHighlight {
+                    messages: messages.remove(&highlight.id.get()).unwrap_or_default(),
+                    highlight,
+                })
+                .collect(),
+            threads: db::list_book_chat_threads(pool, id).await?,
+            messages: db::list_all_book_messages(pool, id).await?,
+            record,
+            source: files.source,
+            html: files.html,
+            assets: files.assets,
+        });
+    }
+
+    let mut settings = BTreeMap::new();
+    for key in SettingKey::all().iter().filter(|key| !key.is_secret()) {
+        if let Some(value) = db::get_setting(pool, key.as_str()).await? {
+            settings.insert(key.as_str().to_string(), value);
+        }
+    }
+
+    let book_count = books.len();
+    let manifest = Manifest {
+        version: BACKUP_VERSION,
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        books,
+        settings,
+    };
+    let manifest = serde_json::to_vec_pretty(&manifest)?;
+    blocking(move || {
+        write_entry(&mut zip, MANIFEST_FILE, &manifest)?;
+        zip.finish()?;
+        Ok(())
+    })
+    .await?;
+
+    println!(
+        "[Backup] Wrote {book_count} books and {highlight_count} highlights to {}",
+        path.display()
+    );
+    Ok(BackupSummary {
+        path: path.to_string_lossy().to_string(),
+        books: book_count,
+        highlights: highlight_count,
+    })
+}
+
+/// Restores one book's rows under its ID in this library
+async fn restore_annotations(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+    book: &BackupBook,
+    report: &mut RestoreReport,
+) -> Result<(), BackupError> {
+    if let Some(position) = &book.position {
+        db::restore_book_position(pool, book_id, position).await?;
+    }
+    for bookmark in &book.bookmarks {
+        db::restore_bookmark(pool, book_id, bookmark).await?;
+    }
+    for BackupHighlight {
+        highlight,
+        messages,
+    } in &book.highlights
+    {
+        let (highlight_id, added) = db::restore_highlight(pool, book_id, highlight).await?;
+        if added {
+            report.highlights_added += 1;
+        }
+        for message in messages {
+            db::restore_highlight_message(pool, highlight_id, message).await?;
+        }
+    }
+
+    let mut threads = HashMap::new();
+    for thread in &book.threads {
+        let id = db::restore_chat_thread(pool, book_id, thread).await?;
+        threads.insert(thread.id.get(), id);
+    }
+    for message in &book.messages {
+        // Messages of a thread that wasn't backed up go to the default conversation
+        let thread_id = message
+            .thread_id
+            .and_then(|thread| threads.get(&thread.get()).copied());
+        db::restore_book_message(pool, book_id, thread_id, message).await?;
+    }
+    Ok(())
+}
+
+/// Merges a backup archive at `path` into the library
+///
+/// Highlights merged into an existing book keep the backup's selectors; the
+/// caller re-anchors `merged_book_ids` against the text it has here.
+pub async fn import_library(
+    pool: &Pool<Postgres>,
+    books_dir: &Path,
+    path: &Path,
+) -> Result<RestoreReport, BackupError> {
+    let (mut archive, manifest) = blocking({
+        let path = path.to_path_buf();
+        move || {
+            let mut archive = ZipArchive::new(File::open(&path)?)?;
+            let manifest = read_manifest(&mut archive)?;
+            Ok((archive, manifest))
+        }
+    })
+    .await?;
+    let mut report = RestoreReport::default();
+
+    for book in &manifest.books {
+        let record = &book.record;
+        let existing = db::find_restore_book(
+            pool,
+            record.gutenberg_id.get(),
+            record.content_hash.as_deref(),
+        )
+        .await?;
+        let (book_id, gutenberg_id) = if let Some(found) = existing {
+            report.books_merged += 1;
+            report.merged_book_ids.push(found.0);
+            found
+        } else {
+            let (source, html);
+            (archive, source, html) = blocking({
+                let source = book.source.clone();
+                let html = book.html.clone();
+                move || {
+                    let source = source
+                        .as_deref()
+                        .map(|name| read_entry(&mut archive, name))
+                        .transpose()?;
+                    let html = html
+                        .as_deref()
+                        .map(|name| read_entry(&mut archive, name))
+                        .transpose()?
+                        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned());
+                    Ok((archive, source, html))
+                }
+            })
+            .await?;
+            let id = db::restore_book(pool, record, source.as_deref(), html.as_deref()).await?;
+            report.books_added += 1;
+            (id, record.gutenberg_id.get())
+        };
+
+        let assets_dir = books_dir.join(format!("{gutenberg_id}_assets"));
+        archive = blocking({
+            let names = book.assets.clone();
+            move || {
+                restore_assets(&mut archive, &assets_dir, &names)?;
+                Ok(archive)
+            }
+        })
+        .await?;
+
+        restore_annotations(pool, book_id, book, &mut report).await?;
+    }
+
+    for (key, value) in &manifest.settings {
+        let restorable = key.parse::<SettingKey>().is_ok_and(|key| !key.is_secret());
+        if restorable && db::restore_setting(pool, key, value).await? {
+            report.settings_added += 1;
+        }
+    }
+
+    println!(
+        "[Backup] Restored {} new and {} existing books from {}",
+        report.books_added,
+        report.books_merged,
+        path.display()
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{BookId, GutenbergId};
+    use std::io::Cursor;
+
+    fn archive(manifest: &serde_json::Value) -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        write_entry(&mut zip, "books/1/source.mobi", b"BOOKMOBI").unwrap();
+        write_entry(
+            &mut zip,
+            MANIFEST_FILE,
+            &serde_json::to_vec(manifest).unwrap(),
+        )
+        .unwrap();
+        ZipArchive::new(zip.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_manifest_round_trip() {
+        let manifest = Manifest {
+            version: BACKUP_VERSION,
+            exported_at: "2024-05-01T00:00:00Z".to_string(),
+            books: vec![BackupBook {
+                record: BookRecord {
+                    id: BookId::new(1),
+                    gutenberg_id: GutenbergId::new(1342),
+                    title: "Pride and Prejudice".to_string(),
+                    authors: "Austen, Jane".to_string(),
+                    publication_year: None,
+                    cover_url: None,
+                    first_image_index: None,
+                    languages: vec!["en".to_string()],
+                    source_url: None,
+                    content_hash: Some("abc".to_string()),
+                    status: None,
+                    status_updated_at: None,
+                    started_at: None,
+                    finished_at: None,
+                    last_opened_at: None,
+                    created_at: "2024-04-01 09:00:00+00".to_string(),
+                },
+                source: Some("books/1/source.mobi".to_string()),
+                html: None,
+                assets: Vec::new(),
+                position: None,
+                bookmarks: Vec::new(),
+                highlights: Vec::new(),
+                threads: Vec::new(),
+                messages: Vec::new(),
+            }],
+            settings: BTreeMap::from([("default_model".to_string(), "gpt".to_string())]),
+        };
+
+        let mut archive = archive(&serde_json::to_value(&manifest).unwrap());
+        let read = read_manifest(&mut archive).unwrap();
+        assert_eq!(read.books[0].record.gutenberg_id.get(), 1342);
+        assert_eq!(read.books[0].record.title, "Pride and Prejudice");
+        assert_eq!(
+            read.settings.get("default_model").map(String::as_str),
+            Some("gpt")
+        );
+        let source = read.books[0].source.as_deref().unwrap();
+        assert_eq!(read_entry(&mut archive, source).unwrap(), b"BOOKMOBI");
+    }
+
+    #[test]
+    fn test_rejects_newer_versions() {
+        let mut archive = archive(&serde_json::json!({ "version": BACKUP_VERSION + 1 }));
+        assert!(matches!(
+            read_manifest(&mut archive),
+            Err(BackupError::UnsupportedVersion(v)) if v == BACKUP_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn test_secrets_are_not_backed_up() {
+        assert!(SettingKey::OpenaiApiKey.is_secret());
+        assert!(!SettingKey::DefaultModel.is_secret());
+    }
+}
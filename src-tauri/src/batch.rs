@@ -0,0 +1,453 @@
+//! Batch download of a whole catalog
+//!
+//! Walks every page of a catalog search (following `next` links), keeps the
+//! books that have a MOBI in one of the wanted languages and aren't already in
+//! the library, and downloads them one at a time. Each download goes through
+//! `books::fetch_mobi`, so gutenberg.org is only hit at the pace of
+//! its throttle (or not at all with a mirror configured).
+
+use crate::books::{self, GutenbergDownload};
+use crate::db;
+use crate::db::postgres::DbError;
+use crate::gutendex::{self, CatalogQuery, CatalogSource, GutendexBook, GutendexClient};
+use crate::mirror::GutenbergMirror;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{AppHandle, Emitter};
+use thiserror::Error;
+
+pub const PROGRESS_EVENT: &str = "catalog-download-progress";
+
+const MOBI_MIME: &str = "application/x-mobipocket-ebook";
+
+#[derive(Debug, Error)]
+pub enum BatchError {
+    #[error("A catalog download is already running")]
+    AlreadyRunning,
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbError),
+}
+
+/// What to download: a catalog, narrowed like a catalog page
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CatalogDownloadRequest {
+    pub catalog_key: String,
+    /// Layered over the catalog's saved query
+    pub query: CatalogQuery,
+    /// Two-letter codes; empty accepts any language
+    pub languages: Vec<String>,
+    /// Stop after adding this many books
+    pub limit: Option<usize>,
+}
+
+/// Where a batch reads catalog pages from and stores books to
+pub struct CatalogDownloadContext<'a> {
+    pub app_handle: &'a AppHandle,
+    pub pool: &'a Pool<Postgres>,
+    pub client: &'a GutendexClient,
+    pub source: CatalogSource,
+    pub base_url: &'a Url,
+    pub mirror: &'a GutenbergMirror,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    InLibrary,
+    Language,
+    NoMobi,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AddedBook {
+    pub gutenberg_id: i64,
+    pub book_id: i64,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedBook {
+    pub gutenberg_id: i64,
+    pub title: String,
+    pub reason: SkipReason,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedBook {
+    pub gutenberg_id: i64,
+    pub title: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CatalogDownloadSummary {
+    /// Catalog results looked at
+    pub scanned: usize,
+    pub added: Vec<AddedBook>,
+    pub skipped: Vec<SkippedBook>,
+    pub failed: Vec<FailedBook>,
+    pub cancelled: bool,
+    /// Why the walk stopped before the last page, if a page failed to load
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CatalogDownloadProgress<'a> {
+    scanned: usize,
+    added: usize,
+    skipped: usize,
+    failed: usize,
+    current: Option<&'a str>,
+}
+
+/// Managed state allowing one batch at a time, cancellable from another command
+#[derive(Debug, Default)]
+pub struct CatalogDownloads {
+    running: AtomicBool,
+    cancelled: AtomicBool,
+}
+
+impl CatalogDownloads {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Asks a running batch to stop after its current book; false if none is running
+    pub fn cancel(&self) -> bool {
+        let running = self.running.load(Ordering::SeqCst);
+        if running {
+            self.cancelled.store(true, Ordering::SeqCst);
+        }
+        running
+    }
+
+    fn begin(&self) -> Result<RunGuard<'_>, BatchError> {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return Err(BatchError::AlreadyRunning);
+        }
+        self.cancelled.store(false, Ordering::SeqCst);
+        Ok(RunGuard(self))
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+struct RunGuard<'a>(&'a CatalogDownloads);
+
+impl Drop for RunGuard<'_> {
+    fn drop(&mut self) {
+        self.0.running.store(false, Ordering::SeqCst);
+    }
+}
+
+/// The book's MOBI download, preferring Gutenberg's declared MOBI format
+pub fn mobi_url(book: &GutendexBook) -> Option<&str> {
+    if let Some(url) = book.formats.get(MOBI_MIME) {
+        return Some(url);
+    }
+    let mut candidates: Vec<(&String, &String)> = book
+        .formats
+        .iter()
+        .filter(|(mime, url)| {
+            mime.to_lowercase().contains("mobi") || url.to_lowercase().ends_with(".mobi")
+        })
+        .collect();
+    candidates.sort();
+    candidates.first().map(|(_, url)| url.as_str())
+}
+
+pub fn cover_url(book: &GutendexBook) -> Option<&str> {
+    book.formats
+        .get("image/jpeg")
+        .or_else(|| book.formats.get("image/png"))
+        .map(String::as_str)
+}
+
+fn format_year(year: Option<i32>) -> String {
+    match year {
+        None => "?".to_string(),
+        Some(year) if year < 0 => format!("{} BCE", year.unsigned_abs()),
+        Some(year) => year.to_string(),
+    }
+}
+
+/// Authors with their dates, as the library page formats them
+pub fn authors_string(book: &GutendexBook) -> String {
+    book.authors
+        .iter()
+        .map(|author| match (author.birth_year, author.death_year) {
+            (None, None) => author.name.clone(),
+            (Some(birth), Some(death)) if birth < 0 && death < 0 => {
+                format!("{} (c. {})", author.name, format_year(Some(birth)))
+            }
+            (birth, death) => format!(
+                "{} ({}–{})",
+                author.name,
+                format_year(birth),
+                format_year(death)
+            ),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Why a catalog result won't be downloaded, if it won't
+pub fn skip_reason(
+    book: &GutendexBook,
+    languages: &[String],
+    library: &HashSet<i64>,
+) -> Option<SkipReason> {
+    if library.contains(&book.id) {
+        return Some(SkipReason::InLibrary);
+    }
+    // Results without language data are given the benefit of the doubt
+    let wrong_language = !languages.is_empty()
+        && !book.languages.is_empty()
+        && !book
+            .languages
+            .iter()
+            .any(|code| languages.iter().any(|l| l.eq_ignore_ascii_case(code)));
+    if wrong_language {
+        return Some(SkipReason::Language);
+    }
+    if mobi_url(book).is_none() {
+        return Some(SkipReason::NoMobi);
+    }
+    None
+}
+
+fn emit_progress(app_handle: &AppHandle, summary: &CatalogDownloadSummary, current: Option<&str>) {
+    let _ = app_handle.emit(
+        PROGRESS_EVENT,
+        CatalogDownloadProgress {
+            scanned: summary.scanned,
+            added: summary.added.len(),
+            skipped: summary.skipped.len(),
+            failed: summary.failed.len(),
+            current,
+        },
+    );
+}
+
+/// Downloads every wanted book in a catalog. Failures of individual books are
+/// collected in the summary; a page that fails to load ends the walk early.
+pub async fn download_catalog(
+    ctx: &CatalogDownloadContext<'_>,
+    downloads: &CatalogDownloads,
+    request: &CatalogDownloadRequest,
+) -> Result<CatalogDownloadSummary, BatchError> {
+    let _guard = downloads.begin()?;
+
+    let mut query = request.query.clone();
+    if !request.languages.is_empty() {
+        query.languages.clone_from(&request.languages);
+    }
+    let mut library: HashSet<i64> = db::list_book_gutenberg_ids(ctx.pool)
+        .await?
+        .into_iter()
+        .collect();
+
+    let mut summary = CatalogDownloadSummary::default();
+    let mut page_url: Option<String> = None;
+    'pages: loop {
+        let page = match gutendex::search_catalog(
+            ctx.client,
+            ctx.pool,
+            ctx.source,
+            ctx.base_url,
+            &request.catalog_key,
+            page_url.take(),
+            &query,
+        )
+        .await
+        {
+            Ok(page) => page,
+            Err(e) => {
+                summary.error = Some(e.to_string());
+                break;
+            }
+        };
+
+        for book in &page.results {
+            if downloads.is_cancelled() {
+                summary.cancelled = true;
+                break 'pages;
+            }
+            if request
+                .limit
+                .is_some_and(|limit| summary.added.len() >= limit)
+            {
+                break 'pages;
+            }
+            summary.scanned += 1;
+
+            if let Some(reason) = skip_reason(book, &request.languages, &library) {
+                summary.skipped.push(SkippedBook {
+                    gutenberg_id: book.id,
+                    title: book.title.clone(),
+                    reason,
+                });
+                continue;
+            }
+            library.insert(book.id);
+            emit_progress(ctx.app_handle, &summary, Some(&book.title));
+
+            let download = GutenbergDownload {
+                gutenberg_id: book.id,
+                title: book.title.clone(),
+                authors: authors_string(book),
+                publication_year: None,
+                cover_url: cover_url(book).map(str::to_string),
+                mobi_url: mobi_url(book).unwrap_or_default().to_string(),
+                languages: book.languages.clone(),
+            };
+            match books::download_gutenberg_book(ctx.app_handle, ctx.pool, ctx.mirror, &download)
+                .await
+            {
+                Ok(book_id) => summary.added.push(AddedBook {
+                    gutenberg_id: book.id,
+                    book_id,
+                    title: book.title.clone(),
+                }),
+                Err(e) => summary.failed.push(FailedBook {
+                    gutenberg_id: book.id,
+                    title: book.title.clone(),
+                    error: e.to_string(),
+                }),
+            }
+        }
+        emit_progress(ctx.app_handle, &summary, None);
+
+        match page.next {
+            Some(next) => page_url = Some(next),
+            None => break,
+        }
+    }
+
+    println!(
+        "[Batch] Catalog '{}': {} added, {} skipped, {} failed",
+        request.catalog_key,
+        summary.added.len(),
+        summary.skipped.len(),
+        summary.failed.len()
+    );
+    emit_progress(ctx.app_handle, &summary, None);
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gutendex::GutendexAuthor;
+
+    fn book(id: i64, languages: &[&str], formats: &[(&str, &str)]) -> GutendexBook {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "title": format!("Book {id}"),
+            "authors": [],
+            "languages": languages,
+            "copyright": false,
+            "formats": formats.iter().copied().collect::<std::collections::HashMap<_, _>>(),
+            "download_count": null,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_mobi_url_prefers_declared_format() {
+        let b = book(
+            1524,
+            &["en"],
+            &[
+                ("application/octet-stream", "https://example.org/1524.mobi"),
+                (
+                    MOBI_MIME,
+                    "https://www.gutenberg.org/ebooks/1524.kf8.images",
+                ),
+            ],
+        );
+        assert_eq!(
+            mobi_url(&b),
+            Some("https://www.gutenberg.org/ebooks/1524.kf8.images")
+        );
+        let b = book(
+            1,
+            &["en"],
+            &[("application/octet-stream", "https://example.org/1.mobi")],
+        );
+        assert_eq!(mobi_url(&b), Some("https://example.org/1.mobi"));
+        assert_eq!(mobi_url(&book(2, &["en"], &[("text/html", "x")])), None);
+    }
+
+    #[test]
+    fn test_skip_reasons() {
+        let mobi = [(MOBI_MIME, "https://www.gutenberg.org/ebooks/1.kf8.images")];
+        let library = HashSet::from([1]);
+        let english = vec!["en".to_string()];
+
+        assert_eq!(
+            skip_reason(&book(1, &["en"], &mobi), &english, &library),
+            Some(SkipReason::InLibrary)
+        );
+        assert_eq!(
+            skip_reason(&book(2, &["fr"], &mobi), &english, &library),
+            Some(SkipReason::Language)
+        );
+        assert_eq!(
+            skip_reason(&book(3, &["EN"], &[]), &english, &library),
+            Some(SkipReason::NoMobi)
+        );
+        assert_eq!(
+            skip_reason(&book(4, &["fr", "en"], &mobi), &english, &library),
+            None
+        );
+        assert_eq!(skip_reason(&book(5, &["fr"], &mobi), &[], &library), None);
+    }
+
+    #[test]
+    fn test_authors_string() {
+        let mut b = book(1, &[], &[]);
+        b.authors = vec![
+            GutendexAuthor {
+                name: "Shakespeare, William".to_string(),
+                birth_year: Some(1564),
+                death_year: Some(1616),
+            },
+            GutendexAuthor {
+                name: "Homer".to_string(),
+                birth_year: Some(-750),
+                death_year: Some(-650),
+            },
+            GutendexAuthor {
+                name: "Anonymous".to_string(),
+                birth_year: None,
+                death_year: None,
+            },
+        ];
+        assert_eq!(
+            authors_string(&b),
+            "Shakespeare, William (1564–1616), Homer (c. 750 BCE), Anonymous"
+        );
+    }
+
+    #[test]
+    fn test_one_batch_at_a_time() {
+        let downloads = CatalogDownloads::new();
+        assert!(!downloads.cancel());
+        {
+            let _guard = downloads.begin().unwrap();
+            assert!(matches!(downloads.begin(), Err(BatchError::AlreadyRunning)));
+            assert!(downloads.cancel());
+            assert!(downloads.is_cancelled());
+        }
+        let _guard = downloads.begin().unwrap();
+        assert!(!downloads.is_cancelled());
+    }
+}
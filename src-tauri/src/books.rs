@@ -1,3 +1,11 @@
+use crate::covers;
+use crate::db::{self, postgres::DbError};
+use crate::mirror::{self, GutenbergMirror, MirrorError, MirrorSource};
+use data_encoding::HEXLOWER;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+use sqlx::{Pool, Postgres};
 use std::env;
 use std::{
     fs,
@@ -18,6 +26,12 @@ pub enum BooksError {
     #[error("Tauri error: {0}")]
     Tauri(#[from] tauri::Error),
 
+    #[error("Database error: {0}")]
+    Db(#[from] DbError),
+
+    #[error("Mirror error: {0}")]
+    Mirror(#[from] MirrorError),
+
     #[error("MOBI extraction error: {0}")]
     Extraction(String),
 
@@ -187,15 +201,12 @@ pub fn fix_mojibake(s: &str) -> Option<String> {
 static GUTENBERG_NEXT_ALLOWED: once_cell::sync::Lazy<tokio::sync::Mutex<Instant>> =
     once_cell::sync::Lazy::new(|| tokio::sync::Mutex::new(Instant::now()));
 
+fn is_gutenberg(url: &str) -> bool {
+    reqwest::Url::parse(url).is_ok_and(|u| mirror::is_gutenberg_url(&u))
+}
+
 async fn throttle_gutenberg_if_needed(url: &str) {
-    let is_gutenberg = reqwest::Url::parse(url)
-        .ok()
-        .and_then(|u| {
-            u.host_str()
-                .map(|h| h == "gutenberg.org" || h.ends_with(".gutenberg.org"))
-        })
-        .unwrap_or(false);
-    if !is_gutenberg {
+    if !is_gutenberg(url) {
         return;
     }
 
@@ -214,27 +225,205 @@ async fn throttle_gutenberg_if_needed(url: &str) {
     }
 }
 
-pub async fn download_mobi_bytes(
-    _app_handle: &AppHandle,
-    _gutenberg_id: i64,
-    mobi_url: String,
-) -> Result<Vec<u8>, BooksError> {
+/// HTTP validators from a previous download of the same file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum MobiFetch {
+    /// The server confirmed our copy is current (HTTP 304)
+    NotModified,
+    Downloaded {
+        bytes: Vec<u8>,
+        validators: Validators,
+    },
+}
+
+/// Fetches a MOBI, from the configured mirror when the URL maps onto one.
+/// With validators set the request is conditional.
+pub async fn fetch_mobi(
+    mobi_url: &str,
+    mirror: &GutenbergMirror,
+    validators: &Validators,
+) -> Result<MobiFetch, BooksError> {
+    let mobi_url = match mirror.resolve(mobi_url) {
+        MirrorSource::Url(url) => url,
+        MirrorSource::File(path) => {
+            let bytes = tokio::fs::read(&path).await.map_err(|e| {
+                if e.kind() == std::io::ErrorKind::NotFound {
+                    BooksError::from(MirrorError::Missing(path))
+                } else {
+                    e.into()
+                }
+            })?;
+            return Ok(MobiFetch::Downloaded {
+                bytes,
+                validators: Validators::default(),
+            });
+        }
+    };
     throttle_gutenberg_if_needed(&mobi_url).await;
 
     let client = reqwest::Client::builder()
         .user_agent("ai-reader/0.1 (polite; see https://www.gutenberg.org/policy/robot)")
         .build()?;
 
-    let resp = client.get(&mobi_url).send().await?;
+    let mut request = client.get(&mobi_url);
+    if let Some(etag) = validators.etag.as_deref() {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = validators.last_modified.as_deref() {
+        request = request.header(IF_MODIFIED_SINCE, last_modified);
+    }
+    let resp = request.send().await?;
     let status = resp.status();
-    if status.as_u16() == 403 || status.as_u16() == 429 {
+    if status == StatusCode::NOT_MODIFIED {
+        return Ok(MobiFetch::NotModified);
+    }
+    if (status == StatusCode::FORBIDDEN || status == StatusCode::TOO_MANY_REQUESTS)
+        && is_gutenberg(&mobi_url)
+    {
         return Err(BooksError::Other(format!(
-            "Project Gutenberg blocked/rate-limited this request (HTTP {status}). Try pausing/resuming, or download fewer books / set up a local mirror per https://www.gutenberg.org/policy/robot"
+            "Project Gutenberg blocked/rate-limited this request (HTTP {status}). Try pausing/resuming, download fewer books, or set a Gutenberg mirror (an HTTP mirror or a local cache/epub directory) in Settings; see https://www.gutenberg.org/policy/robot"
         )));
     }
 
-    let bytes = resp.error_for_status()?.bytes().await?;
-    Ok(bytes.to_vec())
+    let resp = resp.error_for_status()?;
+    let header = |name| {
+        resp.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    let validators = Validators {
+        etag: header(ETAG),
+        last_modified: header(LAST_MODIFIED),
+    };
+    let bytes = resp.bytes().await?;
+    Ok(MobiFetch::Downloaded {
+        bytes: bytes.to_vec(),
+        validators,
+    })
+}
+
+/// Fetches any Gutenberg file (e.g. a cover) through the mirror, unconditionally
+pub async fn fetch_file(url: &str, mirror: &GutenbergMirror) -> Result<Vec<u8>, BooksError> {
+    match fetch_mobi(url, mirror, &Validators::default()).await? {
+        MobiFetch::Downloaded { bytes, .. } => Ok(bytes),
+        MobiFetch::NotModified => Err(BooksError::Other(
+            "Server answered an unconditional request with 304".to_string(),
+        )),
+    }
+}
+
+/// Hex SHA-256 of a book file, used to tell whether a re-download changed anything
+pub fn content_hash(bytes: &[u8]) -> String {
+    HEXLOWER.encode(&Sha256::digest(bytes))
+}
+
+/// A Gutenberg book to add to the library
+#[derive(Debug, Clone)]
+pub struct GutenbergDownload {
+    pub gutenberg_id: i64,
+    pub title: String,
+    pub authors: String,
+    pub publication_year: Option<i32>,
+    pub cover_url: Option<String>,
+    pub mobi_url: String,
+    /// Gutendex language codes, when known
+    pub languages: Vec<String>,
+}
+
+/// Runs `extract_mobi_to_content` on the blocking pool
+pub async fn extract_in_background(
+    app_handle: &AppHandle,
+    gutenberg_id: i64,
+    mobi_bytes: Vec<u8>,
+) -> Result<(String, Option<i32>), BooksError> {
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        println!("[Backend] Extracting MOBI content for book {gutenberg_id}");
+        extract_mobi_to_content(&app_handle, gutenberg_id, &mobi_bytes)
+    })
+    .await
+    .map_err(|e| BooksError::Other(format!("MOBI extraction thread failed: {e}")))?
+}
+
+/// Downloads a book's MOBI, extracts its HTML and saves both; returns the book id
+pub async fn download_gutenberg_book(
+    app_handle: &AppHandle,
+    pool: &Pool<Postgres>,
+    mirror: &GutenbergMirror,
+    download: &GutenbergDownload,
+) -> Result<i64, BooksError> {
+    let gutenberg_id = download.gutenberg_id;
+    println!(
+        "[Backend] Starting download for book {gutenberg_id} from {}",
+        download.mobi_url
+    );
+    let MobiFetch::Downloaded {
+        bytes: mobi_bytes,
+        validators,
+    } = fetch_mobi(&download.mobi_url, mirror, &Validators::default()).await?
+    else {
+        return Err(BooksError::Other(
+            "Server answered an unconditional request with 304".to_string(),
+        ));
+    };
+    println!(
+        "[Backend] Downloaded {} bytes for book {gutenberg_id}",
+        mobi_bytes.len()
+    );
+
+    let (html_content, first_image_index) =
+        extract_in_background(app_handle, gutenberg_id, mobi_bytes.clone()).await?;
+    println!(
+        "[Backend] Extracted HTML ({} chars) for book {gutenberg_id}",
+        html_content.len()
+    );
+
+    println!("[Backend] Upserting book {gutenberg_id} to database");
+    let book_id = db::upsert_book(
+        pool,
+        gutenberg_id,
+        &download.title,
+        &download.authors,
+        download.publication_year,
+        download.cover_url.as_deref(),
+        Some(&mobi_bytes),
+        Some(&html_content),
+        first_image_index,
+    )
+    .await?;
+    // Downloading a trashed book again brings it back
+    db::untrash_book(pool, book_id).await?;
+    db::set_book_source(
+        pool,
+        book_id,
+        &download.mobi_url,
+        validators.etag.as_deref(),
+        validators.last_modified.as_deref(),
+        &content_hash(&mobi_bytes),
+    )
+    .await?;
+    if !download.languages.is_empty() {
+        db::set_book_languages(pool, book_id, &download.languages).await?;
+    }
+    if let Err(e) = covers::cache_cover(
+        app_handle,
+        mirror,
+        gutenberg_id,
+        download.cover_url.as_deref(),
+        Some(&mobi_bytes),
+    )
+    .await
+    {
+        println!("[Covers] Failed to cache cover for book {gutenberg_id}: {e}");
+    }
+    Ok(book_id)
 }
 
 #[inline]
@@ -249,7 +438,7 @@ fn be_u32(b: &[u8], off: usize) -> Option<u32> {
         .map(|s| u32::from_be_bytes([s[0], s[1], s[2], s[3]]))
 }
 
-fn detect_image_format(data: &[u8]) -> Option<&'static str> {
+pub fn detect_image_format(data: &[u8]) -> Option<&'static str> {
     if data.len() < 4 {
         return None;
     }
@@ -314,7 +503,7 @@ fn palmdoc_decompress(input: &[u8]) -> Vec<u8> {
     out
 }
 
-fn escape_html(s: &str) -> String {
+pub fn escape_html(s: &str) -> String {
     let mut out = String::with_capacity(s.len() + s.len() / 8);
     for ch in s.chars() {
         match ch {
@@ -455,6 +644,55 @@ pub fn extract_mobi_images(
     Ok((images, first_image_index))
 }
 
+/// EXTH record holding the cover's offset from the first image record
+const EXTH_COVER_OFFSET: u32 = 201;
+
+/// Returns the payload of an EXTH record from the MOBI header, if present
+fn exth_record(rec0: &[u8], record_type: u32) -> Option<&[u8]> {
+    let mobi_off = find_mobi_header_offset(rec0)?;
+    let exth_flags = be_u32(rec0, mobi_off + 0x70)?;
+    if exth_flags & 0x40 == 0 {
+        return None;
+    }
+    let exth = mobi_off + be_u32(rec0, mobi_off + 4)? as usize;
+    if rec0.get(exth..exth + 4)? != b"EXTH" {
+        return None;
+    }
+
+    let count = be_u32(rec0, exth + 8)?;
+    let mut off = exth + 12;
+    for _ in 0..count {
+        let kind = be_u32(rec0, off)?;
+        let len = be_u32(rec0, off + 4)? as usize;
+        if len < 8 {
+            return None;
+        }
+        if kind == record_type {
+            return rec0.get(off + 8..off + len);
+        }
+        off += len;
+    }
+    None
+}
+
+/// The cover image the MOBI's EXTH header points at, if any
+pub fn extract_mobi_cover(bytes: &[u8]) -> Option<Vec<u8>> {
+    let num_records = be_u16(bytes, 76)? as usize;
+    let offsets = parse_record_offsets(bytes, 78, num_records).ok()?;
+    let rec0 = bytes.get(offsets[0]..*offsets.get(1)?)?;
+
+    let mobi_off = find_mobi_header_offset(rec0)?;
+    let first_image = be_u32(rec0, mobi_off + 0x5c)? as usize;
+    let cover = be_u32(exth_record(rec0, EXTH_COVER_OFFSET)?, 0)? as usize;
+    let index = first_image.checked_add(cover)?;
+    if index == 0 || index >= num_records {
+        return None;
+    }
+
+    let data = bytes.get(offsets[index]..offsets[index + 1])?;
+    detect_image_format(data).map(|_| data.to_vec())
+}
+
 pub fn extract_mobi_to_content(
     app_handle: &AppHandle,
     gutenberg_id: i64,
@@ -558,7 +796,7 @@ pub fn extract_mobi_to_content(
     Ok((html, first_image_index))
 }
 
-fn books_dir(app_handle: &AppHandle) -> Result<PathBuf, BooksError> {
+pub fn books_dir(app_handle: &AppHandle) -> Result<PathBuf, BooksError> {
     let mut base = env::current_dir()
         .or_else(|_| app_handle.path().resolve(".", BaseDirectory::AppLocalData))?;
     if base.file_name().and_then(|s| s.to_str()) == Some("src-tauri") {
@@ -620,7 +858,7 @@ pub fn delete_book_assets(app_handle: &AppHandle, gutenberg_id: i64) -> Result<(
     let dir = books_dir(app_handle)?;
     let assets_dir = dir.join(format!("{gutenberg_id}_assets"));
     if assets_dir.exists() {
-        let _ = fs::remove_dir_all(assets_dir);
+        fs::remove_dir_all(assets_dir)?;
     }
     Ok(())
 }
@@ -645,4 +883,52 @@ mod tests {
         assert_eq!(first_image_index.unwrap(), 449);
         assert!(images.contains_key(&449));
     }
+
+    /// A three-record MOBI: header with an EXTH cover pointer, one text record, one PNG
+    fn mobi_with_cover() -> (Vec<u8>, Vec<u8>) {
+        let image = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+
+        let mut rec0 = vec![0u8; 16 + 0xe8];
+        rec0[8..10].copy_from_slice(&1u16.to_be_bytes());
+        rec0[16..20].copy_from_slice(b"MOBI");
+        rec0[20..24].copy_from_slice(&0xe8u32.to_be_bytes());
+        rec0[16 + 0x5c..16 + 0x60].copy_from_slice(&2u32.to_be_bytes());
+        rec0[16 + 0x70..16 + 0x74].copy_from_slice(&0x40u32.to_be_bytes());
+        rec0.extend_from_slice(b"EXTH");
+        rec0.extend_from_slice(&(12u32 + 11 + 12).to_be_bytes());
+        rec0.extend_from_slice(&2u32.to_be_bytes());
+        for (kind, data) in [
+            (100u32, &b"Bob"[..]),
+            (EXTH_COVER_OFFSET, &0u32.to_be_bytes()[..]),
+        ] {
+            rec0.extend_from_slice(&kind.to_be_bytes());
+            rec0.extend_from_slice(&(8 + u32::try_from(data.len()).unwrap()).to_be_bytes());
+            rec0.extend_from_slice(data);
+        }
+
+        let records = [rec0, b"hello".to_vec(), image.clone()];
+        let mut bytes = vec![0u8; 78];
+        bytes[76..78].copy_from_slice(&3u16.to_be_bytes());
+        let mut offset = 78 + records.len() * 8;
+        for record in &records {
+            bytes.extend_from_slice(&u32::try_from(offset).unwrap().to_be_bytes());
+            bytes.extend_from_slice(&[0; 4]);
+            offset += record.len();
+        }
+        for record in &records {
+            bytes.extend_from_slice(record);
+        }
+        (bytes, image)
+    }
+
+    #[test]
+    fn test_extract_mobi_cover() {
+        let (bytes, image) = mobi_with_cover();
+        assert_eq!(extract_mobi_cover(&bytes), Some(image));
+
+        let mut no_exth = bytes;
+        no_exth[78 + 24 + 16 + 0x73] = 0;
+        assert_eq!(extract_mobi_cover(&no_exth), None);
+        assert_eq!(extract_mobi_cover(b"too short"), None);
+    }
 }
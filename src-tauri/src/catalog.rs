@@ -0,0 +1,674 @@
+//! Local copy of the Project Gutenberg catalog
+//!
+//! Imports Gutenberg's offline catalog — `pg_catalog.csv`, the `rdf-files`
+//! tarball, or an extracted directory of `pg{id}.rdf` files — into local tables
+//! and answers catalog searches from them in the same shape as Gutendex, so the
+//! library page works without network access.
+
+use crate::db;
+use crate::db::postgres::DbError;
+use crate::gutendex::{CatalogQuery, GutendexAuthor, GutendexBook, GutendexResponse};
+use reqwest::Url;
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter};
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+pub const PROGRESS_EVENT: &str = "catalog-import-progress";
+
+/// Results per page, matching Gutendex
+pub const PAGE_SIZE: i64 = 32;
+
+/// Works written to the database per transaction
+const IMPORT_BATCH_SIZE: usize = 500;
+
+const GUTENBERG_BASE: &str = "https://www.gutenberg.org";
+
+#[derive(Debug, Error)]
+pub enum CatalogError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+
+    #[error("RDF error: {0}")]
+    Xml(#[from] roxmltree::Error),
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbError),
+
+    #[error("URL parse error: {0}")]
+    Url(#[from] url::ParseError),
+
+    #[error("Unsupported catalog file: {0}")]
+    Unsupported(String),
+
+    #[error("Import was interrupted")]
+    Interrupted,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CatalogAgent {
+    pub name: String,
+    pub birth_year: Option<i32>,
+    pub death_year: Option<i32>,
+    /// `author`, `translator`, `editor`, ...
+    pub role: String,
+}
+
+/// One catalog entry as read from the CSV or an RDF record
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CatalogWork {
+    pub id: i64,
+    pub title: String,
+    pub issued: Option<String>,
+    pub media_type: String,
+    pub copyright: Option<bool>,
+    pub download_count: Option<i64>,
+    pub agents: Vec<CatalogAgent>,
+    pub subjects: Vec<String>,
+    pub bookshelves: Vec<String>,
+    pub languages: Vec<String>,
+    /// `(mime type, url)` pairs
+    pub formats: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogImportProgress {
+    pub imported: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogImportSummary {
+    pub source: String,
+    pub imported: usize,
+    pub skipped: usize,
+    pub total_works: i64,
+}
+
+// ============================================================================
+// CSV
+// ============================================================================
+
+/// Parses `pg_catalog.csv`
+/// (`Text#,Type,Issued,Title,Language,Authors,Subjects,LoCC,Bookshelves`).
+/// The CSV has no file list, so formats are the standard Gutenberg URLs.
+pub fn parse_catalog_csv<R: Read>(
+    reader: R,
+) -> impl Iterator<Item = Result<Option<CatalogWork>, CatalogError>> {
+    csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(reader)
+        .into_records()
+        .map(|record| Ok(work_from_csv_record(&record?)))
+}
+
+fn work_from_csv_record(record: &csv::StringRecord) -> Option<CatalogWork> {
+    let field = |i: usize| record.get(i).map(str::trim).unwrap_or_default();
+    let id = field(0).parse::<i64>().ok()?;
+    let media_type = field(1);
+
+    Some(CatalogWork {
+        id,
+        title: collapse_title(field(3)),
+        issued: non_empty(field(2)),
+        media_type: media_type.to_string(),
+        copyright: None,
+        download_count: None,
+        agents: split_list(field(5)).map(parse_csv_agent).collect(),
+        subjects: split_list(field(6)).map(str::to_string).collect(),
+        bookshelves: split_list(field(8)).map(str::to_string).collect(),
+        languages: split_list(field(4)).map(str::to_string).collect(),
+        formats: if media_type == "Text" {
+            standard_formats(id)
+        } else {
+            Vec::new()
+        },
+    })
+}
+
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value.split(';').map(str::trim).filter(|s| !s.is_empty())
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+fn collapse_title(title: &str) -> String {
+    title.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Parses `Shakespeare, William, 1564-1616 [Editor]`
+fn parse_csv_agent(value: &str) -> CatalogAgent {
+    let (value, role) = match value.rfind('[') {
+        Some(start) if value.ends_with(']') => (
+            value[..start].trim(),
+            value[start + 1..value.len() - 1].trim().to_lowercase(),
+        ),
+        _ => (value, "author".to_string()),
+    };
+
+    let (name, years) = match value.rsplit_once(',') {
+        Some((name, years)) if looks_like_years(years) => (name.trim(), Some(years.trim())),
+        _ => (value, None),
+    };
+    let (birth_year, death_year) = years
+        .and_then(|years| years.split_once('-'))
+        .map_or((None, None), |(birth, death)| {
+            (parse_year(birth), parse_year(death))
+        });
+
+    CatalogAgent {
+        name: name.to_string(),
+        birth_year,
+        death_year,
+        role,
+    }
+}
+
+fn looks_like_years(value: &str) -> bool {
+    let value = value.trim();
+    value.contains('-') && value.chars().any(|c| c.is_ascii_digit())
+}
+
+/// `1564`, `1564?`, `751? BCE` → signed year
+fn parse_year(value: &str) -> Option<i32> {
+    let value = value.trim();
+    let (digits, bce) = value
+        .strip_suffix("BCE")
+        .or_else(|| value.strip_suffix("BC"))
+        .map_or((value, false), |rest| (rest, true));
+    let digits: String = digits.chars().filter(char::is_ascii_digit).collect();
+    let year = digits.parse::<i32>().ok()?;
+    Some(if bce { -year } else { year })
+}
+
+/// Download URLs Gutenberg serves for every text
+fn standard_formats(id: i64) -> Vec<(String, String)> {
+    [
+        (
+            "text/html",
+            format!("{GUTENBERG_BASE}/ebooks/{id}.html.images"),
+        ),
+        (
+            "application/epub+zip",
+            format!("{GUTENBERG_BASE}/ebooks/{id}.epub3.images"),
+        ),
+        (
+            "application/x-mobipocket-ebook",
+            format!("{GUTENBERG_BASE}/ebooks/{id}.kf8.images"),
+        ),
+        (
+            "text/plain; charset=utf-8",
+            format!("{GUTENBERG_BASE}/ebooks/{id}.txt.utf-8"),
+        ),
+        (
+            "application/rdf+xml",
+            format!("{GUTENBERG_BASE}/ebooks/{id}.rdf"),
+        ),
+        (
+            "image/jpeg",
+            format!("{GUTENBERG_BASE}/cache/epub/{id}/pg{id}.cover.medium.jpg"),
+        ),
+    ]
+    .into_iter()
+    .map(|(mime, url)| (mime.to_string(), url))
+    .collect()
+}
+
+// ============================================================================
+// RDF
+// ============================================================================
+
+/// Parses one `pg{id}.rdf` record. Returns `None` for files without an ebook.
+pub fn parse_rdf(xml: &str) -> Result<Option<CatalogWork>, CatalogError> {
+    let doc = roxmltree::Document::parse(xml)?;
+    let Some(ebook) = doc.descendants().find(|n| n.has_tag_name("ebook")) else {
+        return Ok(None);
+    };
+    let Some(id) = ebook
+        .attributes()
+        .find(|a| a.name() == "about")
+        .and_then(|a| a.value().rsplit('/').next())
+        .and_then(|id| id.parse::<i64>().ok())
+    else {
+        return Ok(None);
+    };
+
+    let mut work = CatalogWork {
+        id,
+        media_type: "Text".to_string(),
+        ..CatalogWork::default()
+    };
+
+    for child in ebook.children().filter(roxmltree::Node::is_element) {
+        match child.tag_name().name() {
+            "title" => work.title = collapse_title(&node_text(child)),
+            "issued" => work.issued = non_empty(node_text(child).trim()),
+            "downloads" => work.download_count = node_text(child).trim().parse().ok(),
+            "rights" => {
+                work.copyright = Some(!node_text(child).to_lowercase().contains("public domain"));
+            }
+            "type" => {
+                if let Some(value) = rdf_value(child) {
+                    work.media_type = value;
+                }
+            }
+            "language" => work.languages.extend(rdf_value(child)),
+            "bookshelf" => work.bookshelves.extend(rdf_value(child)),
+            "subject" => work.subjects.extend(rdf_value(child)),
+            "hasFormat" => work.formats.extend(rdf_format(child)),
+            "creator" => work.agents.extend(rdf_agent(child, "author")),
+            name => {
+                // MARC relator terms: `marcrel:trl`, `marcrel:edt`, ...
+                if child
+                    .tag_name()
+                    .namespace()
+                    .is_some_and(|ns| ns.contains("marc"))
+                {
+                    work.agents.extend(rdf_agent(child, marc_role(name)));
+                }
+            }
+        }
+    }
+    Ok(Some(work))
+}
+
+fn node_text(node: roxmltree::Node<'_, '_>) -> String {
+    node.descendants()
+        .filter(roxmltree::Node::is_text)
+        .filter_map(|n| n.text())
+        .collect()
+}
+
+/// `<x><rdf:Description><rdf:value>v</rdf:value></rdf:Description></x>`
+fn rdf_value(node: roxmltree::Node<'_, '_>) -> Option<String> {
+    node.descendants()
+        .find(|n| n.has_tag_name("value"))
+        .map(|n| node_text(n).trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+fn rdf_format(node: roxmltree::Node<'_, '_>) -> Option<(String, String)> {
+    let file = node.descendants().find(|n| n.has_tag_name("file"))?;
+    let url = file.attributes().find(|a| a.name() == "about")?.value();
+    let mime = file
+        .descendants()
+        .find(|n| n.has_tag_name("format"))
+        .and_then(rdf_value)?;
+    Some((mime, url.to_string()))
+}
+
+fn rdf_agent(node: roxmltree::Node<'_, '_>, role: &str) -> Option<CatalogAgent> {
+    let agent = node.descendants().find(|n| n.has_tag_name("agent"))?;
+    let field = |tag: &str| {
+        agent
+            .children()
+            .find(|n| n.has_tag_name(tag))
+            .map(|n| node_text(n).trim().to_string())
+    };
+    Some(CatalogAgent {
+        name: field("name").filter(|n| !n.is_empty())?,
+        birth_year: field("birthdate").and_then(|y| parse_year(&y)),
+        death_year: field("deathdate").and_then(|y| parse_year(&y)),
+        role: role.to_string(),
+    })
+}
+
+fn marc_role(code: &str) -> &str {
+    match code {
+        "trl" => "translator",
+        "edt" => "editor",
+        "ill" => "illustrator",
+        "com" => "compiler",
+        "aui" => "author of introduction",
+        "ann" => "annotator",
+        "ctb" => "contributor",
+        other => other,
+    }
+}
+
+// ============================================================================
+// IMPORT
+// ============================================================================
+
+/// Reads every work from `path`, sending batches to `tx`. Runs on a blocking thread.
+#[allow(clippy::case_sensitive_file_extension_comparisons)] // `name` is lowercased
+fn read_catalog(path: &Path, tx: &mpsc::Sender<Vec<CatalogWork>>) -> Result<usize, CatalogError> {
+    let mut batcher = Batcher {
+        tx,
+        batch: Vec::with_capacity(IMPORT_BATCH_SIZE),
+        skipped: 0,
+    };
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    if path.is_dir() {
+        read_rdf_dir(path, &mut batcher)?;
+    } else if name.ends_with(".csv") {
+        for work in parse_catalog_csv(BufReader::new(File::open(path)?)) {
+            batcher.push(work?)?;
+        }
+    } else if name.ends_with(".tar.bz2") {
+        let decoder = bzip2::read::BzDecoder::new(BufReader::new(File::open(path)?));
+        read_rdf_tar(decoder, &mut batcher)?;
+    } else if name.ends_with(".tar") {
+        read_rdf_tar(BufReader::new(File::open(path)?), &mut batcher)?;
+    } else if name.ends_with(".rdf") {
+        batcher.push(parse_rdf(&std::fs::read_to_string(path)?)?)?;
+    } else {
+        return Err(CatalogError::Unsupported(path.display().to_string()));
+    }
+    batcher.flush()?;
+    Ok(batcher.skipped)
+}
+
+struct Batcher<'a> {
+    tx: &'a mpsc::Sender<Vec<CatalogWork>>,
+    batch: Vec<CatalogWork>,
+    skipped: usize,
+}
+
+impl Batcher<'_> {
+    fn push(&mut self, work: Option<CatalogWork>) -> Result<(), CatalogError> {
+        match work {
+            Some(work) if !work.title.is_empty() => self.batch.push(work),
+            _ => self.skipped += 1,
+        }
+        if self.batch.len() >= IMPORT_BATCH_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), CatalogError> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        let batch = std::mem::replace(&mut self.batch, Vec::with_capacity(IMPORT_BATCH_SIZE));
+        self.tx
+            .blocking_send(batch)
+            .map_err(|_| CatalogError::Interrupted)
+    }
+}
+
+fn read_rdf_tar<R: Read>(reader: R, batcher: &mut Batcher<'_>) -> Result<(), CatalogError> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.path()?.to_string_lossy().ends_with(".rdf") {
+            continue;
+        }
+        let mut xml = String::new();
+        entry.read_to_string(&mut xml)?;
+        push_rdf(&xml, batcher)?;
+    }
+    Ok(())
+}
+
+fn read_rdf_dir(dir: &Path, batcher: &mut Batcher<'_>) -> Result<(), CatalogError> {
+    let mut pending: Vec<PathBuf> = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "rdf") {
+                push_rdf(&std::fs::read_to_string(&path)?, batcher)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A malformed record is counted as skipped rather than aborting the import
+fn push_rdf(xml: &str, batcher: &mut Batcher<'_>) -> Result<(), CatalogError> {
+    match parse_rdf(xml) {
+        Ok(work) => batcher.push(work),
+        Err(CatalogError::Xml(_)) => batcher.push(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Imports (or refreshes) the local catalog from a Gutenberg catalog file
+pub async fn import_catalog(
+    app_handle: &AppHandle,
+    pool: &Pool<Postgres>,
+    path: PathBuf,
+) -> Result<CatalogImportSummary, CatalogError> {
+    let source = path.display().to_string();
+    let (tx, mut rx) = mpsc::channel::<Vec<CatalogWork>>(4);
+    let reader = tauri::async_runtime::spawn_blocking(move || read_catalog(&path, &tx));
+
+    let mut imported = 0;
+    while let Some(batch) = rx.recv().await {
+        db::import_catalog_works(pool, &batch).await?;
+        imported += batch.len();
+        let _ = app_handle.emit(PROGRESS_EVENT, CatalogImportProgress { imported });
+    }
+
+    let skipped = reader
+        .await
+        .map_err(|e| CatalogError::Io(std::io::Error::other(e.to_string())))??;
+    let total_works = db::count_catalog_works(pool).await?;
+    println!("[Catalog] Imported {imported} works from {source} ({skipped} skipped)");
+
+    Ok(CatalogImportSummary {
+        source,
+        imported,
+        skipped,
+        total_works,
+    })
+}
+
+// ============================================================================
+// SEARCH
+// ============================================================================
+
+/// The 1-based page number of a catalog page URL
+fn page_number(url: &Url) -> i64 {
+    url.query_pairs()
+        .find(|(key, _)| key == "page")
+        .and_then(|(_, value)| value.parse::<i64>().ok())
+        .unwrap_or(1)
+        .max(1)
+}
+
+fn page_link(url: &Url, page: i64) -> String {
+    let mut link = url.clone();
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| key != "page")
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    link.set_query(None);
+    {
+        let mut qp = link.query_pairs_mut();
+        for (key, value) in pairs {
+            qp.append_pair(&key, &value);
+        }
+        if page > 1 {
+            qp.append_pair("page", &page.to_string());
+        }
+    }
+    link.to_string()
+}
+
+pub fn to_gutendex_book(work: CatalogWork) -> GutendexBook {
+    let (authors, others): (Vec<_>, Vec<_>) = work
+        .agents
+        .into_iter()
+        .partition(|agent| agent.role == "author");
+    let to_author = |agent: CatalogAgent| GutendexAuthor {
+        name: agent.name,
+        birth_year: agent.birth_year,
+        death_year: agent.death_year,
+    };
+
+    GutendexBook {
+        id: work.id,
+        title: work.title,
+        authors: authors.into_iter().map(to_author).collect(),
+        translators: others
+            .into_iter()
+            .filter(|agent| agent.role == "translator")
+            .map(to_author)
+            .collect(),
+        subjects: work.subjects,
+        bookshelves: work.bookshelves,
+        languages: work.languages,
+        summaries: Vec::new(),
+        media_type: Some(work.media_type),
+        copyright: work.copyright,
+        formats: work.formats.into_iter().collect(),
+        download_count: work.download_count,
+    }
+}
+
+/// Answers a Gutendex-style catalog URL from the local tables
+pub async fn search_local(
+    pool: &Pool<Postgres>,
+    url: &str,
+) -> Result<GutendexResponse, CatalogError> {
+    let url = Url::parse(url)?;
+    let query = CatalogQuery::from_url(&url);
+    let page = page_number(&url);
+    let (count, works) = db::search_catalog_works(pool, &query, page, PAGE_SIZE).await?;
+
+    let next = (page * PAGE_SIZE < count).then(|| page_link(&url, page + 1));
+    let previous = (page > 1).then(|| page_link(&url, page - 1));
+    Ok(GutendexResponse {
+        count,
+        next,
+        previous,
+        results: works.into_iter().map(to_gutendex_book).collect(),
+        cached: true,
+        stale: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_catalog_csv() {
+        let csv = "Text#,Type,Issued,Title,Language,Authors,Subjects,LoCC,Bookshelves\n\
+            1524,Text,1998-11-01,\"Hamlet, Prince of Denmark\",en,\"Shakespeare, William, 1564-1616\",\"Tragedies; Princes -- Drama\",PR,\"Browsing: Literature; Plays\"\n\
+            9999,Sound,2004-01-01,Some Recording,en,,,,\n\
+            2000,Text,2001-01-01,\"Don Quijote\",es,\"Cervantes Saavedra, Miguel de, 1547-1616; Ormsby, John, 1829-1895 [Translator]\",,,\n";
+        let works: Vec<CatalogWork> = parse_catalog_csv(csv.as_bytes())
+            .filter_map(|w| w.unwrap())
+            .collect();
+
+        assert_eq!(works.len(), 3);
+        let hamlet = &works[0];
+        assert_eq!(hamlet.id, 1524);
+        assert_eq!(hamlet.title, "Hamlet, Prince of Denmark");
+        assert_eq!(hamlet.languages, vec!["en"]);
+        assert_eq!(hamlet.subjects, vec!["Tragedies", "Princes -- Drama"]);
+        assert_eq!(hamlet.bookshelves, vec!["Browsing: Literature", "Plays"]);
+        assert_eq!(
+            hamlet.agents,
+            vec![CatalogAgent {
+                name: "Shakespeare, William".to_string(),
+                birth_year: Some(1564),
+                death_year: Some(1616),
+                role: "author".to_string(),
+            }]
+        );
+        assert!(hamlet
+            .formats
+            .iter()
+            .any(|(mime, url)| mime == "application/x-mobipocket-ebook"
+                && url.ends_with("/1524.kf8.images")));
+
+        assert!(works[1].formats.is_empty());
+        assert_eq!(works[2].agents[1].role, "translator");
+        assert_eq!(works[2].agents[1].name, "Ormsby, John");
+    }
+
+    #[test]
+    fn test_parse_year() {
+        assert_eq!(parse_year("1564"), Some(1564));
+        assert_eq!(parse_year("1564?"), Some(1564));
+        assert_eq!(parse_year("751? BCE"), Some(-751));
+        assert_eq!(parse_year(""), None);
+    }
+
+    #[test]
+    fn test_parse_rdf() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<rdf:RDF xmlns:dcterms="http://purl.org/dc/terms/" xmlns:pgterms="http://www.gutenberg.org/2009/pgterms/"
+  xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#" xmlns:marcrel="http://id.loc.gov/vocabulary/relators/"
+  xmlns:dcam="http://purl.org/dc/dcam/">
+  <pgterms:ebook rdf:about="ebooks/1524">
+    <dcterms:title>Hamlet, Prince of Denmark</dcterms:title>
+    <dcterms:issued rdf:datatype="http://www.w3.org/2001/XMLSchema#date">1998-11-01</dcterms:issued>
+    <dcterms:rights>Public domain in the USA.</dcterms:rights>
+    <pgterms:downloads rdf:datatype="http://www.w3.org/2001/XMLSchema#integer">12345</pgterms:downloads>
+    <dcterms:creator>
+      <pgterms:agent rdf:about="2009/agents/65">
+        <pgterms:name>Shakespeare, William</pgterms:name>
+        <pgterms:birthdate rdf:datatype="http://www.w3.org/2001/XMLSchema#integer">1564</pgterms:birthdate>
+        <pgterms:deathdate rdf:datatype="http://www.w3.org/2001/XMLSchema#integer">1616</pgterms:deathdate>
+      </pgterms:agent>
+    </dcterms:creator>
+    <dcterms:language><rdf:Description><rdf:value rdf:datatype="http://purl.org/dc/terms/RFC4646">en</rdf:value></rdf:Description></dcterms:language>
+    <dcterms:subject><rdf:Description><dcam:memberOf rdf:resource="http://purl.org/dc/terms/LCSH"/><rdf:value>Tragedies</rdf:value></rdf:Description></dcterms:subject>
+    <pgterms:bookshelf><rdf:Description><rdf:value>Plays</rdf:value></rdf:Description></pgterms:bookshelf>
+    <dcterms:hasFormat>
+      <pgterms:file rdf:about="https://www.gutenberg.org/ebooks/1524.kf8.images">
+        <dcterms:format><rdf:Description><rdf:value rdf:datatype="http://purl.org/dc/terms/IMT">application/x-mobipocket-ebook</rdf:value></rdf:Description></dcterms:format>
+      </pgterms:file>
+    </dcterms:hasFormat>
+    <dcterms:type><rdf:Description><rdf:value>Text</rdf:value></rdf:Description></dcterms:type>
+  </pgterms:ebook>
+</rdf:RDF>"#;
+        let work = parse_rdf(xml).unwrap().unwrap();
+        assert_eq!(work.id, 1524);
+        assert_eq!(work.title, "Hamlet, Prince of Denmark");
+        assert_eq!(work.issued.as_deref(), Some("1998-11-01"));
+        assert_eq!(work.copyright, Some(false));
+        assert_eq!(work.download_count, Some(12345));
+        assert_eq!(work.agents[0].name, "Shakespeare, William");
+        assert_eq!(work.agents[0].death_year, Some(1616));
+        assert_eq!(work.languages, vec!["en"]);
+        assert_eq!(work.subjects, vec!["Tragedies"]);
+        assert_eq!(work.bookshelves, vec!["Plays"]);
+        assert_eq!(
+            work.formats,
+            vec![(
+                "application/x-mobipocket-ebook".to_string(),
+                "https://www.gutenberg.org/ebooks/1524.kf8.images".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_local_query_and_page_links() {
+        let url = Url::parse(
+            "https://gutendex.com/books/?search=Shakespeare%2C%20William&topic=tragedy&languages=en,FR&page=2",
+        )
+        .unwrap();
+        let query = CatalogQuery::from_url(&url);
+        assert_eq!(query.search.as_deref(), Some("Shakespeare, William"));
+        assert_eq!(query.topic.as_deref(), Some("tragedy"));
+        assert_eq!(query.languages, vec!["en", "fr"]);
+        assert_eq!(page_number(&url), 2);
+
+        assert_eq!(
+            page_link(&url, 1),
+            "https://gutendex.com/books/?search=Shakespeare%2C+William&topic=tragedy&languages=en%2CFR"
+        );
+        assert!(page_link(&url, 3).ends_with("&page=3"));
+    }
+}
@@ -0,0 +1,285 @@
+//! Book covers
+//!
+//! Covers are cached beside a book's extracted images as `cover.{ext}` plus a
+//! small JPEG thumbnail for library grids. The MOBI's own cover (EXTH record
+//! 201) is preferred so batch downloads don't cost a second request per book;
+//! otherwise the catalog's cover URL is fetched. Books with neither get a
+//! generated SVG placeholder showing the title and author, stored as
+//! `cover-placeholder.svg` so the catalog isn't asked again on every request.
+
+use crate::books::{self, BooksError};
+use crate::db::postgres::DbError;
+use crate::db::{self, BookCover};
+use crate::mirror::GutenbergMirror;
+use image::{codecs::jpeg::JpegEncoder, ImageError};
+use sqlx::{Pool, Postgres};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+use thiserror::Error;
+
+const THUMBNAIL_WIDTH: u32 = 160;
+const THUMBNAIL_HEIGHT: u32 = 240;
+const THUMBNAIL_QUALITY: u8 = 80;
+const THUMBNAIL_FILE: &str = "cover-thumb.jpg";
+const PLACEHOLDER_FILE: &str = "cover-placeholder.svg";
+const COVER_EXTENSIONS: [&str; 3] = ["jpg", "png", "gif"];
+
+/// Placeholder title lines are wrapped at this many characters
+const PLACEHOLDER_LINE_CHARS: usize = 16;
+const PLACEHOLDER_MAX_LINES: usize = 6;
+
+#[derive(Debug, Error)]
+pub enum CoverError {
+    #[error("Image error: {0}")]
+    Image(#[from] ImageError),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Books(#[from] BooksError),
+
+    #[error(transparent)]
+    Db(#[from] DbError),
+
+    #[error("Not an image: {0}")]
+    Unsupported(String),
+}
+
+fn cover_dir(app_handle: &AppHandle, gutenberg_id: i64) -> Result<PathBuf, BooksError> {
+    Ok(books::books_dir(app_handle)?.join(format!("{gutenberg_id}_assets")))
+}
+
+/// The cached cover (or its thumbnail) in `dir`, if one has been stored
+fn cached_cover(dir: &Path, thumbnail: bool) -> Option<PathBuf> {
+    if thumbnail {
+        return Some(dir.join(THUMBNAIL_FILE)).filter(|p| p.exists());
+    }
+    COVER_EXTENSIONS
+        .iter()
+        .map(|ext| dir.join(format!("cover.{ext}")))
+        .find(|p| p.exists())
+}
+
+/// Scales a cover down to fit the thumbnail box and re-encodes it as JPEG
+pub fn make_thumbnail(bytes: &[u8]) -> Result<Vec<u8>, CoverError> {
+    let thumb = image::load_from_memory(bytes)?
+        .thumbnail(THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT)
+        .to_rgb8();
+    let mut out = Vec::new();
+    JpegEncoder::new_with_quality(&mut out, THUMBNAIL_QUALITY).encode_image(&thumb)?;
+    Ok(out)
+}
+
+/// Writes `bytes` as the cover in `dir`, replacing any previous cover
+pub fn store_cover(dir: &Path, bytes: &[u8]) -> Result<(), CoverError> {
+    let ext = books::detect_image_format(bytes)
+        .ok_or_else(|| CoverError::Unsupported(format!("{} bytes", bytes.len())))?;
+    let thumbnail = make_thumbnail(bytes)?;
+
+    fs::create_dir_all(dir)?;
+    for old in COVER_EXTENSIONS {
+        let _ = fs::remove_file(dir.join(format!("cover.{old}")));
+    }
+    let _ = fs::remove_file(dir.join(PLACEHOLDER_FILE));
+    fs::write(dir.join(format!("cover.{ext}")), bytes)?;
+    fs::write(dir.join(THUMBNAIL_FILE), thumbnail)?;
+    Ok(())
+}
+
+/// Caches a book's cover from its MOBI or, failing that, its cover URL.
+/// Returns whether a cover was stored.
+pub async fn cache_cover(
+    app_handle: &AppHandle,
+    mirror: &GutenbergMirror,
+    gutenberg_id: i64,
+    cover_url: Option<&str>,
+    mobi_bytes: Option<&[u8]>,
+) -> Result<bool, CoverError> {
+    let bytes = match mobi_bytes.and_then(books::extract_mobi_cover) {
+        Some(bytes) => bytes,
+        None => match cover_url {
+            Some(url) => books::fetch_file(url, mirror).await?,
+            None => return Ok(false),
+        },
+    };
+
+    let dir = cover_dir(app_handle, gutenberg_id)?;
+    tauri::async_runtime::spawn_blocking(move || store_cover(&dir, &bytes))
+        .await
+        .map_err(|e| BooksError::Other(format!("Cover thread failed: {e}")))??;
+    println!("[Covers] Cached cover for book {gutenberg_id}");
+    Ok(true)
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Greedy word wrap; the last line is cut with an ellipsis when text overflows
+fn wrap_words(text: &str, width: usize, max_lines: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut overflow = false;
+    for word in text.split_whitespace() {
+        let full = lines.len() == max_lines;
+        match lines.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= width => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ if full => {
+                overflow = true;
+                break;
+            }
+            _ => lines.push(word.chars().take(width).collect()),
+        }
+    }
+    if overflow {
+        if let Some(last) = lines.last_mut() {
+            last.push('…');
+        }
+    }
+    lines
+}
+
+/// A 2:3 SVG cover with the title and author on a colour picked from the title
+pub fn placeholder_svg(title: &str, authors: &str) -> String {
+    let hue = title
+        .bytes()
+        .fold(0u32, |h, b| h.wrapping_mul(31).wrapping_add(u32::from(b)))
+        % 360;
+
+    let title_lines = wrap_words(title, PLACEHOLDER_LINE_CHARS, PLACEHOLDER_MAX_LINES);
+    let mut tspans = String::new();
+    for (i, line) in title_lines.iter().enumerate() {
+        let dy = if i == 0 { "0" } else { "1.2em" };
+        let _ = write!(
+            tspans,
+            r#"<tspan x="100" dy="{dy}">{}</tspan>"#,
+            escape_xml(line)
+        );
+    }
+    let author = wrap_words(authors, PLACEHOLDER_LINE_CHARS + 8, 1)
+        .pop()
+        .unwrap_or_default();
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="300" viewBox="0 0 200 300"><rect width="200" height="300" fill="hsl({hue},35%,32%)"/><rect x="12" y="12" width="176" height="276" fill="none" stroke="hsl({hue},35%,70%)" stroke-width="2"/><text x="100" y="90" fill="#fff" font-family="Georgia,serif" font-size="18" font-weight="bold" text-anchor="middle">{tspans}</text><text x="100" y="262" fill="hsl({hue},35%,85%)" font-family="Georgia,serif" font-size="12" text-anchor="middle">{}</text></svg>"##,
+        escape_xml(&author)
+    )
+}
+
+fn data_url(path: &Path, bytes: &[u8]) -> String {
+    let mime = match path.extension().and_then(|s| s.to_str()) {
+        Some("png") => "image/png",
+        Some("gif") => "image/gif",
+        Some("svg") => "image/svg+xml",
+        _ => "image/jpeg",
+    };
+    format!("data:{mime};base64,{}", data_encoding::BASE64.encode(bytes))
+}
+
+/// A book's cover (or thumbnail) as a data URL. Books downloaded before covers
+/// were cached get theirs cached on first request; anything without a cover
+/// gets the placeholder.
+pub async fn get_book_cover(
+    app_handle: &AppHandle,
+    pool: &Pool<Postgres>,
+    mirror: &GutenbergMirror,
+    book: &BookCover,
+    thumbnail: bool,
+) -> Result<String, CoverError> {
+    let gutenberg_id = book.gutenberg_id.get();
+    let dir = cover_dir(app_handle, gutenberg_id)?;
+    let placeholder = dir.join(PLACEHOLDER_FILE);
+
+    if cached_cover(&dir, thumbnail).is_none() && !placeholder.exists() {
+        let mobi_data = db::get_book_mobi_data(pool, book.id.get()).await?;
+        if let Err(e) = cache_cover(
+            app_handle,
+            mirror,
+            gutenberg_id,
+            book.cover_url.as_deref(),
+            mobi_data.as_deref(),
+        )
+        .await
+        {
+            println!("[Covers] Failed to cache cover for book {gutenberg_id}: {e}");
+        }
+        if cached_cover(&dir, thumbnail).is_none() {
+            tokio::fs::create_dir_all(&dir).await?;
+            tokio::fs::write(&placeholder, placeholder_svg(&book.title, &book.authors)).await?;
+        }
+    }
+
+    let path = cached_cover(&dir, thumbnail).unwrap_or(placeholder);
+    let bytes = tokio::fs::read(&path).await?;
+    Ok(data_url(&path, &bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, RgbImage};
+    use std::io::Cursor;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut out = Cursor::new(Vec::new());
+        RgbImage::from_pixel(width, height, image::Rgb([200, 40, 40]))
+            .write_to(&mut out, ImageFormat::Png)
+            .unwrap();
+        out.into_inner()
+    }
+
+    #[test]
+    fn test_thumbnail_fits_box() {
+        let thumb = image::load_from_memory(&make_thumbnail(&png(600, 1200)).unwrap()).unwrap();
+        assert_eq!((thumb.width(), thumb.height()), (120, 240));
+        assert!(make_thumbnail(b"not an image").is_err());
+    }
+
+    #[test]
+    fn test_store_cover_replaces_previous() {
+        let dir = std::env::temp_dir().join(format!("covers-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(PLACEHOLDER_FILE), "<svg/>").unwrap();
+        store_cover(&dir, &png(40, 60)).unwrap();
+        assert!(!dir.join(PLACEHOLDER_FILE).exists());
+        assert_eq!(cached_cover(&dir, false), Some(dir.join("cover.png")));
+        assert_eq!(cached_cover(&dir, true), Some(dir.join(THUMBNAIL_FILE)));
+        assert!(store_cover(&dir, b"GIF?").is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_placeholder_svg() {
+        let svg = placeholder_svg(
+            "The Strange Case of Dr Jekyll & Mr Hyde",
+            "Stevenson, Robert Louis",
+        );
+        assert!(svg.contains(r#"<tspan x="100" dy="0">The Strange Case</tspan>"#));
+        assert!(svg.contains(">of Dr Jekyll &amp;</tspan>"));
+        assert!(svg.contains(">Stevenson, Robert Louis</text>"));
+        assert_eq!(
+            svg,
+            placeholder_svg(
+                "The Strange Case of Dr Jekyll & Mr Hyde",
+                "Stevenson, Robert Louis"
+            )
+        );
+    }
+
+    #[test]
+    fn test_wrap_words_truncates() {
+        assert_eq!(
+            wrap_words("one two three four", 7, 2),
+            vec!["one two".to_string(), "three…".to_string()]
+        );
+        assert!(wrap_words("   ", 10, 3).is_empty());
+    }
+}
@@ -4,7 +4,15 @@
 
 pub mod postgres;
 
-use crate::types::{BookId, GutenbergId, HighlightId, MessageId, MessageRole, ThreadId};
+use crate::anchoring::TextSelectors;
+use crate::gutendex::CatalogQuery;
+use crate::locator::Locator;
+use crate::types::{
+    BookId, BookmarkId, CatalogId, CollectionId, Color, GutenbergId, HighlightId, HighlightStyle,
+    LexiconEntryId, MessageId, MessageRole, PositionChange, PositionEntryId, ReadingStatus,
+    SessionId, Tag, ThreadId, TrashKind,
+};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 
 // ============================================================================
@@ -24,14 +32,138 @@ pub struct Book {
     pub html_content: Option<String>,
     pub first_image_index: Option<i32>,
     pub created_at: String,
+    pub status: Option<ReadingStatus>,
+    pub status_updated_at: Option<String>,
+    /// First time the status was set to reading
+    pub started_at: Option<String>,
+    /// Last time the status was set to finished
+    pub finished_at: Option<String>,
+    pub last_opened_at: Option<String>,
+}
+
+/// A book's title and authors, for matching imported annotations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookTitle {
+    pub id: BookId,
+    pub title: String,
+    pub authors: String,
+}
+
+/// What's needed to find or draw a book's cover
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookCover {
+    pub id: BookId,
+    pub gutenberg_id: GutenbergId,
+    pub title: String,
+    pub authors: String,
+    pub cover_url: Option<String>,
+}
+
+/// A book's metadata without its content, for library backups
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookRecord {
+    pub id: BookId,
+    pub gutenberg_id: GutenbergId,
+    pub title: String,
+    pub authors: String,
+    pub publication_year: Option<i32>,
+    pub cover_url: Option<String>,
+    pub first_image_index: Option<i32>,
+    pub languages: Vec<String>,
+    pub source_url: Option<String>,
+    /// Hex SHA-256 of `mobi_data`
+    pub content_hash: Option<String>,
+    pub status: Option<ReadingStatus>,
+    pub status_updated_at: Option<String>,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    pub last_opened_at: Option<String>,
+    pub created_at: String,
+}
+
+/// Where a library book's file was downloaded from, for update checks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookSource {
+    pub book_id: BookId,
+    pub gutenberg_id: GutenbergId,
+    pub title: String,
+    /// `None` for books downloaded before sources were recorded
+    pub source_url: Option<String>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// Hex SHA-256 of `mobi_data`
+    pub content_hash: Option<String>,
+    pub checked_at: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BookPosition {
-    pub cfi: String,
+    /// Positions saved before locators were stored carry only their CFI
+    #[serde(flatten)]
+    pub locator: Locator,
+    pub updated_at: String,
+}
+
+/// A marked place in a book, without a text range
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub id: BookmarkId,
+    pub book_id: BookId,
+    #[serde(flatten)]
+    pub locator: Locator,
+    pub label: Option<String>,
+    pub color: Option<Color>,
+    pub created_at: String,
     pub updated_at: String,
 }
 
+/// A position the reader left, kept so it can be returned to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PositionEntry {
+    pub id: PositionEntryId,
+    pub book_id: BookId,
+    #[serde(flatten)]
+    pub locator: Locator,
+    pub change: PositionChange,
+    pub created_at: String,
+}
+
+/// One stretch of reading, from opening a book to closing it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadingSession {
+    pub id: SessionId,
+    pub book_id: BookId,
+    pub started_at: String,
+    /// Time of the last heartbeat while the session is open
+    pub ended_at: String,
+    pub start_locator: Locator,
+    pub end_locator: Locator,
+    pub words_read: i64,
+    pub duration_secs: i64,
+    pub ended: bool,
+    /// Seconds since `ended_at`, as of the query
+    #[serde(skip)]
+    pub idle_secs: i64,
+}
+
+/// Reading totals for one local day
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadingDay {
+    pub day: NaiveDate,
+    pub seconds: i64,
+    pub words: i64,
+    pub words_per_minute: Option<f64>,
+    pub book_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FinishedBook {
+    pub book_id: BookId,
+    pub title: String,
+    /// Day of the first session that reached the end
+    pub finished_on: NaiveDate,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Highlight {
     pub id: HighlightId,
@@ -44,6 +176,94 @@ pub struct Highlight {
     pub note: Option<String>,
     pub created_at: String,
     pub updated_at: String,
+    pub color: Option<Color>,
+    pub style: HighlightStyle,
+    pub tags: Vec<Tag>,
+    /// Text just before and after `text` in the book, for re-anchoring
+    pub quote_prefix: String,
+    pub quote_suffix: String,
+    /// Char range of `text` in the book's plain text, when it was found
+    pub text_start: Option<i64>,
+    pub text_end: Option<i64>,
+    /// The text could no longer be found after the book's HTML changed
+    pub orphaned: bool,
+}
+
+#[cfg(test)]
+impl Highlight {
+    /// A plain highlight of `text` with no note, tags or text position
+    pub fn fixture(id: i64, book_id: i64, text: &str) -> Self {
+        Self {
+            id: HighlightId::new(id),
+            book_id: BookId::new(book_id),
+            start_path: "[0]".to_string(),
+            start_offset: 0,
+            end_path: "[0]".to_string(),
+            end_offset: 0,
+            text: text.to_string(),
+            note: None,
+            created_at: "2024-05-01 10:00:00+00".to_string(),
+            updated_at: "2024-05-01 10:00:00+00".to_string(),
+            color: None,
+            style: HighlightStyle::default(),
+            tags: Vec::new(),
+            quote_prefix: String::new(),
+            quote_suffix: String::new(),
+            text_start: None,
+            text_end: None,
+            orphaned: false,
+        }
+    }
+}
+
+/// Fields of a highlight to be created
+#[derive(Debug, Clone, Default)]
+pub struct NewHighlight {
+    pub book_id: i64,
+    pub start_path: String,
+    pub start_offset: i64,
+    pub end_path: String,
+    pub end_offset: i64,
+    pub text: String,
+    pub note: Option<String>,
+    pub color: Option<Color>,
+    pub style: HighlightStyle,
+    pub selectors: Option<TextSelectors>,
+}
+
+/// A highlight with its book, for listings across the library
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryHighlight {
+    #[serde(flatten)]
+    pub highlight: Highlight,
+    pub book_title: String,
+    pub book_authors: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighlightTagCount {
+    pub tag: Tag,
+    pub highlight_count: i64,
+}
+
+/// Filters for highlight listings; unset fields match every highlight
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HighlightFilter {
+    /// Normalized like `Color::new` before matching
+    pub color: Option<String>,
+    /// Normalized like `Tag::new` before matching
+    pub tag: Option<String>,
+    pub style: Option<HighlightStyle>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HighlightSort {
+    #[default]
+    Date,
+    /// By book title, then in order of creation within each book
+    Book,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -77,35 +297,255 @@ pub struct BookChatThread {
     pub updated_at: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LexiconEntry {
+    pub id: LexiconEntryId,
+    pub term: String,
+    pub replacement: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CastMember {
+    pub book_id: BookId,
+    pub character: String,
+    pub voice_id: String,
+    pub updated_at: String,
+}
+
+/// A saved catalog on the library page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Catalog {
+    pub id: CatalogId,
+    pub key: String,
+    pub name: String,
+    /// Stored in the `filters` column
+    pub query: CatalogQuery,
+    pub position: i32,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// A user-defined group of library books
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Collection {
+    pub id: CollectionId,
+    pub name: String,
+    pub position: i32,
+    pub book_count: i64,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagCount {
+    pub tag: Tag,
+    pub book_count: i64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BookSort {
+    #[default]
+    Title,
+    Author,
+    DateAdded,
+    LastOpened,
+    Status,
+}
+
+/// Filters for `list_books_filtered`; unset fields match every book
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BookListFilter {
+    pub collection_id: Option<CollectionId>,
+    /// Normalized like `Tag::new` before matching
+    pub tag: Option<String>,
+    pub status: Option<ReadingStatus>,
+    /// Case-insensitive substring of the authors
+    pub author: Option<String>,
+    pub sort: BookSort,
+    pub descending: bool,
+}
+
+/// A cached HTTP response body with its validators
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpCacheEntry {
+    pub url: String,
+    pub body: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub fetched_at: String,
+    /// Younger than the TTL it was looked up with
+    pub fresh: bool,
+}
+
+/// Progress syncing with a remote
+#[derive(Debug, Clone, Default)]
+pub struct SyncRemoteState {
+    /// The remote's pull cursor after the last sync
+    pub cursor: Option<String>,
+    /// When the last sync started; local changes after it haven't been pushed
+    pub synced_at: Option<DateTime<Utc>>,
+}
+
+/// A trashed book, highlight, chat thread or set of book messages, restorable
+/// until purged. A trashed book's highlights and chats aren't listed
+/// separately; they go and come back with it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashItem {
+    pub kind: TrashKind,
+    /// The book, highlight or thread ID; the trash batch for messages
+    pub id: i64,
+    pub book_id: BookId,
+    pub book_title: String,
+    /// Authors, highlight text, thread title or message count and thread
+    pub label: String,
+    pub deleted_at: String,
+}
+
+/// What a purge deleted for good
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PurgedTrash {
+    pub books: u64,
+    pub highlights: u64,
+    pub threads: u64,
+    pub messages: u64,
+    /// Purged books, whose assets on disk go with them
+    #[serde(skip)]
+    pub gutenberg_ids: Vec<GutenbergId>,
+}
+
 // ============================================================================
 // RE-EXPORTS: All operations delegate to postgres
 // ============================================================================
 
 pub use postgres::add_book_message;
+pub use postgres::add_book_tag;
+pub use postgres::add_book_to_collection;
 pub use postgres::add_highlight_message;
+pub use postgres::add_highlight_tag;
+pub use postgres::apply_sync_delete;
+pub use postgres::apply_sync_fields;
 pub use postgres::clear_default_book_messages;
+pub use postgres::clear_http_cache;
+pub use postgres::count_catalog_works;
 pub use postgres::create_book_chat_thread;
+pub use postgres::create_bookmark;
+pub use postgres::create_catalog;
+pub use postgres::create_collection;
 pub use postgres::create_highlight;
+pub use postgres::create_lexicon_entry;
+pub use postgres::database_now;
+pub use postgres::delete_book_cast_member;
 pub use postgres::delete_book_chat_thread;
 pub use postgres::delete_book_message;
 pub use postgres::delete_book_messages;
 pub use postgres::delete_book_thread_messages;
+pub use postgres::delete_bookmark;
+pub use postgres::delete_catalog;
+pub use postgres::delete_collection;
 pub use postgres::delete_highlight;
+pub use postgres::delete_lexicon_entry;
+pub use postgres::delete_position_entry;
+pub use postgres::delete_tag;
+pub use postgres::end_reading_session;
+pub use postgres::extend_reading_session;
+pub use postgres::find_restore_book;
 pub use postgres::get_book;
+pub use postgres::get_book_content_hash;
+pub use postgres::get_book_cover;
+pub use postgres::get_book_mobi_data;
 pub use postgres::get_book_position;
+pub use postgres::get_catalog_by_key;
+pub use postgres::get_highlight;
+pub use postgres::get_http_cache_entry;
 pub use postgres::get_pool;
+pub use postgres::get_position_entry;
+pub use postgres::get_reading_session;
 pub use postgres::get_setting;
+pub use postgres::get_sync_remote;
 pub use postgres::get_thread_max_citation_index;
-pub use postgres::hard_delete_book;
+pub use postgres::import_catalog_works;
 pub use postgres::init;
+pub use postgres::latest_position_entry;
+pub use postgres::list_all_book_messages;
+pub use postgres::list_all_highlights;
+pub use postgres::list_book_cast;
 pub use postgres::list_book_chat_threads;
+pub use postgres::list_book_collections;
+pub use postgres::list_book_gutenberg_ids;
 pub use postgres::list_book_messages;
+pub use postgres::list_book_records;
+pub use postgres::list_book_sources;
+pub use postgres::list_book_tags;
+pub use postgres::list_book_titles;
+pub use postgres::list_bookmarks;
 pub use postgres::list_books;
+pub use postgres::list_books_filtered;
+pub use postgres::list_catalogs;
+pub use postgres::list_collections;
+pub use postgres::list_export_highlights;
+pub use postgres::list_finished_books;
 pub use postgres::list_highlight_messages;
+pub use postgres::list_highlight_tags;
 pub use postgres::list_highlights;
+pub use postgres::list_lexicon_entries;
+pub use postgres::list_messages_for_highlights;
+pub use postgres::list_position_history;
+pub use postgres::list_reading_dates;
+pub use postgres::list_reading_days;
+pub use postgres::list_reading_sessions;
+pub use postgres::list_sync_changes;
+pub use postgres::list_sync_pending;
+pub use postgres::list_tags;
+pub use postgres::list_trash;
+pub use postgres::load_sync_record;
+pub use postgres::local_today;
+pub use postgres::purge_expired_trash;
+pub use postgres::purge_trash_item;
+pub use postgres::push_position_entry;
+pub use postgres::put_http_cache_entry;
+pub use postgres::query_books;
+pub use postgres::remove_book_from_collection;
+pub use postgres::remove_book_tag;
+pub use postgres::remove_highlight_tag;
 pub use postgres::rename_book_chat_thread;
+pub use postgres::rename_collection;
+pub use postgres::rename_tag;
+pub use postgres::restore_book;
+pub use postgres::restore_book_message;
+pub use postgres::restore_book_position;
+pub use postgres::restore_bookmark;
+pub use postgres::restore_chat_thread;
+pub use postgres::restore_highlight;
+pub use postgres::restore_highlight_message;
+pub use postgres::restore_setting;
+pub use postgres::restore_trash_item;
+pub use postgres::save_sync_pending;
+pub use postgres::save_sync_remote;
+pub use postgres::search_catalog_works;
+pub use postgres::set_book_cast_voice;
+pub use postgres::set_book_languages;
 pub use postgres::set_book_position;
+pub use postgres::set_book_source;
+pub use postgres::set_book_status;
+pub use postgres::set_bookmark_locator;
+pub use postgres::set_highlight_selectors;
+pub use postgres::set_highlight_style;
 pub use postgres::set_setting;
 pub use postgres::set_thread_last_cfi;
+pub use postgres::start_reading_session;
+pub use postgres::sync_device_id;
+pub use postgres::touch_book_checked;
+pub use postgres::touch_book_opened;
+pub use postgres::touch_http_cache_entry;
+pub use postgres::trash_book;
+pub use postgres::untrash_book;
+pub use postgres::update_book_content;
+pub use postgres::update_bookmark;
+pub use postgres::update_catalog;
 pub use postgres::update_highlight_note;
+pub use postgres::update_lexicon_entry;
 pub use postgres::upsert_book;
@@ -3,14 +3,36 @@
 //! This module uses runtime SQL queries (not compile-time checked macros)
 //! to avoid requiring `DATABASE_URL` at build time.
 
+use chrono::{DateTime, NaiveDate, Utc};
 use once_cell::sync::OnceCell;
+use serde_json::Value;
 use sqlx::postgres::PgPoolOptions;
-use sqlx::{Pool, Postgres, Row};
+use sqlx::types::Json;
+use sqlx::{Pool, Postgres, QueryBuilder, Row};
+use std::collections::{BTreeMap, HashMap};
 use std::env;
+use std::fmt::Write as _;
 use thiserror::Error;
 
-use super::{Book, BookChatThread, BookMessage, BookPosition, Highlight, HighlightMessage};
-use crate::types::{BookId, GutenbergId, HighlightId, MessageId, MessageRole, ThreadId};
+use super::{
+    Book, BookChatThread, BookCover, BookListFilter, BookMessage, BookPosition, BookRecord,
+    BookSort, BookSource, BookTitle, Bookmark, CastMember, Catalog, Collection, FinishedBook,
+    Highlight, HighlightFilter, HighlightMessage, HighlightSort, HighlightTagCount, HttpCacheEntry,
+    LexiconEntry, LibraryHighlight, NewHighlight, PositionEntry, PurgedTrash, ReadingDay,
+    ReadingSession, SyncRemoteState, TagCount, TrashItem,
+};
+use crate::anchoring::TextSelectors;
+use crate::catalog::{CatalogAgent, CatalogWork};
+use crate::gutendex::{default_catalogs, CatalogQuery, CatalogSort};
+use crate::library::{BookCursor, BookFilter, BookPage, BookQuerySort, BookSummary};
+use crate::locator::Locator;
+use crate::normalize::DEFAULT_LEXICON;
+use crate::sync::{FieldValue, LocalRecord, SyncRecord, SyncTable, SYNC_TABLES};
+use crate::types::{
+    BookId, BookmarkId, CatalogId, Cfi, CollectionId, Color, GutenbergId, HighlightId,
+    HighlightStyle, LexiconEntryId, MessageId, MessageRole, PositionChange, PositionEntryId,
+    ReadingStatus, SessionId, SettingKey, Tag, ThreadId, TrashKind,
+};
 
 static POOL: OnceCell<Pool<Postgres>> = OnceCell::new();
 
@@ -75,6 +97,14 @@ async fn init_schema(pool: &Pool<Postgres>) -> Result<(), DbError> {
         .execute(pool)
         .await?;
 
+    // Internal bookkeeping such as one-time seed markers, kept apart from the
+    // user settings so it never shows up in `get_setting`, backups or sync
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS schema_meta (key TEXT PRIMARY KEY, value TEXT NOT NULL)",
+    )
+    .execute(pool)
+    .await?;
+
     // Book table
     sqlx::query(
         r"CREATE TABLE IF NOT EXISTS book (
@@ -108,6 +138,79 @@ async fn init_schema(pool: &Pool<Postgres>) -> Result<(), DbError> {
         .execute(pool)
         .await?;
 
+    // Where each book was downloaded from, for update checks
+    sqlx::query(
+        r"ALTER TABLE book
+            ADD COLUMN IF NOT EXISTS source_url TEXT,
+            ADD COLUMN IF NOT EXISTS etag TEXT,
+            ADD COLUMN IF NOT EXISTS last_modified TEXT,
+            ADD COLUMN IF NOT EXISTS content_hash TEXT,
+            ADD COLUMN IF NOT EXISTS checked_at TIMESTAMPTZ",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "UPDATE book SET content_hash = encode(sha256(mobi_data), 'hex') WHERE content_hash IS NULL AND mobi_data IS NOT NULL",
+    )
+    .execute(pool)
+    .await?;
+
+    // Reading status and when the book was last opened
+    sqlx::query(
+        r"ALTER TABLE book
+            ADD COLUMN IF NOT EXISTS reading_status TEXT,
+            ADD COLUMN IF NOT EXISTS status_updated_at TIMESTAMPTZ,
+            ADD COLUMN IF NOT EXISTS started_at TIMESTAMPTZ,
+            ADD COLUMN IF NOT EXISTS finished_at TIMESTAMPTZ,
+            ADD COLUMN IF NOT EXISTS last_opened_at TIMESTAMPTZ",
+    )
+    .execute(pool)
+    .await?;
+
+    // Book languages, from Gutendex at download or the local catalog
+    sqlx::query("ALTER TABLE book ADD COLUMN IF NOT EXISTS languages TEXT[] NOT NULL DEFAULT '{}'")
+        .execute(pool)
+        .await?;
+
+    // Collections and tags for grouping library books
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS collection (
+            id BIGSERIAL PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            position INTEGER NOT NULL DEFAULT 0,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS book_collection (
+            collection_id BIGINT NOT NULL REFERENCES collection(id) ON DELETE CASCADE,
+            book_id BIGINT NOT NULL REFERENCES book(id) ON DELETE CASCADE,
+            added_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            PRIMARY KEY (collection_id, book_id)
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS book_tag (
+            book_id BIGINT NOT NULL REFERENCES book(id) ON DELETE CASCADE,
+            tag TEXT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            PRIMARY KEY (book_id, tag)
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_book_tag_tag ON book_tag(tag)")
+        .execute(pool)
+        .await?;
+
     // Book position table
     sqlx::query(
         r"CREATE TABLE IF NOT EXISTS book_position (
@@ -119,6 +222,85 @@ async fn init_schema(pool: &Pool<Postgres>) -> Result<(), DbError> {
     .execute(pool)
     .await?;
 
+    // Structured locator; the CFI became optional
+    sqlx::query(
+        r"ALTER TABLE book_position
+            ADD COLUMN IF NOT EXISTS chapter_index INTEGER,
+            ADD COLUMN IF NOT EXISTS chapter_title TEXT,
+            ADD COLUMN IF NOT EXISTS chapter_offset BIGINT NOT NULL DEFAULT 0,
+            ADD COLUMN IF NOT EXISTS progression DOUBLE PRECISION NOT NULL DEFAULT 0,
+            ADD COLUMN IF NOT EXISTS quote TEXT NOT NULL DEFAULT '',
+            ALTER COLUMN cfi DROP NOT NULL",
+    )
+    .execute(pool)
+    .await?;
+
+    // Bookmarks: a locator with an optional label and color
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS bookmark (
+            id BIGSERIAL PRIMARY KEY,
+            book_id BIGINT NOT NULL REFERENCES book(id) ON DELETE CASCADE,
+            locator JSONB NOT NULL,
+            label TEXT,
+            color TEXT,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_bookmark_book ON bookmark(book_id)")
+        .execute(pool)
+        .await?;
+
+    // Positions left behind, for jumping back
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS book_position_history (
+            id BIGSERIAL PRIMARY KEY,
+            book_id BIGINT NOT NULL REFERENCES book(id) ON DELETE CASCADE,
+            locator JSONB NOT NULL,
+            change TEXT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_book_position_history_book ON book_position_history(book_id, id)",
+    )
+    .execute(pool)
+    .await?;
+
+    // Reading sessions, extended by heartbeats from the reader
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS reading_session (
+            id BIGSERIAL PRIMARY KEY,
+            book_id BIGINT NOT NULL REFERENCES book(id) ON DELETE CASCADE,
+            started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            ended_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            start_locator JSONB NOT NULL,
+            end_locator JSONB NOT NULL,
+            words_read BIGINT NOT NULL DEFAULT 0,
+            ended BOOLEAN NOT NULL DEFAULT FALSE
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_reading_session_book ON reading_session(book_id, started_at)",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_reading_session_started ON reading_session(started_at)",
+    )
+    .execute(pool)
+    .await?;
+
     // Highlight table
     sqlx::query(
         r"CREATE TABLE IF NOT EXISTS highlight (
@@ -141,6 +323,50 @@ async fn init_schema(pool: &Pool<Postgres>) -> Result<(), DbError> {
         .execute(pool)
         .await?;
 
+    // Highlight color, style and tags
+    sqlx::query(
+        r"ALTER TABLE highlight
+            ADD COLUMN IF NOT EXISTS color TEXT,
+            ADD COLUMN IF NOT EXISTS style TEXT NOT NULL DEFAULT 'highlight'",
+    )
+    .execute(pool)
+    .await?;
+
+    // Text selectors for re-anchoring highlights when the HTML changes
+    sqlx::query(
+        r"ALTER TABLE highlight
+            ADD COLUMN IF NOT EXISTS quote_prefix TEXT NOT NULL DEFAULT '',
+            ADD COLUMN IF NOT EXISTS quote_suffix TEXT NOT NULL DEFAULT '',
+            ADD COLUMN IF NOT EXISTS text_start BIGINT,
+            ADD COLUMN IF NOT EXISTS text_end BIGINT,
+            ADD COLUMN IF NOT EXISTS orphaned BOOLEAN NOT NULL DEFAULT FALSE",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS tag (
+            id BIGSERIAL PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS highlight_tag (
+            highlight_id BIGINT NOT NULL REFERENCES highlight(id) ON DELETE CASCADE,
+            tag_id BIGINT NOT NULL REFERENCES tag(id) ON DELETE CASCADE,
+            PRIMARY KEY (highlight_id, tag_id)
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_highlight_tag_tag ON highlight_tag(tag_id)")
+        .execute(pool)
+        .await?;
+
     // Highlight message table
     sqlx::query(
         r"CREATE TABLE IF NOT EXISTS highlight_message (
@@ -202,570 +428,3985 @@ async fn init_schema(pool: &Pool<Postgres>) -> Result<(), DbError> {
         .execute(pool)
         .await?;
 
-    Ok(())
-}
+    // TTS pronunciation lexicon
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS tts_lexicon (
+            id BIGSERIAL PRIMARY KEY,
+            term TEXT NOT NULL UNIQUE,
+            replacement TEXT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
 
-// ============================================================================
-// ZERO-COST ROW MAPPING HELPERS
-// ============================================================================
-// These generic helpers eliminate runtime column-name lookups by using
-// positional indices. The compiler monomorphizes each call site.
+    seed_default_lexicon(pool).await?;
 
-/// Maps a Book row using positional indices instead of runtime column lookups
-#[inline]
-fn map_book_row(row: &sqlx::postgres::PgRow) -> Book {
-    Book {
-        id: BookId::new(row.get::<i64, _>(0)),
-        gutenberg_id: GutenbergId::new(row.get::<i64, _>(1)),
-        title: row.get(2),
-        authors: row.get(3),
-        publication_year: row.get(4),
-        cover_url: row.get(5),
-        mobi_data: row.get(6),
-        html_content: row.get(7),
-        first_image_index: row.get(8),
-        created_at: row.get::<Option<String>, _>(9).unwrap_or_default(),
-    }
-}
+    // Per-book cast for multi-voice narration of plays
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS book_cast (
+            book_id BIGINT NOT NULL REFERENCES book(id) ON DELETE CASCADE,
+            character TEXT NOT NULL,
+            voice_id TEXT NOT NULL,
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            PRIMARY KEY (book_id, character)
+        )",
+    )
+    .execute(pool)
+    .await?;
 
-/// Maps a Highlight row using positional indices
-#[inline]
-fn map_highlight_row(row: &sqlx::postgres::PgRow) -> Highlight {
-    Highlight {
-        id: HighlightId::new(row.get::<i64, _>(0)),
-        book_id: BookId::new(row.get::<i64, _>(1)),
-        start_path: row.get(2),
-        start_offset: row.get(3),
-        end_path: row.get(4),
-        end_offset: row.get(5),
-        text: row.get(6),
-        note: row.get(7),
-        created_at: row.get::<Option<String>, _>(8).unwrap_or_default(),
-        updated_at: row.get::<Option<String>, _>(9).unwrap_or_default(),
-    }
-}
+    // User-defined catalogs shown on the library page
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS catalog (
+            id BIGSERIAL PRIMARY KEY,
+            key TEXT NOT NULL UNIQUE,
+            name TEXT NOT NULL,
+            filters JSONB NOT NULL DEFAULT '{}',
+            position INTEGER NOT NULL DEFAULT 0,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
 
-/// Maps a `HighlightMessage` row using positional indices
-#[inline]
-fn map_highlight_message_row(row: &sqlx::postgres::PgRow) -> HighlightMessage {
-    HighlightMessage {
-        id: MessageId::new(row.get::<i64, _>(0)),
-        highlight_id: HighlightId::new(row.get::<i64, _>(1)),
-        role: row.get::<String, _>(2).parse().unwrap_or(MessageRole::User),
-        content: row.get(3),
-        created_at: row.get::<Option<String>, _>(4).unwrap_or_default(),
-    }
-}
+    seed_default_catalogs(pool).await?;
 
-/// Maps a `BookChatThread` row using positional indices
-#[inline]
-fn map_book_chat_thread_row(row: &sqlx::postgres::PgRow) -> BookChatThread {
-    BookChatThread {
-        id: ThreadId::new(row.get::<i64, _>(0)),
-        book_id: BookId::new(row.get::<i64, _>(1)),
-        title: row.get(2),
-        last_cfi: row.get(3),
-        created_at: row.get::<Option<String>, _>(4).unwrap_or_default(),
-        updated_at: row.get::<Option<String>, _>(5).unwrap_or_default(),
-    }
-}
+    // Local copy of the Project Gutenberg catalog
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS catalog_work (
+            id BIGINT PRIMARY KEY,
+            title TEXT NOT NULL,
+            issued TEXT,
+            media_type TEXT NOT NULL,
+            copyright BOOLEAN,
+            download_count BIGINT
+        )",
+    )
+    .execute(pool)
+    .await?;
 
-/// Maps a `BookMessage` row using positional indices
-#[inline]
-fn map_book_message_row(row: &sqlx::postgres::PgRow) -> BookMessage {
-    BookMessage {
-        id: MessageId::new(row.get::<i64, _>(0)),
-        book_id: BookId::new(row.get::<i64, _>(1)),
-        thread_id: row.get::<Option<i64>, _>(2).map(ThreadId::new),
-        role: row.get::<String, _>(3).parse().unwrap_or(MessageRole::User),
-        content: row.get(4),
-        reasoning_summary: row.get(5),
-        context_map: row.get(6),
-        created_at: row.get::<Option<String>, _>(7).unwrap_or_default(),
-    }
-}
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS catalog_agent (
+            id BIGSERIAL PRIMARY KEY,
+            agent_key TEXT NOT NULL UNIQUE,
+            name TEXT NOT NULL,
+            birth_year INTEGER,
+            death_year INTEGER
+        )",
+    )
+    .execute(pool)
+    .await?;
 
-// ============================================================================
-// BOOK OPERATIONS
-// ============================================================================
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS catalog_work_agent (
+            work_id BIGINT NOT NULL REFERENCES catalog_work(id) ON DELETE CASCADE,
+            agent_id BIGINT NOT NULL REFERENCES catalog_agent(id) ON DELETE CASCADE,
+            role TEXT NOT NULL,
+            position INTEGER NOT NULL,
+            PRIMARY KEY (work_id, agent_id, role)
+        )",
+    )
+    .execute(pool)
+    .await?;
 
-#[allow(clippy::too_many_arguments)]
-pub async fn upsert_book(
-    pool: &Pool<Postgres>,
-    gutenberg_id: i64,
-    title: &str,
-    authors: &str,
-    publication_year: Option<i32>,
-    cover_url: Option<&str>,
-    mobi_data: Option<&[u8]>,
-    html_content: Option<&str>,
-    first_image_index: Option<i32>,
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS catalog_subject (
+            id BIGSERIAL PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS catalog_work_subject (
+            work_id BIGINT NOT NULL REFERENCES catalog_work(id) ON DELETE CASCADE,
+            subject_id BIGINT NOT NULL REFERENCES catalog_subject(id) ON DELETE CASCADE,
+            PRIMARY KEY (work_id, subject_id)
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS catalog_bookshelf (
+            id BIGSERIAL PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS catalog_work_bookshelf (
+            work_id BIGINT NOT NULL REFERENCES catalog_work(id) ON DELETE CASCADE,
+            bookshelf_id BIGINT NOT NULL REFERENCES catalog_bookshelf(id) ON DELETE CASCADE,
+            PRIMARY KEY (work_id, bookshelf_id)
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS catalog_work_language (
+            work_id BIGINT NOT NULL REFERENCES catalog_work(id) ON DELETE CASCADE,
+            code TEXT NOT NULL,
+            PRIMARY KEY (work_id, code)
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS catalog_format (
+            work_id BIGINT NOT NULL REFERENCES catalog_work(id) ON DELETE CASCADE,
+            mime_type TEXT NOT NULL,
+            url TEXT NOT NULL,
+            PRIMARY KEY (work_id, mime_type)
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_catalog_work_language_code ON catalog_work_language(code)",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r"UPDATE book SET languages = ARRAY(
+            SELECT code FROM catalog_work_language l WHERE l.work_id = book.gutenberg_id ORDER BY code
+        )
+        WHERE languages = '{}'
+            AND EXISTS (SELECT 1 FROM catalog_work_language l WHERE l.work_id = book.gutenberg_id)",
+    )
+    .execute(pool)
+    .await?;
+
+    // Cached Gutendex responses keyed by request URL
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS http_cache (
+            url TEXT PRIMARY KEY,
+            body TEXT NOT NULL,
+            etag TEXT,
+            last_modified TEXT,
+            fetched_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    // Trash: deleted rows keep their deletion time until purged
+    for table in ["book", "highlight", "book_chat_thread", "book_message"] {
+        sqlx::query(&format!(
+            "ALTER TABLE {table} ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ"
+        ))
+        .execute(pool)
+        .await?;
+    }
+
+    // Messages trashed by one delete share a batch, restored and purged together
+    sqlx::query("CREATE SEQUENCE IF NOT EXISTS book_message_trash_batch")
+        .execute(pool)
+        .await?;
+    sqlx::query("ALTER TABLE book_message ADD COLUMN IF NOT EXISTS trash_batch BIGINT")
+        .execute(pool)
+        .await?;
+    // Messages trashed before batches existed: one batch per book and deletion
+    sqlx::query(
+        r"
+        UPDATE book_message m SET trash_batch = g.batch
+        FROM (
+            SELECT book_id, deleted_at, nextval('book_message_trash_batch') AS batch
+            FROM book_message WHERE deleted_at IS NOT NULL AND trash_batch IS NULL
+            GROUP BY book_id, deleted_at
+        ) g
+        WHERE m.book_id = g.book_id AND m.deleted_at = g.deleted_at AND m.trash_batch IS NULL
+        ",
+    )
+    .execute(pool)
+    .await?;
+
+    init_sync_schema(pool).await
+}
+
+/// Sync bookkeeping: a key identifying each synced record across devices,
+/// when each of its fields last changed and tombstones for deleted records.
+/// Triggers record local changes; changes applied from another device set
+/// `sync.applying` so they aren't mistaken for local ones.
+#[allow(clippy::too_many_lines)]
+async fn init_sync_schema(pool: &Pool<Postgres>) -> Result<(), DbError> {
+    for table in SYNC_TABLES.iter().filter(|table| table.key.is_some()) {
+        let name = table.table;
+        sqlx::query(&format!(
+            "ALTER TABLE {name} ADD COLUMN IF NOT EXISTS sync_key TEXT NOT NULL DEFAULT gen_random_uuid()::text"
+        ))
+        .execute(pool)
+        .await?;
+        sqlx::query(&format!(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_{name}_sync_key ON {name}(sync_key)"
+        ))
+        .execute(pool)
+        .await?;
+    }
+
+    // `origin` is the remote a change was pulled from, NULL for local changes
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS sync_field (
+            entity TEXT NOT NULL,
+            record_key TEXT NOT NULL,
+            field TEXT NOT NULL,
+            changed_at TIMESTAMPTZ NOT NULL,
+            origin TEXT,
+            PRIMARY KEY (entity, record_key, field)
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_sync_field_changed ON sync_field(changed_at)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS sync_tombstone (
+            entity TEXT NOT NULL,
+            record_key TEXT NOT NULL,
+            deleted_at TIMESTAMPTZ NOT NULL,
+            origin TEXT,
+            PRIMARY KEY (entity, record_key)
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    // Progress against each remote: its pull cursor and when we last pushed
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS sync_remote (
+            remote TEXT PRIMARY KEY,
+            cursor TEXT,
+            synced_at TIMESTAMPTZ
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    // Pulled records whose book or parent isn't here yet, retried every sync
+    sqlx::query(
+        r"CREATE TABLE IF NOT EXISTS sync_pending (
+            remote TEXT NOT NULL,
+            entity TEXT NOT NULL,
+            record_key TEXT NOT NULL,
+            record JSONB NOT NULL,
+            PRIMARY KEY (remote, entity, record_key)
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    // Arguments: entity, key column, and 'book' when the key column holds a
+    // book ID to be replaced by its Gutenberg ID
+    sqlx::query(
+        r"CREATE OR REPLACE FUNCTION sync_track() RETURNS trigger AS $$
+        DECLARE
+            new_row jsonb := CASE WHEN TG_OP = 'DELETE' THEN NULL ELSE to_jsonb(NEW) END;
+            old_row jsonb := CASE WHEN TG_OP = 'INSERT' THEN NULL ELSE to_jsonb(OLD) END;
+            row_key text := COALESCE(new_row, old_row) ->> TG_ARGV[1];
+        BEGIN
+            IF current_setting('sync.applying', true) = 'on' THEN
+                RETURN NULL;
+            END IF;
+            IF TG_ARGV[2] = 'book' THEN
+                SELECT gutenberg_id::text INTO row_key FROM book WHERE id = row_key::bigint;
+            END IF;
+            IF row_key IS NULL THEN
+                RETURN NULL;
+            END IF;
+            IF TG_OP = 'DELETE' THEN
+                INSERT INTO sync_tombstone (entity, record_key, deleted_at)
+                VALUES (TG_ARGV[0], row_key, NOW())
+                ON CONFLICT (entity, record_key) DO UPDATE
+                    SET deleted_at = EXCLUDED.deleted_at, origin = NULL;
+                DELETE FROM sync_field WHERE entity = TG_ARGV[0] AND record_key = row_key;
+            ELSE
+                INSERT INTO sync_field (entity, record_key, field, changed_at)
+                SELECT TG_ARGV[0], row_key, n.key, NOW() FROM jsonb_each(new_row) n
+                WHERE old_row IS NULL OR n.value IS DISTINCT FROM old_row -> n.key
+                ON CONFLICT (entity, record_key, field) DO UPDATE
+                    SET changed_at = EXCLUDED.changed_at, origin = NULL;
+                DELETE FROM sync_tombstone WHERE entity = TG_ARGV[0] AND record_key = row_key;
+            END IF;
+            RETURN NULL;
+        END
+        $$ LANGUAGE plpgsql",
+    )
+    .execute(pool)
+    .await?;
+
+    for table in &SYNC_TABLES {
+        let name = table.table;
+        let (key, lookup) = table
+            .key
+            .map_or((table.parents[0].column, "book"), |key| (key, ""));
+        sqlx::query(&format!("DROP TRIGGER IF EXISTS sync_track ON {name}"))
+            .execute(pool)
+            .await?;
+        sqlx::query(&format!(
+            "CREATE TRIGGER sync_track AFTER INSERT OR UPDATE OR DELETE ON {name} FOR EACH ROW EXECUTE FUNCTION sync_track('{}', '{key}', '{lookup}')",
+            table.entity.as_str()
+        ))
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
+}
+
+/// `schema_meta` key recording that the default lexicon was seeded
+const LEXICON_SEEDED: &str = "tts_lexicon_seeded";
+
+/// Whether a one-time seed has run. Seeds are recorded rather than rerun
+/// whenever their table is empty, so defaults the user deleted stay deleted.
+async fn is_seeded(pool: &Pool<Postgres>, marker: &str) -> Result<bool, DbError> {
+    // Earlier versions recorded the marker in the user settings
+    sqlx::query(
+        r"WITH moved AS (DELETE FROM settings WHERE key = $1 RETURNING key, value)
+        INSERT INTO schema_meta (key, value) SELECT key, value FROM moved
+        ON CONFLICT (key) DO NOTHING",
+    )
+    .bind(marker)
+    .execute(pool)
+    .await?;
+
+    let (seeded,): (bool,) =
+        sqlx::query_as("SELECT EXISTS(SELECT 1 FROM schema_meta WHERE key = $1)")
+            .bind(marker)
+            .fetch_one(pool)
+            .await?;
+    Ok(seeded)
+}
+
+async fn mark_seeded(pool: &Pool<Postgres>, marker: &str) -> Result<(), DbError> {
+    sqlx::query(
+        "INSERT INTO schema_meta (key, value) VALUES ($1, 'true') ON CONFLICT (key) DO NOTHING",
+    )
+    .bind(marker)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Seeds the built-in archaic contractions into the lexicon, once. Databases
+/// from before the seed was recorded are only seeded if still empty.
+async fn seed_default_lexicon(pool: &Pool<Postgres>) -> Result<(), DbError> {
+    if is_seeded(pool, LEXICON_SEEDED).await? {
+        return Ok(());
+    }
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM tts_lexicon")
+        .fetch_one(pool)
+        .await?;
+    if count > 0 {
+        return mark_seeded(pool, LEXICON_SEEDED).await;
+    }
+
+    for (term, replacement) in DEFAULT_LEXICON {
+        sqlx::query(
+            "INSERT INTO tts_lexicon (term, replacement) VALUES ($1, $2) ON CONFLICT (term) DO NOTHING",
+        )
+        .bind(term)
+        .bind(replacement)
+        .execute(pool)
+        .await?;
+    }
+    mark_seeded(pool, LEXICON_SEEDED).await
+}
+
+/// `schema_meta` key recording that the default catalogs were seeded
+const CATALOGS_SEEDED: &str = "catalogs_seeded";
+
+/// Seeds the built-in catalogs, once. Databases from before the seed was
+/// recorded are only seeded if they have no catalogs.
+async fn seed_default_catalogs(pool: &Pool<Postgres>) -> Result<(), DbError> {
+    if is_seeded(pool, CATALOGS_SEEDED).await? {
+        return Ok(());
+    }
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM catalog")
+        .fetch_one(pool)
+        .await?;
+    if count > 0 {
+        return mark_seeded(pool, CATALOGS_SEEDED).await;
+    }
+
+    for (position, (key, name, query)) in default_catalogs().into_iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO catalog (key, name, filters, position) VALUES ($1, $2, $3, $4) ON CONFLICT (key) DO NOTHING",
+        )
+        .bind(key)
+        .bind(name)
+        .bind(Json(query))
+        .bind(i32::try_from(position).unwrap_or(i32::MAX))
+        .execute(pool)
+        .await?;
+    }
+    mark_seeded(pool, CATALOGS_SEEDED).await
+}
+
+// ============================================================================
+// ZERO-COST ROW MAPPING HELPERS
+// ============================================================================
+// These generic helpers eliminate runtime column-name lookups by using
+// positional indices. The compiler monomorphizes each call site.
+
+/// Maps a Book row using positional indices instead of runtime column lookups
+#[inline]
+fn map_book_row(row: &sqlx::postgres::PgRow) -> Book {
+    Book {
+        id: BookId::new(row.get::<i64, _>(0)),
+        gutenberg_id: GutenbergId::new(row.get::<i64, _>(1)),
+        title: row.get(2),
+        authors: row.get(3),
+        publication_year: row.get(4),
+        cover_url: row.get(5),
+        mobi_data: row.get(6),
+        html_content: row.get(7),
+        first_image_index: row.get(8),
+        created_at: row.get::<Option<String>, _>(9).unwrap_or_default(),
+        status: row
+            .get::<Option<String>, _>(10)
+            .and_then(|status| status.parse().ok()),
+        status_updated_at: row.get(11),
+        started_at: row.get(12),
+        finished_at: row.get(13),
+        last_opened_at: row.get(14),
+    }
+}
+
+/// Maps a Highlight row using positional indices
+#[inline]
+fn map_highlight_row(row: &sqlx::postgres::PgRow) -> Highlight {
+    Highlight {
+        id: HighlightId::new(row.get::<i64, _>(0)),
+        book_id: BookId::new(row.get::<i64, _>(1)),
+        start_path: row.get(2),
+        start_offset: row.get(3),
+        end_path: row.get(4),
+        end_offset: row.get(5),
+        text: row.get(6),
+        note: row.get(7),
+        created_at: row.get::<Option<String>, _>(8).unwrap_or_default(),
+        updated_at: row.get::<Option<String>, _>(9).unwrap_or_default(),
+        color: row.get::<Option<String>, _>(10).map(Color::new_unchecked),
+        style: row.get::<String, _>(11).parse().unwrap_or_default(),
+        tags: row
+            .get::<Vec<String>, _>(12)
+            .into_iter()
+            .map(Tag::new_unchecked)
+            .collect(),
+        quote_prefix: row.get(13),
+        quote_suffix: row.get(14),
+        text_start: row.get(15),
+        text_end: row.get(16),
+        orphaned: row.get(17),
+    }
+}
+
+/// Maps a `LibraryHighlight` row: highlight columns, then the book's title and authors
+#[inline]
+fn map_library_highlight_row(row: &sqlx::postgres::PgRow) -> LibraryHighlight {
+    LibraryHighlight {
+        highlight: map_highlight_row(row),
+        book_title: row.get(18),
+        book_authors: row.get(19),
+    }
+}
+
+/// Maps a `HighlightMessage` row using positional indices
+#[inline]
+fn map_highlight_message_row(row: &sqlx::postgres::PgRow) -> HighlightMessage {
+    HighlightMessage {
+        id: MessageId::new(row.get::<i64, _>(0)),
+        highlight_id: HighlightId::new(row.get::<i64, _>(1)),
+        role: row.get::<String, _>(2).parse().unwrap_or(MessageRole::User),
+        content: row.get(3),
+        created_at: row.get::<Option<String>, _>(4).unwrap_or_default(),
+    }
+}
+
+/// Maps a `BookChatThread` row using positional indices
+#[inline]
+fn map_book_chat_thread_row(row: &sqlx::postgres::PgRow) -> BookChatThread {
+    BookChatThread {
+        id: ThreadId::new(row.get::<i64, _>(0)),
+        book_id: BookId::new(row.get::<i64, _>(1)),
+        title: row.get(2),
+        last_cfi: row.get(3),
+        created_at: row.get::<Option<String>, _>(4).unwrap_or_default(),
+        updated_at: row.get::<Option<String>, _>(5).unwrap_or_default(),
+    }
+}
+
+/// Maps a `BookMessage` row using positional indices
+#[inline]
+fn map_book_message_row(row: &sqlx::postgres::PgRow) -> BookMessage {
+    BookMessage {
+        id: MessageId::new(row.get::<i64, _>(0)),
+        book_id: BookId::new(row.get::<i64, _>(1)),
+        thread_id: row.get::<Option<i64>, _>(2).map(ThreadId::new),
+        role: row.get::<String, _>(3).parse().unwrap_or(MessageRole::User),
+        content: row.get(4),
+        reasoning_summary: row.get(5),
+        context_map: row.get(6),
+        created_at: row.get::<Option<String>, _>(7).unwrap_or_default(),
+    }
+}
+
+/// Maps a `LexiconEntry` row using positional indices
+#[inline]
+fn map_lexicon_entry_row(row: &sqlx::postgres::PgRow) -> LexiconEntry {
+    LexiconEntry {
+        id: LexiconEntryId::new(row.get::<i64, _>(0)),
+        term: row.get(1),
+        replacement: row.get(2),
+        created_at: row.get::<Option<String>, _>(3).unwrap_or_default(),
+        updated_at: row.get::<Option<String>, _>(4).unwrap_or_default(),
+    }
+}
+
+/// Maps a `CastMember` row using positional indices
+#[inline]
+fn map_cast_member_row(row: &sqlx::postgres::PgRow) -> CastMember {
+    CastMember {
+        book_id: BookId::new(row.get::<i64, _>(0)),
+        character: row.get(1),
+        voice_id: row.get(2),
+        updated_at: row.get::<Option<String>, _>(3).unwrap_or_default(),
+    }
+}
+
+/// Maps a `Catalog` row using positional indices
+#[inline]
+fn map_catalog_row(row: &sqlx::postgres::PgRow) -> Result<Catalog, DbError> {
+    Ok(Catalog {
+        id: CatalogId::new(row.get::<i64, _>(0)),
+        key: row.get(1),
+        name: row.get(2),
+        query: row.try_get::<Json<CatalogQuery>, _>(3)?.0,
+        position: row.get(4),
+        created_at: row.get::<Option<String>, _>(5).unwrap_or_default(),
+        updated_at: row.get::<Option<String>, _>(6).unwrap_or_default(),
+    })
+}
+
+/// Maps an `HttpCacheEntry` row using positional indices
+#[inline]
+fn map_http_cache_entry_row(row: &sqlx::postgres::PgRow) -> HttpCacheEntry {
+    HttpCacheEntry {
+        url: row.get(0),
+        body: row.get(1),
+        etag: row.get(2),
+        last_modified: row.get(3),
+        fetched_at: row.get::<Option<String>, _>(4).unwrap_or_default(),
+        fresh: row.get(5),
+    }
+}
+
+// ============================================================================
+// BOOK OPERATIONS
+// ============================================================================
+
+#[allow(clippy::too_many_arguments)]
+pub async fn upsert_book(
+    pool: &Pool<Postgres>,
+    gutenberg_id: i64,
+    title: &str,
+    authors: &str,
+    publication_year: Option<i32>,
+    cover_url: Option<&str>,
+    mobi_data: Option<&[u8]>,
+    html_content: Option<&str>,
+    first_image_index: Option<i32>,
 ) -> Result<i64, DbError> {
     let row: (i64,) = sqlx::query_as(
         r"
-        INSERT INTO book (gutenberg_id, title, authors, publication_year, cover_url, mobi_data, html_content, first_image_index)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-        ON CONFLICT (gutenberg_id) DO UPDATE SET
-            title = EXCLUDED.title,
-            authors = EXCLUDED.authors,
-            publication_year = EXCLUDED.publication_year,
-            cover_url = EXCLUDED.cover_url,
-            mobi_data = EXCLUDED.mobi_data,
-            html_content = EXCLUDED.html_content,
-            first_image_index = EXCLUDED.first_image_index
+        INSERT INTO book (gutenberg_id, title, authors, publication_year, cover_url, mobi_data, html_content, first_image_index)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT (gutenberg_id) DO UPDATE SET
+            title = EXCLUDED.title,
+            authors = EXCLUDED.authors,
+            publication_year = EXCLUDED.publication_year,
+            cover_url = EXCLUDED.cover_url,
+            mobi_data = EXCLUDED.mobi_data,
+            html_content = EXCLUDED.html_content,
+            first_image_index = EXCLUDED.first_image_index
+        RETURNING id
+        ",
+    )
+    .bind(gutenberg_id)
+    .bind(title)
+    .bind(authors)
+    .bind(publication_year)
+    .bind(cover_url)
+    .bind(mobi_data)
+    .bind(html_content)
+    .bind(first_image_index)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| {
+        println!("[Backend] Book upsert failed for {}: {}", gutenberg_id, e);
+        e
+    })?;
+    println!(
+        "[Backend] Successfully upserted book {} (id: {})",
+        gutenberg_id, row.0
+    );
+    Ok(row.0)
+}
+
+const BOOK_COLUMNS: &str = "id, gutenberg_id, title, authors, publication_year, cover_url, mobi_data, html_content, first_image_index, created_at::text, reading_status, status_updated_at::text, started_at::text, finished_at::text, last_opened_at::text";
+
+pub async fn list_books(pool: &Pool<Postgres>) -> Result<Vec<Book>, DbError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {BOOK_COLUMNS} FROM book WHERE deleted_at IS NULL ORDER BY title ASC"
+    ))
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.iter().map(map_book_row).collect())
+}
+
+pub async fn list_book_titles(pool: &Pool<Postgres>) -> Result<Vec<BookTitle>, DbError> {
+    let rows =
+        sqlx::query("SELECT id, title, authors FROM book WHERE deleted_at IS NULL ORDER BY id ASC")
+            .fetch_all(pool)
+            .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| BookTitle {
+            id: BookId::new(row.get::<i64, _>(0)),
+            title: row.get(1),
+            authors: row.get(2),
+        })
+        .collect())
+}
+
+/// Lists summaries of the books matching every set field of `filter`, in the
+/// requested order
+pub async fn list_books_filtered(
+    pool: &Pool<Postgres>,
+    filter: &BookListFilter,
+) -> Result<Vec<BookSummary>, DbError> {
+    let mut qb = QueryBuilder::<Postgres>::new(format!(
+        "SELECT {BOOK_SUMMARY_COLUMNS} FROM book b LEFT JOIN book_position p ON p.book_id = b.id WHERE b.deleted_at IS NULL"
+    ));
+
+    if let Some(collection_id) = filter.collection_id {
+        qb.push(" AND EXISTS (SELECT 1 FROM book_collection bc WHERE bc.book_id = b.id AND bc.collection_id = ")
+            .push_bind(collection_id.get())
+            .push(")");
+    }
+    if let Some(tag) = filter.tag.as_deref().and_then(|tag| Tag::new(tag).ok()) {
+        qb.push(" AND EXISTS (SELECT 1 FROM book_tag bt WHERE bt.book_id = b.id AND bt.tag = ")
+            .push_bind(tag.into_string())
+            .push(")");
+    }
+    if let Some(status) = filter.status {
+        qb.push(" AND b.reading_status = ")
+            .push_bind(status.as_str());
+    }
+    if let Some(author) = filter
+        .author
+        .as_deref()
+        .map(str::trim)
+        .filter(|a| !a.is_empty())
+    {
+        qb.push(" AND b.authors ILIKE ")
+            .push_bind(format!("%{}%", escape_like(author)));
+    }
+
+    let direction = if filter.descending { "DESC" } else { "ASC" };
+    let order = match filter.sort {
+        BookSort::Title => format!("b.title {direction}"),
+        BookSort::Author => format!("b.authors {direction}, b.title ASC"),
+        BookSort::DateAdded => format!("b.created_at {direction}"),
+        BookSort::LastOpened => format!("b.last_opened_at {direction} NULLS LAST, b.title ASC"),
+        BookSort::Status => format!(
+            "CASE b.reading_status WHEN 'reading' THEN 0 WHEN 'to_read' THEN 1 WHEN 'finished' THEN 2 WHEN 'abandoned' THEN 3 ELSE 4 END {direction}, b.title ASC"
+        ),
+    };
+    qb.push(" ORDER BY ").push(order).push(", b.id ASC");
+
+    let rows = qb.build().fetch_all(pool).await?;
+    Ok(rows.iter().map(map_book_summary_row).collect())
+}
+
+/// Gutenberg ids already in the library, without loading any book content
+pub async fn list_book_gutenberg_ids(pool: &Pool<Postgres>) -> Result<Vec<i64>, DbError> {
+    let rows: Vec<(i64,)> =
+        sqlx::query_as("SELECT gutenberg_id FROM book WHERE deleted_at IS NULL")
+            .fetch_all(pool)
+            .await?;
+    Ok(rows.into_iter().map(|(id,)| id).collect())
+}
+
+/// Records where a book's file came from and what it hashed to
+pub async fn set_book_source(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+    source_url: &str,
+    etag: Option<&str>,
+    last_modified: Option<&str>,
+    content_hash: &str,
+) -> Result<(), DbError> {
+    sqlx::query(
+        r"
+        UPDATE book SET source_url = $1, etag = $2, last_modified = $3, content_hash = $4, checked_at = NOW()
+        WHERE id = $5
+        ",
+    )
+    .bind(source_url)
+    .bind(etag)
+    .bind(last_modified)
+    .bind(content_hash)
+    .bind(book_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn touch_book_checked(pool: &Pool<Postgres>, book_id: i64) -> Result<(), DbError> {
+    sqlx::query("UPDATE book SET checked_at = NOW() WHERE id = $1")
+        .bind(book_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Maps a `BookSource` row using positional indices
+#[inline]
+fn map_book_source_row(row: &sqlx::postgres::PgRow) -> BookSource {
+    BookSource {
+        book_id: BookId::new(row.get::<i64, _>(0)),
+        gutenberg_id: GutenbergId::new(row.get::<i64, _>(1)),
+        title: row.get(2),
+        source_url: row.get(3),
+        etag: row.get(4),
+        last_modified: row.get(5),
+        content_hash: row.get(6),
+        checked_at: row.get(7),
+    }
+}
+
+pub async fn list_book_sources(pool: &Pool<Postgres>) -> Result<Vec<BookSource>, DbError> {
+    let rows = sqlx::query(
+        r"
+        SELECT id, gutenberg_id, title, source_url, etag, last_modified, content_hash, checked_at::text
+        FROM book WHERE deleted_at IS NULL ORDER BY title ASC
+        ",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.iter().map(map_book_source_row).collect())
+}
+
+/// Replaces a book's file and extracted HTML after a newer edition was downloaded
+pub async fn update_book_content(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+    mobi_data: &[u8],
+    html_content: &str,
+    first_image_index: Option<i32>,
+) -> Result<(), DbError> {
+    sqlx::query(
+        "UPDATE book SET mobi_data = $1, html_content = $2, first_image_index = $3 WHERE id = $4",
+    )
+    .bind(mobi_data)
+    .bind(html_content)
+    .bind(first_image_index)
+    .bind(book_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_book(pool: &Pool<Postgres>, book_id: i64) -> Result<Book, DbError> {
+    let row = sqlx::query(&format!(
+        "SELECT {BOOK_COLUMNS} FROM book WHERE id = $1 AND deleted_at IS NULL"
+    ))
+    .bind(book_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(map_book_row(&row))
+}
+
+/// A book's title and cover URL, without loading its content
+pub async fn get_book_cover(pool: &Pool<Postgres>, book_id: i64) -> Result<BookCover, DbError> {
+    let row = sqlx::query(
+        "SELECT id, gutenberg_id, title, authors, cover_url FROM book WHERE id = $1 AND deleted_at IS NULL",
+    )
+    .bind(book_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(BookCover {
+        id: BookId::new(row.get::<i64, _>(0)),
+        gutenberg_id: GutenbergId::new(row.get::<i64, _>(1)),
+        title: row.get(2),
+        authors: row.get(3),
+        cover_url: row.get(4),
+    })
+}
+
+/// A book's downloaded MOBI file, if it has one
+pub async fn get_book_mobi_data(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+) -> Result<Option<Vec<u8>>, DbError> {
+    let (mobi_data,): (Option<Vec<u8>>,) =
+        sqlx::query_as("SELECT mobi_data FROM book WHERE id = $1")
+            .bind(book_id)
+            .fetch_one(pool)
+            .await?;
+    Ok(mobi_data)
+}
+
+/// Hash of a book's MOBI file, which changes when a new edition replaces it
+pub async fn get_book_content_hash(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+) -> Result<Option<String>, DbError> {
+    let (content_hash,): (Option<String>,) =
+        sqlx::query_as("SELECT content_hash FROM book WHERE id = $1")
+            .bind(book_id)
+            .fetch_one(pool)
+            .await?;
+    Ok(content_hash)
+}
+
+/// Sets or clears a book's reading status. Moving to reading records the first
+/// start; moving to finished records the finish time.
+pub async fn set_book_status(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+    status: Option<ReadingStatus>,
+) -> Result<(), DbError> {
+    sqlx::query(
+        r"
+        UPDATE book SET
+            reading_status = $1,
+            status_updated_at = NOW(),
+            started_at = CASE WHEN $1 = 'reading' THEN COALESCE(started_at, NOW()) ELSE started_at END,
+            finished_at = CASE WHEN $1 = 'finished' THEN NOW() ELSE finished_at END
+        WHERE id = $2
+        ",
+    )
+    .bind(status.map(ReadingStatus::as_str))
+    .bind(book_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_book_languages(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+    languages: &[String],
+) -> Result<(), DbError> {
+    let languages: Vec<String> = languages.iter().map(|code| code.to_lowercase()).collect();
+    sqlx::query("UPDATE book SET languages = $1 WHERE id = $2")
+        .bind(languages)
+        .bind(book_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn touch_book_opened(pool: &Pool<Postgres>, book_id: i64) -> Result<(), DbError> {
+    sqlx::query("UPDATE book SET last_opened_at = NOW() WHERE id = $1")
+        .bind(book_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Takes a book back out of the trash, e.g. when it's downloaded again
+pub async fn untrash_book(pool: &Pool<Postgres>, book_id: i64) -> Result<(), DbError> {
+    sqlx::query("UPDATE book SET deleted_at = NULL WHERE id = $1 AND deleted_at IS NOT NULL")
+        .bind(book_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Moves a book to the trash, taking its highlights and chats out of every list
+pub async fn trash_book(pool: &Pool<Postgres>, book_id: i64) -> Result<(), DbError> {
+    sqlx::query("UPDATE book SET deleted_at = NOW() WHERE id = $1 AND deleted_at IS NULL")
+        .bind(book_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// ============================================================================
+// LIBRARY QUERY OPERATIONS
+// ============================================================================
+
+/// Columns of a `BookSummary`, selected from `book b` joined with `book_position p`
+const BOOK_SUMMARY_COLUMNS: &str = r"b.id, b.gutenberg_id, b.title, b.authors, b.publication_year, b.cover_url,
+    b.languages, b.reading_status, b.created_at::text, p.updated_at::text,
+    (SELECT COUNT(*) FROM highlight h WHERE h.book_id = b.id AND h.deleted_at IS NULL),
+    (SELECT COUNT(*) FROM book_chat_thread t WHERE t.book_id = b.id AND t.deleted_at IS NULL),
+    p.progression, p.chapter_title";
+
+/// Maps a `BookSummary` row using positional indices
+#[inline]
+fn map_book_summary_row(row: &sqlx::postgres::PgRow) -> BookSummary {
+    BookSummary {
+        id: BookId::new(row.get::<i64, _>(0)),
+        gutenberg_id: GutenbergId::new(row.get::<i64, _>(1)),
+        title: row.get(2),
+        authors: row.get(3),
+        publication_year: row.get(4),
+        cover_url: row.get(5),
+        languages: row.get(6),
+        status: row
+            .get::<Option<String>, _>(7)
+            .and_then(|status| status.parse().ok()),
+        created_at: row.get::<Option<String>, _>(8).unwrap_or_default(),
+        last_read_at: row.get(9),
+        highlight_count: row.get(10),
+        thread_count: row.get(11),
+        progression: row.get(12),
+        chapter_title: row.get(13),
+    }
+}
+
+fn push_book_filter(qb: &mut QueryBuilder<'_, Postgres>, filter: &BookFilter) {
+    let substring = |value: Option<&String>| {
+        value
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .map(|v| format!("%{}%", escape_like(v)))
+    };
+    if let Some(author) = substring(filter.author.as_ref()) {
+        qb.push(" AND b.authors ILIKE ").push_bind(author);
+    }
+    if let Some(title) = substring(filter.title.as_ref()) {
+        qb.push(" AND b.title ILIKE ").push_bind(title);
+    }
+    if let Some(year) = filter.year_from {
+        qb.push(" AND b.publication_year >= ").push_bind(year);
+    }
+    if let Some(year) = filter.year_to {
+        qb.push(" AND b.publication_year <= ").push_bind(year);
+    }
+    if let Some(language) = filter
+        .language
+        .as_deref()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+    {
+        qb.push(" AND ")
+            .push_bind(language.to_lowercase())
+            .push(" = ANY(b.languages)");
+    }
+    if let Some(has) = filter.has_highlights {
+        qb.push(if has {
+            " AND EXISTS"
+        } else {
+            " AND NOT EXISTS"
+        })
+        .push(" (SELECT 1 FROM highlight h WHERE h.book_id = b.id AND h.deleted_at IS NULL)");
+    }
+    if let Some(has) = filter.has_chats {
+        qb.push(if has {
+            " AND EXISTS"
+        } else {
+            " AND NOT EXISTS"
+        })
+        .push(
+            " (SELECT 1 FROM book_chat_thread t WHERE t.book_id = b.id AND t.deleted_at IS NULL)",
+        );
+    }
+    if let Some(status) = filter.status {
+        qb.push(" AND b.reading_status = ")
+            .push_bind(status.as_str());
+    }
+}
+
+/// One page of library summaries, starting after `cursor`
+pub async fn query_books(
+    pool: &Pool<Postgres>,
+    filter: &BookFilter,
+    sort: BookQuerySort,
+    descending: bool,
+    cursor: Option<&BookCursor>,
+    limit: i64,
+) -> Result<BookPage, DbError> {
+    let mut qb = QueryBuilder::<Postgres>::new(format!(
+        r"
+        SELECT s.*, s.sort_key::text FROM (
+            SELECT {BOOK_SUMMARY_COLUMNS}, {} AS sort_key
+            FROM book b LEFT JOIN book_position p ON p.book_id = b.id
+            WHERE b.deleted_at IS NULL",
+        sort.sql_key()
+    ));
+    push_book_filter(&mut qb, filter);
+    qb.push(") s");
+
+    let (cmp, direction) = if descending {
+        ("<", "DESC")
+    } else {
+        (">", "ASC")
+    };
+    if let Some(cursor) = cursor {
+        qb.push(" WHERE (s.sort_key, s.id) ")
+            .push(cmp)
+            .push(" (CAST(")
+            .push_bind(cursor.key.clone())
+            .push(format!(" AS {}), ", sort.sql_type()))
+            .push_bind(cursor.id)
+            .push(")");
+    }
+    qb.push(format!(
+        " ORDER BY s.sort_key {direction}, s.id {direction} LIMIT "
+    ))
+    .push_bind(limit + 1);
+
+    let rows = qb.build().fetch_all(pool).await?;
+    let has_more = rows.len() > usize::try_from(limit).unwrap_or(usize::MAX);
+    let rows = &rows[..rows.len().min(usize::try_from(limit).unwrap_or(usize::MAX))];
+
+    let next_cursor = rows.last().filter(|_| has_more).map(|row| {
+        BookCursor {
+            sort,
+            key: row.get(15),
+            id: row.get(0),
+        }
+        .encode()
+    });
+    Ok(BookPage {
+        books: rows.iter().map(map_book_summary_row).collect(),
+        next_cursor,
+    })
+}
+
+// ============================================================================
+// BOOK POSITION OPERATIONS
+// ============================================================================
+
+pub async fn set_book_position(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+    locator: &Locator,
+) -> Result<(), DbError> {
+    sqlx::query(
+        r"
+        INSERT INTO book_position (book_id, cfi, chapter_index, chapter_title, chapter_offset, progression, quote)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (book_id) DO UPDATE SET
+            cfi = EXCLUDED.cfi,
+            chapter_index = EXCLUDED.chapter_index,
+            chapter_title = EXCLUDED.chapter_title,
+            chapter_offset = EXCLUDED.chapter_offset,
+            progression = EXCLUDED.progression,
+            quote = EXCLUDED.quote,
+            updated_at = NOW()
+        ",
+    )
+    .bind(book_id)
+    .bind(locator.cfi.as_ref().map(Cfi::as_str))
+    .bind(locator.chapter_index.and_then(|i| i32::try_from(i).ok()))
+    .bind(locator.chapter_title.as_deref())
+    .bind(i64::try_from(locator.chapter_offset).unwrap_or(i64::MAX))
+    .bind(locator.progression)
+    .bind(&locator.quote)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Maps a `BookPosition` row using positional indices
+#[inline]
+fn map_book_position_row(row: &sqlx::postgres::PgRow) -> BookPosition {
+    BookPosition {
+        locator: Locator {
+            chapter_index: row
+                .get::<Option<i32>, _>(0)
+                .and_then(|i| usize::try_from(i).ok()),
+            chapter_title: row.get(1),
+            chapter_offset: usize::try_from(row.get::<i64, _>(2)).unwrap_or(0),
+            progression: row.get(3),
+            quote: row.get(4),
+            cfi: row.get::<Option<String>, _>(5).map(Cfi::new_unchecked),
+        },
+        updated_at: row.get::<Option<String>, _>(6).unwrap_or_default(),
+    }
+}
+
+pub async fn get_book_position(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+) -> Result<Option<BookPosition>, DbError> {
+    let row = sqlx::query(
+        r"
+        SELECT chapter_index, chapter_title, chapter_offset, progression, quote, cfi, updated_at::text
+        FROM book_position WHERE book_id = $1
+        ",
+    )
+    .bind(book_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.as_ref().map(map_book_position_row))
+}
+
+// ============================================================================
+// BOOKMARK OPERATIONS
+// ============================================================================
+
+const BOOKMARK_COLUMNS: &str =
+    "id, book_id, locator, label, color, created_at::text, updated_at::text";
+
+/// Maps a `Bookmark` row using positional indices
+#[inline]
+fn map_bookmark_row(row: &sqlx::postgres::PgRow) -> Bookmark {
+    Bookmark {
+        id: BookmarkId::new(row.get(0)),
+        book_id: BookId::new(row.get(1)),
+        locator: row.get::<Json<Locator>, _>(2).0,
+        label: row.get(3),
+        color: row.get::<Option<String>, _>(4).map(Color::new_unchecked),
+        created_at: row.get(5),
+        updated_at: row.get(6),
+    }
+}
+
+/// A book's bookmarks in reading order
+pub async fn list_bookmarks(pool: &Pool<Postgres>, book_id: i64) -> Result<Vec<Bookmark>, DbError> {
+    let rows = sqlx::query(&format!(
+        r"SELECT {BOOKMARK_COLUMNS} FROM bookmark WHERE book_id = $1
+        ORDER BY (locator->>'progression')::float8, id"
+    ))
+    .bind(book_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(map_bookmark_row).collect())
+}
+
+pub async fn create_bookmark(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+    locator: &Locator,
+    label: Option<&str>,
+    color: Option<&Color>,
+) -> Result<Bookmark, DbError> {
+    let row = sqlx::query(&format!(
+        "INSERT INTO bookmark (book_id, locator, label, color) VALUES ($1, $2, $3, $4) RETURNING {BOOKMARK_COLUMNS}"
+    ))
+    .bind(book_id)
+    .bind(Json(locator))
+    .bind(label)
+    .bind(color.map(Color::as_str))
+    .fetch_one(pool)
+    .await?;
+    Ok(map_bookmark_row(&row))
+}
+
+pub async fn update_bookmark(
+    pool: &Pool<Postgres>,
+    bookmark_id: i64,
+    label: Option<&str>,
+    color: Option<&Color>,
+) -> Result<Bookmark, DbError> {
+    let row = sqlx::query(&format!(
+        "UPDATE bookmark SET label = $2, color = $3, updated_at = NOW() WHERE id = $1 RETURNING {BOOKMARK_COLUMNS}"
+    ))
+    .bind(bookmark_id)
+    .bind(label)
+    .bind(color.map(Color::as_str))
+    .fetch_one(pool)
+    .await?;
+    Ok(map_bookmark_row(&row))
+}
+
+/// Moves a bookmark, e.g. after re-anchoring it in a new edition
+pub async fn set_bookmark_locator(
+    pool: &Pool<Postgres>,
+    bookmark_id: i64,
+    locator: &Locator,
+) -> Result<(), DbError> {
+    sqlx::query("UPDATE bookmark SET locator = $2, updated_at = NOW() WHERE id = $1")
+        .bind(bookmark_id)
+        .bind(Json(locator))
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn delete_bookmark(pool: &Pool<Postgres>, bookmark_id: i64) -> Result<(), DbError> {
+    sqlx::query("DELETE FROM bookmark WHERE id = $1")
+        .bind(bookmark_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// ============================================================================
+// POSITION HISTORY OPERATIONS
+// ============================================================================
+
+const POSITION_ENTRY_COLUMNS: &str = "id, book_id, locator, change, created_at::text";
+
+/// Maps a `PositionEntry` row using positional indices
+#[inline]
+fn map_position_entry_row(row: &sqlx::postgres::PgRow) -> PositionEntry {
+    PositionEntry {
+        id: PositionEntryId::new(row.get(0)),
+        book_id: BookId::new(row.get(1)),
+        locator: row.get::<Json<Locator>, _>(2).0,
+        change: row
+            .get::<String, _>(3)
+            .parse()
+            .unwrap_or(PositionChange::Read),
+        created_at: row.get(4),
+    }
+}
+
+/// Records a position in the book's history, keeping only the newest `limit`
+pub async fn push_position_entry(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+    locator: &Locator,
+    change: PositionChange,
+    limit: i64,
+) -> Result<(), DbError> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("INSERT INTO book_position_history (book_id, locator, change) VALUES ($1, $2, $3)")
+        .bind(book_id)
+        .bind(Json(locator))
+        .bind(change.as_str())
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        r"
+        DELETE FROM book_position_history
+        WHERE book_id = $1 AND id NOT IN (
+            SELECT id FROM book_position_history WHERE book_id = $1 ORDER BY id DESC LIMIT $2
+        )
+        ",
+    )
+    .bind(book_id)
+    .bind(limit)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// A book's history, newest first
+pub async fn list_position_history(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+) -> Result<Vec<PositionEntry>, DbError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {POSITION_ENTRY_COLUMNS} FROM book_position_history WHERE book_id = $1 ORDER BY id DESC"
+    ))
+    .bind(book_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(map_position_entry_row).collect())
+}
+
+pub async fn get_position_entry(
+    pool: &Pool<Postgres>,
+    entry_id: i64,
+) -> Result<PositionEntry, DbError> {
+    let row = sqlx::query(&format!(
+        "SELECT {POSITION_ENTRY_COLUMNS} FROM book_position_history WHERE id = $1"
+    ))
+    .bind(entry_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(map_position_entry_row(&row))
+}
+
+/// The newest entry, optionally of one kind
+pub async fn latest_position_entry(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+    change: Option<PositionChange>,
+) -> Result<Option<PositionEntry>, DbError> {
+    let row = sqlx::query(&format!(
+        r"SELECT {POSITION_ENTRY_COLUMNS} FROM book_position_history
+        WHERE book_id = $1 AND ($2::text IS NULL OR change = $2)
+        ORDER BY id DESC LIMIT 1"
+    ))
+    .bind(book_id)
+    .bind(change.map(PositionChange::as_str))
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.as_ref().map(map_position_entry_row))
+}
+
+pub async fn delete_position_entry(pool: &Pool<Postgres>, entry_id: i64) -> Result<(), DbError> {
+    sqlx::query("DELETE FROM book_position_history WHERE id = $1")
+        .bind(entry_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// ============================================================================
+// READING SESSION OPERATIONS
+// ============================================================================
+
+const SESSION_COLUMNS: &str = "id, book_id, started_at::text, ended_at::text, start_locator, end_locator, words_read, EXTRACT(EPOCH FROM ended_at - started_at)::bigint, ended, EXTRACT(EPOCH FROM NOW() - ended_at)::bigint";
+
+/// Maps a `ReadingSession` row using positional indices
+#[inline]
+fn map_reading_session_row(row: &sqlx::postgres::PgRow) -> ReadingSession {
+    ReadingSession {
+        id: SessionId::new(row.get(0)),
+        book_id: BookId::new(row.get(1)),
+        started_at: row.get(2),
+        ended_at: row.get(3),
+        start_locator: row.get::<Json<Locator>, _>(4).0,
+        end_locator: row.get::<Json<Locator>, _>(5).0,
+        words_read: row.get(6),
+        duration_secs: row.get(7),
+        ended: row.get(8),
+        idle_secs: row.get(9),
+    }
+}
+
+/// Starts a session at `locator`, ending any session still open for the book
+pub async fn start_reading_session(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+    locator: &Locator,
+) -> Result<ReadingSession, DbError> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("UPDATE reading_session SET ended = TRUE WHERE book_id = $1 AND NOT ended")
+        .bind(book_id)
+        .execute(&mut *tx)
+        .await?;
+    let row = sqlx::query(&format!(
+        "INSERT INTO reading_session (book_id, start_locator, end_locator) VALUES ($1, $2, $2) RETURNING {SESSION_COLUMNS}"
+    ))
+    .bind(book_id)
+    .bind(Json(locator))
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(map_reading_session_row(&row))
+}
+
+pub async fn get_reading_session(
+    pool: &Pool<Postgres>,
+    session_id: i64,
+) -> Result<ReadingSession, DbError> {
+    let row = sqlx::query(&format!(
+        "SELECT {SESSION_COLUMNS} FROM reading_session WHERE id = $1"
+    ))
+    .bind(session_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(map_reading_session_row(&row))
+}
+
+/// Moves an open session's end to now and `locator`, adding `words` read
+pub async fn extend_reading_session(
+    pool: &Pool<Postgres>,
+    session_id: i64,
+    locator: &Locator,
+    words: i64,
+    end: bool,
+) -> Result<ReadingSession, DbError> {
+    let row = sqlx::query(&format!(
+        r"UPDATE reading_session
+        SET ended_at = NOW(), end_locator = $2, words_read = words_read + $3, ended = $4
+        WHERE id = $1 AND NOT ended
+        RETURNING {SESSION_COLUMNS}"
+    ))
+    .bind(session_id)
+    .bind(Json(locator))
+    .bind(words)
+    .bind(end)
+    .fetch_one(pool)
+    .await?;
+    Ok(map_reading_session_row(&row))
+}
+
+/// Ends a session at its last heartbeat
+pub async fn end_reading_session(
+    pool: &Pool<Postgres>,
+    session_id: i64,
+) -> Result<ReadingSession, DbError> {
+    let row = sqlx::query(&format!(
+        "UPDATE reading_session SET ended = TRUE WHERE id = $1 RETURNING {SESSION_COLUMNS}"
+    ))
+    .bind(session_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(map_reading_session_row(&row))
+}
+
+/// A book's sessions, newest first
+pub async fn list_reading_sessions(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+    limit: i64,
+) -> Result<Vec<ReadingSession>, DbError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {SESSION_COLUMNS} FROM reading_session WHERE book_id = $1 ORDER BY started_at DESC LIMIT $2"
+    ))
+    .bind(book_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(map_reading_session_row).collect())
+}
+
+/// Time, words and books read per local day in `from..=to`
+pub async fn list_reading_days(
+    pool: &Pool<Postgres>,
+    timezone: &str,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Result<Vec<ReadingDay>, DbError> {
+    let rows = sqlx::query(
+        r"
+        SELECT d.day, d.seconds, d.words,
+            CASE WHEN d.seconds > 0 THEN d.words * 60.0 / d.seconds END::float8,
+            d.book_count
+        FROM (
+            SELECT (started_at AT TIME ZONE $1)::date AS day,
+                SUM(EXTRACT(EPOCH FROM ended_at - started_at))::bigint AS seconds,
+                SUM(words_read)::bigint AS words,
+                COUNT(DISTINCT book_id) AS book_count
+            FROM reading_session
+            GROUP BY 1
+        ) d
+        WHERE ($2::date IS NULL OR d.day >= $2) AND ($3::date IS NULL OR d.day <= $3)
+        ORDER BY d.day
+        ",
+    )
+    .bind(timezone)
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| ReadingDay {
+            day: row.get(0),
+            seconds: row.get(1),
+            words: row.get(2),
+            words_per_minute: row.get(3),
+            book_count: row.get(4),
+        })
+        .collect())
+}
+
+/// Books whose sessions first reached `progression` on a day in `from..=to`
+pub async fn list_finished_books(
+    pool: &Pool<Postgres>,
+    timezone: &str,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    progression: f64,
+) -> Result<Vec<FinishedBook>, DbError> {
+    let rows = sqlx::query(
+        r"
+        SELECT f.book_id, b.title, f.finished_on
+        FROM (
+            SELECT book_id, MIN((ended_at AT TIME ZONE $1)::date) AS finished_on
+            FROM reading_session
+            WHERE (end_locator->>'progression')::float8 >= $4
+            GROUP BY book_id
+        ) f
+        JOIN book b ON b.id = f.book_id AND b.deleted_at IS NULL
+        WHERE ($2::date IS NULL OR f.finished_on >= $2) AND ($3::date IS NULL OR f.finished_on <= $3)
+        ORDER BY f.finished_on, b.title
+        ",
+    )
+    .bind(timezone)
+    .bind(from)
+    .bind(to)
+    .bind(progression)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| FinishedBook {
+            book_id: BookId::new(row.get(0)),
+            title: row.get(1),
+            finished_on: row.get(2),
+        })
+        .collect())
+}
+
+/// Every local day with at least a minute of reading, oldest first
+pub async fn list_reading_dates(
+    pool: &Pool<Postgres>,
+    timezone: &str,
+) -> Result<Vec<NaiveDate>, DbError> {
+    let rows = sqlx::query(
+        r"
+        SELECT (started_at AT TIME ZONE $1)::date AS day
+        FROM reading_session
+        GROUP BY 1
+        HAVING SUM(EXTRACT(EPOCH FROM ended_at - started_at)) >= 60
+        ORDER BY 1
+        ",
+    )
+    .bind(timezone)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+/// Today's date in the given time zone, as the database sees it
+pub async fn local_today(pool: &Pool<Postgres>, timezone: &str) -> Result<NaiveDate, DbError> {
+    Ok(sqlx::query_scalar("SELECT (NOW() AT TIME ZONE $1)::date")
+        .bind(timezone)
+        .fetch_one(pool)
+        .await?)
+}
+
+// ============================================================================
+// SETTINGS OPERATIONS
+// ============================================================================
+
+pub async fn set_setting(pool: &Pool<Postgres>, key: &str, value: &str) -> Result<(), DbError> {
+    sqlx::query(
+        "INSERT INTO settings (key, value) VALUES ($1, $2) ON CONFLICT (key) DO UPDATE SET value = EXCLUDED.value"
+    )
+    .bind(key)
+    .bind(value)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_setting(pool: &Pool<Postgres>, key: &str) -> Result<Option<String>, DbError> {
+    let row = sqlx::query("SELECT value FROM settings WHERE key = $1")
+        .bind(key)
+        .fetch_optional(pool)
+        .await?;
+
+    if let Some(r) = row {
+        return Ok(Some(r.get(0)));
+    }
+
+    // Fallback to environment variables (e.g. "openai_api_key" -> "OPENAI_API_KEY")
+    let env_key = key.to_uppercase();
+    if let Ok(val) = env::var(&env_key) {
+        return Ok(Some(val));
+    }
+
+    Ok(None)
+}
+
+// ============================================================================
+// HIGHLIGHT OPERATIONS
+// ============================================================================
+
+const HIGHLIGHT_COLUMNS: &str = "h.id, h.book_id, h.start_path, h.start_offset, h.end_path, h.end_offset, h.text, h.note, h.created_at::text, h.updated_at::text, h.color, h.style, ARRAY(SELECT t.name FROM highlight_tag ht JOIN tag t ON t.id = ht.tag_id WHERE ht.highlight_id = h.id ORDER BY t.name), h.quote_prefix, h.quote_suffix, h.text_start, h.text_end, h.orphaned";
+
+fn push_highlight_filter(qb: &mut QueryBuilder<'_, Postgres>, filter: &HighlightFilter) {
+    qb.push(" AND h.deleted_at IS NULL");
+    if let Some(color) = filter.color.as_deref().and_then(|c| Color::new(c).ok()) {
+        qb.push(" AND h.color = ").push_bind(color.into_string());
+    }
+    if let Some(tag) = filter.tag.as_deref().and_then(|tag| Tag::new(tag).ok()) {
+        qb.push(" AND EXISTS (SELECT 1 FROM highlight_tag ht JOIN tag t ON t.id = ht.tag_id WHERE ht.highlight_id = h.id AND t.name = ")
+            .push_bind(tag.into_string())
+            .push(")");
+    }
+    if let Some(style) = filter.style {
+        qb.push(" AND h.style = ").push_bind(style.as_str());
+    }
+}
+
+pub async fn list_highlights(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+    filter: &HighlightFilter,
+) -> Result<Vec<Highlight>, DbError> {
+    let mut qb = QueryBuilder::<Postgres>::new(format!(
+        "SELECT {HIGHLIGHT_COLUMNS} FROM highlight h WHERE h.book_id = "
+    ));
+    qb.push_bind(book_id);
+    push_highlight_filter(&mut qb, filter);
+    qb.push(" ORDER BY h.created_at DESC");
+
+    let rows = qb.build().fetch_all(pool).await?;
+    Ok(rows.iter().map(map_highlight_row).collect())
+}
+
+/// Highlights across every book, for a commonplace-book view
+pub async fn list_all_highlights(
+    pool: &Pool<Postgres>,
+    filter: &HighlightFilter,
+    sort: HighlightSort,
+    descending: bool,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<LibraryHighlight>, DbError> {
+    let mut qb = QueryBuilder::<Postgres>::new(format!(
+        "SELECT {HIGHLIGHT_COLUMNS}, b.title, b.authors FROM highlight h JOIN book b ON b.id = h.book_id WHERE b.deleted_at IS NULL"
+    ));
+    push_highlight_filter(&mut qb, filter);
+
+    let direction = if descending { "DESC" } else { "ASC" };
+    let order = match sort {
+        HighlightSort::Date => format!("h.created_at {direction}"),
+        HighlightSort::Book => format!("lower(b.title) {direction}, b.id, h.created_at ASC"),
+    };
+    qb.push(" ORDER BY ")
+        .push(order)
+        .push(", h.id ASC LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind(offset);
+
+    let rows = qb.build().fetch_all(pool).await?;
+    Ok(rows.iter().map(map_library_highlight_row).collect())
+}
+
+/// Highlights to export, one book or every book, grouped by book and in
+/// reading order within each; highlights that couldn't be placed come last
+pub async fn list_export_highlights(
+    pool: &Pool<Postgres>,
+    book_id: Option<i64>,
+    filter: &HighlightFilter,
+) -> Result<Vec<LibraryHighlight>, DbError> {
+    let mut qb = QueryBuilder::<Postgres>::new(format!(
+        "SELECT {HIGHLIGHT_COLUMNS}, b.title, b.authors FROM highlight h JOIN book b ON b.id = h.book_id WHERE b.deleted_at IS NULL"
+    ));
+    if let Some(book_id) = book_id {
+        qb.push(" AND h.book_id = ").push_bind(book_id);
+    }
+    push_highlight_filter(&mut qb, filter);
+    qb.push(" ORDER BY lower(b.title), b.id, h.text_start ASC NULLS LAST, h.created_at, h.id");
+
+    let rows = qb.build().fetch_all(pool).await?;
+    Ok(rows.iter().map(map_library_highlight_row).collect())
+}
+
+pub async fn get_highlight(pool: &Pool<Postgres>, highlight_id: i64) -> Result<Highlight, DbError> {
+    let row = sqlx::query(&format!(
+        "SELECT {HIGHLIGHT_COLUMNS} FROM highlight h WHERE h.id = $1 AND h.deleted_at IS NULL"
+    ))
+    .bind(highlight_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(map_highlight_row(&row))
+}
+
+pub async fn create_highlight(
+    pool: &Pool<Postgres>,
+    highlight: &NewHighlight,
+) -> Result<Highlight, DbError> {
+    let selectors = highlight.selectors.as_ref();
+    let row = sqlx::query(&format!(
+        r"
+        INSERT INTO highlight AS h (book_id, start_path, start_offset, end_path, end_offset, text, note, color, style, quote_prefix, quote_suffix, text_start, text_end)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+        RETURNING {HIGHLIGHT_COLUMNS}
+        "
+    ))
+    .bind(highlight.book_id)
+    .bind(&highlight.start_path)
+    .bind(highlight.start_offset)
+    .bind(&highlight.end_path)
+    .bind(highlight.end_offset)
+    .bind(&highlight.text)
+    .bind(highlight.note.as_deref())
+    .bind(highlight.color.as_ref().map(Color::as_str))
+    .bind(highlight.style.as_str())
+    .bind(selectors.map_or("", |s| s.prefix.as_str()))
+    .bind(selectors.map_or("", |s| s.suffix.as_str()))
+    .bind(selectors.and_then(|s| i64::try_from(s.start).ok()))
+    .bind(selectors.and_then(|s| i64::try_from(s.end).ok()))
+    .fetch_one(pool)
+    .await?;
+
+    Ok(map_highlight_row(&row))
+}
+
+pub async fn update_highlight_note(
+    pool: &Pool<Postgres>,
+    highlight_id: i64,
+    note: Option<&str>,
+) -> Result<Highlight, DbError> {
+    let row = sqlx::query(&format!(
+        r"
+        UPDATE highlight h SET note = $1, updated_at = NOW() WHERE h.id = $2
+        RETURNING {HIGHLIGHT_COLUMNS}
+        "
+    ))
+    .bind(note)
+    .bind(highlight_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(map_highlight_row(&row))
+}
+
+pub async fn set_highlight_style(
+    pool: &Pool<Postgres>,
+    highlight_id: i64,
+    color: Option<&Color>,
+    style: HighlightStyle,
+) -> Result<Highlight, DbError> {
+    let row = sqlx::query(&format!(
+        r"
+        UPDATE highlight h SET color = $1, style = $2, updated_at = NOW() WHERE h.id = $3
+        RETURNING {HIGHLIGHT_COLUMNS}
+        "
+    ))
+    .bind(color.map(Color::as_str))
+    .bind(style.as_str())
+    .bind(highlight_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(map_highlight_row(&row))
+}
+
+/// Stores where a highlight was re-anchored, or flags it as orphaned (keeping
+/// its old selectors) when `selectors` is `None`
+pub async fn set_highlight_selectors(
+    pool: &Pool<Postgres>,
+    highlight_id: i64,
+    selectors: Option<&TextSelectors>,
+) -> Result<(), DbError> {
+    selectors
+        .map_or_else(
+            || sqlx::query("UPDATE highlight SET orphaned = TRUE WHERE id = $1").bind(highlight_id),
+            |s| {
+                sqlx::query(
+                    r"
+                    UPDATE highlight
+                    SET quote_prefix = $2, quote_suffix = $3, text_start = $4, text_end = $5, orphaned = FALSE
+                    WHERE id = $1
+                    ",
+                )
+                .bind(highlight_id)
+                .bind(&s.prefix)
+                .bind(&s.suffix)
+                .bind(i64::try_from(s.start).ok())
+                .bind(i64::try_from(s.end).ok())
+            },
+        )
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Moves a highlight to the trash
+pub async fn delete_highlight(pool: &Pool<Postgres>, highlight_id: i64) -> Result<(), DbError> {
+    sqlx::query(
+        "UPDATE highlight SET deleted_at = NOW(), updated_at = NOW() WHERE id = $1 AND deleted_at IS NULL",
+    )
+    .bind(highlight_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// ============================================================================
+// HIGHLIGHT TAG OPERATIONS
+// ============================================================================
+
+pub async fn add_highlight_tag(
+    pool: &Pool<Postgres>,
+    highlight_id: i64,
+    tag: &Tag,
+) -> Result<(), DbError> {
+    let mut tx = pool.begin().await?;
+    let tag_id: i64 = sqlx::query_scalar(
+        "INSERT INTO tag (name) VALUES ($1) ON CONFLICT (name) DO UPDATE SET name = EXCLUDED.name RETURNING id",
+    )
+    .bind(tag.as_str())
+    .fetch_one(&mut *tx)
+    .await?;
+    sqlx::query(
+        "INSERT INTO highlight_tag (highlight_id, tag_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+    )
+    .bind(highlight_id)
+    .bind(tag_id)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Removes a tag from a highlight, dropping the tag once nothing uses it
+pub async fn remove_highlight_tag(
+    pool: &Pool<Postgres>,
+    highlight_id: i64,
+    tag: &Tag,
+) -> Result<(), DbError> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "DELETE FROM highlight_tag WHERE highlight_id = $1 AND tag_id = (SELECT id FROM tag WHERE name = $2)",
+    )
+    .bind(highlight_id)
+    .bind(tag.as_str())
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        "DELETE FROM tag t WHERE t.name = $1 AND NOT EXISTS (SELECT 1 FROM highlight_tag ht WHERE ht.tag_id = t.id)",
+    )
+    .bind(tag.as_str())
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Every highlight tag with the number of highlights using it
+pub async fn list_highlight_tags(pool: &Pool<Postgres>) -> Result<Vec<HighlightTagCount>, DbError> {
+    let rows: Vec<(String, i64)> = sqlx::query_as(
+        r"SELECT t.name, COUNT(h.id) FROM tag t
+        LEFT JOIN highlight_tag ht ON ht.tag_id = t.id
+        LEFT JOIN highlight h ON h.id = ht.highlight_id AND h.deleted_at IS NULL
+            AND EXISTS (SELECT 1 FROM book b WHERE b.id = h.book_id AND b.deleted_at IS NULL)
+        GROUP BY t.name ORDER BY t.name ASC",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(tag, highlight_count)| HighlightTagCount {
+            tag: Tag::new_unchecked(tag),
+            highlight_count,
+        })
+        .collect())
+}
+
+// ============================================================================
+// HIGHLIGHT MESSAGE OPERATIONS
+// ============================================================================
+
+pub async fn list_highlight_messages(
+    pool: &Pool<Postgres>,
+    highlight_id: i64,
+) -> Result<Vec<HighlightMessage>, DbError> {
+    let rows = sqlx::query(
+        r"
+        SELECT id, highlight_id, role, content, created_at::text
+        FROM highlight_message WHERE highlight_id = $1 ORDER BY created_at ASC
+        ",
+    )
+    .bind(highlight_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.iter().map(map_highlight_message_row).collect())
+}
+
+/// Messages of several highlights, oldest first
+pub async fn list_messages_for_highlights(
+    pool: &Pool<Postgres>,
+    highlight_ids: &[i64],
+) -> Result<Vec<HighlightMessage>, DbError> {
+    let rows = sqlx::query(
+        r"
+        SELECT id, highlight_id, role, content, created_at::text
+        FROM highlight_message WHERE highlight_id = ANY($1) ORDER BY created_at ASC, id ASC
+        ",
+    )
+    .bind(highlight_ids)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.iter().map(map_highlight_message_row).collect())
+}
+
+pub async fn add_highlight_message(
+    pool: &Pool<Postgres>,
+    highlight_id: i64,
+    role: &str,
+    content: &str,
+) -> Result<HighlightMessage, DbError> {
+    let row = sqlx::query(
+        r"
+        INSERT INTO highlight_message (highlight_id, role, content)
+        VALUES ($1, $2, $3)
+        RETURNING id, highlight_id, role, content, created_at::text
+        ",
+    )
+    .bind(highlight_id)
+    .bind(role)
+    .bind(content)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(map_highlight_message_row(&row))
+}
+
+// ============================================================================
+// BOOK CHAT THREAD OPERATIONS
+// ============================================================================
+
+pub async fn list_book_chat_threads(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+) -> Result<Vec<BookChatThread>, DbError> {
+    let rows = sqlx::query(
+        r"
+        SELECT id, book_id, title, last_cfi, created_at::text, updated_at::text
+        FROM book_chat_thread WHERE book_id = $1 AND deleted_at IS NULL ORDER BY updated_at DESC
+        ",
+    )
+    .bind(book_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.iter().map(map_book_chat_thread_row).collect())
+}
+
+pub async fn create_book_chat_thread(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+    title: &str,
+) -> Result<BookChatThread, DbError> {
+    let row = sqlx::query(
+        r"
+        INSERT INTO book_chat_thread (book_id, title)
+        VALUES ($1, $2)
+        RETURNING id, book_id, title, last_cfi, created_at::text, updated_at::text
+        ",
+    )
+    .bind(book_id)
+    .bind(title)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(map_book_chat_thread_row(&row))
+}
+
+pub async fn rename_book_chat_thread(
+    pool: &Pool<Postgres>,
+    thread_id: i64,
+    title: &str,
+) -> Result<(), DbError> {
+    sqlx::query("UPDATE book_chat_thread SET title = $1, updated_at = NOW() WHERE id = $2")
+        .bind(title)
+        .bind(thread_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn set_thread_last_cfi(
+    pool: &Pool<Postgres>,
+    thread_id: i64,
+    cfi: &str,
+) -> Result<(), DbError> {
+    sqlx::query("UPDATE book_chat_thread SET last_cfi = $1, updated_at = NOW() WHERE id = $2")
+        .bind(cfi)
+        .bind(thread_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Moves a chat thread, with its messages, to the trash. The messages are
+/// stamped with the thread's deletion time, which is how a restore finds them.
+pub async fn delete_book_chat_thread(pool: &Pool<Postgres>, thread_id: i64) -> Result<(), DbError> {
+    let mut tx = pool.begin().await?;
+    let trashed = sqlx::query(
+        "UPDATE book_chat_thread SET deleted_at = NOW() WHERE id = $1 AND deleted_at IS NULL",
+    )
+    .bind(thread_id)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if trashed > 0 {
+        sqlx::query(&format!(
+            "UPDATE book_message SET {TRASH_MESSAGES} WHERE thread_id = $1 AND deleted_at IS NULL"
+        ))
+        .bind(thread_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+// ============================================================================
+// BOOK MESSAGE OPERATIONS
+// ============================================================================
+
+/// Moves the matched messages to the trash as one batch
+const TRASH_MESSAGES: &str =
+    "deleted_at = NOW(), trash_batch = (SELECT nextval('book_message_trash_batch'))";
+
+pub async fn list_book_messages(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+    thread_id: Option<i64>,
+) -> Result<Vec<BookMessage>, DbError> {
+    let rows = sqlx::query(
+        r"
+        SELECT id, book_id, thread_id, role, content, reasoning_summary, context_map, created_at::text
+        FROM book_message WHERE book_id = $1 AND (thread_id = $2 OR (thread_id IS NULL AND $2 IS NULL))
+            AND deleted_at IS NULL
+        ORDER BY created_at ASC
+        ",
+    )
+    .bind(book_id)
+    .bind(thread_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.iter().map(map_book_message_row).collect())
+}
+
+pub async fn add_book_message(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+    thread_id: Option<i64>,
+    role: &str,
+    content: &str,
+    reasoning_summary: Option<&str>,
+    context_map: Option<&str>,
+) -> Result<BookMessage, DbError> {
+    let row = sqlx::query(
+        r"
+        INSERT INTO book_message (book_id, thread_id, role, content, reasoning_summary, context_map)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, book_id, thread_id, role, content, reasoning_summary, context_map, created_at::text
+        ",
+    )
+    .bind(book_id)
+    .bind(thread_id)
+    .bind(role)
+    .bind(content)
+    .bind(reasoning_summary)
+    .bind(context_map)
+    .fetch_one(pool)
+    .await?;
+
+    if let Some(tid) = thread_id {
+        sqlx::query("UPDATE book_chat_thread SET updated_at = NOW() WHERE id = $1")
+            .bind(tid)
+            .execute(pool)
+            .await?;
+    }
+
+    Ok(map_book_message_row(&row))
+}
+
+/// Moves every message of a book to the trash
+pub async fn delete_book_messages(pool: &Pool<Postgres>, book_id: i64) -> Result<(), DbError> {
+    sqlx::query(&format!(
+        "UPDATE book_message SET {TRASH_MESSAGES} WHERE book_id = $1 AND deleted_at IS NULL"
+    ))
+    .bind(book_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_book_message(pool: &Pool<Postgres>, message_id: i64) -> Result<(), DbError> {
+    sqlx::query(&format!(
+        "UPDATE book_message SET {TRASH_MESSAGES} WHERE id = $1 AND deleted_at IS NULL"
+    ))
+    .bind(message_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn clear_default_book_messages(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+) -> Result<(), DbError> {
+    sqlx::query(&format!(
+        "UPDATE book_message SET {TRASH_MESSAGES} WHERE book_id = $1 AND thread_id IS NULL AND deleted_at IS NULL"
+    ))
+    .bind(book_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_thread_max_citation_index(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+    thread_id: Option<i64>,
+) -> Result<i32, DbError> {
+    let rows = sqlx::query(
+        r"
+        SELECT context_map FROM book_message
+        WHERE book_id = $1 AND (thread_id = $2 OR (thread_id IS NULL AND $2 IS NULL))
+        AND context_map IS NOT NULL
+        ",
+    )
+    .bind(book_id)
+    .bind(thread_id)
+    .fetch_all(pool)
+    .await?;
+
+    // Optimized JSON parsing: avoid repeated String allocations and clones
+    let max_index = rows
+        .iter()
+        .filter_map(|row| row.get::<Option<&str>, _>(0))
+        .filter_map(|json_str| serde_json::from_str::<serde_json::Value>(json_str).ok())
+        .filter_map(|json| {
+            json.as_object()
+                .and_then(|obj| obj.keys().filter_map(|k| k.parse::<i32>().ok()).max())
+        })
+        .max()
+        .unwrap_or(0);
+
+    Ok(max_index)
+}
+
+pub async fn delete_book_thread_messages(
+    pool: &Pool<Postgres>,
+    thread_id: i64,
+) -> Result<(), DbError> {
+    sqlx::query(&format!(
+        "UPDATE book_message SET {TRASH_MESSAGES} WHERE thread_id = $1 AND deleted_at IS NULL"
+    ))
+    .bind(thread_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// ============================================================================
+// TTS LEXICON OPERATIONS
+// ============================================================================
+
+pub async fn list_lexicon_entries(pool: &Pool<Postgres>) -> Result<Vec<LexiconEntry>, DbError> {
+    let rows = sqlx::query(
+        r"
+        SELECT id, term, replacement, created_at::text, updated_at::text
+        FROM tts_lexicon ORDER BY term ASC
+        ",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.iter().map(map_lexicon_entry_row).collect())
+}
+
+pub async fn create_lexicon_entry(
+    pool: &Pool<Postgres>,
+    term: &str,
+    replacement: &str,
+) -> Result<LexiconEntry, DbError> {
+    let row = sqlx::query(
+        r"
+        INSERT INTO tts_lexicon (term, replacement)
+        VALUES ($1, $2)
+        ON CONFLICT (term) DO UPDATE SET replacement = EXCLUDED.replacement, updated_at = NOW()
+        RETURNING id, term, replacement, created_at::text, updated_at::text
+        ",
+    )
+    .bind(term)
+    .bind(replacement)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(map_lexicon_entry_row(&row))
+}
+
+pub async fn update_lexicon_entry(
+    pool: &Pool<Postgres>,
+    entry_id: i64,
+    term: &str,
+    replacement: &str,
+) -> Result<LexiconEntry, DbError> {
+    let row = sqlx::query(
+        r"
+        UPDATE tts_lexicon SET term = $1, replacement = $2, updated_at = NOW() WHERE id = $3
+        RETURNING id, term, replacement, created_at::text, updated_at::text
+        ",
+    )
+    .bind(term)
+    .bind(replacement)
+    .bind(entry_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(map_lexicon_entry_row(&row))
+}
+
+pub async fn delete_lexicon_entry(pool: &Pool<Postgres>, entry_id: i64) -> Result<(), DbError> {
+    sqlx::query("DELETE FROM tts_lexicon WHERE id = $1")
+        .bind(entry_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// ============================================================================
+// BOOK CAST OPERATIONS
+// ============================================================================
+
+pub async fn list_book_cast(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+) -> Result<Vec<CastMember>, DbError> {
+    let rows = sqlx::query(
+        r"
+        SELECT book_id, character, voice_id, updated_at::text
+        FROM book_cast WHERE book_id = $1 ORDER BY character ASC
+        ",
+    )
+    .bind(book_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.iter().map(map_cast_member_row).collect())
+}
+
+pub async fn set_book_cast_voice(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+    character: &str,
+    voice_id: &str,
+) -> Result<CastMember, DbError> {
+    let row = sqlx::query(
+        r"
+        INSERT INTO book_cast (book_id, character, voice_id)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (book_id, character) DO UPDATE SET voice_id = EXCLUDED.voice_id, updated_at = NOW()
+        RETURNING book_id, character, voice_id, updated_at::text
+        ",
+    )
+    .bind(book_id)
+    .bind(character)
+    .bind(voice_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(map_cast_member_row(&row))
+}
+
+pub async fn delete_book_cast_member(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+    character: &str,
+) -> Result<(), DbError> {
+    sqlx::query("DELETE FROM book_cast WHERE book_id = $1 AND character = $2")
+        .bind(book_id)
+        .bind(character)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// ============================================================================
+// CATALOG OPERATIONS
+// ============================================================================
+
+const CATALOG_COLUMNS: &str =
+    "id, key, name, filters, position, created_at::text, updated_at::text";
+
+pub async fn list_catalogs(pool: &Pool<Postgres>) -> Result<Vec<Catalog>, DbError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {CATALOG_COLUMNS} FROM catalog ORDER BY position ASC, id ASC"
+    ))
+    .fetch_all(pool)
+    .await?;
+
+    rows.iter().map(map_catalog_row).collect()
+}
+
+pub async fn get_catalog_by_key(
+    pool: &Pool<Postgres>,
+    key: &str,
+) -> Result<Option<Catalog>, DbError> {
+    let row = sqlx::query(&format!(
+        "SELECT {CATALOG_COLUMNS} FROM catalog WHERE key = $1"
+    ))
+    .bind(key)
+    .fetch_optional(pool)
+    .await?;
+
+    row.as_ref().map(map_catalog_row).transpose()
+}
+
+/// Adds a catalog at the end of the list
+pub async fn create_catalog(
+    pool: &Pool<Postgres>,
+    key: &str,
+    name: &str,
+    query: &CatalogQuery,
+) -> Result<Catalog, DbError> {
+    let row = sqlx::query(&format!(
+        r"
+        INSERT INTO catalog (key, name, filters, position)
+        VALUES ($1, $2, $3, (SELECT COALESCE(MAX(position) + 1, 0) FROM catalog))
+        RETURNING {CATALOG_COLUMNS}
+        "
+    ))
+    .bind(key)
+    .bind(name)
+    .bind(Json(query))
+    .fetch_one(pool)
+    .await?;
+
+    map_catalog_row(&row)
+}
+
+pub async fn update_catalog(
+    pool: &Pool<Postgres>,
+    catalog_id: i64,
+    name: &str,
+    query: &CatalogQuery,
+) -> Result<Catalog, DbError> {
+    let row = sqlx::query(&format!(
+        r"
+        UPDATE catalog SET name = $1, filters = $2, updated_at = NOW() WHERE id = $3
+        RETURNING {CATALOG_COLUMNS}
+        "
+    ))
+    .bind(name)
+    .bind(Json(query))
+    .bind(catalog_id)
+    .fetch_one(pool)
+    .await?;
+
+    map_catalog_row(&row)
+}
+
+pub async fn delete_catalog(pool: &Pool<Postgres>, catalog_id: i64) -> Result<(), DbError> {
+    sqlx::query("DELETE FROM catalog WHERE id = $1")
+        .bind(catalog_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// ============================================================================
+// COLLECTION OPERATIONS
+// ============================================================================
+
+const COLLECTION_COLUMNS: &str = "c.id, c.name, c.position, (SELECT COUNT(*) FROM book_collection bc JOIN book b ON b.id = bc.book_id WHERE bc.collection_id = c.id AND b.deleted_at IS NULL), c.created_at::text, c.updated_at::text";
+
+/// Maps a `Collection` row using positional indices
+#[inline]
+fn map_collection_row(row: &sqlx::postgres::PgRow) -> Collection {
+    Collection {
+        id: CollectionId::new(row.get::<i64, _>(0)),
+        name: row.get(1),
+        position: row.get(2),
+        book_count: row.get(3),
+        created_at: row.get::<Option<String>, _>(4).unwrap_or_default(),
+        updated_at: row.get::<Option<String>, _>(5).unwrap_or_default(),
+    }
+}
+
+pub async fn list_collections(pool: &Pool<Postgres>) -> Result<Vec<Collection>, DbError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {COLLECTION_COLUMNS} FROM collection c ORDER BY c.position ASC, c.id ASC"
+    ))
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.iter().map(map_collection_row).collect())
+}
+
+/// Collections a book belongs to
+pub async fn list_book_collections(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+) -> Result<Vec<Collection>, DbError> {
+    let rows = sqlx::query(&format!(
+        r"
+        SELECT {COLLECTION_COLUMNS} FROM collection c
+        JOIN book_collection m ON m.collection_id = c.id
+        WHERE m.book_id = $1
+        ORDER BY c.position ASC, c.id ASC
+        "
+    ))
+    .bind(book_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.iter().map(map_collection_row).collect())
+}
+
+/// Adds a collection at the end of the list
+pub async fn create_collection(pool: &Pool<Postgres>, name: &str) -> Result<Collection, DbError> {
+    let (id,): (i64,) = sqlx::query_as(
+        r"
+        INSERT INTO collection (name, position)
+        VALUES ($1, (SELECT COALESCE(MAX(position) + 1, 0) FROM collection))
         RETURNING id
         ",
     )
-    .bind(gutenberg_id)
-    .bind(title)
-    .bind(authors)
-    .bind(publication_year)
-    .bind(cover_url)
-    .bind(mobi_data)
-    .bind(html_content)
-    .bind(first_image_index)
+    .bind(name)
     .fetch_one(pool)
-    .await
-    .map_err(|e| {
-        println!("[Backend] Book upsert failed for {}: {}", gutenberg_id, e);
-        e
-    })?;
-    println!(
-        "[Backend] Successfully upserted book {} (id: {})",
-        gutenberg_id, row.0
-    );
-    Ok(row.0)
+    .await?;
+
+    get_collection(pool, id).await
+}
+
+async fn get_collection(pool: &Pool<Postgres>, collection_id: i64) -> Result<Collection, DbError> {
+    let row = sqlx::query(&format!(
+        "SELECT {COLLECTION_COLUMNS} FROM collection c WHERE c.id = $1"
+    ))
+    .bind(collection_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(map_collection_row(&row))
+}
+
+pub async fn rename_collection(
+    pool: &Pool<Postgres>,
+    collection_id: i64,
+    name: &str,
+) -> Result<Collection, DbError> {
+    sqlx::query("UPDATE collection SET name = $1, updated_at = NOW() WHERE id = $2")
+        .bind(name)
+        .bind(collection_id)
+        .execute(pool)
+        .await?;
+
+    get_collection(pool, collection_id).await
+}
+
+pub async fn delete_collection(pool: &Pool<Postgres>, collection_id: i64) -> Result<(), DbError> {
+    sqlx::query("DELETE FROM collection WHERE id = $1")
+        .bind(collection_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn add_book_to_collection(
+    pool: &Pool<Postgres>,
+    collection_id: i64,
+    book_id: i64,
+) -> Result<(), DbError> {
+    sqlx::query(
+        "INSERT INTO book_collection (collection_id, book_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+    )
+    .bind(collection_id)
+    .bind(book_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn remove_book_from_collection(
+    pool: &Pool<Postgres>,
+    collection_id: i64,
+    book_id: i64,
+) -> Result<(), DbError> {
+    sqlx::query("DELETE FROM book_collection WHERE collection_id = $1 AND book_id = $2")
+        .bind(collection_id)
+        .bind(book_id)
+        .execute(pool)
+        .await?;
+    Ok(())
 }
 
-pub async fn list_books(pool: &Pool<Postgres>) -> Result<Vec<Book>, DbError> {
-    let rows = sqlx::query(
-        r"
-        SELECT id, gutenberg_id, title, authors, publication_year, cover_url, mobi_data, html_content, first_image_index, created_at::text
-        FROM book ORDER BY title ASC
-        "
+// ============================================================================
+// TAG OPERATIONS
+// ============================================================================
+
+/// Every tag in use, with how many books carry it
+pub async fn list_tags(pool: &Pool<Postgres>) -> Result<Vec<TagCount>, DbError> {
+    let rows: Vec<(String, i64)> = sqlx::query_as(
+        r"SELECT bt.tag, COUNT(*) FROM book_tag bt JOIN book b ON b.id = bt.book_id
+            WHERE b.deleted_at IS NULL GROUP BY bt.tag ORDER BY bt.tag ASC",
     )
     .fetch_all(pool)
     .await?;
 
-    Ok(rows.iter().map(map_book_row).collect())
+    Ok(rows
+        .into_iter()
+        .map(|(tag, book_count)| TagCount {
+            tag: Tag::new_unchecked(tag),
+            book_count,
+        })
+        .collect())
 }
 
-pub async fn get_book(pool: &Pool<Postgres>, book_id: i64) -> Result<Book, DbError> {
-    let row = sqlx::query(
+pub async fn list_book_tags(pool: &Pool<Postgres>, book_id: i64) -> Result<Vec<Tag>, DbError> {
+    let rows: Vec<(String,)> =
+        sqlx::query_as("SELECT tag FROM book_tag WHERE book_id = $1 ORDER BY tag ASC")
+            .bind(book_id)
+            .fetch_all(pool)
+            .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(tag,)| Tag::new_unchecked(tag))
+        .collect())
+}
+
+pub async fn add_book_tag(pool: &Pool<Postgres>, book_id: i64, tag: &Tag) -> Result<(), DbError> {
+    sqlx::query("INSERT INTO book_tag (book_id, tag) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+        .bind(book_id)
+        .bind(tag.as_str())
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn remove_book_tag(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+    tag: &Tag,
+) -> Result<(), DbError> {
+    sqlx::query("DELETE FROM book_tag WHERE book_id = $1 AND tag = $2")
+        .bind(book_id)
+        .bind(tag.as_str())
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Renames a tag on every book; books already carrying `to` keep a single copy
+pub async fn rename_tag(pool: &Pool<Postgres>, from: &Tag, to: &Tag) -> Result<(), DbError> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(
         r"
-        SELECT id, gutenberg_id, title, authors, publication_year, cover_url, mobi_data, html_content, first_image_index, created_at::text
-        FROM book WHERE id = $1
+        INSERT INTO book_tag (book_id, tag, created_at)
+        SELECT book_id, $2, created_at FROM book_tag WHERE tag = $1
+        ON CONFLICT DO NOTHING
         ",
     )
-    .bind(book_id)
-    .fetch_one(pool)
+    .bind(from.as_str())
+    .bind(to.as_str())
+    .execute(&mut *tx)
     .await?;
-
-    Ok(map_book_row(&row))
+    sqlx::query("DELETE FROM book_tag WHERE tag = $1 AND tag <> $2")
+        .bind(from.as_str())
+        .bind(to.as_str())
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
 }
 
-pub async fn hard_delete_book(pool: &Pool<Postgres>, book_id: i64) -> Result<(), DbError> {
-    sqlx::query("DELETE FROM book WHERE id = $1")
-        .bind(book_id)
+/// Removes a tag from every book
+pub async fn delete_tag(pool: &Pool<Postgres>, tag: &Tag) -> Result<(), DbError> {
+    sqlx::query("DELETE FROM book_tag WHERE tag = $1")
+        .bind(tag.as_str())
         .execute(pool)
         .await?;
     Ok(())
 }
 
 // ============================================================================
-// BOOK POSITION OPERATIONS
+// HTTP CACHE OPERATIONS
 // ============================================================================
 
-pub async fn set_book_position(
+/// Looks up a cached response; `fresh` is computed against `ttl_seconds`
+pub async fn get_http_cache_entry(
     pool: &Pool<Postgres>,
-    book_id: i64,
-    cfi: &str,
+    url: &str,
+    ttl_seconds: i64,
+) -> Result<Option<HttpCacheEntry>, DbError> {
+    let row = sqlx::query(
+        r"
+        SELECT url, body, etag, last_modified, fetched_at::text,
+               fetched_at > NOW() - make_interval(secs => $2::double precision)
+        FROM http_cache WHERE url = $1
+        ",
+    )
+    .bind(url)
+    .bind(ttl_seconds)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.as_ref().map(map_http_cache_entry_row))
+}
+
+pub async fn put_http_cache_entry(
+    pool: &Pool<Postgres>,
+    url: &str,
+    body: &str,
+    etag: Option<&str>,
+    last_modified: Option<&str>,
 ) -> Result<(), DbError> {
     sqlx::query(
         r"
-        INSERT INTO book_position (book_id, cfi)
-        VALUES ($1, $2)
-        ON CONFLICT (book_id) DO UPDATE SET cfi = EXCLUDED.cfi, updated_at = NOW()
+        INSERT INTO http_cache (url, body, etag, last_modified)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (url) DO UPDATE SET
+            body = EXCLUDED.body,
+            etag = EXCLUDED.etag,
+            last_modified = EXCLUDED.last_modified,
+            fetched_at = NOW()
         ",
     )
-    .bind(book_id)
-    .bind(cfi)
+    .bind(url)
+    .bind(body)
+    .bind(etag)
+    .bind(last_modified)
     .execute(pool)
     .await?;
     Ok(())
 }
 
-pub async fn get_book_position(
-    pool: &Pool<Postgres>,
-    book_id: i64,
-) -> Result<Option<BookPosition>, DbError> {
-    let row = sqlx::query("SELECT cfi, updated_at::text FROM book_position WHERE book_id = $1")
-        .bind(book_id)
-        .fetch_optional(pool)
+/// Marks a cached response as revalidated (HTTP 304)
+pub async fn touch_http_cache_entry(pool: &Pool<Postgres>, url: &str) -> Result<(), DbError> {
+    sqlx::query("UPDATE http_cache SET fetched_at = NOW() WHERE url = $1")
+        .bind(url)
+        .execute(pool)
         .await?;
+    Ok(())
+}
 
-    Ok(row.map(|r| BookPosition {
-        cfi: r.get(0),
-        updated_at: r.get::<Option<String>, _>(1).unwrap_or_default(),
-    }))
+pub async fn clear_http_cache(pool: &Pool<Postgres>) -> Result<u64, DbError> {
+    let result = sqlx::query("DELETE FROM http_cache").execute(pool).await?;
+    Ok(result.rows_affected())
 }
 
 // ============================================================================
-// SETTINGS OPERATIONS
+// LOCAL CATALOG OPERATIONS
 // ============================================================================
 
-pub async fn set_setting(pool: &Pool<Postgres>, key: &str, value: &str) -> Result<(), DbError> {
-    sqlx::query(
-        "INSERT INTO settings (key, value) VALUES ($1, $2) ON CONFLICT (key) DO UPDATE SET value = EXCLUDED.value"
-    )
-    .bind(key)
-    .bind(value)
-    .execute(pool)
-    .await?;
+/// Inserts or replaces a batch of catalog works with all their relations
+#[allow(clippy::too_many_lines)]
+pub async fn import_catalog_works(
+    pool: &Pool<Postgres>,
+    works: &[CatalogWork],
+) -> Result<(), DbError> {
+    let mut tx = pool.begin().await?;
+
+    for work in works {
+        sqlx::query(
+            r"
+            INSERT INTO catalog_work (id, title, issued, media_type, copyright, download_count)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (id) DO UPDATE SET
+                title = EXCLUDED.title,
+                issued = EXCLUDED.issued,
+                media_type = EXCLUDED.media_type,
+                copyright = COALESCE(EXCLUDED.copyright, catalog_work.copyright),
+                download_count = COALESCE(EXCLUDED.download_count, catalog_work.download_count)
+            ",
+        )
+        .bind(work.id)
+        .bind(&work.title)
+        .bind(&work.issued)
+        .bind(&work.media_type)
+        .bind(work.copyright)
+        .bind(work.download_count)
+        .execute(&mut *tx)
+        .await?;
+
+        for table in [
+            "catalog_work_agent",
+            "catalog_work_subject",
+            "catalog_work_bookshelf",
+            "catalog_work_language",
+            "catalog_format",
+        ] {
+            sqlx::query(&format!("DELETE FROM {table} WHERE work_id = $1"))
+                .bind(work.id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        for (position, agent) in work.agents.iter().enumerate() {
+            let agent_key = format!(
+                "{}|{}|{}",
+                agent.name,
+                agent.birth_year.map(|y| y.to_string()).unwrap_or_default(),
+                agent.death_year.map(|y| y.to_string()).unwrap_or_default()
+            );
+            let (agent_id,): (i64,) = sqlx::query_as(
+                r"
+                INSERT INTO catalog_agent (agent_key, name, birth_year, death_year)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (agent_key) DO UPDATE SET name = EXCLUDED.name
+                RETURNING id
+                ",
+            )
+            .bind(&agent_key)
+            .bind(&agent.name)
+            .bind(agent.birth_year)
+            .bind(agent.death_year)
+            .fetch_one(&mut *tx)
+            .await?;
+
+            sqlx::query(
+                r"
+                INSERT INTO catalog_work_agent (work_id, agent_id, role, position)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT DO NOTHING
+                ",
+            )
+            .bind(work.id)
+            .bind(agent_id)
+            .bind(&agent.role)
+            .bind(i32::try_from(position).unwrap_or(i32::MAX))
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        for (names, table, link, column) in [
+            (
+                &work.subjects,
+                "catalog_subject",
+                "catalog_work_subject",
+                "subject_id",
+            ),
+            (
+                &work.bookshelves,
+                "catalog_bookshelf",
+                "catalog_work_bookshelf",
+                "bookshelf_id",
+            ),
+        ] {
+            for name in names {
+                let (id,): (i64,) = sqlx::query_as(&format!(
+                    "INSERT INTO {table} (name) VALUES ($1) \
+                     ON CONFLICT (name) DO UPDATE SET name = EXCLUDED.name RETURNING id"
+                ))
+                .bind(name)
+                .fetch_one(&mut *tx)
+                .await?;
+
+                sqlx::query(&format!(
+                    "INSERT INTO {link} (work_id, {column}) VALUES ($1, $2) ON CONFLICT DO NOTHING"
+                ))
+                .bind(work.id)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        for code in &work.languages {
+            sqlx::query(
+                "INSERT INTO catalog_work_language (work_id, code) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+            )
+            .bind(work.id)
+            .bind(code.to_lowercase())
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        for (mime_type, url) in &work.formats {
+            sqlx::query(
+                r"
+                INSERT INTO catalog_format (work_id, mime_type, url) VALUES ($1, $2, $3)
+                ON CONFLICT (work_id, mime_type) DO UPDATE SET url = EXCLUDED.url
+                ",
+            )
+            .bind(work.id)
+            .bind(mime_type)
+            .bind(url)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+
+    tx.commit().await?;
     Ok(())
 }
 
-pub async fn get_setting(pool: &Pool<Postgres>, key: &str) -> Result<Option<String>, DbError> {
-    let row = sqlx::query("SELECT value FROM settings WHERE key = $1")
-        .bind(key)
-        .fetch_optional(pool)
+pub async fn count_catalog_works(pool: &Pool<Postgres>) -> Result<i64, DbError> {
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM catalog_work")
+        .fetch_one(pool)
         .await?;
+    Ok(count)
+}
 
-    if let Some(r) = row {
-        return Ok(Some(r.get(0)));
+fn escape_like(term: &str) -> String {
+    term.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+fn like_pattern(term: &str) -> String {
+    format!("%{}%", escape_like(term))
+}
+
+/// Appends the `WHERE` clause for a local catalog query, mirroring Gutendex's
+/// semantics for each filter
+fn push_catalog_filters(builder: &mut QueryBuilder<'_, Postgres>, query: &CatalogQuery) {
+    builder.push(" WHERE w.media_type = 'Text'");
+
+    // Each search word must appear in the title or an author's name
+    for term in query
+        .search
+        .iter()
+        .flat_map(|search| search.split_whitespace())
+    {
+        let pattern = like_pattern(term);
+        builder
+            .push(" AND (w.title ILIKE ")
+            .push_bind(pattern.clone())
+            .push(
+                " OR EXISTS (SELECT 1 FROM catalog_work_agent wa \
+                 JOIN catalog_agent a ON a.id = wa.agent_id \
+                 WHERE wa.work_id = w.id AND wa.role = 'author' AND a.name ILIKE ",
+            )
+            .push_bind(pattern)
+            .push("))");
     }
 
-    // Fallback to environment variables (e.g. "openai_api_key" -> "OPENAI_API_KEY")
-    let env_key = key.to_uppercase();
-    if let Ok(val) = env::var(&env_key) {
-        return Ok(Some(val));
+    if let Some(topic) = query
+        .topic
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+    {
+        let pattern = like_pattern(topic);
+        builder
+            .push(
+                " AND (EXISTS (SELECT 1 FROM catalog_work_subject ws \
+                 JOIN catalog_subject s ON s.id = ws.subject_id \
+                 WHERE ws.work_id = w.id AND s.name ILIKE ",
+            )
+            .push_bind(pattern.clone())
+            .push(
+                ") OR EXISTS (SELECT 1 FROM catalog_work_bookshelf wb \
+                 JOIN catalog_bookshelf b ON b.id = wb.bookshelf_id \
+                 WHERE wb.work_id = w.id AND b.name ILIKE ",
+            )
+            .push_bind(pattern)
+            .push("))");
     }
 
-    Ok(None)
+    if !query.languages.is_empty() {
+        let languages: Vec<String> = query.languages.iter().map(|l| l.to_lowercase()).collect();
+        builder
+            .push(
+                " AND EXISTS (SELECT 1 FROM catalog_work_language l \
+                 WHERE l.work_id = w.id AND l.code = ANY(",
+            )
+            .push_bind(languages)
+            .push("))");
+    }
+
+    // An author alive on or after the start year, and one alive on or before the end year
+    if let Some(year) = query.author_year_start {
+        builder
+            .push(
+                " AND EXISTS (SELECT 1 FROM catalog_work_agent wa \
+                 JOIN catalog_agent a ON a.id = wa.agent_id \
+                 WHERE wa.work_id = w.id AND wa.role = 'author' AND a.death_year >= ",
+            )
+            .push_bind(year)
+            .push(")");
+    }
+    if let Some(year) = query.author_year_end {
+        builder
+            .push(
+                " AND EXISTS (SELECT 1 FROM catalog_work_agent wa \
+                 JOIN catalog_agent a ON a.id = wa.agent_id \
+                 WHERE wa.work_id = w.id AND wa.role = 'author' AND a.birth_year <= ",
+            )
+            .push_bind(year)
+            .push(")");
+    }
+
+    if let Some(copyright) = query.copyright {
+        builder.push(" AND w.copyright = ").push_bind(copyright);
+    }
+
+    if !query.ids.is_empty() {
+        builder
+            .push(" AND w.id = ANY(")
+            .push_bind(query.ids.clone())
+            .push(")");
+    }
+
+    if let Some(mime_type) = query
+        .mime_type
+        .as_deref()
+        .map(str::trim)
+        .filter(|m| !m.is_empty())
+    {
+        builder
+            .push(
+                " AND EXISTS (SELECT 1 FROM catalog_format f \
+                 WHERE f.work_id = w.id AND f.mime_type LIKE ",
+            )
+            .push_bind(format!("{}%", escape_like(mime_type)))
+            .push(")");
+    }
+}
+
+/// Runs a local catalog search; returns the total match count and one page of works
+pub async fn search_catalog_works(
+    pool: &Pool<Postgres>,
+    query: &CatalogQuery,
+    page: i64,
+    page_size: i64,
+) -> Result<(i64, Vec<CatalogWork>), DbError> {
+    let mut count_query = QueryBuilder::new("SELECT COUNT(*) FROM catalog_work w");
+    push_catalog_filters(&mut count_query, query);
+    let (count,): (i64,) = count_query.build_query_as().fetch_one(pool).await?;
+
+    let mut page_query = QueryBuilder::new(
+        "SELECT w.id, w.title, w.issued, w.media_type, w.copyright, w.download_count FROM catalog_work w",
+    );
+    push_catalog_filters(&mut page_query, query);
+    page_query.push(match query.sort {
+        Some(CatalogSort::Ascending) => " ORDER BY w.id ASC",
+        Some(CatalogSort::Descending) => " ORDER BY w.id DESC",
+        Some(CatalogSort::Popular) | None => " ORDER BY w.download_count DESC NULLS LAST, w.id ASC",
+    });
+    page_query
+        .push(" LIMIT ")
+        .push_bind(page_size)
+        .push(" OFFSET ")
+        .push_bind((page - 1).max(0) * page_size);
+    let rows = page_query.build().fetch_all(pool).await?;
+
+    let mut works: Vec<CatalogWork> = rows
+        .iter()
+        .map(|row| CatalogWork {
+            id: row.get(0),
+            title: row.get(1),
+            issued: row.get(2),
+            media_type: row.get(3),
+            copyright: row.get(4),
+            download_count: row.get(5),
+            ..CatalogWork::default()
+        })
+        .collect();
+    load_catalog_relations(pool, &mut works).await?;
+    Ok((count, works))
+}
+
+async fn load_catalog_relations(
+    pool: &Pool<Postgres>,
+    works: &mut [CatalogWork],
+) -> Result<(), DbError> {
+    let ids: Vec<i64> = works.iter().map(|w| w.id).collect();
+    let index: HashMap<i64, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+
+    let agents = sqlx::query(
+        r"
+        SELECT wa.work_id, a.name, a.birth_year, a.death_year, wa.role
+        FROM catalog_work_agent wa JOIN catalog_agent a ON a.id = wa.agent_id
+        WHERE wa.work_id = ANY($1) ORDER BY wa.work_id, wa.position
+        ",
+    )
+    .bind(&ids)
+    .fetch_all(pool)
+    .await?;
+    for row in &agents {
+        if let Some(&i) = index.get(&row.get::<i64, _>(0)) {
+            works[i].agents.push(CatalogAgent {
+                name: row.get(1),
+                birth_year: row.get(2),
+                death_year: row.get(3),
+                role: row.get(4),
+            });
+        }
+    }
+
+    let names = sqlx::query(
+        r"
+        SELECT ws.work_id, 'subject', s.name FROM catalog_work_subject ws
+        JOIN catalog_subject s ON s.id = ws.subject_id WHERE ws.work_id = ANY($1)
+        UNION ALL
+        SELECT wb.work_id, 'bookshelf', b.name FROM catalog_work_bookshelf wb
+        JOIN catalog_bookshelf b ON b.id = wb.bookshelf_id WHERE wb.work_id = ANY($1)
+        UNION ALL
+        SELECT work_id, 'language', code FROM catalog_work_language WHERE work_id = ANY($1)
+        ORDER BY 1, 2, 3
+        ",
+    )
+    .bind(&ids)
+    .fetch_all(pool)
+    .await?;
+    for row in &names {
+        if let Some(&i) = index.get(&row.get::<i64, _>(0)) {
+            let work = &mut works[i];
+            let name: String = row.get(2);
+            match row.get::<String, _>(1).as_str() {
+                "subject" => work.subjects.push(name),
+                "bookshelf" => work.bookshelves.push(name),
+                _ => work.languages.push(name),
+            }
+        }
+    }
+
+    let formats = sqlx::query(
+        "SELECT work_id, mime_type, url FROM catalog_format WHERE work_id = ANY($1) ORDER BY work_id, mime_type",
+    )
+    .bind(&ids)
+    .fetch_all(pool)
+    .await?;
+    for row in &formats {
+        if let Some(&i) = index.get(&row.get::<i64, _>(0)) {
+            works[i].formats.push((row.get(1), row.get(2)));
+        }
+    }
+
+    Ok(())
 }
 
 // ============================================================================
-// HIGHLIGHT OPERATIONS
+// BACKUP OPERATIONS
 // ============================================================================
+// Restores merge into the existing library: rows already present (matched by
+// book and creation time) are kept, so restoring the same archive twice adds
+// nothing.
 
-pub async fn list_highlights(
-    pool: &Pool<Postgres>,
-    book_id: i64,
-) -> Result<Vec<Highlight>, DbError> {
+pub async fn list_book_records(pool: &Pool<Postgres>) -> Result<Vec<BookRecord>, DbError> {
     let rows = sqlx::query(
         r"
-        SELECT id, book_id, start_path, start_offset, end_path, end_offset, text, note, created_at::text, updated_at::text
-        FROM highlight WHERE book_id = $1 ORDER BY created_at DESC
+        SELECT id, gutenberg_id, title, authors, publication_year, cover_url, first_image_index,
+            languages, source_url, content_hash, reading_status, status_updated_at::text,
+            started_at::text, finished_at::text, last_opened_at::text, created_at::text
+        FROM book WHERE deleted_at IS NULL ORDER BY id
         ",
     )
-    .bind(book_id)
     .fetch_all(pool)
     .await?;
 
-    Ok(rows.iter().map(map_highlight_row).collect())
+    Ok(rows
+        .iter()
+        .map(|row| BookRecord {
+            id: BookId::new(row.get::<i64, _>(0)),
+            gutenberg_id: GutenbergId::new(row.get::<i64, _>(1)),
+            title: row.get(2),
+            authors: row.get(3),
+            publication_year: row.get(4),
+            cover_url: row.get(5),
+            first_image_index: row.get(6),
+            languages: row.get(7),
+            source_url: row.get(8),
+            content_hash: row.get(9),
+            status: row
+                .get::<Option<String>, _>(10)
+                .and_then(|status| status.parse().ok()),
+            status_updated_at: row.get(11),
+            started_at: row.get(12),
+            finished_at: row.get(13),
+            last_opened_at: row.get(14),
+            created_at: row.get::<Option<String>, _>(15).unwrap_or_default(),
+        })
+        .collect())
 }
 
-#[allow(clippy::too_many_arguments)]
-pub async fn create_highlight(
+/// Every message of a book outside the trash, in and out of threads
+pub async fn list_all_book_messages(
     pool: &Pool<Postgres>,
     book_id: i64,
-    start_path: &str,
-    start_offset: i64,
-    end_path: &str,
-    end_offset: i64,
-    text: &str,
-    note: Option<&str>,
-) -> Result<Highlight, DbError> {
-    let row = sqlx::query(
+) -> Result<Vec<BookMessage>, DbError> {
+    let rows = sqlx::query(
         r"
-        INSERT INTO highlight (book_id, start_path, start_offset, end_path, end_offset, text, note)
-        VALUES ($1, $2, $3, $4, $5, $6, $7)
-        RETURNING id, book_id, start_path, start_offset, end_path, end_offset, text, note, created_at::text, updated_at::text
+        SELECT m.id, m.book_id, m.thread_id, m.role, m.content, m.reasoning_summary, m.context_map,
+            m.created_at::text
+        FROM book_message m
+        WHERE m.book_id = $1 AND m.deleted_at IS NULL
+            AND NOT EXISTS (SELECT 1 FROM book_chat_thread t WHERE t.id = m.thread_id AND t.deleted_at IS NOT NULL)
+        ORDER BY m.created_at ASC, m.id ASC
         ",
     )
     .bind(book_id)
-    .bind(start_path)
-    .bind(start_offset)
-    .bind(end_path)
-    .bind(end_offset)
-    .bind(text)
-    .bind(note)
-    .fetch_one(pool)
+    .fetch_all(pool)
     .await?;
 
-    Ok(map_highlight_row(&row))
+    Ok(rows.iter().map(map_book_message_row).collect())
 }
 
-pub async fn update_highlight_note(
+/// The library book a backed-up book corresponds to, by Gutenberg ID or by the
+/// hash of its source file, with its Gutenberg ID
+pub async fn find_restore_book(
     pool: &Pool<Postgres>,
-    highlight_id: i64,
-    note: Option<&str>,
-) -> Result<Highlight, DbError> {
-    let row = sqlx::query(
+    gutenberg_id: i64,
+    content_hash: Option<&str>,
+) -> Result<Option<(i64, i64)>, DbError> {
+    Ok(sqlx::query_as(
         r"
-        UPDATE highlight SET note = $1, updated_at = NOW() WHERE id = $2
-        RETURNING id, book_id, start_path, start_offset, end_path, end_offset, text, note, created_at::text, updated_at::text
+        SELECT id, gutenberg_id FROM book
+        WHERE (gutenberg_id = $1 OR ($2::text IS NOT NULL AND content_hash = $2))
+            AND deleted_at IS NULL
+        ORDER BY gutenberg_id = $1 DESC LIMIT 1
         ",
     )
-    .bind(note)
-    .bind(highlight_id)
+    .bind(gutenberg_id)
+    .bind(content_hash)
+    .fetch_optional(pool)
+    .await?)
+}
+
+/// Adds a backed-up book to the library, returning its new ID. A copy of the
+/// book in the trash is taken out of it instead.
+pub async fn restore_book(
+    pool: &Pool<Postgres>,
+    book: &BookRecord,
+    mobi_data: Option<&[u8]>,
+    html_content: Option<&str>,
+) -> Result<i64, DbError> {
+    let (id,): (i64,) = sqlx::query_as(
+        r"
+        INSERT INTO book (gutenberg_id, title, authors, publication_year, cover_url, mobi_data,
+            html_content, first_image_index, languages, source_url, content_hash, reading_status,
+            status_updated_at, started_at, finished_at, last_opened_at, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13::timestamptz,
+            $14::timestamptz, $15::timestamptz, $16::timestamptz, COALESCE($17::timestamptz, NOW()))
+        ON CONFLICT (gutenberg_id) DO UPDATE SET deleted_at = NULL
+        RETURNING id
+        ",
+    )
+    .bind(book.gutenberg_id.get())
+    .bind(&book.title)
+    .bind(&book.authors)
+    .bind(book.publication_year)
+    .bind(&book.cover_url)
+    .bind(mobi_data)
+    .bind(html_content)
+    .bind(book.first_image_index)
+    .bind(&book.languages)
+    .bind(&book.source_url)
+    .bind(&book.content_hash)
+    .bind(book.status.map(ReadingStatus::as_str))
+    .bind(&book.status_updated_at)
+    .bind(&book.started_at)
+    .bind(&book.finished_at)
+    .bind(&book.last_opened_at)
+    .bind(Some(book.created_at.as_str()).filter(|at| !at.is_empty()))
     .fetch_one(pool)
     .await?;
-
-    Ok(map_highlight_row(&row))
+    Ok(id)
 }
 
-pub async fn delete_highlight(pool: &Pool<Postgres>, highlight_id: i64) -> Result<(), DbError> {
-    sqlx::query("DELETE FROM highlight WHERE id = $1")
-        .bind(highlight_id)
-        .execute(pool)
-        .await?;
+/// Restores a reading position unless the library's is more recent
+pub async fn restore_book_position(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+    position: &BookPosition,
+) -> Result<(), DbError> {
+    let locator = &position.locator;
+    sqlx::query(
+        r"
+        INSERT INTO book_position (book_id, cfi, chapter_index, chapter_title, chapter_offset, progression, quote, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8::timestamptz)
+        ON CONFLICT (book_id) DO UPDATE SET
+            cfi = EXCLUDED.cfi,
+            chapter_index = EXCLUDED.chapter_index,
+            chapter_title = EXCLUDED.chapter_title,
+            chapter_offset = EXCLUDED.chapter_offset,
+            progression = EXCLUDED.progression,
+            quote = EXCLUDED.quote,
+            updated_at = EXCLUDED.updated_at
+        WHERE book_position.updated_at < EXCLUDED.updated_at
+        ",
+    )
+    .bind(book_id)
+    .bind(locator.cfi.as_ref().map(Cfi::as_str))
+    .bind(locator.chapter_index.and_then(|i| i32::try_from(i).ok()))
+    .bind(&locator.chapter_title)
+    .bind(i64::try_from(locator.chapter_offset).unwrap_or(i64::MAX))
+    .bind(locator.progression)
+    .bind(&locator.quote)
+    .bind(&position.updated_at)
+    .execute(pool)
+    .await?;
     Ok(())
 }
 
-// ============================================================================
-// HIGHLIGHT MESSAGE OPERATIONS
-// ============================================================================
-
-pub async fn list_highlight_messages(
+pub async fn restore_bookmark(
     pool: &Pool<Postgres>,
-    highlight_id: i64,
-) -> Result<Vec<HighlightMessage>, DbError> {
-    let rows = sqlx::query(
+    book_id: i64,
+    bookmark: &Bookmark,
+) -> Result<(), DbError> {
+    sqlx::query(
         r"
-        SELECT id, highlight_id, role, content, created_at::text
-        FROM highlight_message WHERE highlight_id = $1 ORDER BY created_at ASC
+        INSERT INTO bookmark (book_id, locator, label, color, created_at, updated_at)
+        SELECT $1, $2, $3, $4, $5::timestamptz, $6::timestamptz
+        WHERE NOT EXISTS (SELECT 1 FROM bookmark WHERE book_id = $1 AND created_at = $5::timestamptz)
         ",
     )
-    .bind(highlight_id)
-    .fetch_all(pool)
+    .bind(book_id)
+    .bind(Json(&bookmark.locator))
+    .bind(&bookmark.label)
+    .bind(bookmark.color.as_ref().map(Color::as_str))
+    .bind(&bookmark.created_at)
+    .bind(&bookmark.updated_at)
+    .execute(pool)
     .await?;
+    Ok(())
+}
 
-    Ok(rows.iter().map(map_highlight_message_row).collect())
+/// Restores a highlight with its tags, returning its ID in the library and
+/// whether it was added
+pub async fn restore_highlight(
+    pool: &Pool<Postgres>,
+    book_id: i64,
+    highlight: &Highlight,
+) -> Result<(i64, bool), DbError> {
+    let existing: Option<(i64,)> = sqlx::query_as(
+        "SELECT id FROM highlight WHERE book_id = $1 AND text = $2 AND created_at = $3::timestamptz",
+    )
+    .bind(book_id)
+    .bind(&highlight.text)
+    .bind(&highlight.created_at)
+    .fetch_optional(pool)
+    .await?;
+    if let Some((id,)) = existing {
+        return Ok((id, false));
+    }
+
+    let (id,): (i64,) = sqlx::query_as(
+        r"
+        INSERT INTO highlight (book_id, start_path, start_offset, end_path, end_offset, text, note,
+            color, style, quote_prefix, quote_suffix, text_start, text_end, orphaned, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15::timestamptz, $16::timestamptz)
+        RETURNING id
+        ",
+    )
+    .bind(book_id)
+    .bind(&highlight.start_path)
+    .bind(highlight.start_offset)
+    .bind(&highlight.end_path)
+    .bind(highlight.end_offset)
+    .bind(&highlight.text)
+    .bind(&highlight.note)
+    .bind(highlight.color.as_ref().map(Color::as_str))
+    .bind(highlight.style.as_str())
+    .bind(&highlight.quote_prefix)
+    .bind(&highlight.quote_suffix)
+    .bind(highlight.text_start)
+    .bind(highlight.text_end)
+    .bind(highlight.orphaned)
+    .bind(&highlight.created_at)
+    .bind(&highlight.updated_at)
+    .fetch_one(pool)
+    .await?;
+    for tag in &highlight.tags {
+        add_highlight_tag(pool, id, tag).await?;
+    }
+    Ok((id, true))
 }
 
-pub async fn add_highlight_message(
+pub async fn restore_highlight_message(
     pool: &Pool<Postgres>,
-    highlight_id: i64,
-    role: &str,
-    content: &str,
-) -> Result<HighlightMessage, DbError> {
-    let row = sqlx::query(
+    highlight_id: i64,
+    message: &HighlightMessage,
+) -> Result<(), DbError> {
+    sqlx::query(
         r"
-        INSERT INTO highlight_message (highlight_id, role, content)
-        VALUES ($1, $2, $3)
-        RETURNING id, highlight_id, role, content, created_at::text
+        INSERT INTO highlight_message (highlight_id, role, content, created_at)
+        SELECT $1, $2, $3, $4::timestamptz
+        WHERE NOT EXISTS (
+            SELECT 1 FROM highlight_message
+            WHERE highlight_id = $1 AND created_at = $4::timestamptz AND content = $3
+        )
         ",
     )
     .bind(highlight_id)
-    .bind(role)
-    .bind(content)
-    .fetch_one(pool)
+    .bind(message.role.as_str())
+    .bind(&message.content)
+    .bind(&message.created_at)
+    .execute(pool)
     .await?;
-
-    Ok(map_highlight_message_row(&row))
+    Ok(())
 }
 
-// ============================================================================
-// BOOK CHAT THREAD OPERATIONS
-// ============================================================================
-
-pub async fn list_book_chat_threads(
+/// Restores a chat thread, returning its ID in the library
+pub async fn restore_chat_thread(
     pool: &Pool<Postgres>,
     book_id: i64,
-) -> Result<Vec<BookChatThread>, DbError> {
-    let rows = sqlx::query(
+    thread: &BookChatThread,
+) -> Result<i64, DbError> {
+    let existing: Option<(i64,)> = sqlx::query_as(
+        "SELECT id FROM book_chat_thread WHERE book_id = $1 AND created_at = $2::timestamptz",
+    )
+    .bind(book_id)
+    .bind(&thread.created_at)
+    .fetch_optional(pool)
+    .await?;
+    if let Some((id,)) = existing {
+        return Ok(id);
+    }
+
+    let (id,): (i64,) = sqlx::query_as(
         r"
-        SELECT id, book_id, title, last_cfi, created_at::text, updated_at::text
-        FROM book_chat_thread WHERE book_id = $1 ORDER BY updated_at DESC
+        INSERT INTO book_chat_thread (book_id, title, last_cfi, created_at, updated_at)
+        VALUES ($1, $2, $3, $4::timestamptz, $5::timestamptz)
+        RETURNING id
         ",
     )
     .bind(book_id)
-    .fetch_all(pool)
+    .bind(&thread.title)
+    .bind(&thread.last_cfi)
+    .bind(&thread.created_at)
+    .bind(&thread.updated_at)
+    .fetch_one(pool)
     .await?;
-
-    Ok(rows.iter().map(map_book_chat_thread_row).collect())
+    Ok(id)
 }
 
-pub async fn create_book_chat_thread(
+pub async fn restore_book_message(
     pool: &Pool<Postgres>,
     book_id: i64,
-    title: &str,
-) -> Result<BookChatThread, DbError> {
-    let row = sqlx::query(
+    thread_id: Option<i64>,
+    message: &BookMessage,
+) -> Result<(), DbError> {
+    sqlx::query(
         r"
-        INSERT INTO book_chat_thread (book_id, title)
-        VALUES ($1, $2)
-        RETURNING id, book_id, title, last_cfi, created_at::text, updated_at::text
+        INSERT INTO book_message (book_id, thread_id, role, content, reasoning_summary, context_map, created_at)
+        SELECT $1, $2, $3, $4, $5, $6, $7::timestamptz
+        WHERE NOT EXISTS (
+            SELECT 1 FROM book_message
+            WHERE book_id = $1 AND created_at = $7::timestamptz AND content = $4
+        )
         ",
     )
     .bind(book_id)
-    .bind(title)
-    .fetch_one(pool)
+    .bind(thread_id)
+    .bind(message.role.as_str())
+    .bind(&message.content)
+    .bind(&message.reasoning_summary)
+    .bind(&message.context_map)
+    .bind(&message.created_at)
+    .execute(pool)
     .await?;
+    Ok(())
+}
 
-    Ok(map_book_chat_thread_row(&row))
+/// Sets a setting that has no value yet; returns whether it was set
+pub async fn restore_setting(
+    pool: &Pool<Postgres>,
+    key: &str,
+    value: &str,
+) -> Result<bool, DbError> {
+    let result = sqlx::query(
+        "INSERT INTO settings (key, value) VALUES ($1, $2) ON CONFLICT (key) DO NOTHING",
+    )
+    .bind(key)
+    .bind(value)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
 }
 
-pub async fn rename_book_chat_thread(
+// ============================================================================
+// SYNC OPERATIONS
+// ============================================================================
+// Records are read and written generically from `SYNC_TABLES`: a record is a
+// JSON object of the table's fields plus its parents' keys, each field with
+// its change time from `sync_field`, or the row's stamp column for rows that
+// predate sync.
+
+/// This device's ID, generated on first use
+pub async fn sync_device_id(pool: &Pool<Postgres>) -> Result<String, DbError> {
+    let key = SettingKey::SyncDeviceId.as_str();
+    sqlx::query(
+        "INSERT INTO settings (key, value) VALUES ($1, gen_random_uuid()::text) ON CONFLICT (key) DO NOTHING",
+    )
+    .bind(key)
+    .execute(pool)
+    .await?;
+    Ok(
+        sqlx::query_scalar("SELECT value FROM settings WHERE key = $1")
+            .bind(key)
+            .fetch_one(pool)
+            .await?,
+    )
+}
+
+/// The database clock, which also stamps `sync_field` and `sync_tombstone`
+pub async fn database_now(pool: &Pool<Postgres>) -> Result<DateTime<Utc>, DbError> {
+    Ok(sqlx::query_scalar("SELECT NOW()").fetch_one(pool).await?)
+}
+
+pub async fn get_sync_remote(
     pool: &Pool<Postgres>,
-    thread_id: i64,
-    title: &str,
-) -> Result<(), DbError> {
-    sqlx::query("UPDATE book_chat_thread SET title = $1, updated_at = NOW() WHERE id = $2")
-        .bind(title)
-        .bind(thread_id)
-        .execute(pool)
+    remote: &str,
+) -> Result<SyncRemoteState, DbError> {
+    let row = sqlx::query("SELECT cursor, synced_at FROM sync_remote WHERE remote = $1")
+        .bind(remote)
+        .fetch_optional(pool)
         .await?;
-    Ok(())
+    Ok(
+        row.map_or_else(SyncRemoteState::default, |row| SyncRemoteState {
+            cursor: row.get(0),
+            synced_at: row.get(1),
+        }),
+    )
 }
 
-pub async fn set_thread_last_cfi(
+pub async fn save_sync_remote(
     pool: &Pool<Postgres>,
-    thread_id: i64,
-    cfi: &str,
+    remote: &str,
+    cursor: &str,
+    synced_at: DateTime<Utc>,
 ) -> Result<(), DbError> {
-    sqlx::query("UPDATE book_chat_thread SET last_cfi = $1, updated_at = NOW() WHERE id = $2")
-        .bind(cfi)
-        .bind(thread_id)
-        .execute(pool)
-        .await?;
+    sqlx::query(
+        r"INSERT INTO sync_remote (remote, cursor, synced_at) VALUES ($1, $2, $3)
+        ON CONFLICT (remote) DO UPDATE SET cursor = EXCLUDED.cursor, synced_at = EXCLUDED.synced_at",
+    )
+    .bind(remote)
+    .bind(cursor)
+    .bind(synced_at)
+    .execute(pool)
+    .await?;
     Ok(())
 }
 
-pub async fn delete_book_chat_thread(pool: &Pool<Postgres>, thread_id: i64) -> Result<(), DbError> {
-    sqlx::query("DELETE FROM book_chat_thread WHERE id = $1")
-        .bind(thread_id)
-        .execute(pool)
+/// Pulled records still waiting for their book or parent
+pub async fn list_sync_pending(
+    pool: &Pool<Postgres>,
+    remote: &str,
+) -> Result<Vec<SyncRecord>, DbError> {
+    let rows: Vec<(Json<SyncRecord>,)> =
+        sqlx::query_as("SELECT record FROM sync_pending WHERE remote = $1")
+            .bind(remote)
+            .fetch_all(pool)
+            .await?;
+    Ok(rows.into_iter().map(|(Json(record),)| record).collect())
+}
+
+/// Replaces the records waiting for their book or parent
+pub async fn save_sync_pending(
+    pool: &Pool<Postgres>,
+    remote: &str,
+    records: &[SyncRecord],
+) -> Result<(), DbError> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM sync_pending WHERE remote = $1")
+        .bind(remote)
+        .execute(&mut *tx)
         .await?;
+    for record in records {
+        sqlx::query(
+            r"INSERT INTO sync_pending (remote, entity, record_key, record) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (remote, entity, record_key) DO UPDATE SET record = EXCLUDED.record",
+        )
+        .bind(remote)
+        .bind(record.entity.as_str())
+        .bind(&record.key)
+        .bind(Json(record))
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
     Ok(())
 }
 
-// ============================================================================
-// BOOK MESSAGE OPERATIONS
-// ============================================================================
+/// The record key, with the row aliased `t` and its parents `p0`, `p1`, ...
+fn sync_key_expr(table: &SyncTable) -> String {
+    table.key.map_or_else(
+        || format!("p0.{}::text", table.parents[0].key),
+        |key| format!("t.{key}"),
+    )
+}
 
-pub async fn list_book_messages(
+/// Condition matching the row whose key is bound to `$param`
+fn sync_key_filter(table: &SyncTable, param: usize) -> String {
+    table.key.map_or_else(
+        || {
+            let parent = &table.parents[0];
+            format!(
+                "t.{} = (SELECT id FROM {} WHERE {}::text = ${param})",
+                parent.column, parent.table, parent.key
+            )
+        },
+        |key| format!("t.{key} = ${param}"),
+    )
+}
+
+/// Selects key, record object and stamp for a table's rows
+fn sync_select(table: &SyncTable) -> String {
+    let mut pairs: Vec<String> = table
+        .fields
+        .iter()
+        .map(|field| format!("'{field}', t.{field}"))
+        .collect();
+    let mut joins = String::new();
+    for (i, parent) in table.parents.iter().enumerate() {
+        pairs.push(format!("'{}', p{i}.{}", parent.column, parent.key));
+        let _ = write!(
+            joins,
+            " LEFT JOIN {} p{i} ON p{i}.id = t.{}",
+            parent.table, parent.column
+        );
+    }
+    format!(
+        "SELECT {}, jsonb_build_object({}), t.{} FROM {} t{joins}",
+        sync_key_expr(table),
+        pairs.join(", "),
+        table.stamp,
+        table.table
+    )
+}
+
+async fn sync_field_stamps(
     pool: &Pool<Postgres>,
-    book_id: i64,
-    thread_id: Option<i64>,
-) -> Result<Vec<BookMessage>, DbError> {
+    table: &SyncTable,
+    keys: &[String],
+) -> Result<HashMap<(String, String), DateTime<Utc>>, DbError> {
     let rows = sqlx::query(
-        r"
-        SELECT id, book_id, thread_id, role, content, reasoning_summary, context_map, created_at::text
-        FROM book_message WHERE book_id = $1 AND (thread_id = $2 OR (thread_id IS NULL AND $2 IS NULL))
-        ORDER BY created_at ASC
-        ",
+        "SELECT record_key, field, changed_at FROM sync_field WHERE entity = $1 AND record_key = ANY($2)",
     )
-    .bind(book_id)
-    .bind(thread_id)
+    .bind(table.entity.as_str())
+    .bind(keys)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .iter()
+        .map(|row| ((row.get(0), row.get(1)), row.get(2)))
+        .collect())
+}
+
+fn sync_fields(
+    key: &str,
+    record: Value,
+    stamp: DateTime<Utc>,
+    stamps: &HashMap<(String, String), DateTime<Utc>>,
+) -> BTreeMap<String, FieldValue> {
+    let Value::Object(values) = record else {
+        return BTreeMap::new();
+    };
+    values
+        .into_iter()
+        .map(|(field, value)| {
+            let changed_at = stamps
+                .get(&(key.to_string(), field.clone()))
+                .copied()
+                .unwrap_or(stamp);
+            (field, FieldValue { value, changed_at })
+        })
+        .collect()
+}
+
+/// Records changed or deleted here since `since`, or all of them
+pub async fn list_sync_changes(
+    pool: &Pool<Postgres>,
+    table: &SyncTable,
+    since: Option<DateTime<Utc>>,
+) -> Result<Vec<SyncRecord>, DbError> {
+    let entity = table.entity.as_str();
+    let rows = sqlx::query(&format!(
+        r"{} WHERE $2::timestamptz IS NULL OR {} IN (
+            SELECT record_key FROM sync_field
+            WHERE entity = $1 AND origin IS NULL AND changed_at > $2
+        )",
+        sync_select(table),
+        sync_key_expr(table)
+    ))
+    .bind(entity)
+    .bind(since)
     .fetch_all(pool)
     .await?;
 
-    Ok(rows.iter().map(map_book_message_row).collect())
+    let rows: Vec<(String, Value, DateTime<Utc>)> = rows
+        .iter()
+        .filter_map(|row| Some((row.get::<Option<String>, _>(0)?, row.get(1), row.get(2))))
+        .collect();
+    let keys: Vec<String> = rows.iter().map(|(key, ..)| key.clone()).collect();
+    let stamps = sync_field_stamps(pool, table, &keys).await?;
+    let mut records: Vec<SyncRecord> = rows
+        .into_iter()
+        .map(|(key, record, stamp)| SyncRecord {
+            entity: table.entity,
+            fields: sync_fields(&key, record, stamp, &stamps),
+            key,
+            deleted_at: None,
+        })
+        .collect();
+
+    let tombstones = sqlx::query(
+        r"SELECT record_key, deleted_at FROM sync_tombstone
+        WHERE entity = $1 AND origin IS NULL AND ($2::timestamptz IS NULL OR deleted_at > $2)",
+    )
+    .bind(entity)
+    .bind(since)
+    .fetch_all(pool)
+    .await?;
+    records.extend(tombstones.iter().map(|row| SyncRecord {
+        entity: table.entity,
+        key: row.get(0),
+        fields: BTreeMap::new(),
+        deleted_at: row.get(1),
+    }));
+    Ok(records)
 }
 
-pub async fn add_book_message(
+pub async fn load_sync_record(
     pool: &Pool<Postgres>,
-    book_id: i64,
-    thread_id: Option<i64>,
-    role: &str,
-    content: &str,
-    reasoning_summary: Option<&str>,
-    context_map: Option<&str>,
-) -> Result<BookMessage, DbError> {
-    let row = sqlx::query(
-        r"
-        INSERT INTO book_message (book_id, thread_id, role, content, reasoning_summary, context_map)
-        VALUES ($1, $2, $3, $4, $5, $6)
-        RETURNING id, book_id, thread_id, role, content, reasoning_summary, context_map, created_at::text
-        ",
+    table: &SyncTable,
+    key: &str,
+) -> Result<LocalRecord, DbError> {
+    let row = sqlx::query(&format!(
+        "{} WHERE {}",
+        sync_select(table),
+        sync_key_filter(table, 1)
+    ))
+    .bind(key)
+    .fetch_optional(pool)
+    .await?;
+    let fields = match row {
+        Some(row) => {
+            let stamps = sync_field_stamps(pool, table, &[key.to_string()]).await?;
+            sync_fields(key, row.get(1), row.get(2), &stamps)
+        }
+        None => BTreeMap::new(),
+    };
+    let deleted_at = sqlx::query_scalar(
+        "SELECT deleted_at FROM sync_tombstone WHERE entity = $1 AND record_key = $2",
     )
-    .bind(book_id)
-    .bind(thread_id)
-    .bind(role)
-    .bind(content)
-    .bind(reasoning_summary)
-    .bind(context_map)
-    .fetch_one(pool)
+    .bind(table.entity.as_str())
+    .bind(key)
+    .fetch_optional(pool)
     .await?;
+    Ok(LocalRecord { fields, deleted_at })
+}
 
-    if let Some(tid) = thread_id {
-        sqlx::query("UPDATE book_chat_thread SET updated_at = NOW() WHERE id = $1")
-            .bind(tid)
-            .execute(pool)
-            .await?;
+/// Writes pulled fields, creating the record when it doesn't exist here.
+/// Returns false, writing nothing, when a parent isn't in this library.
+pub async fn apply_sync_fields(
+    pool: &Pool<Postgres>,
+    table: &SyncTable,
+    key: &str,
+    fields: &BTreeMap<String, FieldValue>,
+    origin: &str,
+) -> Result<bool, DbError> {
+    let mut values: serde_json::Map<String, Value> = table
+        .columns()
+        .filter_map(|column| Some((column.to_string(), fields.get(column)?.value.clone())))
+        .collect();
+    if table.key.is_none() {
+        values
+            .entry(table.parents[0].column)
+            .or_insert_with(|| Value::String(key.to_string()));
     }
 
-    Ok(map_book_message_row(&row))
-}
+    let mut tx = pool.begin().await?;
+    sqlx::query("SELECT set_config('sync.applying', 'on', true)")
+        .execute(&mut *tx)
+        .await?;
 
-pub async fn delete_book_messages(pool: &Pool<Postgres>, book_id: i64) -> Result<(), DbError> {
-    sqlx::query("DELETE FROM book_message WHERE book_id = $1")
-        .bind(book_id)
-        .execute(pool)
+    // Parents travel by key; swap in their IDs here
+    for parent in table.parents {
+        let parent_key = match values.get(parent.column) {
+            None | Some(Value::Null) => continue,
+            Some(Value::String(parent_key)) => parent_key.clone(),
+            Some(other) => other.to_string(),
+        };
+        let id: Option<i64> = sqlx::query_scalar(&format!(
+            "SELECT id FROM {} WHERE {}::text = $1",
+            parent.table, parent.key
+        ))
+        .bind(&parent_key)
+        .fetch_optional(&mut *tx)
         .await?;
-    Ok(())
-}
+        let Some(id) = id else {
+            return Ok(false);
+        };
+        values.insert(parent.column.to_string(), Value::from(id));
+    }
 
-pub async fn delete_book_message(pool: &Pool<Postgres>, message_id: i64) -> Result<(), DbError> {
-    sqlx::query("DELETE FROM book_message WHERE id = $1")
-        .bind(message_id)
-        .execute(pool)
+    let exists: bool = sqlx::query_scalar(&format!(
+        "SELECT EXISTS (SELECT 1 FROM {} t WHERE {})",
+        table.table,
+        sync_key_filter(table, 1)
+    ))
+    .bind(key)
+    .fetch_one(&mut *tx)
+    .await?;
+    if let (false, Some(key_column)) = (exists, table.key) {
+        values.insert(key_column.to_string(), Value::String(key.to_string()));
+    }
+
+    if !values.is_empty() {
+        let columns: Vec<&str> = values.keys().map(String::as_str).collect();
+        let from_record: Vec<String> = columns.iter().map(|column| format!("r.{column}")).collect();
+        let source = format!(
+            "SELECT {} FROM jsonb_populate_record(NULL::{}, $1) r",
+            from_record.join(", "),
+            table.table
+        );
+        let sql = if exists {
+            format!(
+                "UPDATE {} t SET ({}) = ({source}) WHERE {}",
+                table.table,
+                columns.join(", "),
+                sync_key_filter(table, 2)
+            )
+        } else {
+            format!(
+                "INSERT INTO {} ({}) {source}",
+                table.table,
+                columns.join(", ")
+            )
+        };
+        sqlx::query(&sql)
+            .bind(Value::Object(values))
+            .bind(key)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    let (names, stamps): (Vec<String>, Vec<DateTime<Utc>>) = fields
+        .iter()
+        .filter(|(name, _)| table.columns().any(|column| column == name.as_str()))
+        .map(|(name, field)| (name.clone(), field.changed_at))
+        .unzip();
+    sqlx::query(
+        r"INSERT INTO sync_field (entity, record_key, field, changed_at, origin)
+        SELECT $1, $2, f.field, f.changed_at, $5
+        FROM UNNEST($3::text[], $4::timestamptz[]) AS f(field, changed_at)
+        ON CONFLICT (entity, record_key, field) DO UPDATE
+            SET changed_at = EXCLUDED.changed_at, origin = EXCLUDED.origin",
+    )
+    .bind(table.entity.as_str())
+    .bind(key)
+    .bind(&names)
+    .bind(&stamps)
+    .bind(origin)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM sync_tombstone WHERE entity = $1 AND record_key = $2")
+        .bind(table.entity.as_str())
+        .bind(key)
+        .execute(&mut *tx)
         .await?;
-    Ok(())
+
+    tx.commit().await?;
+    Ok(true)
 }
 
-pub async fn clear_default_book_messages(
+/// Deletes a record deleted on another device, keeping its tombstone
+pub async fn apply_sync_delete(
     pool: &Pool<Postgres>,
-    book_id: i64,
+    table: &SyncTable,
+    key: &str,
+    deleted_at: DateTime<Utc>,
+    origin: &str,
 ) -> Result<(), DbError> {
-    sqlx::query("DELETE FROM book_message WHERE book_id = $1 AND thread_id IS NULL")
-        .bind(book_id)
-        .execute(pool)
+    let mut tx = pool.begin().await?;
+    sqlx::query("SELECT set_config('sync.applying', 'on', true)")
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(&format!(
+        "DELETE FROM {} t WHERE {}",
+        table.table,
+        sync_key_filter(table, 1)
+    ))
+    .bind(key)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM sync_field WHERE entity = $1 AND record_key = $2")
+        .bind(table.entity.as_str())
+        .bind(key)
+        .execute(&mut *tx)
         .await?;
+    sqlx::query(
+        r"INSERT INTO sync_tombstone (entity, record_key, deleted_at, origin) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (entity, record_key) DO UPDATE
+            SET deleted_at = EXCLUDED.deleted_at, origin = EXCLUDED.origin",
+    )
+    .bind(table.entity.as_str())
+    .bind(key)
+    .bind(deleted_at)
+    .bind(origin)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
     Ok(())
 }
 
-pub async fn get_thread_max_citation_index(
-    pool: &Pool<Postgres>,
-    book_id: i64,
-    thread_id: Option<i64>,
-) -> Result<i32, DbError> {
+// ============================================================================
+// TRASH OPERATIONS
+// ============================================================================
+// Deleting a book, highlight, chat thread or a book's messages only stamps
+// `deleted_at`; rows stay restorable until purged. Each message delete is its
+// own trash entry, keyed by `trash_batch`; messages trashed with their thread
+// go and come back with it.
+
+pub async fn list_trash(pool: &Pool<Postgres>) -> Result<Vec<TrashItem>, DbError> {
     let rows = sqlx::query(
         r"
-        SELECT context_map FROM book_message
-        WHERE book_id = $1 AND (thread_id = $2 OR (thread_id IS NULL AND $2 IS NULL))
-        AND context_map IS NOT NULL
+        SELECT 'book', b.id, b.id, b.title, b.authors, b.deleted_at
+        FROM book b WHERE b.deleted_at IS NOT NULL
+        UNION ALL
+        SELECT 'highlight', h.id, b.id, b.title, h.text, h.deleted_at
+        FROM highlight h JOIN book b ON b.id = h.book_id
+        WHERE h.deleted_at IS NOT NULL AND b.deleted_at IS NULL
+        UNION ALL
+        SELECT 'thread', t.id, b.id, b.title, t.title, t.deleted_at
+        FROM book_chat_thread t JOIN book b ON b.id = t.book_id
+        WHERE t.deleted_at IS NOT NULL AND b.deleted_at IS NULL
+        UNION ALL
+        SELECT 'messages', m.trash_batch, b.id, b.title,
+            COUNT(*) || ' messages' || COALESCE(
+                CASE WHEN COUNT(DISTINCT m.thread_id) = 1 THEN ' from ' || MAX(t.title) END, ''
+            ),
+            MAX(m.deleted_at)
+        FROM book_message m JOIN book b ON b.id = m.book_id
+        LEFT JOIN book_chat_thread t ON t.id = m.thread_id
+        WHERE m.deleted_at IS NOT NULL AND b.deleted_at IS NULL AND t.deleted_at IS NULL
+        GROUP BY m.trash_batch, b.id, b.title
+        ORDER BY 6 DESC, 2 DESC
         ",
     )
-    .bind(book_id)
-    .bind(thread_id)
     .fetch_all(pool)
     .await?;
 
-    // Optimized JSON parsing: avoid repeated String allocations and clones
-    let max_index = rows
+    Ok(rows
         .iter()
-        .filter_map(|row| row.get::<Option<&str>, _>(0))
-        .filter_map(|json_str| serde_json::from_str::<serde_json::Value>(json_str).ok())
-        .filter_map(|json| {
-            json.as_object()
-                .and_then(|obj| obj.keys().filter_map(|k| k.parse::<i32>().ok()).max())
+        .filter_map(|row| {
+            Some(TrashItem {
+                kind: row.get::<String, _>(0).parse().ok()?,
+                id: row.get(1),
+                book_id: BookId::new(row.get::<i64, _>(2)),
+                book_title: row.get(3),
+                label: row.get(4),
+                deleted_at: row.get::<DateTime<Utc>, _>(5).to_rfc3339(),
+            })
         })
-        .max()
-        .unwrap_or(0);
+        .collect())
+}
 
-    Ok(max_index)
+fn not_in_trash(kind: TrashKind, id: i64) -> DbError {
+    DbError::Other(format!("No {kind} {id} in the trash"))
 }
 
-pub async fn delete_book_thread_messages(
+pub async fn restore_trash_item(
     pool: &Pool<Postgres>,
-    thread_id: i64,
+    kind: TrashKind,
+    id: i64,
 ) -> Result<(), DbError> {
-    sqlx::query("DELETE FROM book_message WHERE thread_id = $1")
-        .bind(thread_id)
-        .execute(pool)
-        .await?;
+    let mut tx = pool.begin().await?;
+    let sql = match kind {
+        TrashKind::Book => {
+            "UPDATE book SET deleted_at = NULL WHERE id = $1 AND deleted_at IS NOT NULL"
+        }
+        TrashKind::Highlight => {
+            "UPDATE highlight SET deleted_at = NULL, updated_at = NOW() WHERE id = $1 AND deleted_at IS NOT NULL"
+        }
+        TrashKind::Thread => {
+            // The messages trashed with the thread, before its deletion time is cleared
+            sqlx::query(
+                r"
+                UPDATE book_message m SET deleted_at = NULL, trash_batch = NULL
+                FROM book_chat_thread t
+                WHERE t.id = $1 AND m.thread_id = t.id AND m.deleted_at = t.deleted_at
+                ",
+            )
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+            "UPDATE book_chat_thread SET deleted_at = NULL, updated_at = NOW() WHERE id = $1 AND deleted_at IS NOT NULL"
+        }
+        TrashKind::Messages => {
+            // Not the messages of a thread that is still in the trash
+            "UPDATE book_message m SET deleted_at = NULL, trash_batch = NULL WHERE m.trash_batch = $1 AND m.deleted_at IS NOT NULL AND NOT EXISTS (SELECT 1 FROM book_chat_thread t WHERE t.id = m.thread_id AND t.deleted_at IS NOT NULL)"
+        }
+    };
+    let restored = sqlx::query(sql)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    if restored == 0 {
+        return Err(not_in_trash(kind, id));
+    }
+    tx.commit().await?;
     Ok(())
 }
+
+/// Deletes one trash entry for good
+pub async fn purge_trash_item(
+    pool: &Pool<Postgres>,
+    kind: TrashKind,
+    id: i64,
+) -> Result<PurgedTrash, DbError> {
+    let mut purged = PurgedTrash::default();
+    match kind {
+        TrashKind::Book => {
+            let rows: Vec<(i64,)> = sqlx::query_as(
+                "DELETE FROM book WHERE id = $1 AND deleted_at IS NOT NULL RETURNING gutenberg_id",
+            )
+            .bind(id)
+            .fetch_all(pool)
+            .await?;
+            purged.books = rows.len() as u64;
+            purged.gutenberg_ids = rows.into_iter().map(|(id,)| GutenbergId::new(id)).collect();
+        }
+        TrashKind::Highlight => {
+            purged.highlights =
+                sqlx::query("DELETE FROM highlight WHERE id = $1 AND deleted_at IS NOT NULL")
+                    .bind(id)
+                    .execute(pool)
+                    .await?
+                    .rows_affected();
+        }
+        TrashKind::Thread => {
+            purged.threads = sqlx::query(
+                "DELETE FROM book_chat_thread WHERE id = $1 AND deleted_at IS NOT NULL",
+            )
+            .bind(id)
+            .execute(pool)
+            .await?
+            .rows_affected();
+        }
+        TrashKind::Messages => {
+            purged.messages = sqlx::query(
+                "DELETE FROM book_message m WHERE m.trash_batch = $1 AND m.deleted_at IS NOT NULL AND NOT EXISTS (SELECT 1 FROM book_chat_thread t WHERE t.id = m.thread_id AND t.deleted_at IS NOT NULL)",
+            )
+            .bind(id)
+            .execute(pool)
+            .await?
+            .rows_affected();
+        }
+    }
+    if purged.books + purged.highlights + purged.threads + purged.messages == 0 {
+        return Err(not_in_trash(kind, id));
+    }
+    Ok(purged)
+}
+
+/// Deletes for good everything trashed more than `retention_days` ago;
+/// zero empties the trash
+pub async fn purge_expired_trash(
+    pool: &Pool<Postgres>,
+    retention_days: i32,
+) -> Result<PurgedTrash, DbError> {
+    const EXPIRED: &str = "deleted_at < NOW() - make_interval(days => $1)";
+    let mut tx = pool.begin().await?;
+    let rows: Vec<(i64,)> = sqlx::query_as(&format!(
+        "DELETE FROM book WHERE {EXPIRED} RETURNING gutenberg_id"
+    ))
+    .bind(retention_days)
+    .fetch_all(&mut *tx)
+    .await?;
+    let mut purged = PurgedTrash {
+        books: rows.len() as u64,
+        gutenberg_ids: rows.into_iter().map(|(id,)| GutenbergId::new(id)).collect(),
+        ..PurgedTrash::default()
+    };
+    for (table, count) in [
+        ("highlight", &mut purged.highlights),
+        ("book_chat_thread", &mut purged.threads),
+        ("book_message", &mut purged.messages),
+    ] {
+        *count = sqlx::query(&format!("DELETE FROM {table} WHERE {EXPIRED}"))
+            .bind(retention_days)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+    }
+    tx.commit().await?;
+    Ok(purged)
+}
@@ -0,0 +1,512 @@
+//! Highlight export
+//!
+//! Writes highlights with their notes and `highlight_message` discussions to a
+//! file: Markdown grouped by book and chapter, JSON in a versioned schema that
+//! doesn't follow the database, CSV with one row per highlight, or a
+//! tab-separated file Anki imports as a deck of the highlights tagged
+//! `vocabulary`. Highlights are gathered into `ExportBook`s first so every
+//! format sees the same grouping and order.
+
+use crate::books::escape_html;
+use crate::db::postgres::DbError;
+use crate::db::{self, HighlightMessage, LibraryHighlight};
+use crate::text::BookText;
+use crate::types::MessageRole;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::Path;
+use thiserror::Error;
+
+/// Bumped whenever a field of the JSON export changes meaning or goes away
+pub const EXPORT_VERSION: u32 = 1;
+
+/// Highlights with this tag become Anki cards
+pub const VOCABULARY_TAG: &str = "vocabulary";
+
+const CSV_HEADERS: [&str; 10] = [
+    "book",
+    "authors",
+    "chapter",
+    "text",
+    "note",
+    "color",
+    "style",
+    "tags",
+    "created_at",
+    "discussion",
+];
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("No highlights to export")]
+    Empty,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    #[default]
+    Markdown,
+    Json,
+    Csv,
+    /// Tab-separated notes with Anki's import headers
+    Anki,
+}
+
+impl ExportFormat {
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Json => "json",
+            Self::Csv => "csv",
+            Self::Anki => "txt",
+        }
+    }
+}
+
+/// The JSON export; field names are kept stable across releases
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighlightExport {
+    pub version: u32,
+    pub exported_at: String,
+    pub books: Vec<ExportBook>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportBook {
+    pub id: i64,
+    pub title: String,
+    pub authors: String,
+    pub highlights: Vec<ExportHighlight>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportHighlight {
+    pub id: i64,
+    /// Title of the chapter the highlight is in, when it could be placed
+    pub chapter: Option<String>,
+    pub text: String,
+    pub note: Option<String>,
+    pub color: Option<String>,
+    pub style: String,
+    pub tags: Vec<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    pub messages: Vec<ExportMessage>,
+    /// Text around the highlight, used for flashcard context
+    #[serde(skip)]
+    pub context: (String, String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportMessage {
+    pub role: MessageRole,
+    pub content: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportSummary {
+    pub path: String,
+    pub books: usize,
+    pub highlights: usize,
+}
+
+/// Groups highlights (already ordered by book, then position) into books,
+/// attaching their chapter from `texts` and their discussion
+pub fn group_highlights(
+    highlights: Vec<LibraryHighlight>,
+    messages: Vec<HighlightMessage>,
+    texts: &HashMap<i64, BookText>,
+) -> Vec<ExportBook> {
+    let mut discussions: HashMap<i64, Vec<ExportMessage>> = HashMap::new();
+    for message in messages {
+        discussions
+            .entry(message.highlight_id.get())
+            .or_default()
+            .push(ExportMessage {
+                role: message.role,
+                content: message.content,
+                created_at: message.created_at,
+            });
+    }
+
+    let mut books: Vec<ExportBook> = Vec::new();
+    for LibraryHighlight {
+        highlight,
+        book_title,
+        book_authors,
+    } in highlights
+    {
+        let book_id = highlight.book_id.get();
+        let chapter = texts.get(&book_id).and_then(|text| {
+            let start = usize::try_from(highlight.text_start?).ok()?;
+            (!highlight.orphaned)
+                .then(|| text.chapter_at(start))
+                .flatten()
+                .map(|chapter| chapter.title.clone())
+        });
+        let entry = ExportHighlight {
+            id: highlight.id.get(),
+            chapter,
+            text: highlight.text,
+            note: highlight.note.filter(|note| !note.trim().is_empty()),
+            color: highlight.color.map(|color| color.as_str().to_string()),
+            style: highlight.style.as_str().to_string(),
+            tags: highlight
+                .tags
+                .iter()
+                .map(|tag| tag.as_str().to_string())
+                .collect(),
+            created_at: highlight.created_at,
+            updated_at: highlight.updated_at,
+            messages: discussions.remove(&highlight.id.get()).unwrap_or_default(),
+            context: (highlight.quote_prefix, highlight.quote_suffix),
+        };
+        match books.last_mut() {
+            Some(book) if book.id == book_id => book.highlights.push(entry),
+            _ => books.push(ExportBook {
+                id: book_id,
+                title: book_title,
+                authors: book_authors,
+                highlights: vec![entry],
+            }),
+        }
+    }
+    books
+}
+
+const fn speaker(role: MessageRole) -> &'static str {
+    match role {
+        MessageRole::User => "You",
+        MessageRole::Assistant => "Assistant",
+        MessageRole::System => "System",
+    }
+}
+
+/// Markdown with a heading per book and chapter, each quote followed by its
+/// note and discussion
+pub fn to_markdown(books: &[ExportBook]) -> String {
+    let mut out = String::new();
+    for book in books {
+        let _ = writeln!(out, "# {}\n", book.title.trim());
+        if !book.authors.trim().is_empty() {
+            let _ = writeln!(out, "*{}*\n", book.authors.trim());
+        }
+
+        let mut chapter: Option<&str> = None;
+        let mut first = true;
+        for highlight in &book.highlights {
+            let current = highlight.chapter.as_deref();
+            if first || current != chapter {
+                let _ = writeln!(out, "## {}\n", current.unwrap_or("Other highlights").trim());
+                chapter = current;
+                first = false;
+            }
+
+            for line in highlight.text.trim().lines() {
+                let _ = writeln!(out, "> {}", line.trim_end());
+            }
+            out.push('\n');
+            if let Some(note) = &highlight.note {
+                let _ = writeln!(out, "{}\n", note.trim());
+            }
+            if !highlight.tags.is_empty() {
+                let tags: Vec<String> = highlight
+                    .tags
+                    .iter()
+                    .map(|tag| format!("`{tag}`"))
+                    .collect();
+                let _ = writeln!(out, "Tags: {}\n", tags.join(" "));
+            }
+            if !highlight.messages.is_empty() {
+                out.push_str("**Discussion**\n\n");
+                for message in &highlight.messages {
+                    let _ = writeln!(
+                        out,
+                        "**{}:** {}\n",
+                        speaker(message.role),
+                        message.content.trim()
+                    );
+                }
+            }
+            out.push_str("---\n\n");
+        }
+    }
+    out
+}
+
+pub fn to_json(books: Vec<ExportBook>, exported_at: String) -> Result<String, ExportError> {
+    Ok(serde_json::to_string_pretty(&HighlightExport {
+        version: EXPORT_VERSION,
+        exported_at,
+        books,
+    })?)
+}
+
+pub fn to_csv(books: &[ExportBook]) -> Result<String, ExportError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(CSV_HEADERS)?;
+    for book in books {
+        for highlight in &book.highlights {
+            let discussion: Vec<String> = highlight
+                .messages
+                .iter()
+                .map(|message| format!("{}: {}", speaker(message.role), message.content.trim()))
+                .collect();
+            writer.write_record([
+                book.title.as_str(),
+                book.authors.as_str(),
+                highlight.chapter.as_deref().unwrap_or_default(),
+                highlight.text.as_str(),
+                highlight.note.as_deref().unwrap_or_default(),
+                highlight.color.as_deref().unwrap_or_default(),
+                highlight.style.as_str(),
+                highlight.tags.join("; ").as_str(),
+                highlight.created_at.as_str(),
+                discussion.join("\n\n").as_str(),
+            ])?;
+        }
+    }
+    let bytes = writer
+        .into_inner()
+        .map_err(csv::IntoInnerError::into_error)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// A field of an Anki note: HTML, on one line, without tabs
+fn anki_field(s: &str) -> String {
+    anki_html(s.trim())
+}
+
+/// Like `anki_field`, keeping surrounding whitespace
+fn anki_html(s: &str) -> String {
+    escape_html(s)
+        .replace('\t', " ")
+        .replace("\r\n", "\n")
+        .replace('\n', "<br>")
+}
+
+/// Anki tags can't contain spaces
+fn anki_tag(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+/// Anki's text import format: one note per vocabulary highlight, the word on
+/// the front and the note, its sentence and the book on the back
+pub fn to_anki(books: &[ExportBook], deck: &str) -> String {
+    let mut out = format!(
+        "#separator:tab\n#html:true\n#notetype:Basic\n#deck:{}\n#tags column:3\n",
+        anki_field(deck)
+    );
+    for book in books {
+        for highlight in &book.highlights {
+            if !highlight.tags.iter().any(|tag| tag == VOCABULARY_TAG) {
+                continue;
+            }
+            let (prefix, suffix) = &highlight.context;
+            let mut back = highlight
+                .note
+                .as_deref()
+                .map(anki_field)
+                .unwrap_or_default();
+            if !back.is_empty() {
+                back.push_str("<br><br>");
+            }
+            let _ = write!(
+                back,
+                "<i>…{}<b>{}</b>{}…</i><br>— {}",
+                anki_html(prefix.trim_start()),
+                anki_field(&highlight.text),
+                anki_html(suffix.trim_end()),
+                anki_field(&book.title)
+            );
+            let tags: Vec<String> = highlight.tags.iter().map(|tag| anki_tag(tag)).collect();
+            let _ = writeln!(
+                out,
+                "{}\t{back}\t{}",
+                anki_field(&highlight.text),
+                tags.join(" ")
+            );
+        }
+    }
+    out
+}
+
+/// Renders `books` in `format`; `None` for an Anki deck without vocabulary
+pub fn render(
+    books: Vec<ExportBook>,
+    format: ExportFormat,
+    exported_at: String,
+) -> Result<Option<String>, ExportError> {
+    Ok(Some(match format {
+        ExportFormat::Markdown => to_markdown(&books),
+        ExportFormat::Json => to_json(books, exported_at)?,
+        ExportFormat::Csv => to_csv(&books)?,
+        ExportFormat::Anki => {
+            let deck = match books.as_slice() {
+                [book] => format!("Vocabulary::{}", book.title.trim().replace("::", ":")),
+                _ => "Vocabulary".to_string(),
+            };
+            let has_cards = books
+                .iter()
+                .flat_map(|book| &book.highlights)
+                .any(|highlight| highlight.tags.iter().any(|tag| tag == VOCABULARY_TAG));
+            if !has_cards {
+                return Ok(None);
+            }
+            to_anki(&books, &deck)
+        }
+    }))
+}
+
+/// Exports `highlights` (from `db::list_export_highlights`) to `path`. `texts`
+/// places highlights in chapters; books missing from it are exported without
+/// chapters.
+pub async fn export_highlights(
+    pool: &Pool<Postgres>,
+    highlights: Vec<LibraryHighlight>,
+    texts: &HashMap<i64, BookText>,
+    format: ExportFormat,
+    path: &Path,
+) -> Result<ExportSummary, ExportError> {
+    if highlights.is_empty() {
+        return Err(ExportError::Empty);
+    }
+    let ids: Vec<i64> = highlights.iter().map(|h| h.highlight.id.get()).collect();
+    let messages = db::list_messages_for_highlights(pool, &ids).await?;
+    let books = group_highlights(highlights, messages, texts);
+    let book_count = books.len();
+    let highlight_count = ids.len();
+
+    let exported_at = chrono::Utc::now().to_rfc3339();
+    let content = render(books, format, exported_at)?.ok_or(ExportError::Empty)?;
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(path, content).await?;
+
+    println!(
+        "[Export] Wrote {highlight_count} highlights from {book_count} books to {}",
+        path.display()
+    );
+    Ok(ExportSummary {
+        path: path.to_string_lossy().to_string(),
+        books: book_count,
+        highlights: highlight_count,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Highlight;
+    use crate::text::html_to_book_text;
+    use crate::types::{HighlightId, MessageId, Tag};
+
+    fn highlight(id: i64, book_id: i64, text: &str, start: Option<i64>) -> LibraryHighlight {
+        LibraryHighlight {
+            highlight: Highlight {
+                quote_prefix: "the ".to_string(),
+                quote_suffix: " of".to_string(),
+                text_start: start,
+                ..Highlight::fixture(id, book_id, text)
+            },
+            book_title: format!("Book {book_id}"),
+            book_authors: "Anon".to_string(),
+        }
+    }
+
+    fn fixture() -> Vec<ExportBook> {
+        let text = html_to_book_text(
+            "<html><body><h2>One</h2><p>It was the best of times.</p>\
+             <h2>Two</h2><p>It was the worst of times.</p></body></html>",
+        );
+        let second = text.chapters[1].start;
+        let texts = HashMap::from([(1, text)]);
+
+        let mut best = highlight(1, 1, "best", Some(10));
+        best.highlight.note = Some("Superlative".to_string());
+        best.highlight.tags = vec![Tag::new("Vocabulary").unwrap()];
+        let worst = highlight(2, 1, "worst", i64::try_from(second).ok().map(|s| s + 10));
+        let other = highlight(3, 2, "elsewhere", None);
+        let messages = vec![HighlightMessage {
+            id: MessageId::new(1),
+            highlight_id: HighlightId::new(1),
+            role: MessageRole::User,
+            content: "Why best?".to_string(),
+            created_at: String::new(),
+        }];
+        group_highlights(vec![best, worst, other], messages, &texts)
+    }
+
+    #[test]
+    fn test_group_by_book_and_chapter() {
+        let books = fixture();
+        assert_eq!(books.len(), 2);
+        assert_eq!(books[0].highlights.len(), 2);
+        assert_eq!(books[0].highlights[0].chapter.as_deref(), Some("One"));
+        assert_eq!(books[0].highlights[1].chapter.as_deref(), Some("Two"));
+        assert_eq!(books[0].highlights[0].messages.len(), 1);
+        assert_eq!(books[1].highlights[0].chapter, None);
+
+        let markdown = to_markdown(&books);
+        assert!(markdown.contains("# Book 1\n\n*Anon*\n\n## One\n\n> best\n\nSuperlative\n"));
+        assert!(markdown.contains("**You:** Why best?"));
+        assert!(markdown.contains("## Two\n\n> worst"));
+        assert!(markdown.contains("# Book 2\n\n*Anon*\n\n## Other highlights\n\n> elsewhere"));
+    }
+
+    #[test]
+    fn test_json_and_csv() {
+        let json = to_json(fixture(), "2024-05-02T00:00:00Z".to_string()).unwrap();
+        let parsed: HighlightExport = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.version, EXPORT_VERSION);
+        assert_eq!(parsed.books[0].highlights[0].tags, vec!["vocabulary"]);
+        assert_eq!(
+            parsed.books[0].highlights[0].messages[0].role,
+            MessageRole::User
+        );
+
+        let csv = to_csv(&fixture()).unwrap();
+        let mut reader = csv::Reader::from_reader(csv.as_bytes());
+        let rows: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(&rows[0][2], "One");
+        assert_eq!(&rows[0][9], "You: Why best?");
+    }
+
+    #[test]
+    fn test_anki_deck_has_only_vocabulary() {
+        let deck = render(fixture(), ExportFormat::Anki, String::new())
+            .unwrap()
+            .unwrap();
+        assert!(deck.starts_with("#separator:tab\n#html:true\n"));
+        assert!(deck.contains("#deck:Vocabulary\n"));
+        let cards: Vec<&str> = deck.lines().filter(|line| !line.starts_with('#')).collect();
+        assert_eq!(
+            cards,
+            vec!["best\tSuperlative<br><br><i>…the <b>best</b> of…</i><br>— Book 1\tvocabulary"]
+        );
+
+        let mut books = fixture();
+        books[0].highlights[0].tags.clear();
+        assert!(render(books, ExportFormat::Anki, String::new())
+            .unwrap()
+            .is_none());
+    }
+}
@@ -1,8 +1,18 @@
-use reqwest::Url;
+use crate::catalog::{self, CatalogError};
+use crate::db::postgres::DbError;
+use crate::db::{self, HttpCacheEntry};
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{StatusCode, Url};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
 use std::borrow::Cow;
+use std::time::Duration;
 use thiserror::Error;
 
+/// How long a cached response is served without asking Gutendex again
+pub const CACHE_TTL: Duration = Duration::from_hours(1);
+
 #[derive(Debug, Error)]
 pub enum GutendexError {
     #[error("HTTP error: {0}")]
@@ -17,6 +27,18 @@ pub enum GutendexError {
     #[error("Invalid URL: {0}")]
     InvalidUrl(String),
 
+    #[error("Database error: {0}")]
+    Db(#[from] DbError),
+
+    #[error("Invalid response: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Offline and not cached: {0}")]
+    NotCached(String),
+
+    #[error("Local catalog error: {0}")]
+    Catalog(#[from] CatalogError),
+
     #[allow(dead_code)]
     #[error("{0}")]
     Other(String),
@@ -35,6 +57,19 @@ pub struct GutendexBook {
     pub title: String,
     pub authors: Vec<GutendexAuthor>,
     pub copyright: Option<bool>,
+    #[serde(default)]
+    pub translators: Vec<GutendexAuthor>,
+    #[serde(default)]
+    pub subjects: Vec<String>,
+    #[serde(default)]
+    pub bookshelves: Vec<String>,
+    #[serde(default)]
+    pub languages: Vec<String>,
+    /// Auto-generated blurbs Gutendex attaches to popular books
+    #[serde(default)]
+    pub summaries: Vec<String>,
+    #[serde(default)]
+    pub media_type: Option<String>,
     pub formats: std::collections::HashMap<String, String>,
     pub download_count: Option<i64>,
 }
@@ -45,84 +80,443 @@ pub struct GutendexResponse {
     pub next: Option<String>,
     pub previous: Option<String>,
     pub results: Vec<GutendexBook>,
+    /// Served from the local cache rather than a fresh download
+    #[serde(default)]
+    pub cached: bool,
+    /// Served from the cache past its TTL because Gutendex was unreachable
+    #[serde(default)]
+    pub stale: bool,
+}
+
+/// Where catalog pages come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CatalogSource {
+    /// Gutendex, through the response cache
+    #[default]
+    Online,
+    /// Only cached Gutendex responses, however old
+    Offline,
+    /// The imported Gutenberg catalog (see `catalog.rs`)
+    Local,
+}
+
+/// A response body and where it came from
+struct Fetched {
+    body: String,
+    cached: bool,
+    stale: bool,
 }
 
-fn catalog_base_url(catalog_key: &str) -> Option<&'static str> {
-    match catalog_key {
-        "all" => Some("https://gutendex.com/books/"),
-        "shakespeare" => Some("https://gutendex.com/books/?search=Shakespeare%2C%20William"),
-        "greek-tragedy" => {
-            Some("https://gutendex.com/books/?search=aeschylus%20sophocles%20euripides%20tragedy")
+impl Fetched {
+    fn from_cache(entry: HttpCacheEntry, stale: bool) -> Self {
+        Self {
+            body: entry.body,
+            cached: true,
+            stale,
         }
-        "greek-epic" => Some("https://gutendex.com/books/?search=homer%20hesiod%20epic%20myth"),
-        "roman-drama" => Some("https://gutendex.com/books/?search=roman%20drama"),
-        "mythology" => Some("https://gutendex.com/books/?search=mythology"),
-        "philosophy" => Some("https://gutendex.com/books/?search=philosophy"),
-        "gothic" => Some("https://gutendex.com/books/?search=gothic"),
-        "science-fiction" => Some("https://gutendex.com/books/?search=science%20fiction"),
-        "poetry" => Some("https://gutendex.com/books/?search=poetry"),
-        _ => None,
     }
 }
 
-fn build_catalog_url(
-    base_url: &str,
-    search_query: Option<&str>,
-    topic: Option<&str>,
-) -> Result<String, GutendexError> {
-    let mut url = Url::parse(base_url)?;
-    let mut existing_search: Option<String> = None;
-    let mut other_pairs: Vec<(String, String)> = Vec::new();
+/// Shared Gutendex client with a persistent response cache in the database
+#[derive(Debug, Clone)]
+pub struct GutendexClient {
+    client: reqwest::Client,
+    ttl: Duration,
+}
+
+impl GutendexClient {
+    pub fn new() -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .unwrap_or_default();
+        Self {
+            client,
+            ttl: CACHE_TTL,
+        }
+    }
+
+    /// GETs `url` through the cache: fresh entries are served directly, stale
+    /// ones are revalidated with their ETag/Last-Modified, and when Gutendex is
+    /// unreachable any cached copy is served and marked stale.
+    async fn fetch_json<T: DeserializeOwned>(
+        &self,
+        pool: &Pool<Postgres>,
+        url: &str,
+        source: CatalogSource,
+    ) -> Result<(T, Fetched), GutendexError> {
+        let ttl_seconds = i64::try_from(self.ttl.as_secs()).unwrap_or(i64::MAX);
+        let entry = db::get_http_cache_entry(pool, url, ttl_seconds).await?;
+
+        let fetched = match entry {
+            Some(entry) if entry.fresh => Fetched::from_cache(entry, false),
+            Some(entry) if source == CatalogSource::Offline => Fetched::from_cache(entry, true),
+            None if source == CatalogSource::Offline => {
+                return Err(GutendexError::NotCached(url.to_string()));
+            }
+            entry => self.revalidate(pool, url, entry).await?,
+        };
+        let value = serde_json::from_str(&fetched.body)?;
+        Ok((value, fetched))
+    }
+
+    async fn revalidate(
+        &self,
+        pool: &Pool<Postgres>,
+        url: &str,
+        entry: Option<HttpCacheEntry>,
+    ) -> Result<Fetched, GutendexError> {
+        let mut request = self.client.get(url);
+        if let Some(entry) = &entry {
+            for (name, value) in conditional_headers(entry) {
+                request = request.header(name, value);
+            }
+        }
+
+        let response = match request.send().await.and_then(reject_server_error) {
+            Ok(response) => response,
+            Err(e) => {
+                let Some(entry) = entry else {
+                    return Err(e.into());
+                };
+                println!("[Gutendex] {e}; serving stale cache for {url}");
+                return Ok(Fetched::from_cache(entry, true));
+            }
+        };
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            if let Some(entry) = entry {
+                db::touch_http_cache_entry(pool, url).await?;
+                return Ok(Fetched::from_cache(entry, false));
+            }
+        }
+
+        let response = response.error_for_status()?;
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+        let body = response.text().await?;
+
+        // Only cache bodies that parse, so a bad response can't poison the cache
+        serde_json::from_str::<serde_json::Value>(&body)?;
+        db::put_http_cache_entry(pool, url, &body, etag.as_deref(), last_modified.as_deref())
+            .await?;
+        Ok(Fetched {
+            body,
+            cached: false,
+            stale: false,
+        })
+    }
+}
+
+impl Default for GutendexClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Treats 5xx like a network failure so a stale copy can be served instead
+fn reject_server_error(response: reqwest::Response) -> reqwest::Result<reqwest::Response> {
+    if response.status().is_server_error() {
+        response.error_for_status()
+    } else {
+        Ok(response)
+    }
+}
+
+/// Validators to send when revalidating a cached response
+fn conditional_headers(entry: &HttpCacheEntry) -> Vec<(reqwest::header::HeaderName, &str)> {
+    let mut headers = Vec::new();
+    if let Some(etag) = entry.etag.as_deref() {
+        headers.push((IF_NONE_MATCH, etag));
+    }
+    if let Some(last_modified) = entry.last_modified.as_deref() {
+        headers.push((IF_MODIFIED_SINCE, last_modified));
+    }
+    headers
+}
+
+/// Gutendex's book list endpoint, used unless `gutendex_base_url` is set
+pub const GUTENDEX_BOOKS_URL: &str = "https://gutendex.com/books/";
+
+/// Parses the `gutendex_base_url` setting. A self-hosted Gutendex may be given
+/// by its root; the books endpoint under it is assumed.
+pub fn parse_base_url(value: &str) -> Result<Url, GutendexError> {
+    let mut url = Url::parse(value.trim())?;
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return Err(GutendexError::InvalidUrl(format!(
+            "{value}: the Gutendex base URL must be http(s)"
+        )));
+    }
+    url.set_query(None);
+    url.set_fragment(None);
+    let path = match url.path().trim_end_matches('/') {
+        "" => "/books/".to_string(),
+        path => format!("{path}/"),
+    };
+    url.set_path(&path);
+    Ok(url)
+}
+
+/// Gutendex result ordering
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CatalogSort {
+    Popular,
+    Ascending,
+    Descending,
+}
+
+impl CatalogSort {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Popular => "popular",
+            Self::Ascending => "ascending",
+            Self::Descending => "descending",
+        }
+    }
 
-    for (key, value) in url.query_pairs() {
-        if key == "search" {
-            existing_search = Some(value.to_string());
-        } else if key != "topic" {
-            other_pairs.push((key.to_string(), value.to_string()));
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "popular" => Some(Self::Popular),
+            "ascending" => Some(Self::Ascending),
+            "descending" => Some(Self::Descending),
+            _ => None,
         }
     }
+}
+
+/// A typed Gutendex query. Catalogs save one, and the library page layers the
+/// user's own filters over it with [`CatalogQuery::refine`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CatalogQuery {
+    /// Words matched against titles and author names
+    pub search: Option<String>,
+    /// Substring of a subject or bookshelf
+    pub topic: Option<String>,
+    /// Two-letter language codes
+    pub languages: Vec<String>,
+    /// Authors alive at some point in this range
+    pub author_year_start: Option<i32>,
+    pub author_year_end: Option<i32>,
+    /// `Some(false)` for public domain in the USA only
+    pub copyright: Option<bool>,
+    /// Project Gutenberg ebook numbers
+    pub ids: Vec<i64>,
+    /// MIME type prefix, e.g. `application/x-mobipocket-ebook`
+    pub mime_type: Option<String>,
+    pub sort: Option<CatalogSort>,
+}
+
+impl CatalogQuery {
+    pub fn search(search: &str) -> Self {
+        Self {
+            search: Some(search.to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// Layers `other` over this query: its search terms are appended to ours,
+    /// and every other filter it sets replaces ours.
+    pub fn refine(&self, other: &Self) -> Self {
+        let search = [self.search.as_deref(), other.search.as_deref()]
+            .into_iter()
+            .filter_map(trimmed)
+            .collect::<Vec<_>>()
+            .join(" ");
+        let pick = |ours: &Option<String>, theirs: &Option<String>| {
+            trimmed(theirs.as_deref())
+                .or_else(|| trimmed(ours.as_deref()))
+                .map(str::to_string)
+        };
 
-    let mut combined_search = existing_search.unwrap_or_default();
-    if let Some(query) = search_query {
-        let trimmed = query.trim();
-        if !trimmed.is_empty() {
-            if combined_search.is_empty() {
-                combined_search = trimmed.to_string();
+        Self {
+            search: (!search.is_empty()).then_some(search),
+            topic: pick(&self.topic, &other.topic),
+            languages: if other.languages.is_empty() {
+                self.languages.clone()
             } else {
-                combined_search.push(' ');
-                combined_search.push_str(trimmed);
+                other.languages.clone()
+            },
+            author_year_start: other.author_year_start.or(self.author_year_start),
+            author_year_end: other.author_year_end.or(self.author_year_end),
+            copyright: other.copyright.or(self.copyright),
+            ids: if other.ids.is_empty() {
+                self.ids.clone()
+            } else {
+                other.ids.clone()
+            },
+            mime_type: pick(&self.mime_type, &other.mime_type),
+            sort: other.sort.or(self.sort),
+        }
+    }
+
+    /// Reads the parameters Gutendex understands back out of a URL; anything
+    /// else (including `page`) is ignored.
+    pub fn from_url(url: &Url) -> Self {
+        let mut query = Self::default();
+        for (key, value) in url.query_pairs() {
+            let value = value.trim();
+            match key.as_ref() {
+                "search" => query.search = trimmed(Some(value)).map(str::to_string),
+                "topic" => query.topic = trimmed(Some(value)).map(str::to_string),
+                "languages" => {
+                    query.languages = split_list(value).map(str::to_lowercase).collect();
+                }
+                "author_year_start" => query.author_year_start = value.parse().ok(),
+                "author_year_end" => query.author_year_end = value.parse().ok(),
+                "copyright" => query.copyright = value.parse().ok(),
+                "ids" => query.ids = split_list(value).filter_map(|id| id.parse().ok()).collect(),
+                "mime_type" => query.mime_type = trimmed(Some(value)).map(str::to_string),
+                "sort" => query.sort = CatalogSort::parse(value),
+                _ => {}
             }
         }
+        query
+    }
+
+    /// The query string parameters, in Gutendex's documented order
+    fn to_pairs(&self) -> Vec<(&'static str, String)> {
+        let mut pairs = Vec::new();
+        if let Some(search) = trimmed(self.search.as_deref()) {
+            pairs.push(("search", search.to_string()));
+        }
+        if let Some(topic) = trimmed(self.topic.as_deref()) {
+            pairs.push(("topic", topic.to_string()));
+        }
+        let languages: Vec<&str> = self
+            .languages
+            .iter()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .collect();
+        if !languages.is_empty() {
+            pairs.push(("languages", languages.join(",")));
+        }
+        if let Some(year) = self.author_year_start {
+            pairs.push(("author_year_start", year.to_string()));
+        }
+        if let Some(year) = self.author_year_end {
+            pairs.push(("author_year_end", year.to_string()));
+        }
+        if let Some(copyright) = self.copyright {
+            pairs.push(("copyright", copyright.to_string()));
+        }
+        if !self.ids.is_empty() {
+            let ids: Vec<String> = self.ids.iter().map(i64::to_string).collect();
+            pairs.push(("ids", ids.join(",")));
+        }
+        if let Some(mime_type) = trimmed(self.mime_type.as_deref()) {
+            pairs.push(("mime_type", mime_type.to_string()));
+        }
+        if let Some(sort) = self.sort {
+            pairs.push(("sort", sort.as_str().to_string()));
+        }
+        pairs
     }
+}
 
-    let topic = topic.and_then(|t| {
-        let trimmed = t.trim();
-        if trimmed.is_empty() {
-            None
-        } else {
-            Some(trimmed)
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').map(str::trim).filter(|v| !v.is_empty())
+}
+
+/// Key of the catalog with every book, which category pages refine
+pub const ALL_CATALOG_KEY: &str = "all";
+
+/// Shakespeare's works, for the dedicated Shakespeare page
+pub fn shakespeare_query() -> CatalogQuery {
+    CatalogQuery::search("Shakespeare, William")
+}
+
+/// The catalogs a fresh database starts with: `(key, name, query)`
+pub fn default_catalogs() -> Vec<(&'static str, &'static str, CatalogQuery)> {
+    vec![
+        (ALL_CATALOG_KEY, "All Books", CatalogQuery::default()),
+        ("shakespeare", "Shakespeare", shakespeare_query()),
+        (
+            "greek-tragedy",
+            "Greek Tragedy",
+            CatalogQuery::search("aeschylus sophocles euripides tragedy"),
+        ),
+        (
+            "greek-epic",
+            "Greek Epic",
+            CatalogQuery::search("homer hesiod epic myth"),
+        ),
+        (
+            "roman-drama",
+            "Roman Drama",
+            CatalogQuery::search("roman drama"),
+        ),
+        ("mythology", "Mythology", CatalogQuery::search("mythology")),
+        (
+            "philosophy",
+            "Philosophy",
+            CatalogQuery::search("philosophy"),
+        ),
+        ("gothic", "Gothic", CatalogQuery::search("gothic")),
+        (
+            "science-fiction",
+            "Science Fiction",
+            CatalogQuery::search("science fiction"),
+        ),
+        ("poetry", "Poetry", CatalogQuery::search("poetry")),
+    ]
+}
+
+/// Derives a URL-safe catalog key from its display name
+pub fn catalog_key_for(name: &str) -> String {
+    let mut key = String::new();
+    for c in name.trim().chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            key.push(c);
+        } else if !key.is_empty() && !key.ends_with('-') {
+            key.push('-');
         }
-    });
+    }
+    key.trim_end_matches('-').to_string()
+}
+
+fn trimmed(value: Option<&str>) -> Option<&str> {
+    value.map(str::trim).filter(|v| !v.is_empty())
+}
+
+/// Compiles a query into a Gutendex URL. Parameters already on `base_url` are
+/// refined by `query` rather than duplicated.
+fn build_catalog_url(base_url: &str, query: &CatalogQuery) -> Result<String, GutendexError> {
+    let mut url = Url::parse(base_url)?;
+    let query = CatalogQuery::from_url(&url).refine(query);
 
     url.set_query(None);
-    {
+    let pairs = query.to_pairs();
+    if !pairs.is_empty() {
         let mut qp = url.query_pairs_mut();
-        for (key, value) in other_pairs {
-            qp.append_pair(&key, &value);
-        }
-        if !combined_search.is_empty() {
-            qp.append_pair("search", &combined_search);
-        }
-        if let Some(t) = topic {
-            qp.append_pair("topic", t);
+        for (key, value) in pairs {
+            qp.append_pair(key, &value);
         }
     }
 
     Ok(url.to_string())
 }
 
-fn sanitize_page_url(page_url: &str) -> Result<Cow<'_, str>, GutendexError> {
-    if page_url.starts_with("https://gutendex.com/books/") {
+/// Only follows `next`/`previous` links that stay on the configured endpoint
+fn sanitize_page_url<'a>(page_url: &'a str, base_url: &Url) -> Result<Cow<'a, str>, GutendexError> {
+    let url = Url::parse(page_url)
+        .map_err(|_| GutendexError::InvalidUrl("Invalid catalog URL.".to_string()))?;
+    let same_endpoint = url.scheme() == base_url.scheme()
+        && url.host_str() == base_url.host_str()
+        && url.port_or_known_default() == base_url.port_or_known_default()
+        && url.path().starts_with(base_url.path())
+        && url.username().is_empty()
+        && url.password().is_none();
+    if same_endpoint {
         Ok(Cow::Borrowed(page_url))
     } else {
         Err(GutendexError::InvalidUrl(
@@ -131,45 +525,242 @@ fn sanitize_page_url(page_url: &str) -> Result<Cow<'_, str>, GutendexError> {
     }
 }
 
+/// Fetches a page of a saved catalog, refined by `refinement`. The built-in
+/// "all" catalog still works if its row was deleted, since categories use it.
 pub async fn search_catalog(
+    client: &GutendexClient,
+    pool: &Pool<Postgres>,
+    source: CatalogSource,
+    base_url: &Url,
     catalog_key: &str,
     page_url: Option<String>,
-    search_query: Option<String>,
-    topic: Option<String>,
+    refinement: &CatalogQuery,
 ) -> Result<GutendexResponse, GutendexError> {
-    let url: Cow<'_, str> = if let Some(ref page_url) = page_url {
-        sanitize_page_url(page_url)?
+    let query = if page_url.is_some() {
+        CatalogQuery::default()
     } else {
-        let base_url = catalog_base_url(catalog_key)
-            .ok_or_else(|| GutendexError::UnknownCatalog(catalog_key.to_string()))?;
-        Cow::Owned(build_catalog_url(
-            base_url,
-            search_query.as_deref(),
-            topic.as_deref(),
-        )?)
+        match db::get_catalog_by_key(pool, catalog_key).await? {
+            Some(catalog) => catalog.query.refine(refinement),
+            None if catalog_key == ALL_CATALOG_KEY => refinement.clone(),
+            None => return Err(GutendexError::UnknownCatalog(catalog_key.to_string())),
+        }
     };
+    search_query(client, pool, source, base_url, page_url, &query).await
+}
 
-    let client = reqwest::Client::new();
-    let resp = client.get(url.as_ref()).send().await?.error_for_status()?;
-    Ok(resp.json::<GutendexResponse>().await?)
+/// Fetches `page_url`, or the first page of `query` without one
+pub async fn search_query(
+    client: &GutendexClient,
+    pool: &Pool<Postgres>,
+    source: CatalogSource,
+    base_url: &Url,
+    page_url: Option<String>,
+    query: &CatalogQuery,
+) -> Result<GutendexResponse, GutendexError> {
+    let url: Cow<'_, str> = match page_url {
+        Some(ref page_url) => sanitize_page_url(page_url, base_url)?,
+        None => Cow::Owned(build_catalog_url(base_url.as_str(), query)?),
+    };
+
+    if source == CatalogSource::Local {
+        return Ok(catalog::search_local(pool, url.as_ref()).await?);
+    }
+
+    let (mut response, fetched): (GutendexResponse, _) =
+        client.fetch_json(pool, url.as_ref(), source).await?;
+    response.cached = fetched.cached;
+    response.stale = fetched.stale;
+    Ok(response)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn default_catalog_url(key: &str) -> String {
+        let (_, _, query) = default_catalogs()
+            .into_iter()
+            .find(|(k, _, _)| *k == key)
+            .unwrap();
+        build_catalog_url(GUTENDEX_BOOKS_URL, &query).unwrap()
+    }
+
     #[test]
-    fn test_catalog_base_urls() {
+    fn test_default_catalogs() {
+        assert_eq!(default_catalogs().len(), 10);
+
         // Greek Epic should include broader search
-        let epic = catalog_base_url("greek-epic").unwrap();
-        assert!(epic.contains("homer%20hesiod%20epic%20myth"));
+        let epic = default_catalog_url("greek-epic");
+        assert!(epic.contains("search=homer+hesiod+epic+myth"));
 
         // Greek Tragedy should include major playwrights
-        let tragedy = catalog_base_url("greek-tragedy").unwrap();
-        assert!(tragedy.contains("aeschylus%20sophocles%20euripides%20tragedy"));
+        let tragedy = default_catalog_url("greek-tragedy");
+        assert!(tragedy.contains("search=aeschylus+sophocles+euripides+tragedy"));
 
-        // New categories
-        assert!(catalog_base_url("gothic").is_some());
-        assert!(catalog_base_url("philosophy").is_some());
+        assert_eq!(default_catalog_url("all"), "https://gutendex.com/books/");
+    }
+
+    #[test]
+    fn test_catalog_key_for() {
+        assert_eq!(catalog_key_for("Restoration Comedy"), "restoration-comedy");
+        assert_eq!(catalog_key_for("  Sci-Fi & Fantasy! "), "sci-fi-fantasy");
+    }
+
+    fn full_query() -> CatalogQuery {
+        CatalogQuery {
+            search: Some("comedy".to_string()),
+            topic: Some("drama".to_string()),
+            languages: vec!["en".to_string(), "fr".to_string()],
+            author_year_start: Some(1660),
+            author_year_end: Some(1710),
+            copyright: Some(false),
+            ids: vec![1524, 2265],
+            mime_type: Some("application/x-mobipocket-ebook".to_string()),
+            sort: Some(CatalogSort::Popular),
+        }
+    }
+
+    #[test]
+    fn test_build_catalog_url_from_query() {
+        assert_eq!(
+            build_catalog_url(GUTENDEX_BOOKS_URL, &full_query()).unwrap(),
+            "https://gutendex.com/books/?search=comedy&topic=drama&languages=en%2Cfr\
+             &author_year_start=1660&author_year_end=1710&copyright=false&ids=1524%2C2265\
+             &mime_type=application%2Fx-mobipocket-ebook&sort=popular"
+        );
+        assert_eq!(
+            build_catalog_url(
+                GUTENDEX_BOOKS_URL,
+                &CatalogQuery {
+                    languages: vec!["de".to_string()],
+                    author_year_end: Some(-200),
+                    sort: Some(CatalogSort::Descending),
+                    ..CatalogQuery::default()
+                }
+            )
+            .unwrap(),
+            "https://gutendex.com/books/?languages=de&author_year_end=-200&sort=descending"
+        );
+        assert_eq!(
+            build_catalog_url(
+                "https://gutendex.com/books/?search=homer",
+                &CatalogQuery::search("iliad")
+            )
+            .unwrap(),
+            "https://gutendex.com/books/?search=homer+iliad"
+        );
+    }
+
+    #[test]
+    fn test_refine_catalog_query() {
+        let refined = full_query().refine(&CatalogQuery {
+            search: Some(" wycherley ".to_string()),
+            topic: Some("restoration".to_string()),
+            languages: vec!["en".to_string()],
+            ..CatalogQuery::default()
+        });
+        assert_eq!(refined.search.as_deref(), Some("comedy wycherley"));
+        assert_eq!(refined.topic.as_deref(), Some("restoration"));
+        assert_eq!(refined.languages, vec!["en"]);
+        // Filters the refinement leaves unset are kept
+        assert_eq!(refined.author_year_start, Some(1660));
+        assert_eq!(refined.ids, vec![1524, 2265]);
+        assert_eq!(refined.sort, Some(CatalogSort::Popular));
+
+        assert_eq!(full_query().refine(&CatalogQuery::default()), full_query());
+    }
+
+    #[test]
+    fn test_catalog_query_round_trips_through_url() {
+        let url =
+            Url::parse(&build_catalog_url(GUTENDEX_BOOKS_URL, &full_query()).unwrap()).unwrap();
+        assert_eq!(CatalogQuery::from_url(&url), full_query());
+    }
+
+    #[test]
+    fn test_book_with_extended_fields() {
+        let book: GutendexBook = serde_json::from_str(
+            r#"{"id":2000,"title":"Don Quijote","authors":[],
+                "translators":[{"name":"Ormsby, John","birth_year":1829,"death_year":1895}],
+                "subjects":["Spain -- Fiction"],"bookshelves":["Best Books Ever Listings"],
+                "languages":["es"],"summaries":["A knight errant."],"copyright":false,
+                "media_type":"Text","formats":{},"download_count":10}"#,
+        )
+        .unwrap();
+        assert_eq!(book.translators[0].name, "Ormsby, John");
+        assert_eq!(book.languages, vec!["es"]);
+        assert_eq!(book.summaries, vec!["A knight errant."]);
+        assert_eq!(book.media_type.as_deref(), Some("Text"));
+
+        // Older cached responses without the extra fields still parse
+        let book: GutendexBook = serde_json::from_str(
+            r#"{"id":1,"title":"T","authors":[],"copyright":null,"formats":{},"download_count":null}"#,
+        )
+        .unwrap();
+        assert!(book.subjects.is_empty());
+    }
+
+    #[test]
+    fn test_parse_base_url() {
+        assert_eq!(
+            parse_base_url(GUTENDEX_BOOKS_URL).unwrap().as_str(),
+            GUTENDEX_BOOKS_URL
+        );
+        assert_eq!(
+            parse_base_url("http://localhost:8000").unwrap().as_str(),
+            "http://localhost:8000/books/"
+        );
+        assert_eq!(
+            parse_base_url("https://books.example.org/api/books?x=1")
+                .unwrap()
+                .as_str(),
+            "https://books.example.org/api/books/"
+        );
+        assert!(parse_base_url("file:///srv/gutendex").is_err());
+        assert!(parse_base_url("not a url").is_err());
+    }
+
+    #[test]
+    fn test_sanitize_page_url_against_base() {
+        let base = parse_base_url("http://localhost:8000").unwrap();
+        assert!(sanitize_page_url("http://localhost:8000/books/?page=2", &base).is_ok());
+        assert!(sanitize_page_url("http://localhost:8001/books/?page=2", &base).is_err());
+        assert!(sanitize_page_url("https://gutendex.com/books/?page=2", &base).is_err());
+        assert!(sanitize_page_url("http://localhost:8000/admin/", &base).is_err());
+
+        let default = parse_base_url(GUTENDEX_BOOKS_URL).unwrap();
+        assert!(sanitize_page_url("https://gutendex.com/books/?page=2", &default).is_ok());
+        assert!(sanitize_page_url("https://gutendex.com.evil.test/books/", &default).is_err());
+    }
+
+    #[test]
+    fn test_conditional_headers() {
+        let mut entry = HttpCacheEntry {
+            url: "https://gutendex.com/books/".to_string(),
+            body: "{}".to_string(),
+            etag: None,
+            last_modified: None,
+            fetched_at: String::new(),
+            fresh: false,
+        };
+        assert!(conditional_headers(&entry).is_empty());
+
+        entry.etag = Some("\"abc\"".to_string());
+        entry.last_modified = Some("Wed, 21 Oct 2015 07:28:00 GMT".to_string());
+        let headers = conditional_headers(&entry);
+        assert_eq!(headers[0], (IF_NONE_MATCH, "\"abc\""));
+        assert_eq!(
+            headers[1],
+            (IF_MODIFIED_SINCE, "Wed, 21 Oct 2015 07:28:00 GMT")
+        );
+    }
+
+    #[test]
+    fn test_cached_flags_default_off() {
+        let response: GutendexResponse =
+            serde_json::from_str(r#"{"count":0,"next":null,"previous":null,"results":[]}"#)
+                .unwrap();
+        assert!(!response.cached);
+        assert!(!response.stale);
     }
 }
@@ -0,0 +1,162 @@
+//! Reading position history
+//!
+//! `book_position` holds one row per book, so before it is overwritten the old
+//! position is pushed onto the book's history when the move was significant:
+//! a jump (the table of contents, a citation link, dragging to the end) or a
+//! stretch of ordinary reading since the last entry. Jumps are what "where was
+//! I" goes back to. Each book keeps its newest `HISTORY_LIMIT` entries.
+//! Entries are re-anchored by their quote when restored, since the book may
+//! have been updated since.
+
+use crate::db::postgres::DbError;
+use crate::db::{self, PositionEntry};
+use crate::locator::{self, Locator};
+use crate::text::BookText;
+use crate::types::PositionChange;
+use sqlx::{Pool, Postgres};
+
+pub const HISTORY_LIMIT: i64 = 100;
+
+/// A move forward by more than this many chars in one step is a jump
+const JUMP_FORWARD_CHARS: usize = 6000;
+
+/// Turning back a page or two is reading; further back is a jump
+const JUMP_BACK_CHARS: usize = 3000;
+
+/// Reading this far past the last entry records a checkpoint
+const CHECKPOINT_CHARS: usize = 20_000;
+
+/// Whether moving from `previous` to `next` should record `previous` in the
+/// history, and as what. `last_entry` is the newest history entry.
+pub fn classify_move(
+    text: &BookText,
+    previous: &Locator,
+    next: &Locator,
+    last_entry: Option<&Locator>,
+) -> Option<PositionChange> {
+    let from = previous.offset(text);
+    let to = next.offset(text);
+    let skipped_chapters = match (previous.chapter_index, next.chapter_index) {
+        (Some(a), Some(b)) => b > a + 1 || b < a,
+        _ => false,
+    };
+    if skipped_chapters || to > from + JUMP_FORWARD_CHARS || from > to + JUMP_BACK_CHARS {
+        return Some(PositionChange::Jump);
+    }
+
+    let since_entry = last_entry.map_or(from, |entry| from.abs_diff(entry.offset(text)));
+    (since_entry >= CHECKPOINT_CHARS).then_some(PositionChange::Read)
+}
+
+/// Saves the reader's position, first recording the previous one in the
+/// history when the move was significant
+pub async fn save_position(
+    pool: &Pool<Postgres>,
+    text: &BookText,
+    book_id: i64,
+    locator: &Locator,
+) -> Result<(), DbError> {
+    if let Some(previous) = db::get_book_position(pool, book_id).await? {
+        let last_entry = db::latest_position_entry(pool, book_id, None).await?;
+        if let Some(change) = classify_move(
+            text,
+            &previous.locator,
+            locator,
+            last_entry.as_ref().map(|entry| &entry.locator),
+        ) {
+            db::push_position_entry(pool, book_id, &previous.locator, change, HISTORY_LIMIT)
+                .await?;
+        }
+    }
+    db::set_book_position(pool, book_id, locator).await
+}
+
+/// Moves the reading position back to a history entry. The entry is taken off
+/// the history and the position being left is recorded as a restore, so it
+/// can be returned to.
+pub async fn restore_position(
+    pool: &Pool<Postgres>,
+    text: &BookText,
+    entry: &PositionEntry,
+) -> Result<Locator, DbError> {
+    let book_id = entry.book_id.get();
+    let locator = locator::relocate(text, &entry.locator);
+    if let Some(current) = db::get_book_position(pool, book_id).await? {
+        db::push_position_entry(
+            pool,
+            book_id,
+            &current.locator,
+            PositionChange::Restore,
+            HISTORY_LIMIT,
+        )
+        .await?;
+    }
+    db::delete_position_entry(pool, entry.id.get()).await?;
+    db::set_book_position(pool, book_id, &locator).await?;
+    Ok(locator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::html_to_book_text;
+
+    fn book() -> BookText {
+        let chapter = |n: usize| {
+            format!(
+                "<h2>Chapter {n}</h2><p>{}</p>",
+                "Words upon words upon words. ".repeat(400)
+            )
+        };
+        html_to_book_text(&format!(
+            "<html><body>{}</body></html>",
+            (1..=4).map(chapter).collect::<String>()
+        ))
+    }
+
+    #[test]
+    fn test_classify_page_turns_and_jumps() {
+        let text = book();
+        let at = |offset| Locator::at_offset(&text, offset, None);
+        let start = text.chapters[1].start;
+
+        // A page forward or back is ordinary reading
+        assert_eq!(
+            classify_move(&text, &at(start), &at(start + 1500), Some(&at(start))),
+            None
+        );
+        assert_eq!(
+            classify_move(&text, &at(start + 1500), &at(start), Some(&at(start))),
+            None
+        );
+
+        // Skipping chapters or going far back is a jump
+        let end = at(text.chapters[3].start);
+        assert_eq!(
+            classify_move(&text, &at(start), &end, Some(&at(start))),
+            Some(PositionChange::Jump)
+        );
+        assert_eq!(
+            classify_move(&text, &at(start + 5000), &at(start), Some(&at(start))),
+            Some(PositionChange::Jump)
+        );
+    }
+
+    #[test]
+    fn test_classify_checkpoint_after_long_read() {
+        let text = book();
+        let at = |offset| Locator::at_offset(&text, offset, None);
+        let from = at(CHECKPOINT_CHARS + 100);
+        let next = at(CHECKPOINT_CHARS + 1600);
+
+        assert_eq!(
+            classify_move(&text, &from, &next, Some(&at(0))),
+            Some(PositionChange::Read)
+        );
+        assert_eq!(
+            classify_move(&text, &from, &next, Some(&at(CHECKPOINT_CHARS))),
+            None
+        );
+        assert_eq!(classify_move(&text, &at(100), &at(1600), None), None);
+    }
+}
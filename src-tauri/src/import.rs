@@ -0,0 +1,572 @@
+//! Annotation import
+//!
+//! Reads highlights made elsewhere — a Kindle's `My Clippings.txt` or a CSV
+//! with quote, note and location columns as exported by Readwise — and adds
+//! them to matching library books. Each clipping's book is found by title and
+//! author, then its quote is located in the book text so the highlight gets
+//! the same selectors as one made in the reader. Clippings that can't be
+//! placed are returned in the report rather than dropped.
+
+use crate::anchoring::{self, Anchorer};
+use crate::db::postgres::DbError;
+use crate::db::{self, BookTitle, HighlightFilter, NewHighlight};
+use crate::text::{char_slice, BookText};
+use crate::types::{Color, Tag};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
+
+/// Separates entries in `My Clippings.txt`
+const KINDLE_SEPARATOR: &str = "==========";
+
+/// Kindle locations are roughly this many chars of the book apart; only used
+/// to choose between repeated quotes
+const CHARS_PER_LOCATION: usize = 128;
+
+/// Title words ignored when matching books
+const TITLE_STOP_WORDS: [&str; 3] = ["the", "a", "an"];
+
+#[derive(Debug, Error)]
+pub enum ImportError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+
+    #[error("CSV has no quote column (expected one of: {})", QUOTE_COLUMNS.join(", "))]
+    MissingQuoteColumn,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportSource {
+    /// `My Clippings.txt`
+    Kindle,
+    Csv,
+}
+
+impl ImportSource {
+    /// Guesses the source from a file name
+    pub fn from_path(path: &str) -> Self {
+        if path.to_lowercase().ends_with(".csv") {
+            Self::Csv
+        } else {
+            Self::Kindle
+        }
+    }
+}
+
+/// A highlight read from an import file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Clipping {
+    pub title: String,
+    pub authors: String,
+    /// Empty for a Kindle note that belongs to no highlight
+    pub text: String,
+    pub note: Option<String>,
+    pub location: Option<usize>,
+    pub tags: Vec<String>,
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnmatchedReason {
+    /// A note without highlighted text
+    NoQuote,
+    BookNotFound,
+    /// The book's text couldn't be loaded
+    TextUnavailable,
+    QuoteNotFound,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UnmatchedClipping {
+    #[serde(flatten)]
+    pub clipping: Clipping,
+    pub reason: UnmatchedReason,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportReport {
+    pub imported: usize,
+    /// Clippings already highlighted at the same place
+    pub duplicates: usize,
+    pub unmatched: Vec<UnmatchedClipping>,
+}
+
+impl ImportReport {
+    pub fn unmatch(&mut self, clippings: Vec<Clipping>, reason: UnmatchedReason) {
+        self.unmatched.extend(
+            clippings
+                .into_iter()
+                .map(|clipping| UnmatchedClipping { clipping, reason }),
+        );
+    }
+}
+
+// ============================================================================
+// PARSING
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KindleKind {
+    Highlight,
+    Note,
+    Bookmark,
+}
+
+/// Splits `Title (Author)` into its parts; the author is the last parenthesized
+/// group, since titles can contain parentheses too
+fn split_kindle_title(line: &str) -> (String, String) {
+    let line = line.trim();
+    if let Some(open) = line.strip_suffix(')').and_then(|rest| rest.rfind('(')) {
+        let title = line[..open].trim();
+        if !title.is_empty() {
+            return (
+                title.to_string(),
+                line[open + 1..line.len() - 1].trim().to_string(),
+            );
+        }
+    }
+    (line.to_string(), String::new())
+}
+
+/// The kind and location range from a line like
+/// `- Your Highlight on page 5 | Location 62-63 | Added on ...`
+fn parse_kindle_meta(line: &str) -> Option<(KindleKind, Option<(usize, usize)>)> {
+    let lower = line.to_lowercase();
+    let first = lower.split('|').next()?;
+    let kind = if first.contains("highlight") {
+        KindleKind::Highlight
+    } else if first.contains("note") {
+        KindleKind::Note
+    } else if first.contains("bookmark") {
+        KindleKind::Bookmark
+    } else {
+        return None;
+    };
+
+    let location = ["location ", "loc. "].iter().find_map(|label| {
+        let at = lower.find(label)? + label.len();
+        let digits: String = lower[at..]
+            .chars()
+            .take_while(|c| c.is_ascii_digit() || *c == '-')
+            .collect();
+        let mut parts = digits.split('-').filter_map(|n| n.parse::<usize>().ok());
+        let start = parts.next()?;
+        Some((start, parts.next().unwrap_or(start).max(start)))
+    });
+    Some((kind, location))
+}
+
+/// Parses `My Clippings.txt`. Notes are attached to the highlight they were
+/// typed on, which ends at the note's location; bookmarks are skipped.
+pub fn parse_kindle_clippings(content: &str) -> Vec<Clipping> {
+    let mut clippings: Vec<(Clipping, Option<(usize, usize)>)> = Vec::new();
+    let content = content.replace("\r\n", "\n");
+
+    for entry in content.split(KINDLE_SEPARATOR) {
+        let mut lines = entry
+            .lines()
+            .map(|line| line.trim_start_matches('\u{feff}'))
+            .skip_while(|line| line.trim().is_empty());
+        let (Some(title_line), Some(meta_line)) = (lines.next(), lines.next()) else {
+            continue;
+        };
+        let Some((kind, location)) = parse_kindle_meta(meta_line) else {
+            continue;
+        };
+        let body = lines.collect::<Vec<_>>().join("\n").trim().to_string();
+        let (title, authors) = split_kindle_title(title_line);
+
+        match kind {
+            KindleKind::Highlight if !body.is_empty() => {
+                clippings.push((
+                    Clipping {
+                        title,
+                        authors,
+                        text: body,
+                        location: location.map(|(start, _)| start),
+                        ..Clipping::default()
+                    },
+                    location,
+                ));
+            }
+            KindleKind::Note if !body.is_empty() => {
+                let highlight = location.and_then(|(at, _)| {
+                    clippings.iter_mut().rev().find(|(clipping, range)| {
+                        clipping.title == title
+                            && !clipping.text.is_empty()
+                            && range.is_some_and(|(start, end)| (start..=end).contains(&at))
+                    })
+                });
+                if let Some((clipping, _)) = highlight {
+                    clipping.note = Some(match clipping.note.take() {
+                        Some(note) => format!("{note}\n\n{body}"),
+                        None => body,
+                    });
+                } else {
+                    clippings.push((
+                        Clipping {
+                            title,
+                            authors,
+                            note: Some(body),
+                            location: location.map(|(start, _)| start),
+                            ..Clipping::default()
+                        },
+                        location,
+                    ));
+                }
+            }
+            // Empty highlights and notes, and bookmarks
+            KindleKind::Highlight | KindleKind::Note | KindleKind::Bookmark => {}
+        }
+    }
+    clippings
+        .into_iter()
+        .map(|(clipping, _)| clipping)
+        .collect()
+}
+
+const QUOTE_COLUMNS: [&str; 4] = ["highlight", "quote", "text", "excerpt"];
+const NOTE_COLUMNS: [&str; 3] = ["note", "notes", "comment"];
+const LOCATION_COLUMNS: [&str; 4] = ["location", "loc", "position", "page"];
+const TITLE_COLUMNS: [&str; 3] = ["book title", "title", "book"];
+const AUTHOR_COLUMNS: [&str; 4] = ["book author", "author", "authors", "book authors"];
+const TAG_COLUMNS: [&str; 2] = ["tags", "tag"];
+const COLOR_COLUMNS: [&str; 2] = ["color", "colour"];
+
+/// Parses a CSV with a header row. Only a quote column is required; the others
+/// are found by their usual names in any case, e.g. Readwise's `Highlight`,
+/// `Book Title`, `Book Author`, `Note`, `Location` and `Tags`.
+pub fn parse_csv_clippings(content: &str) -> Result<Vec<Clipping>, ImportError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(content.trim_start_matches('\u{feff}').as_bytes());
+    let headers: Vec<String> = reader
+        .headers()?
+        .iter()
+        .map(|h| h.trim().to_lowercase())
+        .collect();
+    let column = |names: &[&str]| {
+        names
+            .iter()
+            .find_map(|name| headers.iter().position(|h| h == name))
+    };
+    let quote = column(&QUOTE_COLUMNS).ok_or(ImportError::MissingQuoteColumn)?;
+    let note = column(&NOTE_COLUMNS);
+    let location = column(&LOCATION_COLUMNS);
+    let title = column(&TITLE_COLUMNS);
+    let authors = column(&AUTHOR_COLUMNS);
+    let tags = column(&TAG_COLUMNS);
+    let color = column(&COLOR_COLUMNS);
+
+    let mut clippings = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let field = |index: Option<usize>| {
+            index
+                .and_then(|i| record.get(i))
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        };
+        let (text, note) = (field(Some(quote)), field(note));
+        if text.is_none() && note.is_none() {
+            continue;
+        }
+        clippings.push(Clipping {
+            title: field(title).unwrap_or_default().to_string(),
+            authors: field(authors).unwrap_or_default().to_string(),
+            text: text.unwrap_or_default().to_string(),
+            note: note.map(str::to_string),
+            location: field(location).and_then(|loc| loc.parse().ok()),
+            tags: field(tags)
+                .map(|tags| {
+                    tags.split([',', ';'])
+                        .map(str::trim)
+                        .filter(|tag| !tag.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            color: field(color).map(str::to_string),
+        });
+    }
+    Ok(clippings)
+}
+
+// ============================================================================
+// BOOK MATCHING
+// ============================================================================
+
+/// Title words for matching: subtitles, bracketed edition notes, punctuation
+/// and leading articles dropped
+fn title_words(title: &str) -> Vec<String> {
+    let mut main = String::new();
+    let mut depth = 0usize;
+    for c in title.chars() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            ':' | ';' if depth == 0 => break,
+            _ if depth == 0 => main.push(c),
+            _ => {}
+        }
+    }
+    let mut words: Vec<String> = main
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    if words.len() > 1 && TITLE_STOP_WORDS.contains(&words[0].as_str()) {
+        words.remove(0);
+    }
+    words
+}
+
+/// Name parts long enough to tell authors apart, in any order, so that
+/// "Austen, Jane" and "Jane Austen" match
+fn author_names(authors: &str) -> HashSet<String> {
+    authors
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| part.chars().count() > 2)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// The library book a clipping's title and author refer to. Titles must agree
+/// word for word, or one be the start of the other; the author decides between
+/// several such books and rules a single one out only when it shares no name.
+pub fn match_book(title: &str, authors: &str, books: &[BookTitle]) -> Option<i64> {
+    let wanted = title_words(title);
+    if wanted.is_empty() {
+        return None;
+    }
+    let scored: Vec<(&BookTitle, bool)> = books
+        .iter()
+        .filter_map(|book| {
+            let words = title_words(&book.title);
+            let shorter = wanted.len().min(words.len());
+            (!words.is_empty() && wanted[..shorter] == words[..shorter])
+                .then_some((book, words == wanted))
+        })
+        .collect();
+
+    let names = author_names(authors);
+    let by_author =
+        |book: &BookTitle| names.is_empty() || !author_names(&book.authors).is_disjoint(&names);
+    let best = scored
+        .iter()
+        .filter(|(book, _)| by_author(book))
+        .max_by_key(|(book, exact)| (*exact, std::cmp::Reverse(book.id.get())));
+    match best {
+        Some((book, _)) => Some(book.id.get()),
+        None if scored.len() == 1 && names.is_empty() => Some(scored[0].0.id.get()),
+        None => None,
+    }
+}
+
+// ============================================================================
+// IMPORT
+// ============================================================================
+
+/// Clippings grouped by the library book they belong to
+#[derive(Debug, Default)]
+pub struct ImportPlan {
+    pub books: Vec<(i64, Vec<Clipping>)>,
+    pub report: ImportReport,
+}
+
+/// Matches clippings to books, or puts them all in `book_id` when given
+pub async fn plan_import(
+    pool: &Pool<Postgres>,
+    clippings: Vec<Clipping>,
+    book_id: Option<i64>,
+) -> Result<ImportPlan, DbError> {
+    let books = if book_id.is_some() {
+        Vec::new()
+    } else {
+        db::list_book_titles(pool).await?
+    };
+    let mut plan = ImportPlan::default();
+    let mut by_book: HashMap<i64, Vec<Clipping>> = HashMap::new();
+    let mut titles: HashMap<(String, String), Option<i64>> = HashMap::new();
+
+    for clipping in clippings {
+        if clipping.text.trim().is_empty() {
+            plan.report
+                .unmatch(vec![clipping], UnmatchedReason::NoQuote);
+            continue;
+        }
+        let matched = book_id.or_else(|| {
+            *titles
+                .entry((clipping.title.clone(), clipping.authors.clone()))
+                .or_insert_with(|| match_book(&clipping.title, &clipping.authors, &books))
+        });
+        match matched {
+            Some(id) => by_book.entry(id).or_default().push(clipping),
+            None => plan
+                .report
+                .unmatch(vec![clipping], UnmatchedReason::BookNotFound),
+        }
+    }
+    plan.books = by_book.into_iter().collect();
+    plan.books.sort_by_key(|(id, _)| *id);
+    Ok(plan)
+}
+
+/// Locates each clipping in the book's text and creates its highlight,
+/// skipping ones already highlighted at the same place
+pub async fn import_book_clippings(
+    pool: &Pool<Postgres>,
+    text: &BookText,
+    book_id: i64,
+    clippings: Vec<Clipping>,
+    report: &mut ImportReport,
+) -> Result<(), DbError> {
+    let mut existing: HashSet<(i64, i64)> =
+        db::list_highlights(pool, book_id, &HighlightFilter::default())
+            .await?
+            .into_iter()
+            .filter_map(|highlight| Some((highlight.text_start?, highlight.text_end?)))
+            .collect();
+
+    let anchorer = Anchorer::new(text);
+    for clipping in clippings {
+        let hint = clipping.location.map(|loc| loc * CHARS_PER_LOCATION);
+        let Some(selectors) = anchorer.anchor(&clipping.text, "", "", hint) else {
+            report.unmatch(vec![clipping], UnmatchedReason::QuoteNotFound);
+            continue;
+        };
+        let range = (
+            i64::try_from(selectors.start).unwrap_or(i64::MAX),
+            i64::try_from(selectors.end).unwrap_or(i64::MAX),
+        );
+        if !existing.insert(range) {
+            report.duplicates += 1;
+            continue;
+        }
+
+        let tags: Vec<Tag> = clipping
+            .tags
+            .iter()
+            .filter_map(|tag| Tag::new(tag).ok())
+            .collect();
+        let highlight = NewHighlight {
+            book_id,
+            text: char_slice(&text.text, selectors.start, selectors.end).to_string(),
+            note: clipping.note,
+            color: clipping
+                .color
+                .as_deref()
+                .and_then(|color| Color::new(color).ok()),
+            selectors: Some(selectors),
+            ..NewHighlight::default()
+        };
+        anchoring::create_highlight(pool, &highlight, &tags).await?;
+        report.imported += 1;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::BookId;
+
+    const CLIPPINGS: &str = "\u{feff}Pride and Prejudice (Austen, Jane)\r\n\
+        - Your Highlight on page 1 | Location 5-6 | Added on Sunday, March 3, 2019 10:15:23 AM\r\n\
+        \r\n\
+        It is a truth universally acknowledged\r\n\
+        ==========\r\n\
+        Pride and Prejudice (Austen, Jane)\r\n\
+        - Your Note on page 1 | Location 6 | Added on Sunday, March 3, 2019 10:16:00 AM\r\n\
+        \r\n\
+        Famous opening\r\n\
+        ==========\r\n\
+        Pride and Prejudice (Austen, Jane)\r\n\
+        - Your Bookmark on page 3 | Location 40 | Added on Sunday, March 3, 2019 10:20:00 AM\r\n\
+        \r\n\
+        \r\n\
+        ==========\r\n\
+        Frankenstein (Penguin Classics) (Mary Shelley)\r\n\
+        - Your Note at location 120 | Added on Monday, March 4, 2019 9:00:00 PM\r\n\
+        \r\n\
+        Check this later\r\n\
+        ==========\r\n";
+
+    fn book(id: i64, title: &str, authors: &str) -> BookTitle {
+        BookTitle {
+            id: BookId::new(id),
+            title: title.to_string(),
+            authors: authors.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_kindle_clippings() {
+        let clippings = parse_kindle_clippings(CLIPPINGS);
+        assert_eq!(clippings.len(), 2);
+        assert_eq!(clippings[0].title, "Pride and Prejudice");
+        assert_eq!(clippings[0].authors, "Austen, Jane");
+        assert_eq!(clippings[0].text, "It is a truth universally acknowledged");
+        assert_eq!(clippings[0].note.as_deref(), Some("Famous opening"));
+        assert_eq!(clippings[0].location, Some(5));
+
+        // A note on no highlight is kept so it can be reported
+        assert_eq!(clippings[1].title, "Frankenstein (Penguin Classics)");
+        assert_eq!(clippings[1].authors, "Mary Shelley");
+        assert!(clippings[1].text.is_empty());
+        assert_eq!(clippings[1].location, Some(120));
+    }
+
+    #[test]
+    fn test_parse_csv_clippings() {
+        let csv = "Highlight,Book Title,Book Author,Note,Location,Tags\n\
+                   \"It was the best of times\",A Tale of Two Cities,Charles Dickens,,12,\"vocabulary, opening\"\n\
+                   ,,,,,\n";
+        let clippings = parse_csv_clippings(csv).unwrap();
+        assert_eq!(clippings.len(), 1);
+        assert_eq!(clippings[0].text, "It was the best of times");
+        assert_eq!(clippings[0].title, "A Tale of Two Cities");
+        assert_eq!(clippings[0].note, None);
+        assert_eq!(clippings[0].location, Some(12));
+        assert_eq!(clippings[0].tags, vec!["vocabulary", "opening"]);
+
+        assert!(matches!(
+            parse_csv_clippings("Title,Note\nX,Y\n"),
+            Err(ImportError::MissingQuoteColumn)
+        ));
+    }
+
+    #[test]
+    fn test_match_book() {
+        let books = [
+            book(1, "Pride and Prejudice", "Austen, Jane"),
+            book(
+                2,
+                "Frankenstein; Or, The Modern Prometheus",
+                "Shelley, Mary Wollstonecraft",
+            ),
+            book(3, "The Iliad", "Homer"),
+            book(4, "The Iliad", "Homer; Pope, Alexander"),
+        ];
+        assert_eq!(
+            match_book("Pride and Prejudice", "Jane Austen", &books),
+            Some(1)
+        );
+        assert_eq!(
+            match_book("Frankenstein (Penguin Classics)", "Mary Shelley", &books),
+            Some(2)
+        );
+        assert_eq!(match_book("Iliad", "Alexander Pope", &books), Some(4));
+        assert_eq!(
+            match_book("Pride and Prejudice", "Seth Grahame-Smith", &books),
+            None
+        );
+        assert_eq!(match_book("Emma", "Jane Austen", &books), None);
+    }
+}
@@ -1,15 +1,50 @@
+mod anchoring;
+mod audiobook;
+mod backup;
+mod batch;
 mod books;
+mod catalog;
+mod covers;
 mod db;
+mod export;
 mod gutendex;
+mod history;
+mod import;
+mod library;
+mod locator;
+mod mirror;
+mod normalize;
+mod play;
 mod pocket;
+mod reading;
+mod sync;
+mod text;
+mod tts;
 mod types;
+mod updates;
+mod wav;
 
 use anyhow::Context;
-use db::{Book, BookChatThread, BookMessage, BookPosition, Highlight, HighlightMessage};
-use pocket::{SidecarState, SidecarStatus};
+use batch::CatalogDownloads;
+use db::{
+    Book, BookChatThread, BookListFilter, BookMessage, BookPosition, Bookmark, CastMember, Catalog,
+    Collection, Highlight, HighlightFilter, HighlightMessage, HighlightSort, HighlightTagCount,
+    LexiconEntry, LibraryHighlight, NewHighlight, PositionEntry, PurgedTrash, ReadingSession,
+    TagCount, TrashItem,
+};
+use gutendex::GutendexClient;
+use mirror::GutenbergMirror;
+use normalize::{NormalizeOptions, Normalizer, SpeechPart};
+use pocket::SidecarState;
 use sqlx::{Pool, Postgres};
+use std::collections::HashMap;
 use std::fs;
+use std::str::FromStr;
+use std::sync::Arc;
+use sync::SyncClient;
 use tauri::{AppHandle, Manager, State};
+use tts::TtsClient;
+use types::{Color, HighlightStyle, ReadingStatus, SettingKey, Tag, TrashKind};
 
 /// Helper to convert `anyhow::Result` to Tauri-compatible Result<T, String>
 fn cmd<T>(result: anyhow::Result<T>) -> Result<T, String> {
@@ -22,33 +57,206 @@ async fn db_init(_pool: State<'_, Pool<Postgres>>) -> Result<(), String> {
     Ok(())
 }
 
+async fn catalog_source(pool: &Pool<Postgres>) -> anyhow::Result<gutendex::CatalogSource> {
+    let source = setting_or(pool, SettingKey::CatalogSource, "gutendex").await?;
+    if source.trim().eq_ignore_ascii_case("local") {
+        return Ok(gutendex::CatalogSource::Local);
+    }
+    let offline = setting_or(pool, SettingKey::GutendexOffline, "false").await?;
+    Ok(if offline.trim().eq_ignore_ascii_case("true") {
+        gutendex::CatalogSource::Offline
+    } else {
+        gutendex::CatalogSource::Online
+    })
+}
+
+async fn gutendex_base_url(pool: &Pool<Postgres>) -> anyhow::Result<reqwest::Url> {
+    let value = setting_or(
+        pool,
+        SettingKey::GutendexBaseUrl,
+        gutendex::GUTENDEX_BOOKS_URL,
+    )
+    .await?;
+    gutendex::parse_base_url(&value).context("reading the Gutendex base URL")
+}
+
+async fn gutenberg_mirror(pool: &Pool<Postgres>) -> anyhow::Result<GutenbergMirror> {
+    let value = setting_or(pool, SettingKey::GutenbergMirror, "").await?;
+    GutenbergMirror::parse(&value).context("reading the Gutenberg mirror")
+}
+
 #[tauri::command]
 async fn gutendex_shakespeare_page(
     _app_handle: AppHandle,
-    _pool: State<'_, Pool<Postgres>>,
+    pool: State<'_, Pool<Postgres>>,
+    gutendex_client: State<'_, GutendexClient>,
     page_url: Option<String>,
 ) -> Result<gutendex::GutendexResponse, String> {
     cmd(async {
-        gutendex::search_catalog("shakespeare", page_url, None, None)
-            .await
-            .context("fetching shakespeare page")
+        let source = catalog_source(&pool).await?;
+        let base_url = gutendex_base_url(&pool).await?;
+        gutendex::search_query(
+            &gutendex_client,
+            &pool,
+            source,
+            &base_url,
+            page_url,
+            &gutendex::shakespeare_query(),
+        )
+        .await
+        .context("fetching shakespeare page")
     }
     .await)
 }
 
+#[allow(clippy::too_many_arguments)]
 #[tauri::command]
 async fn gutendex_catalog_page(
     _app_handle: AppHandle,
-    _pool: State<'_, Pool<Postgres>>,
+    pool: State<'_, Pool<Postgres>>,
+    gutendex_client: State<'_, GutendexClient>,
     catalog_key: String,
     page_url: Option<String>,
     search_query: Option<String>,
     topic: Option<String>,
+    query: Option<gutendex::CatalogQuery>,
 ) -> Result<gutendex::GutendexResponse, String> {
     cmd(async {
-        gutendex::search_catalog(&catalog_key, page_url, search_query, topic)
+        let source = catalog_source(&pool).await?;
+        let mut refinement = query.unwrap_or_default();
+        refinement.search = search_query.or(refinement.search);
+        refinement.topic = topic.or(refinement.topic);
+        let base_url = gutendex_base_url(&pool).await?;
+        gutendex::search_catalog(
+            &gutendex_client,
+            &pool,
+            source,
+            &base_url,
+            &catalog_key,
+            page_url,
+            &refinement,
+        )
+        .await
+        .with_context(|| format!("fetching catalog page for {catalog_key}"))
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn list_catalogs(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+) -> Result<Vec<Catalog>, String> {
+    cmd(async {
+        db::list_catalogs(&pool)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("listing catalogs")
+    }
+    .await)
+}
+
+/// Creates a catalog; the key defaults to a slug of the name
+#[tauri::command]
+async fn create_catalog(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    name: String,
+    key: Option<String>,
+    query: gutendex::CatalogQuery,
+) -> Result<Catalog, String> {
+    cmd(async {
+        if name.trim().is_empty() {
+            anyhow::bail!("Catalog name cannot be empty");
+        }
+        let key = gutendex::catalog_key_for(key.as_deref().unwrap_or(&name));
+        if key.is_empty() {
+            anyhow::bail!("Catalog key cannot be empty");
+        }
+        db::create_catalog(&pool, &key, name.trim(), &query)
+            .await
+            .map_err(anyhow::Error::from)
+            .with_context(|| format!("creating catalog {key}"))
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn update_catalog(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    catalog_id: i64,
+    name: String,
+    query: gutendex::CatalogQuery,
+) -> Result<Catalog, String> {
+    cmd(async {
+        if name.trim().is_empty() {
+            anyhow::bail!("Catalog name cannot be empty");
+        }
+        db::update_catalog(&pool, catalog_id, name.trim(), &query)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("updating catalog")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn delete_catalog(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    catalog_id: i64,
+) -> Result<(), String> {
+    cmd(async {
+        db::delete_catalog(&pool, catalog_id)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("deleting catalog")
+    }
+    .await)
+}
+
+/// Imports `pg_catalog.csv` or the RDF catalog (tarball or extracted directory)
+/// for offline catalog search
+#[tauri::command]
+async fn import_gutenberg_catalog(
+    app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    path: String,
+) -> Result<catalog::CatalogImportSummary, String> {
+    cmd(async {
+        catalog::import_catalog(&app_handle, &pool, std::path::PathBuf::from(&path))
+            .await
+            .with_context(|| format!("importing Gutenberg catalog from {path}"))
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn count_local_catalog_works(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+) -> Result<i64, String> {
+    cmd(async {
+        db::count_catalog_works(&pool)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("counting local catalog works")
+    }
+    .await)
+}
+
+/// Drops all cached Gutendex responses; returns how many were removed
+#[tauri::command]
+async fn clear_gutendex_cache(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+) -> Result<u64, String> {
+    cmd(async {
+        db::clear_http_cache(&pool)
             .await
-            .with_context(|| format!("fetching catalog page for {catalog_key}"))
+            .map_err(anyhow::Error::from)
+            .context("clearing Gutendex cache")
     }
     .await)
 }
@@ -68,256 +276,1378 @@ async fn list_books(
 }
 
 #[tauri::command]
-async fn get_book(
+async fn list_books_filtered(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    filter: BookListFilter,
+) -> Result<Vec<library::BookSummary>, String> {
+    cmd(async {
+        db::list_books_filtered(&pool, &filter)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("listing filtered books from database")
+    }
+    .await)
+}
+
+/// One page of lightweight library rows; pass `next_cursor` back for the next
+#[tauri::command]
+async fn query_books(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    filter: library::BookFilter,
+    sort: Option<library::BookQuerySort>,
+    descending: Option<bool>,
+    cursor: Option<String>,
+    limit: Option<i64>,
+) -> Result<library::BookPage, String> {
+    cmd(async {
+        let sort = sort.unwrap_or_default();
+        let cursor = cursor
+            .as_deref()
+            .map(|c| library::BookCursor::decode(c, sort))
+            .transpose()?;
+        db::query_books(
+            &pool,
+            &filter,
+            sort,
+            descending.unwrap_or(false),
+            cursor.as_ref(),
+            library::page_size(limit),
+        )
+        .await
+        .map_err(anyhow::Error::from)
+        .context("querying library books")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn set_book_status(
     _app_handle: AppHandle,
     pool: State<'_, Pool<Postgres>>,
     book_id: i64,
-) -> Result<Book, String> {
+    status: Option<ReadingStatus>,
+) -> Result<(), String> {
     cmd(async {
-        db::get_book(&pool, book_id)
+        db::set_book_status(&pool, book_id, status)
             .await
             .map_err(anyhow::Error::from)
-            .with_context(|| format!("getting book {book_id} from database"))
+            .context("setting reading status")
     }
     .await)
 }
 
-#[allow(clippy::too_many_arguments)]
 #[tauri::command]
-async fn download_gutenberg_mobi(
-    app_handle: AppHandle,
+async fn list_collections(
+    _app_handle: AppHandle,
     pool: State<'_, Pool<Postgres>>,
-    gutenberg_id: i64,
-    title: String,
-    authors: String,
-    publication_year: Option<i32>,
-    cover_url: Option<String>,
-    mobi_url: String,
-) -> Result<i64, String> {
+) -> Result<Vec<Collection>, String> {
+    cmd(async {
+        db::list_collections(&pool)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("listing collections")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn list_book_collections(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    book_id: i64,
+) -> Result<Vec<Collection>, String> {
+    cmd(async {
+        db::list_book_collections(&pool, book_id)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("listing book collections")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn create_collection(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    name: String,
+) -> Result<Collection, String> {
+    cmd(async {
+        if name.trim().is_empty() {
+            anyhow::bail!("Collection name cannot be empty");
+        }
+        db::create_collection(&pool, name.trim())
+            .await
+            .map_err(anyhow::Error::from)
+            .with_context(|| format!("creating collection {}", name.trim()))
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn rename_collection(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    collection_id: i64,
+    name: String,
+) -> Result<Collection, String> {
+    cmd(async {
+        if name.trim().is_empty() {
+            anyhow::bail!("Collection name cannot be empty");
+        }
+        db::rename_collection(&pool, collection_id, name.trim())
+            .await
+            .map_err(anyhow::Error::from)
+            .context("renaming collection")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn delete_collection(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    collection_id: i64,
+) -> Result<(), String> {
+    cmd(async {
+        db::delete_collection(&pool, collection_id)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("deleting collection")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn add_book_to_collection(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    collection_id: i64,
+    book_id: i64,
+) -> Result<(), String> {
+    cmd(async {
+        db::add_book_to_collection(&pool, collection_id, book_id)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("adding book to collection")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn remove_book_from_collection(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    collection_id: i64,
+    book_id: i64,
+) -> Result<(), String> {
+    cmd(async {
+        db::remove_book_from_collection(&pool, collection_id, book_id)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("removing book from collection")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn list_tags(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+) -> Result<Vec<TagCount>, String> {
+    cmd(async {
+        db::list_tags(&pool)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("listing tags")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn list_book_tags(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    book_id: i64,
+) -> Result<Vec<Tag>, String> {
+    cmd(async {
+        db::list_book_tags(&pool, book_id)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("listing book tags")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn add_book_tag(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    book_id: i64,
+    tag: String,
+) -> Result<Tag, String> {
+    cmd(async {
+        let tag = Tag::new(&tag)?;
+        db::add_book_tag(&pool, book_id, &tag)
+            .await
+            .map_err(anyhow::Error::from)
+            .with_context(|| format!("tagging book with {}", tag.as_str()))?;
+        Ok(tag)
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn remove_book_tag(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    book_id: i64,
+    tag: String,
+) -> Result<(), String> {
+    cmd(async {
+        db::remove_book_tag(&pool, book_id, &Tag::new(&tag)?)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("removing book tag")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn rename_tag(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    from: String,
+    to: String,
+) -> Result<Tag, String> {
+    cmd(async {
+        let (from, to) = (Tag::new(&from)?, Tag::new(&to)?);
+        db::rename_tag(&pool, &from, &to)
+            .await
+            .map_err(anyhow::Error::from)
+            .with_context(|| format!("renaming tag {}", from.as_str()))?;
+        Ok(to)
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn delete_tag(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    tag: String,
+) -> Result<(), String> {
+    cmd(async {
+        db::delete_tag(&pool, &Tag::new(&tag)?)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("deleting tag")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn get_book(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    book_id: i64,
+) -> Result<Book, String> {
+    cmd(async {
+        db::get_book(&pool, book_id)
+            .await
+            .map_err(anyhow::Error::from)
+            .with_context(|| format!("getting book {book_id} from database"))
+    }
+    .await)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[tauri::command]
+async fn download_gutenberg_mobi(
+    app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    gutenberg_id: i64,
+    title: String,
+    authors: String,
+    publication_year: Option<i32>,
+    cover_url: Option<String>,
+    mobi_url: String,
+    languages: Option<Vec<String>>,
+) -> Result<i64, String> {
+    cmd(async {
+        let mirror = gutenberg_mirror(&pool).await?;
+        let download = books::GutenbergDownload {
+            gutenberg_id,
+            title,
+            authors,
+            publication_year,
+            cover_url,
+            mobi_url,
+            languages: languages.unwrap_or_default(),
+        };
+        books::download_gutenberg_book(&app_handle, &pool, &mirror, &download)
+            .await
+            .map_err(anyhow::Error::from)
+            .with_context(|| format!("downloading book {gutenberg_id} ({})", download.title))
+    }
+    .await)
+}
+
+/// Downloads every book in a catalog that isn't in the library yet
+#[tauri::command]
+async fn download_catalog(
+    app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    gutendex_client: State<'_, GutendexClient>,
+    downloads: State<'_, CatalogDownloads>,
+    request: batch::CatalogDownloadRequest,
+) -> Result<batch::CatalogDownloadSummary, String> {
+    cmd(async {
+        let base_url = gutendex_base_url(&pool).await?;
+        let mirror = gutenberg_mirror(&pool).await?;
+        let ctx = batch::CatalogDownloadContext {
+            app_handle: &app_handle,
+            pool: &pool,
+            client: &gutendex_client,
+            source: catalog_source(&pool).await?,
+            base_url: &base_url,
+            mirror: &mirror,
+        };
+        batch::download_catalog(&ctx, &downloads, &request)
+            .await
+            .map_err(anyhow::Error::from)
+            .with_context(|| format!("downloading catalog {}", request.catalog_key))
+    }
+    .await)
+}
+
+/// Stops a running catalog download after its current book
+#[tauri::command]
+async fn cancel_catalog_download(
+    _app_handle: AppHandle,
+    downloads: State<'_, CatalogDownloads>,
+) -> Result<bool, String> {
+    Ok(downloads.cancel())
+}
+
+/// Re-checks every library book against its source and re-extracts new editions
+#[tauri::command]
+async fn check_for_updates(
+    app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+) -> Result<updates::UpdateReport, String> {
+    cmd(async {
+        let mirror = gutenberg_mirror(&pool).await?;
+        updates::check_for_updates(&app_handle, &pool, &mirror)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("checking library books for updates")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn get_book_html(
+    app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    book_id: i64,
+) -> Result<String, String> {
+    cmd(async {
+        db::touch_book_opened(&pool, book_id)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("recording book open")?;
+        load_book_html(&app_handle, &pool, book_id).await
+    }
+    .await)
+}
+
+/// Returns the book HTML, regenerating it from `mobi_data` when the stored copy
+/// is missing or looks corrupted
+async fn load_book_html(
+    app_handle: &AppHandle,
+    pool: &Pool<Postgres>,
+    book_id: i64,
+) -> anyhow::Result<String> {
+    let book = db::get_book(pool, book_id)
+        .await
+        .map_err(anyhow::Error::from)
+        .with_context(|| format!("getting book metadata for {book_id}"))?;
+
+    if let Some(html) = book.html_content {
+        // Check for issues that might require regeneration
+        let has_invalid_controls = books::has_invalid_controls(html.as_bytes());
+        let needs_regeneration = html.is_empty()
+            || books::looks_like_mojibake(&html)
+            || books::has_many_replacements(&html)
+            || has_invalid_controls;
+
+        if !needs_regeneration {
+            return Ok(html);
+        }
+    }
+
+    // Needs regeneration or initial extraction from stored mobi_data
+    if let Some(ref mobi_bytes) = book.mobi_data {
+        let mobi_bytes_clone = mobi_bytes.clone();
+        let app_handle_clone = app_handle.clone();
+        let gutenberg_id = book.gutenberg_id.get();
+
+        let (html_content, first_image_index) = tauri::async_runtime::spawn_blocking(move || {
+            books::extract_mobi_to_content(&app_handle_clone, gutenberg_id, &mobi_bytes_clone)
+        })
+        .await
+        .context("waiting for extraction thread during regeneration")?
+        .map_err(anyhow::Error::from)
+        .context("regenerating html from mobi data")?;
+
+        db::upsert_book(
+            pool,
+            book.gutenberg_id.get(),
+            &book.title,
+            &book.authors,
+            book.publication_year,
+            book.cover_url.as_deref(),
+            Some(mobi_bytes),
+            Some(&html_content),
+            first_image_index,
+        )
+        .await
+        .map_err(anyhow::Error::from)
+        .context("updating book record after regeneration")?;
+
+        // Highlights were anchored in the old HTML
+        let html = html_content.clone();
+        let text = tauri::async_runtime::spawn_blocking(move || text::html_to_book_text(&html))
+            .await
+            .context("waiting for text extraction thread")?;
+        if let Err(e) = anchoring::reanchor_book(pool, book_id, &text).await {
+            println!("[Backend] Failed to re-anchor highlights for book {book_id}: {e}");
+        }
+
+        return Ok(html_content);
+    }
+
+    anyhow::bail!("Book has no HTML content or MOBI data available");
+}
+
+/// Loads the book HTML and converts it to plain text with a chapter outline
+async fn load_book_text(
+    app_handle: &AppHandle,
+    pool: &Pool<Postgres>,
+    book_id: i64,
+) -> anyhow::Result<text::BookText> {
+    let html = load_book_html(app_handle, pool, book_id).await?;
+    tauri::async_runtime::spawn_blocking(move || text::html_to_book_text(&html))
+        .await
+        .context("waiting for text extraction thread")
+}
+
+/// `load_book_text` through the cache, for commands called often on one book
+async fn cached_book_text(
+    app_handle: &AppHandle,
+    pool: &Pool<Postgres>,
+    cache: &text::BookTextCache,
+    book_id: i64,
+) -> anyhow::Result<Arc<text::BookText>> {
+    let content_hash = db::get_book_content_hash(pool, book_id)
+        .await
+        .map_err(anyhow::Error::from)
+        .with_context(|| format!("getting the content hash of book {book_id}"))?;
+    if let Some(text) = cache.get(book_id, content_hash.as_deref()).await {
+        return Ok(text);
+    }
+    let text = Arc::new(load_book_text(app_handle, pool, book_id).await?);
+    cache.insert(book_id, content_hash, Arc::clone(&text)).await;
+    Ok(text)
+}
+
+#[tauri::command]
+async fn list_book_chapters(
+    app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    book_id: i64,
+) -> Result<Vec<text::Chapter>, String> {
+    cmd(async { Ok(load_book_text(&app_handle, &pool, book_id).await?.chapters) }.await)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[tauri::command]
+async fn export_audiobook(
+    app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    tts_client: State<'_, TtsClient>,
+    book_id: i64,
+    start_chapter: Option<usize>,
+    end_chapter: Option<usize>,
+    voice_id: Option<String>,
+    multi_voice: Option<bool>,
+    format: Option<audiobook::AudiobookFormat>,
+    output_path: Option<String>,
+) -> Result<audiobook::AudiobookExport, String> {
+    cmd(async {
+        let book = db::get_book(&pool, book_id)
+            .await
+            .map_err(anyhow::Error::from)
+            .with_context(|| format!("getting book metadata for {book_id}"))?;
+        let book_text = load_book_text(&app_handle, &pool, book_id).await?;
+
+        let reader = match voice_id {
+            Some(voice) => voice,
+            None => setting_or(&pool, SettingKey::PocketVoiceId, tts::DEFAULT_VOICE).await?,
+        };
+        let narrator = setting_or(&pool, SettingKey::TtsNarratorVoice, &reader).await?;
+        let cast: HashMap<String, String> = db::list_book_cast(&pool, book_id)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("loading book cast")?
+            .into_iter()
+            .map(|member| (member.character, member.voice_id))
+            .collect();
+        let multi_voice = multi_voice.unwrap_or(true) && !cast.is_empty();
+        let voices = audiobook::NarrationVoices {
+            reader,
+            narrator,
+            cast,
+        };
+        let normalizer = load_tts_normalizer(&pool).await?;
+
+        let chapters = {
+            let title = book.title.clone();
+            tauri::async_runtime::spawn_blocking(move || {
+                let play = multi_voice.then(|| play::detect_play_structure(&book_text));
+                let narration = audiobook::Narration {
+                    voices: &voices,
+                    normalizer: &normalizer,
+                    play: play.as_ref().filter(|play| play.is_play),
+                };
+                audiobook::plan_chapters(&book_text, &title, start_chapter, end_chapter, &narration)
+            })
+            .await
+            .context("waiting for narration planning thread")?
+            .map_err(anyhow::Error::from)
+            .context("planning narration")?
+        };
+
+        audiobook::export_audiobook(
+            &app_handle,
+            &tts_client,
+            audiobook::ExportRequest {
+                book_id,
+                gutenberg_id: book.gutenberg_id.get(),
+                book_title: &book.title,
+                chapters,
+                format: format.unwrap_or_default(),
+                output_path: output_path.map(std::path::PathBuf::from),
+            },
+        )
+        .await
+        .map_err(anyhow::Error::from)
+        .with_context(|| format!("exporting audiobook for {}", book.title))
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn get_book_cover(
+    app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    book_id: i64,
+    thumbnail: bool,
+) -> Result<String, String> {
+    cmd(async {
+        let book = db::get_book_cover(&pool, book_id)
+            .await
+            .map_err(anyhow::Error::from)
+            .with_context(|| format!("getting book metadata for {book_id}"))?;
+        let mirror = gutenberg_mirror(&pool).await?;
+        covers::get_book_cover(&app_handle, &pool, &mirror, &book, thumbnail)
+            .await
+            .map_err(anyhow::Error::from)
+            .with_context(|| format!("loading the cover for {}", book.title))
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn get_book_image_data(
+    app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    book_id: i64,
+    relative_index: i32,
+) -> Result<String, String> {
+    cmd(async {
+        let book = db::get_book(&pool, book_id)
+            .await
+            .map_err(anyhow::Error::from)
+            .with_context(|| format!("getting book metadata for {book_id}"))?;
+        let first_image_index = book
+            .first_image_index
+            .ok_or_else(|| anyhow::anyhow!("Book has no image index"))?;
+
+        let absolute_index = usize::try_from(first_image_index + relative_index - 1).unwrap_or(0);
+
+        let path = books::get_book_asset_path(&app_handle, book.gutenberg_id.get(), absolute_index)
+            .map_err(anyhow::Error::from)
+            .with_context(|| format!("finding asset path for index {relative_index}"))?;
+
+        let bytes = tauri::async_runtime::spawn_blocking({
+            let path = path.clone();
+            move || fs::read(&path).map(|b| (b, path))
+        })
+        .await
+        .context("waiting for image read thread")?
+        .with_context(|| format!("reading image from {}", path.display()))?;
+
+        let (bytes, path) = bytes;
+        let extension = path.extension().and_then(|s| s.to_str()).unwrap_or("jpeg");
+        let mime = match extension {
+            "png" => "image/png",
+            "gif" => "image/gif",
+            _ => "image/jpeg",
+        };
+
+        let b64 = data_encoding::BASE64.encode(&bytes);
+        Ok(format!("data:{mime};base64,{b64}"))
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn list_tts_voices(tts_client: State<'_, TtsClient>) -> Result<Vec<tts::TtsVoice>, String> {
+    cmd(async {
+        tts_client
+            .voices()
+            .await
+            .map_err(anyhow::Error::from)
+            .context("fetching voices from TTS server")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn detect_play_structure(
+    app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    book_id: i64,
+) -> Result<play::PlayStructure, String> {
+    cmd(async {
+        let book_text = load_book_text(&app_handle, &pool, book_id).await?;
+        tauri::async_runtime::spawn_blocking(move || play::detect_play_structure(&book_text))
+            .await
+            .context("waiting for play detection thread")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn list_book_cast(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    book_id: i64,
+) -> Result<Vec<CastMember>, String> {
+    cmd(async {
+        db::list_book_cast(&pool, book_id)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("listing book cast")
+    }
+    .await)
+}
+
+/// Assigns a voice to a character; `None` removes the assignment
+#[tauri::command]
+async fn set_book_cast_voice(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    book_id: i64,
+    character: String,
+    voice_id: Option<String>,
+) -> Result<(), String> {
+    cmd(async {
+        match voice_id {
+            Some(voice_id) => db::set_book_cast_voice(&pool, book_id, &character, &voice_id)
+                .await
+                .map(|_| ())
+                .map_err(anyhow::Error::from)
+                .with_context(|| format!("assigning voice to {character}")),
+            None => db::delete_book_cast_member(&pool, book_id, &character)
+                .await
+                .map_err(anyhow::Error::from)
+                .with_context(|| format!("removing voice for {character}")),
+        }
+    }
+    .await)
+}
+
+/// Detects the play's characters and gives every uncast speaker a voice from the
+/// TTS server, keeping existing assignments
+#[tauri::command]
+async fn auto_cast_book(
+    app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    tts_client: State<'_, TtsClient>,
+    book_id: i64,
+) -> Result<Vec<CastMember>, String> {
+    cmd(async {
+        let book_text = load_book_text(&app_handle, &pool, book_id).await?;
+        let play =
+            tauri::async_runtime::spawn_blocking(move || play::detect_play_structure(&book_text))
+                .await
+                .context("waiting for play detection thread")?;
+        if !play.is_play {
+            anyhow::bail!("Book does not look like a play");
+        }
+
+        let voices: Vec<String> = tts_client
+            .voices()
+            .await
+            .map_err(anyhow::Error::from)
+            .context("fetching voices from TTS server")?
+            .into_iter()
+            .map(|voice| voice.id)
+            .collect();
+        let reader = setting_or(&pool, SettingKey::PocketVoiceId, tts::DEFAULT_VOICE).await?;
+        let narrator = setting_or(&pool, SettingKey::TtsNarratorVoice, &reader).await?;
+        let existing: HashMap<String, String> = db::list_book_cast(&pool, book_id)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("loading book cast")?
+            .into_iter()
+            .map(|member| (member.character, member.voice_id))
+            .collect();
+
+        for (character, voice_id) in play::auto_assign_voices(&play, &existing, &voices, &narrator)
+        {
+            db::set_book_cast_voice(&pool, book_id, &character, &voice_id)
+                .await
+                .map_err(anyhow::Error::from)
+                .with_context(|| format!("assigning voice to {character}"))?;
+        }
+
+        db::list_book_cast(&pool, book_id)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("listing book cast")
+    }
+    .await)
+}
+
+async fn setting_or(
+    pool: &Pool<Postgres>,
+    key: SettingKey,
+    default: &str,
+) -> anyhow::Result<String> {
+    Ok(db::get_setting(pool, key.as_str())
+        .await
+        .map_err(anyhow::Error::from)
+        .with_context(|| format!("reading setting '{key}'"))?
+        .filter(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| default.to_string()))
+}
+
+/// Parses a setting, falling back to the type's default when unset or invalid
+async fn parsed_setting<T: FromStr + Default>(
+    pool: &Pool<Postgres>,
+    key: SettingKey,
+) -> anyhow::Result<T> {
+    Ok(db::get_setting(pool, key.as_str())
+        .await
+        .map_err(anyhow::Error::from)
+        .with_context(|| format!("reading setting '{key}'"))?
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_default())
+}
+
+/// Builds the TTS normalizer from the stored lexicon and narration settings
+async fn load_tts_normalizer(pool: &Pool<Postgres>) -> anyhow::Result<Normalizer> {
+    let entries = db::list_lexicon_entries(pool)
+        .await
+        .map_err(anyhow::Error::from)
+        .context("loading TTS lexicon")?;
+    let options = NormalizeOptions {
+        stage_directions: parsed_setting(pool, SettingKey::TtsStageDirections).await?,
+        speaker_labels: parsed_setting(pool, SettingKey::TtsSpeakerLabels).await?,
+    };
+    Ok(Normalizer::new(
+        entries.into_iter().map(|e| (e.term, e.replacement)),
+        options,
+    ))
+}
+
+#[tauri::command]
+async fn normalize_tts_text(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    text: String,
+) -> Result<Vec<SpeechPart>, String> {
+    cmd(async {
+        let normalizer = load_tts_normalizer(&pool).await?;
+        Ok(normalizer.normalize(&text))
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn list_tts_lexicon(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+) -> Result<Vec<LexiconEntry>, String> {
+    cmd(async {
+        db::list_lexicon_entries(&pool)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("listing TTS lexicon")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn create_tts_lexicon_entry(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    term: String,
+    replacement: String,
+) -> Result<LexiconEntry, String> {
+    cmd(async {
+        if term.trim().is_empty() {
+            anyhow::bail!("Lexicon term cannot be empty");
+        }
+        db::create_lexicon_entry(&pool, term.trim(), &replacement)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("creating lexicon entry")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn update_tts_lexicon_entry(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    entry_id: i64,
+    term: String,
+    replacement: String,
+) -> Result<LexiconEntry, String> {
+    cmd(async {
+        if term.trim().is_empty() {
+            anyhow::bail!("Lexicon term cannot be empty");
+        }
+        db::update_lexicon_entry(&pool, entry_id, term.trim(), &replacement)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("updating lexicon entry")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn delete_tts_lexicon_entry(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    entry_id: i64,
+) -> Result<(), String> {
+    cmd(async {
+        db::delete_lexicon_entry(&pool, entry_id)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("deleting lexicon entry")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn get_book_position(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    book_id: i64,
+) -> Result<Option<BookPosition>, String> {
+    cmd(async {
+        db::get_book_position(&pool, book_id)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("getting book position")
+    }
+    .await)
+}
+
+/// Resolves the reader's position against the book text and saves it. Large
+/// jumps leave the previous position in the book's history.
+#[tauri::command]
+async fn set_book_position(
+    app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    text_cache: State<'_, text::BookTextCache>,
+    book_id: i64,
+    position: locator::LocatorInput,
+) -> Result<locator::Locator, String> {
+    cmd(async {
+        let text = cached_book_text(&app_handle, &pool, &text_cache, book_id).await?;
+        let locator = locator::resolve(&text, &position)?;
+        history::save_position(&pool, &text, book_id, &locator)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("saving book position")?;
+        Ok(locator)
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn list_bookmarks(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    book_id: i64,
+) -> Result<Vec<Bookmark>, String> {
+    cmd(async {
+        db::list_bookmarks(&pool, book_id)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("listing bookmarks")
+    }
+    .await)
+}
+
+/// Blank labels are stored as no label
+fn bookmark_label(label: Option<&str>) -> Option<&str> {
+    label.map(str::trim).filter(|label| !label.is_empty())
+}
+
+#[tauri::command]
+async fn create_bookmark(
+    app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    text_cache: State<'_, text::BookTextCache>,
+    book_id: i64,
+    position: locator::LocatorInput,
+    label: Option<String>,
+    color: Option<String>,
+) -> Result<Bookmark, String> {
+    cmd(async {
+        let color = color.as_deref().map(Color::new).transpose()?;
+        let text = cached_book_text(&app_handle, &pool, &text_cache, book_id).await?;
+        let locator = locator::resolve(&text, &position)?;
+        db::create_bookmark(
+            &pool,
+            book_id,
+            &locator,
+            bookmark_label(label.as_deref()),
+            color.as_ref(),
+        )
+        .await
+        .map_err(anyhow::Error::from)
+        .context("creating bookmark")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn update_bookmark(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    bookmark_id: i64,
+    label: Option<String>,
+    color: Option<String>,
+) -> Result<Bookmark, String> {
+    cmd(async {
+        let color = color.as_deref().map(Color::new).transpose()?;
+        db::update_bookmark(
+            &pool,
+            bookmark_id,
+            bookmark_label(label.as_deref()),
+            color.as_ref(),
+        )
+        .await
+        .map_err(anyhow::Error::from)
+        .context("updating bookmark")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn delete_bookmark(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    bookmark_id: i64,
+) -> Result<(), String> {
+    cmd(async {
+        db::delete_bookmark(&pool, bookmark_id)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("deleting bookmark")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn list_position_history(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    book_id: i64,
+) -> Result<Vec<PositionEntry>, String> {
+    cmd(async {
+        db::list_position_history(&pool, book_id)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("listing position history")
+    }
+    .await)
+}
+
+async fn restore_entry(
+    app_handle: &AppHandle,
+    pool: &Pool<Postgres>,
+    text_cache: &text::BookTextCache,
+    entry: &PositionEntry,
+) -> anyhow::Result<locator::Locator> {
+    let text = cached_book_text(app_handle, pool, text_cache, entry.book_id.get()).await?;
+    history::restore_position(pool, &text, entry)
+        .await
+        .with_context(|| format!("restoring position {}", entry.id))
+}
+
+/// Moves the reading position back to a history entry
+#[tauri::command]
+async fn restore_book_position(
+    app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    text_cache: State<'_, text::BookTextCache>,
+    entry_id: i64,
+) -> Result<locator::Locator, String> {
+    cmd(async {
+        let entry = db::get_position_entry(&pool, entry_id)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("getting position history entry")?;
+        restore_entry(&app_handle, &pool, &text_cache, &entry).await
+    }
+    .await)
+}
+
+/// "Where was I": returns to the position before the latest jump, if any
+#[tauri::command]
+async fn jump_back(
+    app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    text_cache: State<'_, text::BookTextCache>,
+    book_id: i64,
+) -> Result<Option<locator::Locator>, String> {
+    cmd(async {
+        let Some(entry) =
+            db::latest_position_entry(&pool, book_id, Some(types::PositionChange::Jump))
+                .await
+                .map_err(anyhow::Error::from)
+                .context("finding last jump")?
+        else {
+            return Ok(None);
+        };
+        Ok(Some(
+            restore_entry(&app_handle, &pool, &text_cache, &entry).await?,
+        ))
+    }
+    .await)
+}
+
+/// Opens a reading session at the reader's position
+#[tauri::command]
+async fn start_reading_session(
+    app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    text_cache: State<'_, text::BookTextCache>,
+    book_id: i64,
+    position: locator::LocatorInput,
+) -> Result<ReadingSession, String> {
+    cmd(async {
+        let text = cached_book_text(&app_handle, &pool, &text_cache, book_id).await?;
+        let locator = locator::resolve(&text, &position)?;
+        db::start_reading_session(&pool, book_id, &locator)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("starting reading session")
+    }
+    .await)
+}
+
+async fn record_reading_progress(
+    app_handle: &AppHandle,
+    pool: &Pool<Postgres>,
+    text_cache: &text::BookTextCache,
+    session_id: i64,
+    position: Option<&locator::LocatorInput>,
+    end: bool,
+) -> anyhow::Result<ReadingSession> {
+    let session = db::get_reading_session(pool, session_id)
+        .await
+        .with_context(|| format!("getting reading session {session_id}"))?;
+    let text = cached_book_text(app_handle, pool, text_cache, session.book_id.get()).await?;
+    reading::record_progress(pool, &text, &session, position, end)
+        .await
+        .context("recording reading progress")
+}
+
+/// Extends a reading session to the reader's position. The returned session
+/// replaces the old one after a long pause, so callers keep its id.
+#[tauri::command]
+async fn reading_session_heartbeat(
+    app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    text_cache: State<'_, text::BookTextCache>,
+    session_id: i64,
+    position: locator::LocatorInput,
+) -> Result<ReadingSession, String> {
+    cmd(record_reading_progress(
+        &app_handle,
+        &pool,
+        &text_cache,
+        session_id,
+        Some(&position),
+        false,
+    )
+    .await)
+}
+
+#[tauri::command]
+async fn end_reading_session(
+    app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    text_cache: State<'_, text::BookTextCache>,
+    session_id: i64,
+    position: Option<locator::LocatorInput>,
+) -> Result<ReadingSession, String> {
+    cmd(record_reading_progress(
+        &app_handle,
+        &pool,
+        &text_cache,
+        session_id,
+        position.as_ref(),
+        true,
+    )
+    .await)
+}
+
+#[tauri::command]
+async fn list_reading_sessions(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    book_id: i64,
+    limit: Option<i64>,
+) -> Result<Vec<ReadingSession>, String> {
+    cmd(async {
+        db::list_reading_sessions(&pool, book_id, library::page_size(limit))
+            .await
+            .map_err(anyhow::Error::from)
+            .context("listing reading sessions")
+    }
+    .await)
+}
+
+/// Reading time per day, words per minute, books finished and streaks.
+/// `from`/`to` are `YYYY-MM-DD` days in `timezone` (default UTC).
+#[tauri::command]
+async fn get_reading_stats(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    from: Option<chrono::NaiveDate>,
+    to: Option<chrono::NaiveDate>,
+    timezone: Option<String>,
+) -> Result<reading::ReadingStats, String> {
     cmd(async {
-        println!(
-            "[Backend] Starting download for book {} from {}",
-            gutenberg_id, mobi_url
-        );
-        let mobi_bytes = books::download_mobi_bytes(&app_handle, gutenberg_id, mobi_url)
+        let timezone = timezone.unwrap_or_else(|| "UTC".to_string());
+        reading::reading_stats(&pool, &timezone, from, to)
             .await
             .map_err(anyhow::Error::from)
-            .with_context(|| format!("downloading book {gutenberg_id} ({title})"))?;
-        println!(
-            "[Backend] Downloaded {} bytes for book {}",
-            mobi_bytes.len(),
-            gutenberg_id
-        );
+            .with_context(|| format!("computing reading stats in {timezone}"))
+    }
+    .await)
+}
 
-        let (html_content, first_image_index) = {
-            let app_handle = app_handle.clone();
-            let mobi_bytes = mobi_bytes.clone();
-            tauri::async_runtime::spawn_blocking(move || {
-                println!(
-                    "[Backend] Extracting MOBI content for book {}",
-                    gutenberg_id
-                );
-                books::extract_mobi_to_content(&app_handle, gutenberg_id, &mobi_bytes)
-            })
+/// Writes the whole library to a backup archive at `output_path`
+#[tauri::command]
+async fn export_library(
+    app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    output_path: String,
+) -> Result<backup::BackupSummary, String> {
+    cmd(async {
+        let books_dir = books::books_dir(&app_handle).context("locating the books directory")?;
+        backup::export_library(&pool, &books_dir, std::path::Path::new(&output_path))
             .await
-            .context("waiting for mobi extraction thread")?
             .map_err(anyhow::Error::from)
-            .context("extracting mobi to html")?
-        };
-        println!(
-            "[Backend] Extracted HTML ({} chars) for book {}",
-            html_content.len(),
-            gutenberg_id
-        );
-
-        println!("[Backend] Upserting book {} to database", gutenberg_id);
-
-        db::upsert_book(
-            &pool,
-            gutenberg_id,
-            &title,
-            &authors,
-            publication_year,
-            cover_url.as_deref(),
-            Some(&mobi_bytes),
-            Some(&html_content),
-            first_image_index,
-        )
-        .await
-        .map_err(anyhow::Error::from)
-        .context("saving book to database")
+            .with_context(|| format!("backing up the library to {output_path}"))
     }
     .await)
 }
 
+/// Merges a backup archive into the library
 #[tauri::command]
-async fn get_book_html(
+async fn import_library(
     app_handle: AppHandle,
     pool: State<'_, Pool<Postgres>>,
-    book_id: i64,
-) -> Result<String, String> {
+    path: String,
+) -> Result<backup::RestoreReport, String> {
     cmd(async {
-        let book = db::get_book(&pool, book_id)
+        let books_dir = books::books_dir(&app_handle).context("locating the books directory")?;
+        let report = backup::import_library(&pool, &books_dir, std::path::Path::new(&path))
             .await
             .map_err(anyhow::Error::from)
-            .with_context(|| format!("getting book metadata for {book_id}"))?;
-
-        if let Some(html) = book.html_content {
-            // Check for issues that might require regeneration
-            let has_invalid_controls = books::has_invalid_controls(html.as_bytes());
-            let needs_regeneration = html.is_empty()
-                || books::looks_like_mojibake(&html)
-                || books::has_many_replacements(&html)
-                || has_invalid_controls;
-
-            if !needs_regeneration {
-                return Ok(html);
+            .with_context(|| format!("restoring the library from {path}"))?;
+        // The merged books may be a different edition than the backup's
+        for &book_id in &report.merged_book_ids {
+            let reanchored = async {
+                let text = load_book_text(&app_handle, &pool, book_id).await?;
+                anchoring::reanchor_book(&pool, book_id, &text)
+                    .await
+                    .map_err(anyhow::Error::from)
+            }
+            .await;
+            if let Err(e) = reanchored {
+                println!("[Backup] Failed to re-anchor highlights for book {book_id}: {e}");
             }
         }
+        Ok(report)
+    }
+    .await)
+}
 
-        // Needs regeneration or initial extraction from stored mobi_data
-        if let Some(ref mobi_bytes) = book.mobi_data {
-            let mobi_bytes_clone = mobi_bytes.clone();
-            let app_handle_clone = app_handle.clone();
-            let gutenberg_id = book.gutenberg_id.get();
-
-            let (html_content, first_image_index) =
-                tauri::async_runtime::spawn_blocking(move || {
-                    books::extract_mobi_to_content(
-                        &app_handle_clone,
-                        gutenberg_id,
-                        &mobi_bytes_clone,
-                    )
-                })
-                .await
-                .context("waiting for extraction thread during regeneration")?
-                .map_err(anyhow::Error::from)
-                .context("regenerating html from mobi data")?;
-
-            db::upsert_book(
-                &pool,
-                book.gutenberg_id.get(),
-                &book.title,
-                &book.authors,
-                book.publication_year,
-                book.cover_url.as_deref(),
-                Some(mobi_bytes),
-                Some(&html_content),
-                first_image_index,
-            )
+/// Pulls other devices' positions, bookmarks, highlights and chats from the
+/// configured remote, merges them, and pushes this device's changes
+#[tauri::command]
+async fn sync_now(
+    pool: State<'_, Pool<Postgres>>,
+    sync_client: State<'_, SyncClient>,
+) -> Result<sync::SyncReport, String> {
+    cmd(async {
+        let remote = setting_or(&pool, SettingKey::SyncRemote, "").await?;
+        if remote.is_empty() {
+            anyhow::bail!("No sync remote configured");
+        }
+        let token = db::get_setting(&pool, SettingKey::SyncToken.as_str())
             .await
             .map_err(anyhow::Error::from)
-            .context("updating book record after regeneration")?;
-
-            return Ok(html_content);
-        }
-
-        anyhow::bail!("Book has no HTML content or MOBI data available");
+            .context("reading the sync token")?;
+        let remote = sync::SyncRemote::parse(&remote, token)?;
+        sync::sync(&pool, &sync_client, &remote)
+            .await
+            .map_err(anyhow::Error::from)
+            .with_context(|| format!("syncing with {}", remote.id()))
     }
     .await)
 }
 
+/// Moves a book to the trash; its file and assets stay until it's purged
 #[tauri::command]
-async fn get_book_image_data(
-    app_handle: AppHandle,
+async fn trash_book(
+    _app_handle: AppHandle,
     pool: State<'_, Pool<Postgres>>,
     book_id: i64,
-    relative_index: i32,
-) -> Result<String, String> {
+) -> Result<(), String> {
     cmd(async {
-        let book = db::get_book(&pool, book_id)
+        db::trash_book(&pool, book_id)
             .await
             .map_err(anyhow::Error::from)
-            .with_context(|| format!("getting book metadata for {book_id}"))?;
-        let first_image_index = book
-            .first_image_index
-            .ok_or_else(|| anyhow::anyhow!("Book has no image index"))?;
-
-        let absolute_index = usize::try_from(first_image_index + relative_index - 1).unwrap_or(0);
-
-        let path = books::get_book_asset_path(&app_handle, book.gutenberg_id.get(), absolute_index)
-            .map_err(anyhow::Error::from)
-            .with_context(|| format!("finding asset path for index {relative_index}"))?;
-
-        let bytes = tauri::async_runtime::spawn_blocking({
-            let path = path.clone();
-            move || fs::read(&path).map(|b| (b, path))
-        })
-        .await
-        .context("waiting for image read thread")?
-        .with_context(|| format!("reading image from {}", path.display()))?;
-
-        let (bytes, path) = bytes;
-        let extension = path.extension().and_then(|s| s.to_str()).unwrap_or("jpeg");
-        let mime = match extension {
-            "png" => "image/png",
-            "gif" => "image/gif",
-            _ => "image/jpeg",
-        };
-
-        let b64 = data_encoding::BASE64.encode(&bytes);
-        Ok(format!("data:{mime};base64,{b64}"))
+            .context("moving book to the trash")
     }
     .await)
 }
 
 #[tauri::command]
-async fn get_book_position(
+async fn list_trash(
     _app_handle: AppHandle,
     pool: State<'_, Pool<Postgres>>,
-    book_id: i64,
-) -> Result<Option<BookPosition>, String> {
+) -> Result<Vec<TrashItem>, String> {
     cmd(async {
-        db::get_book_position(&pool, book_id)
+        db::list_trash(&pool)
             .await
             .map_err(anyhow::Error::from)
-            .context("getting book position")
+            .context("listing the trash")
     }
     .await)
 }
 
 #[tauri::command]
-async fn set_book_position(
+async fn restore_from_trash(
     _app_handle: AppHandle,
     pool: State<'_, Pool<Postgres>>,
-    book_id: i64,
-    cfi: String,
+    kind: TrashKind,
+    id: i64,
 ) -> Result<(), String> {
     cmd(async {
-        db::set_book_position(&pool, book_id, &cfi)
+        db::restore_trash_item(&pool, kind, id)
             .await
             .map_err(anyhow::Error::from)
-            .context("saving book position")
+            .with_context(|| format!("restoring {kind} {id} from the trash"))
     }
     .await)
 }
 
+/// Deletes a trash entry for good
 #[tauri::command]
-async fn hard_delete_book(
+async fn purge_from_trash(
     app_handle: AppHandle,
     pool: State<'_, Pool<Postgres>>,
-    book_id: i64,
-) -> Result<(), String> {
+    kind: TrashKind,
+    id: i64,
+) -> Result<PurgedTrash, String> {
     cmd(async {
-        let book = db::get_book(&pool, book_id)
+        let purged = db::purge_trash_item(&pool, kind, id)
             .await
             .map_err(anyhow::Error::from)
-            .with_context(|| format!("getting book metadata for deletion: {book_id}"))?;
+            .with_context(|| format!("purging {kind} {id} from the trash"))?;
+        delete_purged_assets(&app_handle, &purged).await?;
+        Ok(purged)
+    }
+    .await)
+}
 
-        // Still delete assets on disk (images)
-        let app_handle_clone = app_handle.clone();
-        tauri::async_runtime::spawn_blocking(move || {
-            let _ = books::delete_book_assets(&app_handle_clone, book.gutenberg_id.get());
-        })
+#[tauri::command]
+async fn empty_trash(
+    app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+) -> Result<PurgedTrash, String> {
+    cmd(purge_expired_trash(&app_handle, &pool, 0).await)
+}
+
+/// Trashed items are purged this many days after deletion unless the
+/// `trash_retention_days` setting says otherwise
+const DEFAULT_TRASH_RETENTION_DAYS: i32 = 30;
+
+async fn trash_retention_days(pool: &Pool<Postgres>) -> anyhow::Result<i32> {
+    let value = setting_or(
+        pool,
+        SettingKey::TrashRetentionDays,
+        &DEFAULT_TRASH_RETENTION_DAYS.to_string(),
+    )
+    .await?;
+    Ok(value.trim().parse().unwrap_or(DEFAULT_TRASH_RETENTION_DAYS))
+}
+
+async fn purge_expired_trash(
+    app_handle: &AppHandle,
+    pool: &Pool<Postgres>,
+    retention_days: i32,
+) -> anyhow::Result<PurgedTrash> {
+    let purged = db::purge_expired_trash(pool, retention_days)
         .await
-        .context("waiting for asset deletion thread")?;
+        .map_err(anyhow::Error::from)
+        .context("purging the trash")?;
+    delete_purged_assets(app_handle, &purged).await?;
+    Ok(purged)
+}
 
-        db::hard_delete_book(&pool, book_id)
-            .await
-            .map_err(anyhow::Error::from)
-            .context("deleting book from database")
-    }
-    .await)
+/// Removes the images of purged books from disk
+async fn delete_purged_assets(app_handle: &AppHandle, purged: &PurgedTrash) -> anyhow::Result<()> {
+    let app_handle = app_handle.clone();
+    let gutenberg_ids = purged.gutenberg_ids.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        for gutenberg_id in gutenberg_ids {
+            if let Err(e) = books::delete_book_assets(&app_handle, gutenberg_id.get()) {
+                println!("[Backend] Failed to delete assets of purged book {gutenberg_id}: {e}");
+            }
+        }
+    })
+    .await
+    .context("waiting for asset deletion thread")
 }
 
 #[tauri::command]
@@ -328,6 +1658,24 @@ async fn set_setting(
     value: String,
 ) -> Result<(), String> {
     cmd(async {
+        // Reject mirror/endpoint values that would only fail later, mid-download
+        match key.parse::<SettingKey>() {
+            Ok(SettingKey::GutendexBaseUrl) if !value.trim().is_empty() => {
+                gutendex::parse_base_url(&value)?;
+            }
+            Ok(SettingKey::GutenbergMirror) => {
+                GutenbergMirror::parse(&value)?;
+            }
+            Ok(SettingKey::SyncRemote) if !value.trim().is_empty() => {
+                sync::SyncRemote::parse(&value, None)?;
+            }
+            Ok(SettingKey::TrashRetentionDays)
+                if !value.trim().parse::<i32>().is_ok_and(|days| days >= 0) =>
+            {
+                anyhow::bail!("Trash retention must be a whole number of days, not '{value}'");
+            }
+            _ => {}
+        }
         db::set_setting(&pool, &key, &value)
             .await
             .map_err(anyhow::Error::from)
@@ -356,9 +1704,10 @@ async fn list_highlights(
     _app_handle: AppHandle,
     pool: State<'_, Pool<Postgres>>,
     book_id: i64,
+    filter: Option<HighlightFilter>,
 ) -> Result<Vec<Highlight>, String> {
     cmd(async {
-        db::list_highlights(&pool, book_id)
+        db::list_highlights(&pool, book_id, &filter.unwrap_or_default())
             .await
             .map_err(anyhow::Error::from)
             .context("listing highlights")
@@ -366,10 +1715,37 @@ async fn list_highlights(
     .await)
 }
 
+/// Highlights from every book, a page at a time
+#[tauri::command]
+async fn list_all_highlights(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    filter: Option<HighlightFilter>,
+    sort: Option<HighlightSort>,
+    descending: Option<bool>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+) -> Result<Vec<LibraryHighlight>, String> {
+    cmd(async {
+        db::list_all_highlights(
+            &pool,
+            &filter.unwrap_or_default(),
+            sort.unwrap_or_default(),
+            descending.unwrap_or(true),
+            library::page_size(limit),
+            offset.unwrap_or(0).max(0),
+        )
+        .await
+        .map_err(anyhow::Error::from)
+        .context("listing highlights across the library")
+    }
+    .await)
+}
+
 #[allow(clippy::too_many_arguments)]
 #[tauri::command]
 async fn create_highlight(
-    _app_handle: AppHandle,
+    app_handle: AppHandle,
     pool: State<'_, Pool<Postgres>>,
     book_id: i64,
     start_path: String,
@@ -378,21 +1754,245 @@ async fn create_highlight(
     end_offset: i64,
     text: String,
     note: Option<String>,
+    color: Option<String>,
+    style: Option<HighlightStyle>,
+    tags: Option<Vec<String>>,
+    quote_prefix: Option<String>,
+    quote_suffix: Option<String>,
 ) -> Result<Highlight, String> {
     cmd(async {
-        db::create_highlight(
-            &pool,
+        let color = color.as_deref().map(Color::new).transpose()?;
+        let tags = tags
+            .unwrap_or_default()
+            .iter()
+            .map(String::as_str)
+            .map(Tag::new)
+            .collect::<Result<Vec<_>, _>>()?;
+        let book_text = load_book_text(&app_handle, &pool, book_id).await?;
+        // The text the reader saw around the selection picks the right
+        // occurrence of repeated quotes
+        let selectors = anchoring::anchor(
+            &book_text,
+            &text,
+            quote_prefix.as_deref().unwrap_or_default(),
+            quote_suffix.as_deref().unwrap_or_default(),
+            None,
+        );
+        let highlight = NewHighlight {
             book_id,
-            &start_path,
+            start_path,
             start_offset,
-            &end_path,
+            end_path,
             end_offset,
-            &text,
-            note.as_deref(),
+            text,
+            note,
+            color,
+            style: style.unwrap_or_default(),
+            selectors,
+        };
+        anchoring::create_highlight(&pool, &highlight, &tags)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("creating highlight")
+    }
+    .await)
+}
+
+/// Matches a book's highlights back into its text, e.g. for highlights made
+/// before text selectors were stored
+#[tauri::command]
+async fn reanchor_highlights(
+    app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    book_id: i64,
+) -> Result<anchoring::ReanchorReport, String> {
+    cmd(async {
+        let text = load_book_text(&app_handle, &pool, book_id).await?;
+        anchoring::reanchor_book(&pool, book_id, &text)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("re-anchoring highlights")
+    }
+    .await)
+}
+
+/// Writes highlights, one book's or the whole library's, with their notes and
+/// discussions to `output_path`, picked by the frontend with the save dialog
+#[tauri::command]
+async fn export_highlights(
+    app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    book_id: Option<i64>,
+    filter: Option<HighlightFilter>,
+    format: Option<export::ExportFormat>,
+    output_path: String,
+) -> Result<export::ExportSummary, String> {
+    cmd(async {
+        let highlights = db::list_export_highlights(&pool, book_id, &filter.unwrap_or_default())
+            .await
+            .map_err(anyhow::Error::from)
+            .context("listing highlights to export")?;
+
+        // Highlights come grouped by book
+        let mut book_ids: Vec<i64> = highlights
+            .iter()
+            .map(|h| h.highlight.book_id.get())
+            .collect();
+        book_ids.dedup();
+        let mut texts = HashMap::new();
+        for id in book_ids {
+            match load_book_text(&app_handle, &pool, id).await {
+                Ok(text) => {
+                    texts.insert(id, text);
+                }
+                Err(e) => {
+                    println!("[Backend] Exporting book {id} without chapters: {e:#}");
+                }
+            }
+        }
+
+        export::export_highlights(
+            &pool,
+            highlights,
+            &texts,
+            format.unwrap_or_default(),
+            std::path::Path::new(&output_path),
+        )
+        .await
+        .map_err(anyhow::Error::from)
+        .with_context(|| format!("exporting highlights to {output_path}"))
+    }
+    .await)
+}
+
+/// Imports highlights from a Kindle `My Clippings.txt` or a CSV at `path`,
+/// into matching library books or all into `book_id`
+#[tauri::command]
+async fn import_annotations(
+    app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    path: String,
+    source: Option<import::ImportSource>,
+    book_id: Option<i64>,
+) -> Result<import::ImportReport, String> {
+    cmd(async {
+        let bytes = tokio::fs::read(&path)
+            .await
+            .with_context(|| format!("reading {path}"))?;
+        let content = String::from_utf8_lossy(&bytes);
+        let clippings = match source.unwrap_or_else(|| import::ImportSource::from_path(&path)) {
+            import::ImportSource::Kindle => import::parse_kindle_clippings(&content),
+            import::ImportSource::Csv => import::parse_csv_clippings(&content)
+                .map_err(anyhow::Error::from)
+                .with_context(|| format!("parsing {path}"))?,
+        };
+
+        let import::ImportPlan { books, mut report } =
+            import::plan_import(&pool, clippings, book_id)
+                .await
+                .map_err(anyhow::Error::from)
+                .context("matching clippings to books")?;
+        for (id, clippings) in books {
+            let text = match load_book_text(&app_handle, &pool, id).await {
+                Ok(text) => text,
+                Err(e) => {
+                    println!("[Import] Can't import clippings into book {id}: {e:#}");
+                    report.unmatch(clippings, import::UnmatchedReason::TextUnavailable);
+                    continue;
+                }
+            };
+            import::import_book_clippings(&pool, &text, id, clippings, &mut report)
+                .await
+                .map_err(anyhow::Error::from)
+                .with_context(|| format!("importing clippings into book {id}"))?;
+        }
+
+        println!(
+            "[Import] Imported {} highlights from {path} ({} duplicates, {} unmatched)",
+            report.imported,
+            report.duplicates,
+            report.unmatched.len()
+        );
+        Ok(report)
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn set_highlight_style(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    highlight_id: i64,
+    color: Option<String>,
+    style: Option<HighlightStyle>,
+) -> Result<Highlight, String> {
+    cmd(async {
+        let color = color.as_deref().map(Color::new).transpose()?;
+        db::set_highlight_style(
+            &pool,
+            highlight_id,
+            color.as_ref(),
+            style.unwrap_or_default(),
         )
         .await
         .map_err(anyhow::Error::from)
-        .context("creating highlight")
+        .context("updating highlight style")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn list_highlight_tags(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+) -> Result<Vec<HighlightTagCount>, String> {
+    cmd(async {
+        db::list_highlight_tags(&pool)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("listing highlight tags")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn add_highlight_tag(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    highlight_id: i64,
+    tag: String,
+) -> Result<Highlight, String> {
+    cmd(async {
+        let tag = Tag::new(&tag)?;
+        db::add_highlight_tag(&pool, highlight_id, &tag)
+            .await
+            .map_err(anyhow::Error::from)
+            .with_context(|| format!("tagging highlight with {}", tag.as_str()))?;
+        db::get_highlight(&pool, highlight_id)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("reloading highlight")
+    }
+    .await)
+}
+
+#[tauri::command]
+async fn remove_highlight_tag(
+    _app_handle: AppHandle,
+    pool: State<'_, Pool<Postgres>>,
+    highlight_id: i64,
+    tag: String,
+) -> Result<Highlight, String> {
+    cmd(async {
+        let tag = Tag::new(&tag)?;
+        db::remove_highlight_tag(&pool, highlight_id, &tag)
+            .await
+            .map_err(anyhow::Error::from)
+            .with_context(|| format!("removing tag {} from highlight", tag.as_str()))?;
+        db::get_highlight(&pool, highlight_id)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("reloading highlight")
     }
     .await)
 }
@@ -675,30 +2275,118 @@ pub fn run() {
                 tauri::Error::Io(std::io::Error::other(format!("Database pool failed: {e}")))
             })?;
             app.manage(pool.clone());
-            
-            let pocket_state = SidecarState::new();
-            let _ = pocket::start_pocket_sidecar_internal(app.app_handle(), &pocket_state);
-            app.manage(pocket_state);
-            
+            app.manage(TtsClient::new());
+            app.manage(SyncClient::new());
+            app.manage(GutendexClient::new());
+            app.manage(CatalogDownloads::new());
+            app.manage(text::BookTextCache::new());
+
+            let purge_handle = app.app_handle().clone();
+            let purge_pool = pool.clone();
+            tauri::async_runtime::spawn(async move {
+                let purged = async {
+                    let days = trash_retention_days(&purge_pool).await?;
+                    purge_expired_trash(&purge_handle, &purge_pool, days).await
+                }
+                .await;
+                if let Err(e) = purged {
+                    println!("[Backend] Purging expired trash failed: {e:#}");
+                }
+            });
+
+            app.manage(SidecarState::new());
+            let app_handle = app.app_handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let launcher = Arc::new(pocket::PocketLauncher::new(app_handle.clone()));
+                let state: State<'_, SidecarState> = app_handle.state();
+                let _ = state.start(launcher).await;
+            });
+
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             db_init,
             gutendex_shakespeare_page,
             gutendex_catalog_page,
+            clear_gutendex_cache,
+            list_catalogs,
+            create_catalog,
+            update_catalog,
+            delete_catalog,
+            import_gutenberg_catalog,
+            count_local_catalog_works,
             download_gutenberg_mobi,
+            download_catalog,
+            cancel_catalog_download,
+            check_for_updates,
             list_books,
+            list_books_filtered,
+            query_books,
+            set_book_status,
+            list_collections,
+            list_book_collections,
+            create_collection,
+            rename_collection,
+            delete_collection,
+            add_book_to_collection,
+            remove_book_from_collection,
+            list_tags,
+            list_book_tags,
+            add_book_tag,
+            remove_book_tag,
+            rename_tag,
+            delete_tag,
             get_book,
             get_book_html,
+            get_book_cover,
             get_book_image_data,
+            list_book_chapters,
+            export_audiobook,
+            normalize_tts_text,
+            list_tts_lexicon,
+            create_tts_lexicon_entry,
+            update_tts_lexicon_entry,
+            delete_tts_lexicon_entry,
+            list_tts_voices,
+            detect_play_structure,
+            list_book_cast,
+            set_book_cast_voice,
+            auto_cast_book,
             get_book_position,
             set_book_position,
-            hard_delete_book,
+            list_position_history,
+            restore_book_position,
+            jump_back,
+            list_bookmarks,
+            create_bookmark,
+            update_bookmark,
+            delete_bookmark,
+            start_reading_session,
+            reading_session_heartbeat,
+            end_reading_session,
+            list_reading_sessions,
+            get_reading_stats,
+            trash_book,
+            list_trash,
+            restore_from_trash,
+            purge_from_trash,
+            empty_trash,
+            export_library,
+            import_library,
+            sync_now,
             set_setting,
             get_setting,
             list_highlights,
+            list_all_highlights,
             create_highlight,
             update_highlight_note,
+            set_highlight_style,
+            reanchor_highlights,
+            export_highlights,
+            import_annotations,
+            list_highlight_tags,
+            add_highlight_tag,
+            remove_highlight_tag,
             delete_highlight,
             list_highlight_messages,
             add_highlight_message,
@@ -731,19 +2419,5 @@ pub fn run() {
 
 fn stop_sidecar_on_exit(app: &AppHandle) {
     let state: State<'_, SidecarState> = app.state();
-    let mut status = state.status.lock().unwrap();
-    let mut child_guard = state.child.lock().unwrap();
-
-    if let Some(child) = child_guard.take() {
-        println!("[Sidecar] Killing pocket-tts sidecar on exit...");
-        match child {
-            pocket::PocketChild::Sidecar(child) => {
-                let _ = child.kill();
-            }
-            pocket::PocketChild::Process(mut child) => {
-                let _ = child.kill();
-            }
-        }
-    }
-    *status = SidecarStatus::Stopped;
+    tauri::async_runtime::block_on(state.stop());
 }
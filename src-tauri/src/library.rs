@@ -0,0 +1,206 @@
+//! Library queries: typed filters, sorting and keyset pagination
+//!
+//! `query_books` returns summary rows (no MOBI or HTML) a page at a time. Pages
+//! are keyed by the sort value and book id of the last row rather than an
+//! offset, so paging stays cheap and stable in large libraries while books are
+//! added. The cursor is opaque to the frontend and tied to the sort it was
+//! issued for.
+
+use crate::types::{BookId, GutenbergId, ReadingStatus};
+use data_encoding::BASE64URL_NOPAD;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 200;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CursorError {
+    #[error("Malformed page cursor")]
+    Malformed,
+
+    #[error("Page cursor was issued for sorting by {0}")]
+    WrongSort(String),
+}
+
+/// Filters for `query_books`; unset fields match every book
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BookFilter {
+    /// Case-insensitive substring of the authors
+    pub author: Option<String>,
+    /// Case-insensitive substring of the title
+    pub title: Option<String>,
+    pub year_from: Option<i32>,
+    pub year_to: Option<i32>,
+    /// Two-letter language code
+    pub language: Option<String>,
+    pub has_highlights: Option<bool>,
+    pub has_chats: Option<bool>,
+    pub status: Option<ReadingStatus>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BookQuerySort {
+    #[default]
+    Title,
+    Author,
+    PublicationYear,
+    DateAdded,
+    LastRead,
+}
+
+impl BookQuerySort {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Title => "title",
+            Self::Author => "author",
+            Self::PublicationYear => "publication_year",
+            Self::DateAdded => "date_added",
+            Self::LastRead => "last_read",
+        }
+    }
+
+    /// SQL expression the rows are ordered by. Nullable keys are coalesced so
+    /// every row has a comparable value for the cursor.
+    pub const fn sql_key(self) -> &'static str {
+        match self {
+            Self::Title => "lower(b.title)",
+            Self::Author => "lower(b.authors)",
+            Self::PublicationYear => "COALESCE(b.publication_year, -2147483648)",
+            Self::DateAdded => "b.created_at",
+            Self::LastRead => "COALESCE(p.updated_at, '-infinity'::timestamptz)",
+        }
+    }
+
+    /// SQL type a cursor's key is cast back to
+    pub const fn sql_type(self) -> &'static str {
+        match self {
+            Self::Title | Self::Author => "text",
+            Self::PublicationYear => "integer",
+            Self::DateAdded | Self::LastRead => "timestamptz",
+        }
+    }
+}
+
+/// Position after the last row of a page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BookCursor {
+    pub sort: BookQuerySort,
+    /// The row's sort key, as Postgres prints it
+    pub key: String,
+    pub id: i64,
+}
+
+impl BookCursor {
+    pub fn encode(&self) -> String {
+        BASE64URL_NOPAD
+            .encode(format!("{}:{}:{}", self.sort.as_str(), self.id, self.key).as_bytes())
+    }
+
+    /// Decodes a cursor, rejecting one issued for a different sort
+    pub fn decode(cursor: &str, sort: BookQuerySort) -> Result<Self, CursorError> {
+        let bytes = BASE64URL_NOPAD
+            .decode(cursor.as_bytes())
+            .map_err(|_| CursorError::Malformed)?;
+        let text = String::from_utf8(bytes).map_err(|_| CursorError::Malformed)?;
+        let (sort_name, rest) = text.split_once(':').ok_or(CursorError::Malformed)?;
+        let (id, key) = rest.split_once(':').ok_or(CursorError::Malformed)?;
+        if sort_name != sort.as_str() {
+            return Err(CursorError::WrongSort(sort_name.to_string()));
+        }
+        Ok(Self {
+            sort,
+            key: key.to_string(),
+            id: id.parse().map_err(|_| CursorError::Malformed)?,
+        })
+    }
+}
+
+/// Clamps a requested page size to `1..=MAX_PAGE_SIZE`
+pub fn page_size(limit: Option<i64>) -> i64 {
+    limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+}
+
+/// A library book without its content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookSummary {
+    pub id: BookId,
+    pub gutenberg_id: GutenbergId,
+    pub title: String,
+    pub authors: String,
+    pub publication_year: Option<i32>,
+    pub cover_url: Option<String>,
+    pub languages: Vec<String>,
+    pub status: Option<ReadingStatus>,
+    pub created_at: String,
+    /// When the reading position last moved
+    pub last_read_at: Option<String>,
+    pub highlight_count: i64,
+    pub thread_count: i64,
+    /// Share of the book read, 0..1
+    pub progression: Option<f64>,
+    /// Chapter of the reading position
+    pub chapter_title: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookPage {
+    pub books: Vec<BookSummary>,
+    /// Pass back to fetch the next page; `None` on the last page
+    pub next_cursor: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = BookCursor {
+            sort: BookQuerySort::LastRead,
+            key: "2024-03-01 10:15:00.5+00".to_string(),
+            id: 42,
+        };
+        let encoded = cursor.encode();
+        assert!(encoded
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        assert_eq!(
+            BookCursor::decode(&encoded, BookQuerySort::LastRead),
+            Ok(cursor)
+        );
+    }
+
+    #[test]
+    fn test_cursor_rejects_other_sort_and_garbage() {
+        let encoded = BookCursor {
+            sort: BookQuerySort::Title,
+            key: "hamlet: prince of denmark".to_string(),
+            id: 7,
+        }
+        .encode();
+        assert_eq!(
+            BookCursor::decode(&encoded, BookQuerySort::Title)
+                .unwrap()
+                .key,
+            "hamlet: prince of denmark"
+        );
+        assert_eq!(
+            BookCursor::decode(&encoded, BookQuerySort::Author),
+            Err(CursorError::WrongSort("title".to_string()))
+        );
+        assert_eq!(
+            BookCursor::decode("not a cursor!", BookQuerySort::Title),
+            Err(CursorError::Malformed)
+        );
+    }
+
+    #[test]
+    fn test_page_size_clamped() {
+        assert_eq!(page_size(None), DEFAULT_PAGE_SIZE);
+        assert_eq!(page_size(Some(0)), 1);
+        assert_eq!(page_size(Some(10_000)), MAX_PAGE_SIZE);
+    }
+}
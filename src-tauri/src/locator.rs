@@ -0,0 +1,245 @@
+//! Reading positions as text locators
+//!
+//! A position is stored as a chapter index and a char offset into that
+//! chapter's plain text (see `text::html_to_book_text`), with the book-wide
+//! progression and a short quote of the text at that point. Nothing refers to
+//! the reader's DOM, so a position still resolves after the HTML is
+//! regenerated, and the quote lets it be found again when a new edition shifts
+//! the text.
+
+use crate::text::{char_slice, BookText};
+use crate::types::{Cfi, TypeValidationError};
+use serde::{Deserialize, Serialize};
+
+/// Length of the quote kept with each locator
+pub const QUOTE_CHARS: usize = 48;
+
+/// Shortest quote prefix `relocate` will search for
+const MIN_QUOTE_CHARS: usize = 12;
+
+/// A position as reported by the reader. Fields are tried in order: chapter
+/// and offset, then quote (nearest to `progression` when both are given), then
+/// progression alone; with none set the position is the start of the book.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LocatorInput {
+    pub chapter_index: Option<usize>,
+    pub chapter_offset: Option<usize>,
+    pub progression: Option<f64>,
+    pub quote: Option<String>,
+    pub cfi: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Locator {
+    /// `None` when the book has no chapter outline
+    pub chapter_index: Option<usize>,
+    pub chapter_title: Option<String>,
+    /// Chars from the start of the chapter (or of the book, without chapters)
+    pub chapter_offset: usize,
+    /// Share of the book before this point, 0..1
+    pub progression: f64,
+    /// Text starting at this point
+    pub quote: String,
+    pub cfi: Option<Cfi>,
+}
+
+impl Locator {
+    /// The locator for a char offset into the book text
+    #[allow(clippy::cast_precision_loss)]
+    pub fn at_offset(text: &BookText, offset: usize, cfi: Option<Cfi>) -> Self {
+        let offset = offset.min(text.char_len());
+        let chapter = text.chapter_at(offset);
+        let progression = if text.char_len() == 0 {
+            0.0
+        } else {
+            offset as f64 / text.char_len() as f64
+        };
+        Self {
+            chapter_index: chapter.map(|c| c.index),
+            chapter_title: chapter.map(|c| c.title.clone()),
+            chapter_offset: offset - chapter.map_or(0, |c| c.start),
+            progression,
+            quote: char_slice(&text.text, offset, offset + QUOTE_CHARS).to_string(),
+            cfi,
+        }
+    }
+
+    /// Char offset into the book text, clamped to the chapter
+    pub fn offset(&self, text: &BookText) -> usize {
+        chapter_offset(text, self.chapter_index, self.chapter_offset)
+    }
+}
+
+fn chapter_offset(text: &BookText, chapter_index: Option<usize>, offset: usize) -> usize {
+    chapter_index
+        .and_then(|i| text.chapters.get(i))
+        .map_or_else(
+            || offset.min(text.char_len()),
+            |chapter| (chapter.start + offset).min(chapter.end),
+        )
+}
+
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+fn progression_offset(text: &BookText, progression: f64) -> usize {
+    (progression.clamp(0.0, 1.0) * text.char_len() as f64).round() as usize
+}
+
+/// Char offset of the occurrence of `quote` nearest to `near`
+fn find_quote(text: &BookText, quote: &str, near: usize) -> Option<usize> {
+    if quote.trim().is_empty() {
+        return None;
+    }
+    let mut chars_before = 0;
+    let mut last_byte = 0;
+    text.text
+        .match_indices(quote)
+        .map(|(byte, _)| {
+            chars_before += text.text[last_byte..byte].chars().count();
+            last_byte = byte;
+            chars_before
+        })
+        .min_by_key(|offset| offset.abs_diff(near))
+}
+
+/// Resolves a reader-reported position against the book text
+pub fn resolve(text: &BookText, input: &LocatorInput) -> Result<Locator, TypeValidationError> {
+    let cfi = input.cfi.clone().map(Cfi::new).transpose()?;
+    if let Some(index) = input.chapter_index.filter(|i| *i >= text.chapters.len()) {
+        return Err(TypeValidationError::InvalidChapterIndex(
+            index,
+            text.chapters.len(),
+        ));
+    }
+    let hint = input.progression.map(|p| progression_offset(text, p));
+
+    let offset = input.chapter_index.map_or_else(
+        || {
+            input
+                .quote
+                .as_deref()
+                .and_then(|quote| find_quote(text, quote, hint.unwrap_or(0)))
+                .or(hint)
+                .unwrap_or(0)
+        },
+        |index| chapter_offset(text, Some(index), input.chapter_offset.unwrap_or(0)),
+    );
+    Ok(Locator::at_offset(text, offset, cfi))
+}
+
+/// Re-anchors a stored locator after the book text changed, preferring the
+/// quote's nearest occurrence to where the chapter and offset now point. The
+/// quote is shortened step by step in case the text after it was edited.
+pub fn relocate(text: &BookText, locator: &Locator) -> Locator {
+    let estimate = locator.offset(text);
+    let quote_len = locator.quote.chars().count();
+    let offset = std::iter::successors(Some(quote_len), |len| Some(len / 2))
+        .take_while(|len| *len >= MIN_QUOTE_CHARS.min(quote_len).max(1))
+        .find_map(|len| find_quote(text, char_slice(&locator.quote, 0, len), estimate))
+        .unwrap_or(estimate);
+    Locator::at_offset(text, offset, locator.cfi.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::html_to_book_text;
+
+    const PLAY: &str = "<html><body><h2>Act I</h2><p>Enter the ghost. The ghost speaks.</p>\
+                        <h2>Act II</h2><p>Enter Hamlet, reading.</p>\
+                        <h2>Act III</h2><p>To be, or not to be.</p></body></html>";
+
+    #[test]
+    fn test_resolve_chapter_offset() {
+        let text = html_to_book_text(PLAY);
+        let locator = resolve(
+            &text,
+            &LocatorInput {
+                chapter_index: Some(2),
+                chapter_offset: Some(9),
+                cfi: Some("epubcfi(/6/2!/4/10)".to_string()),
+                ..LocatorInput::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(locator.chapter_title.as_deref(), Some("Act III"));
+        assert_eq!(locator.chapter_offset, 9);
+        assert!(locator.quote.starts_with("To be"));
+        assert!(locator.progression > 0.7 && locator.progression < 1.0);
+        assert_eq!(locator.offset(&text), text.chapters[2].start + 9);
+    }
+
+    #[test]
+    fn test_resolve_quote_nearest_progression() {
+        let text = html_to_book_text(PLAY);
+        let input = |progression| LocatorInput {
+            quote: Some("ghost".to_string()),
+            progression: Some(progression),
+            ..LocatorInput::default()
+        };
+        let first = resolve(&text, &input(0.0)).unwrap();
+        let second = resolve(&text, &input(0.4)).unwrap();
+        assert!(first.quote.starts_with("ghost. The"));
+        assert!(second.quote.starts_with("ghost speaks"));
+        assert_eq!(first.chapter_index, Some(0));
+    }
+
+    #[test]
+    fn test_resolve_rejects_invalid_cfi() {
+        let text = html_to_book_text(PLAY);
+        let input = LocatorInput {
+            cfi: Some("/4/10".to_string()),
+            ..LocatorInput::default()
+        };
+        assert!(matches!(
+            resolve(&text, &input),
+            Err(TypeValidationError::InvalidCfiFormat(_))
+        ));
+        let start = resolve(&text, &LocatorInput::default()).unwrap();
+        assert_eq!((start.chapter_index, start.chapter_offset), (Some(0), 0));
+        assert!(start.progression.abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_resolve_rejects_unknown_chapter() {
+        let text = html_to_book_text(PLAY);
+        let input = LocatorInput {
+            chapter_index: Some(3),
+            ..LocatorInput::default()
+        };
+        assert!(matches!(
+            resolve(&text, &input),
+            Err(TypeValidationError::InvalidChapterIndex(3, 3))
+        ));
+    }
+
+    #[test]
+    fn test_relocate_after_new_edition() {
+        let old = html_to_book_text(PLAY);
+        let stored = resolve(
+            &old,
+            &LocatorInput {
+                chapter_index: Some(1),
+                chapter_offset: Some(8),
+                ..LocatorInput::default()
+            },
+        )
+        .unwrap();
+        assert!(stored.quote.starts_with("Enter Hamlet"));
+
+        let new = html_to_book_text(
+            "<html><body><h2>Preface</h2><p>A note on this edition.</p>\
+             <h2>Act I</h2><p>Enter the ghost.</p>\
+             <h2>Act II</h2><p>Enter Hamlet, reading.</p></body></html>",
+        );
+        let moved = relocate(&new, &stored);
+        assert_eq!(moved.chapter_title.as_deref(), Some("Act II"));
+        assert_eq!(moved.chapter_index, Some(2));
+        assert!(moved.quote.starts_with("Enter Hamlet"));
+    }
+}
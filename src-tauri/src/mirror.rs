@@ -0,0 +1,197 @@
+//! Project Gutenberg file mirrors
+//!
+//! Gutenberg asks bulk downloaders to use a mirror rather than gutenberg.org.
+//! A mirror is either another HTTP host with Gutenberg's directory layout or a
+//! local directory holding `cache/epub/{id}/` files (e.g. an rsync of the
+//! `cache/epub` tree). Download URLs from Gutendex or the local catalog are
+//! rewritten onto the mirror; anything we can't map is fetched as-is.
+
+use reqwest::Url;
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum MirrorError {
+    #[error("Invalid Gutenberg mirror: {0}")]
+    Invalid(String),
+
+    #[error("Not in the local mirror: {}", .0.display())]
+    Missing(PathBuf),
+}
+
+/// Where Gutenberg files are downloaded from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum GutenbergMirror {
+    /// Straight from gutenberg.org
+    #[default]
+    Direct,
+    /// Another host with Gutenberg's layout, rooted at this URL
+    Http(Url),
+    /// A local directory containing `cache/epub/{id}/`
+    Local(PathBuf),
+}
+
+/// The resolved location of one download
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MirrorSource {
+    Url(String),
+    File(PathBuf),
+}
+
+impl GutenbergMirror {
+    /// Parses the `gutenberg_mirror` setting: empty for gutenberg.org, an
+    /// `http(s)://` URL, or an absolute directory path (optionally `file://`)
+    pub fn parse(value: &str) -> Result<Self, MirrorError> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Ok(Self::Direct);
+        }
+
+        if value.starts_with("http://") || value.starts_with("https://") {
+            let mut url =
+                Url::parse(value).map_err(|e| MirrorError::Invalid(format!("{value}: {e}")))?;
+            if !url.path().ends_with('/') {
+                let path = format!("{}/", url.path());
+                url.set_path(&path);
+            }
+            return Ok(Self::Http(url));
+        }
+
+        let path = if value.starts_with("file://") {
+            Url::parse(value)
+                .ok()
+                .and_then(|url| url.to_file_path().ok())
+                .ok_or_else(|| MirrorError::Invalid(value.to_string()))?
+        } else {
+            PathBuf::from(value)
+        };
+        if !path.is_absolute() {
+            return Err(MirrorError::Invalid(format!(
+                "{value}: a local mirror must be an absolute path"
+            )));
+        }
+        Ok(Self::Local(path))
+    }
+
+    /// Maps a download URL onto this mirror
+    pub fn resolve(&self, url: &str) -> MirrorSource {
+        let relative = match self {
+            Self::Direct => None,
+            Self::Http(_) | Self::Local(_) => mirror_path(url),
+        };
+        match (self, relative) {
+            (Self::Http(base), Some(relative)) => base.join(&relative).map_or_else(
+                |_| MirrorSource::Url(url.to_string()),
+                |u| MirrorSource::Url(u.to_string()),
+            ),
+            (Self::Local(dir), Some(relative)) => MirrorSource::File(dir.join(relative)),
+            _ => MirrorSource::Url(url.to_string()),
+        }
+    }
+}
+
+pub fn is_gutenberg_url(url: &Url) -> bool {
+    url.host_str()
+        .is_some_and(|h| h == "gutenberg.org" || h.ends_with(".gutenberg.org"))
+}
+
+/// The `cache/epub/{id}/...` path a gutenberg.org download URL is served from.
+/// `/ebooks/{id}.{format}` links are redirects to these generated files.
+fn mirror_path(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    if !is_gutenberg_url(&url) {
+        return None;
+    }
+
+    let path = url.path().trim_start_matches('/');
+    if path.starts_with("cache/epub/") {
+        return Some(path.to_string());
+    }
+
+    let (id, format) = path.strip_prefix("ebooks/")?.split_once('.')?;
+    let id: u64 = id.parse().ok()?;
+    let file = match format {
+        "kf8.images" => format!("pg{id}-images-kf8.mobi"),
+        "mobi.images" => format!("pg{id}-images.mobi"),
+        "epub3.images" => format!("pg{id}-images-3.epub"),
+        "epub.images" => format!("pg{id}-images.epub"),
+        "epub.noimages" => format!("pg{id}.epub"),
+        "html.images" => format!("pg{id}-images.html"),
+        "txt.utf-8" => format!("pg{id}.txt"),
+        "rdf" => format!("pg{id}.rdf"),
+        _ => return None,
+    };
+    Some(format!("cache/epub/{id}/{file}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KF8: &str = "https://www.gutenberg.org/ebooks/1524.kf8.images";
+
+    #[test]
+    fn test_parse_mirror_setting() {
+        assert_eq!(
+            GutenbergMirror::parse("  ").unwrap(),
+            GutenbergMirror::Direct
+        );
+        assert_eq!(
+            GutenbergMirror::parse("https://mirror.example.org/gutenberg").unwrap(),
+            GutenbergMirror::Http(Url::parse("https://mirror.example.org/gutenberg/").unwrap())
+        );
+        assert_eq!(
+            GutenbergMirror::parse("/srv/gutenberg").unwrap(),
+            GutenbergMirror::Local(PathBuf::from("/srv/gutenberg"))
+        );
+        assert_eq!(
+            GutenbergMirror::parse("file:///srv/gutenberg").unwrap(),
+            GutenbergMirror::Local(PathBuf::from("/srv/gutenberg"))
+        );
+        assert!(GutenbergMirror::parse("relative/dir").is_err());
+    }
+
+    #[test]
+    fn test_resolve_onto_mirrors() {
+        assert_eq!(
+            GutenbergMirror::Direct.resolve(KF8),
+            MirrorSource::Url(KF8.to_string())
+        );
+
+        let http = GutenbergMirror::parse("https://mirror.example.org/gutenberg").unwrap();
+        assert_eq!(
+            http.resolve(KF8),
+            MirrorSource::Url(
+                "https://mirror.example.org/gutenberg/cache/epub/1524/pg1524-images-kf8.mobi"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            http.resolve("https://www.gutenberg.org/cache/epub/1524/pg1524.cover.medium.jpg"),
+            MirrorSource::Url(
+                "https://mirror.example.org/gutenberg/cache/epub/1524/pg1524.cover.medium.jpg"
+                    .to_string()
+            )
+        );
+
+        let local = GutenbergMirror::parse("/srv/gutenberg").unwrap();
+        assert_eq!(
+            local.resolve("https://www.gutenberg.org/ebooks/2000.epub3.images"),
+            MirrorSource::File(PathBuf::from(
+                "/srv/gutenberg/cache/epub/2000/pg2000-images-3.epub"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_resolve_leaves_other_urls_alone() {
+        let local = GutenbergMirror::parse("/srv/gutenberg").unwrap();
+        for url in [
+            "https://example.com/ebooks/1524.kf8.images",
+            "https://www.gutenberg.org/ebooks/1524",
+            "https://www.gutenberg.org/ebooks/1524.unknown",
+        ] {
+            assert_eq!(local.resolve(url), MirrorSource::Url(url.to_string()));
+        }
+    }
+}
@@ -0,0 +1,567 @@
+//! Text normalization applied before narration
+//!
+//! Early-modern play texts read verbatim trip up TTS: archaic contractions
+//! ("'tis", "o'er"), speaker labels ("HAM."), bracketed stage directions and Roman
+//! numerals in headings. `Normalizer` rewrites a passage into speakable parts using
+//! the user-editable lexicon stored in the database.
+
+use crate::types::TypeValidationError;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Built-in lexicon seeded into `tts_lexicon` the first time the table is created
+pub const DEFAULT_LEXICON: &[(&str, &str)] = &[
+    ("'tis", "it is"),
+    ("'twas", "it was"),
+    ("'twere", "it were"),
+    ("'twill", "it will"),
+    ("'twould", "it would"),
+    ("'gainst", "against"),
+    ("'mongst", "amongst"),
+    ("o'er", "over"),
+    ("e'er", "ever"),
+    ("ne'er", "never"),
+    ("e'en", "even"),
+    ("ta'en", "taken"),
+    ("thou'rt", "thou art"),
+    ("thou'lt", "thou wilt"),
+    ("i' th'", "in the"),
+    ("i' the", "in the"),
+    ("o' th'", "of the"),
+    ("th'", "the"),
+    ("'em", "them"),
+    ("an't", "an it"),
+    ("is't", "is it"),
+    ("do't", "do it"),
+    ("on't", "on it"),
+];
+
+/// How bracketed or `Enter`/`Exit` stage directions are narrated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StageDirectionMode {
+    /// Read inline with the surrounding speech
+    Read,
+    /// Dropped from narration
+    Skip,
+    /// Emitted as separate parts so they can be voiced by the narrator
+    #[default]
+    Narrator,
+}
+
+/// How speaker prefixes such as `HAMLET.` are narrated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpeakerLabelMode {
+    /// Read as a title-cased name ("Hamlet.")
+    #[default]
+    Read,
+    /// Dropped from narration
+    Skip,
+}
+
+impl StageDirectionMode {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Skip => "skip",
+            Self::Narrator => "narrator",
+        }
+    }
+}
+
+impl FromStr for StageDirectionMode {
+    type Err = TypeValidationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read" => Ok(Self::Read),
+            "skip" => Ok(Self::Skip),
+            "narrator" => Ok(Self::Narrator),
+            _ => Err(TypeValidationError::InvalidSettingValue(s.to_string())),
+        }
+    }
+}
+
+impl SpeakerLabelMode {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Skip => "skip",
+        }
+    }
+}
+
+impl FromStr for SpeakerLabelMode {
+    type Err = TypeValidationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read" => Ok(Self::Read),
+            "skip" => Ok(Self::Skip),
+            _ => Err(TypeValidationError::InvalidSettingValue(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NormalizeOptions {
+    pub stage_directions: StageDirectionMode,
+    pub speaker_labels: SpeakerLabelMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PartKind {
+    Speech,
+    StageDirection,
+}
+
+/// A run of normalized text of a single kind
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpeechPart {
+    pub kind: PartKind,
+    pub text: String,
+}
+
+struct LexiconRule {
+    pattern: Vec<char>,
+    replacement: String,
+}
+
+pub struct Normalizer {
+    rules: Vec<LexiconRule>,
+    options: NormalizeOptions,
+}
+
+const HEADING_WORDS: &[&str] = &["ACT", "SCENE", "CHAPTER", "BOOK", "PART", "CANTO"];
+
+const STAGE_DIRECTION_STARTS: &[&str] =
+    &["Enter ", "Exit", "Exeunt", "Re-enter ", "Manet", "Flourish"];
+
+impl Normalizer {
+    /// Builds a normalizer from `(term, replacement)` lexicon pairs
+    pub fn new<I, S, R>(lexicon: I, options: NormalizeOptions) -> Self
+    where
+        I: IntoIterator<Item = (S, R)>,
+        S: AsRef<str>,
+        R: Into<String>,
+    {
+        let mut rules: Vec<LexiconRule> = lexicon
+            .into_iter()
+            .filter_map(|(term, replacement)| {
+                let pattern: Vec<char> = term.as_ref().trim().chars().map(fold_char).collect();
+                (!pattern.is_empty()).then(|| LexiconRule {
+                    pattern,
+                    replacement: replacement.into(),
+                })
+            })
+            .collect();
+        // Longest terms first so "i' th'" wins over "th'"
+        rules.sort_by_key(|rule| std::cmp::Reverse(rule.pattern.len()));
+        Self { rules, options }
+    }
+
+    pub const fn options(&self) -> NormalizeOptions {
+        self.options
+    }
+
+    /// Normalizes text into speakable parts, merging adjacent parts of the same kind
+    pub fn normalize(&self, text: &str) -> Vec<SpeechPart> {
+        let mut parts: Vec<SpeechPart> = Vec::new();
+        for (kind, raw) in split_stage_directions(text) {
+            let kind = match (kind, self.options.stage_directions) {
+                (PartKind::StageDirection, StageDirectionMode::Skip) => continue,
+                (PartKind::StageDirection, StageDirectionMode::Read) => PartKind::Speech,
+                (kind, _) => kind,
+            };
+            let normalized = self.apply_lexicon(&self.normalize_lines(&raw));
+            let normalized = normalized.trim();
+            if normalized.is_empty() {
+                continue;
+            }
+            match parts.last_mut() {
+                Some(last) if last.kind == kind => {
+                    last.text.push('\n');
+                    last.text.push_str(normalized);
+                }
+                _ => parts.push(SpeechPart {
+                    kind,
+                    text: normalized.to_string(),
+                }),
+            }
+        }
+        parts
+    }
+
+    /// Normalizes text into a single string, dropping the part structure
+    pub fn normalize_to_string(&self, text: &str) -> String {
+        self.normalize(text)
+            .into_iter()
+            .map(|part| part.text)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn normalize_lines(&self, text: &str) -> String {
+        text.split('\n')
+            .filter_map(|line| {
+                if let Some(heading) = expand_heading_numerals(line) {
+                    return Some(heading);
+                }
+                match split_speaker_label(line) {
+                    Some((label, rest)) => {
+                        let rest = rest.trim();
+                        match self.options.speaker_labels {
+                            SpeakerLabelMode::Skip if rest.is_empty() => None,
+                            SpeakerLabelMode::Skip => Some(rest.to_string()),
+                            SpeakerLabelMode::Read if rest.is_empty() => {
+                                Some(format!("{}.", title_case(label)))
+                            }
+                            SpeakerLabelMode::Read => {
+                                Some(format!("{}. {rest}", title_case(label)))
+                            }
+                        }
+                    }
+                    None => Some(line.replace('_', "")),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn apply_lexicon(&self, text: &str) -> String {
+        if self.rules.is_empty() {
+            return text.to_string();
+        }
+        let chars: Vec<char> = text.chars().collect();
+        let folded: Vec<char> = chars.iter().copied().map(fold_char).collect();
+        let mut out = String::with_capacity(text.len());
+        let mut i = 0;
+        'outer: while i < chars.len() {
+            for rule in &self.rules {
+                let end = i + rule.pattern.len();
+                if end > folded.len() || folded[i..end] != rule.pattern[..] {
+                    continue;
+                }
+                let starts_word = rule.pattern[0].is_alphanumeric() || rule.pattern[0] == '\'';
+                let ends_word = rule.pattern[rule.pattern.len() - 1].is_alphanumeric();
+                let boundary_before = !starts_word || i == 0 || !chars[i - 1].is_alphanumeric();
+                let boundary_after =
+                    !ends_word || end == chars.len() || !chars[end].is_alphanumeric();
+                if boundary_before && boundary_after {
+                    out.push_str(&match_case(&chars[i..end], &rule.replacement));
+                    i = end;
+                    continue 'outer;
+                }
+            }
+            out.push(chars[i]);
+            i += 1;
+        }
+        out
+    }
+}
+
+/// Folds curly apostrophes to `'` and lowercases for lexicon matching
+fn fold_char(c: char) -> char {
+    match c {
+        '\u{2019}' | '\u{2018}' | '`' => '\'',
+        _ => c.to_lowercase().next().unwrap_or(c),
+    }
+}
+
+/// Carries the capitalization of the matched text over to the replacement
+fn match_case(matched: &[char], replacement: &str) -> String {
+    let letters: Vec<char> = matched
+        .iter()
+        .copied()
+        .filter(|c| c.is_alphabetic())
+        .collect();
+    if letters.len() > 1 && letters.iter().all(|c| c.is_uppercase()) {
+        return replacement.to_uppercase();
+    }
+    if letters.first().is_some_and(|c| c.is_uppercase()) {
+        let mut chars = replacement.chars();
+        return chars.next().map_or_else(String::new, |first| {
+            first.to_uppercase().chain(chars).collect()
+        });
+    }
+    replacement.to_string()
+}
+
+/// Splits text into speech and stage-direction runs
+///
+/// Stage directions are bracketed text (which may span lines) and whole lines that
+/// begin with `Enter`, `Exit`, `Exeunt` and similar cues.
+fn split_stage_directions(text: &str) -> Vec<(PartKind, String)> {
+    let mut runs: Vec<(PartKind, String)> = Vec::new();
+    let mut push = |kind: PartKind, s: &str| {
+        if s.trim().is_empty() {
+            return;
+        }
+        match runs.last_mut() {
+            Some((last_kind, last)) if *last_kind == kind => last.push_str(s),
+            _ => runs.push((kind, s.to_string())),
+        }
+    };
+
+    let mut depth = 0usize;
+    let mut current = String::new();
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim_start().trim_start_matches('_');
+        if depth == 0
+            && STAGE_DIRECTION_STARTS
+                .iter()
+                .any(|s| trimmed.starts_with(s))
+        {
+            push(PartKind::Speech, &std::mem::take(&mut current));
+            push(PartKind::StageDirection, line);
+            continue;
+        }
+        for ch in line.chars() {
+            match ch {
+                '[' => {
+                    if depth == 0 {
+                        push(PartKind::Speech, &std::mem::take(&mut current));
+                    }
+                    depth += 1;
+                }
+                ']' if depth > 0 => {
+                    depth -= 1;
+                    if depth == 0 {
+                        push(PartKind::StageDirection, &std::mem::take(&mut current));
+                    }
+                }
+                _ => current.push(ch),
+            }
+        }
+    }
+    let kind = if depth > 0 {
+        PartKind::StageDirection
+    } else {
+        PartKind::Speech
+    };
+    push(kind, &current);
+    runs
+}
+
+/// Splits `HAMLET. text` or a bare `FIRST CLOWN.` line into label and remainder
+pub fn split_speaker_label(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim_start();
+    let dot = trimmed.find('.')?;
+    let label = &trimmed[..dot];
+    let rest = &trimmed[dot + 1..];
+    let letters = label.chars().filter(|c| c.is_alphabetic()).count();
+    let is_label = letters >= 2
+        && label
+            .chars()
+            .all(|c| c.is_uppercase() || c == ' ' || c == '\'' || c == '\u{2019}' || c == '-')
+        && !label.starts_with(' ')
+        && (rest.is_empty() || rest.starts_with(char::is_whitespace));
+    if !is_label || HEADING_WORDS.contains(&label.split(' ').next().unwrap_or("")) {
+        return None;
+    }
+    Some((label, rest))
+}
+
+fn title_case(label: &str) -> String {
+    label
+        .split(' ')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map_or_else(String::new, |first| {
+                first
+                    .to_uppercase()
+                    .chain(chars.flat_map(char::to_lowercase))
+                    .collect()
+            })
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Rewrites `ACT III` / `SCENE II.` headings as `Act 3` / `Scene 2.`
+fn expand_heading_numerals(line: &str) -> Option<String> {
+    let trimmed = line.trim();
+    let mut words = trimmed.splitn(2, ' ');
+    let heading = words.next()?;
+    if !HEADING_WORDS.contains(&heading.to_uppercase().as_str()) {
+        return None;
+    }
+    let rest = words.next()?;
+    let numeral_len = rest
+        .find(|c: char| !matches!(c, 'I' | 'V' | 'X' | 'L' | 'C' | 'D' | 'M'))
+        .unwrap_or(rest.len());
+    let value = roman_to_int(&rest[..numeral_len])?;
+    let tail = &rest[numeral_len..];
+    if !(tail.is_empty() || tail.starts_with('.') || tail.starts_with(' ') || tail.starts_with(':'))
+    {
+        return None;
+    }
+    Some(format!("{} {value}{tail}", title_case(heading)))
+}
+
+/// Parses an uppercase Roman numeral, rejecting malformed ones like `IIII` or `IC`
+pub fn roman_to_int(s: &str) -> Option<u32> {
+    if s.is_empty() {
+        return None;
+    }
+    let value_of = |c: char| match c {
+        'I' => Some(1),
+        'V' => Some(5),
+        'X' => Some(10),
+        'L' => Some(50),
+        'C' => Some(100),
+        'D' => Some(500),
+        'M' => Some(1000),
+        _ => None,
+    };
+    let values: Vec<u32> = s.chars().map(value_of).collect::<Option<_>>()?;
+    let mut total = 0;
+    for (i, &v) in values.iter().enumerate() {
+        match values.get(i + 1) {
+            Some(&next) if next > v => total -= i64::from(v),
+            _ => total += i64::from(v),
+        }
+    }
+    let total = u32::try_from(total).ok().filter(|&t| t > 0)?;
+    (int_to_roman(total) == s).then_some(total)
+}
+
+fn int_to_roman(mut n: u32) -> String {
+    const TABLE: &[(u32, &str)] = &[
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+    let mut out = String::new();
+    for &(value, numeral) in TABLE {
+        while n >= value {
+            out.push_str(numeral);
+            n -= value;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_normalizer(options: NormalizeOptions) -> Normalizer {
+        Normalizer::new(DEFAULT_LEXICON.iter().copied(), options)
+    }
+
+    #[test]
+    fn test_contractions_expand_with_case() {
+        let n = default_normalizer(NormalizeOptions::default());
+        assert_eq!(
+            n.normalize_to_string("\u{2019}Tis now struck twelve, o'er the hill."),
+            "It is now struck twelve, over the hill."
+        );
+        assert_eq!(
+            n.normalize_to_string("Thou'rt mad i' th' head"),
+            "Thou art mad in the head"
+        );
+        // Word boundaries: "other'" must not match "th'"
+        assert_eq!(n.normalize_to_string("other"), "other");
+    }
+
+    #[test]
+    fn test_speaker_labels() {
+        let read = default_normalizer(NormalizeOptions::default());
+        assert_eq!(
+            read.normalize_to_string("FIRST CLOWN.\nIs she to be buried?"),
+            "First Clown.\nIs she to be buried?"
+        );
+        assert_eq!(
+            read.normalize_to_string("HAM. To be, or not to be"),
+            "Ham. To be, or not to be"
+        );
+
+        let skip = default_normalizer(NormalizeOptions {
+            speaker_labels: SpeakerLabelMode::Skip,
+            ..NormalizeOptions::default()
+        });
+        assert_eq!(
+            skip.normalize_to_string("HAMLET.\nWords, words, words."),
+            "Words, words, words."
+        );
+    }
+
+    #[test]
+    fn test_user_lexicon_expands_abbreviated_labels() {
+        let n = Normalizer::new([("Ham.", "Hamlet.")], NormalizeOptions::default());
+        assert_eq!(n.normalize_to_string("HAM. Ay, madam"), "Hamlet. Ay, madam");
+    }
+
+    #[test]
+    fn test_stage_directions() {
+        let text = "HAMLET.\nAlas, poor Yorick! [_Takes the skull._] I knew him.\nExit Horatio.";
+        let narrator = default_normalizer(NormalizeOptions::default());
+        let parts = narrator.normalize(text);
+        assert_eq!(
+            parts,
+            vec![
+                SpeechPart {
+                    kind: PartKind::Speech,
+                    text: "Hamlet.\nAlas, poor Yorick!".to_string()
+                },
+                SpeechPart {
+                    kind: PartKind::StageDirection,
+                    text: "Takes the skull.".to_string()
+                },
+                SpeechPart {
+                    kind: PartKind::Speech,
+                    text: "I knew him.".to_string()
+                },
+                SpeechPart {
+                    kind: PartKind::StageDirection,
+                    text: "Exit Horatio.".to_string()
+                },
+            ]
+        );
+
+        let skip = default_normalizer(NormalizeOptions {
+            stage_directions: StageDirectionMode::Skip,
+            ..NormalizeOptions::default()
+        });
+        assert_eq!(
+            skip.normalize_to_string(text),
+            "Hamlet.\nAlas, poor Yorick!\nI knew him."
+        );
+    }
+
+    #[test]
+    fn test_heading_numerals() {
+        let n = default_normalizer(NormalizeOptions::default());
+        assert_eq!(n.normalize_to_string("ACT III"), "Act 3");
+        assert_eq!(
+            n.normalize_to_string("SCENE II. A room in the Castle."),
+            "Scene 2. A room in the Castle."
+        );
+        assert_eq!(
+            n.normalize_to_string("SCENE IC. Nowhere"),
+            "SCENE IC. Nowhere"
+        );
+    }
+
+    #[test]
+    fn test_roman_to_int() {
+        assert_eq!(roman_to_int("IV"), Some(4));
+        assert_eq!(roman_to_int("XIV"), Some(14));
+        assert_eq!(roman_to_int("MCMXC"), Some(1990));
+        assert_eq!(roman_to_int("IIII"), None);
+        assert_eq!(roman_to_int(""), None);
+    }
+}
@@ -0,0 +1,320 @@
+//! Play structure detection for multi-voice narration
+//!
+//! Gutenberg play texts mark each speech with an uppercase speaker prefix
+//! (`HAMLET.` on its own line, or `HAM. To be…` inline) and usually open with a
+//! Dramatis Personae list. Speaker labels are resolved against that list so that
+//! abbreviations like `HAM.` and full names like `HAMLET.` share one cast entry.
+
+use crate::normalize::split_speaker_label;
+use crate::text::{collapse_whitespace, BookText};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Minimum number of speaker-prefixed lines before a book is treated as a play
+const MIN_SPEECHES: usize = 20;
+
+const CHARACTER_LIST_TITLES: &[&str] = &[
+    "dramatis person",
+    "persons represented",
+    "characters",
+    "persons of the play",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlayCharacter {
+    /// Canonical uppercase name used as the cast key
+    pub name: String,
+    /// Description from the character list, if any
+    pub description: Option<String>,
+    /// Speaker labels seen in the text that resolve to this character
+    pub aliases: Vec<String>,
+    pub speeches: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PlayStructure {
+    pub is_play: bool,
+    pub characters: Vec<PlayCharacter>,
+    #[serde(skip)]
+    aliases: HashMap<String, String>,
+}
+
+impl PlayStructure {
+    /// Resolves a speaker label (`HAM`, `Hamlet`) to its canonical character name
+    pub fn resolve(&self, label: &str) -> Option<&str> {
+        self.aliases
+            .get(&label.trim().to_uppercase())
+            .map(String::as_str)
+    }
+}
+
+/// A run of text attributed to one speaker (or to no one, for scene text)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Speech {
+    pub speaker: Option<String>,
+    pub text: String,
+}
+
+/// Detects speakers and the character list in a book
+pub fn detect_play_structure(book: &BookText) -> PlayStructure {
+    let listed = parse_character_list(book);
+
+    let mut label_counts: HashMap<String, usize> = HashMap::new();
+    for line in book.text.lines() {
+        if let Some((label, _)) = split_speaker_label(line) {
+            *label_counts.entry(collapse_whitespace(label)).or_default() += 1;
+        }
+    }
+    let total_speeches: usize = label_counts.values().sum();
+
+    let mut characters: Vec<PlayCharacter> = listed
+        .iter()
+        .map(|(name, description)| PlayCharacter {
+            name: name.clone(),
+            description: description.clone(),
+            aliases: Vec::new(),
+            speeches: 0,
+        })
+        .collect();
+    let mut aliases: HashMap<String, String> = HashMap::new();
+
+    let mut labels: Vec<(&String, &usize)> = label_counts.iter().collect();
+    labels.sort();
+    for (label, &count) in labels {
+        let canonical = canonical_name(label, &listed).unwrap_or_else(|| label.clone());
+        let character = if let Some(i) = characters.iter().position(|c| c.name == canonical) {
+            &mut characters[i]
+        } else {
+            characters.push(PlayCharacter {
+                name: canonical.clone(),
+                description: None,
+                aliases: Vec::new(),
+                speeches: 0,
+            });
+            characters.last_mut().expect("just pushed")
+        };
+        character.speeches += count;
+        character.aliases.push(label.clone());
+        aliases.insert(label.clone(), canonical);
+    }
+    for character in &characters {
+        aliases
+            .entry(character.name.clone())
+            .or_insert_with(|| character.name.clone());
+    }
+
+    characters.sort_by(|a, b| b.speeches.cmp(&a.speeches).then(a.name.cmp(&b.name)));
+
+    PlayStructure {
+        is_play: total_speeches >= MIN_SPEECHES && label_counts.len() >= 2,
+        characters,
+        aliases,
+    }
+}
+
+/// Maps a speaker label to a listed character: exact match first, then a unique
+/// listed name that the label abbreviates
+fn canonical_name(label: &str, listed: &[(String, Option<String>)]) -> Option<String> {
+    if listed.iter().any(|(name, _)| name == label) {
+        return Some(label.to_string());
+    }
+    let mut candidates = listed
+        .iter()
+        .filter(|(name, _)| name.starts_with(label) || name.split(' ').any(|w| w == label));
+    match (candidates.next(), candidates.next()) {
+        (Some((name, _)), None) => Some(name.clone()),
+        _ => None,
+    }
+}
+
+/// Parses the Dramatis Personae chapter into `(NAME, description)` pairs
+///
+/// Only entries with an uppercase name are kept (`HAMLET, Prince of Denmark`,
+/// `The GHOST of the late king`); group entries such as `Lords, Ladies` are skipped.
+fn parse_character_list(book: &BookText) -> Vec<(String, Option<String>)> {
+    let Some(chapter) = book.chapters.iter().find(|chapter| {
+        let title = chapter.title.to_lowercase();
+        CHARACTER_LIST_TITLES.iter().any(|t| title.contains(t))
+    }) else {
+        return Vec::new();
+    };
+
+    let text = book.chapter_text(chapter.index).unwrap_or("");
+    let mut out: Vec<(String, Option<String>)> = Vec::new();
+    for line in text.lines().skip(1) {
+        let line = line.trim();
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some(start) = words.iter().position(|w| is_upper_name(w)) else {
+            continue;
+        };
+        let end = words[start..]
+            .iter()
+            .position(|w| !is_upper_name(w) || w.ends_with(','))
+            .map_or(words.len(), |i| {
+                start + i + usize::from(words[start + i].ends_with(','))
+            });
+        let name = words[start..end]
+            .iter()
+            .map(|w| w.trim_end_matches([',', '.', ';', ':']))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if name.is_empty() || out.iter().any(|(n, _)| *n == name) {
+            continue;
+        }
+        let description = line
+            .split_once(',')
+            .map(|(_, rest)| rest.trim().to_string())
+            .filter(|d| !d.is_empty());
+        out.push((name, description));
+    }
+    out
+}
+
+fn is_upper_name(word: &str) -> bool {
+    let word = word.trim_end_matches([',', '.', ';', ':']);
+    word.chars().filter(|c| c.is_alphabetic()).count() >= 2
+        && word
+            .chars()
+            .all(|c| c.is_uppercase() || c == '\'' || c == '-')
+}
+
+/// Splits a passage into speeches. Speaker labels are removed from the speech text
+/// since the voice change already conveys who is speaking.
+pub fn split_speeches(text: &str, play: &PlayStructure) -> Vec<Speech> {
+    let mut speeches: Vec<Speech> = Vec::new();
+    let mut current = Speech::default();
+
+    for line in text.split('\n') {
+        let resolved = split_speaker_label(line).and_then(|(label, rest)| {
+            play.resolve(&collapse_whitespace(label))
+                .map(|name| (name.to_string(), rest))
+        });
+        if let Some((speaker, rest)) = resolved {
+            if !current.text.trim().is_empty() {
+                speeches.push(std::mem::take(&mut current));
+            }
+            current = Speech {
+                speaker: Some(speaker),
+                text: rest.trim().to_string(),
+            };
+            continue;
+        }
+        // A blank line after a speech ends it; following text belongs to the scene
+        if line.trim().is_empty() && current.speaker.is_some() && !current.text.trim().is_empty() {
+            speeches.push(std::mem::take(&mut current));
+            continue;
+        }
+        if !current.text.is_empty() {
+            current.text.push('\n');
+        }
+        current.text.push_str(line);
+    }
+    if !current.text.trim().is_empty() {
+        speeches.push(current);
+    }
+    speeches
+}
+
+/// Assigns voices round-robin to the characters that have no voice yet, most
+/// talkative first, skipping the narrator voice while others are available
+pub fn auto_assign_voices(
+    play: &PlayStructure,
+    existing: &HashMap<String, String>,
+    voices: &[String],
+    narrator: &str,
+) -> Vec<(String, String)> {
+    let pool: Vec<&String> = {
+        let without_narrator: Vec<&String> = voices.iter().filter(|v| *v != narrator).collect();
+        if without_narrator.is_empty() {
+            voices.iter().collect()
+        } else {
+            without_narrator
+        }
+    };
+    if pool.is_empty() {
+        return Vec::new();
+    }
+
+    play.characters
+        .iter()
+        .filter(|c| c.speeches > 0 && !existing.contains_key(&c.name))
+        .enumerate()
+        .map(|(i, c)| (c.name.clone(), pool[i % pool.len()].clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::html_to_book_text;
+
+    fn hamlet() -> BookText {
+        let mut html = String::from(
+            "<body><h3>Dramatis Personæ</h3><p>HAMLET, Prince of Denmark<br>\
+             The GHOST of the late king<br>FIRST CLOWN, a grave-digger<br>\
+             Lords, Ladies, Officers</p><h2>ACT I</h2>",
+        );
+        for _ in 0..10 {
+            html.push_str("<p>HAMLET.<br>Words, words.</p><p>GHOST.<br>Remember me.</p>");
+        }
+        html.push_str("<p>HAM. Alas!</p><p>FIRST CLOWN.<br>Who builds stronger?</p></body>");
+        html_to_book_text(&html)
+    }
+
+    #[test]
+    fn test_detect_play_structure() {
+        let play = detect_play_structure(&hamlet());
+        assert!(play.is_play);
+        let names: Vec<&str> = play.characters.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["HAMLET", "GHOST", "FIRST CLOWN"]);
+        assert_eq!(play.characters[0].speeches, 11);
+        assert_eq!(
+            play.characters[0].description.as_deref(),
+            Some("Prince of Denmark")
+        );
+        assert_eq!(play.resolve("Ham"), Some("HAMLET"));
+    }
+
+    #[test]
+    fn test_prose_is_not_a_play() {
+        let book = html_to_book_text("<body><h2>CHAPTER I.</h2><p>It was a dark night.</p></body>");
+        assert!(!detect_play_structure(&book).is_play);
+    }
+
+    #[test]
+    fn test_split_speeches() {
+        let play = detect_play_structure(&hamlet());
+        let speeches = split_speeches(
+            "Enter Ghost.\n\nHAMLET.\nWords, words.\n\nHAM. Alas!\nPoor Yorick.\n\nThey dig.",
+            &play,
+        );
+        let summary: Vec<(Option<&str>, &str)> = speeches
+            .iter()
+            .map(|s| (s.speaker.as_deref(), s.text.trim()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (None, "Enter Ghost."),
+                (Some("HAMLET"), "Words, words."),
+                (Some("HAMLET"), "Alas!\nPoor Yorick."),
+                (None, "They dig."),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_auto_assign_voices() {
+        let play = detect_play_structure(&hamlet());
+        let existing = HashMap::from([("GHOST".to_string(), "javert".to_string())]);
+        let voices = vec!["alba".to_string(), "marius".to_string(), "jean".to_string()];
+        let assigned = auto_assign_voices(&play, &existing, &voices, "alba");
+        assert_eq!(
+            assigned,
+            vec![
+                ("HAMLET".to_string(), "marius".to_string()),
+                ("FIRST CLOWN".to_string(), "jean".to_string()),
+            ]
+        );
+    }
+}
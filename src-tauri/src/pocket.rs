@@ -1,118 +1,374 @@
 use serde::Serialize;
+use std::io;
 use std::process::{Child, Command, Stdio};
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tauri::{AppHandle, State};
+use tauri_plugin_shell::process::{CommandChild, CommandEvent};
 use tauri_plugin_shell::ShellExt;
-use tauri_plugin_shell::process::CommandChild;
+use tokio::sync::Mutex;
 
-#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+/// How long a stopping server gets to exit after SIGTERM before it is killed
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Lifecycle of the Pocket TTS server.
+///
+/// `Stopped`/`Errored` → `Starting` → `Running` → `Stopping` → `Stopped`. A running
+/// process that exits on its own moves to `Errored`, and a stop requested while
+/// starting wins: the freshly launched process is shut down again.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum SidecarStatus {
     Stopped,
     Starting,
     Running,
+    Stopping,
     Errored,
 }
 
-pub struct SidecarState {
-    pub status: Mutex<SidecarStatus>,
-    pub child: Mutex<Option<PocketChild>>,
+/// A launched server process, abstracted so the state machine can be driven by tests
+pub trait SidecarProcess: Send {
+    fn has_exited(&mut self) -> bool;
+
+    /// Asks the process to exit (SIGTERM where available)
+    fn terminate(&mut self) -> io::Result<()>;
+
+    fn kill(&mut self) -> io::Result<()>;
+}
+
+/// Starts a server process; implemented by the Tauri sidecar/dev-server launcher
+/// and by fakes in tests
+pub trait SidecarLauncher: Send + Sync {
+    fn launch(&self) -> Result<Box<dyn SidecarProcess>, String>;
 }
 
-pub enum PocketChild {
-    Sidecar(CommandChild),
-    Process(Child),
+struct SidecarInner {
+    status: SidecarStatus,
+    process: Option<Box<dyn SidecarProcess>>,
+}
+
+type Launched = Result<Box<dyn SidecarProcess>, String>;
+
+impl SidecarInner {
+    /// Notices a running server that exited without being asked to
+    fn reap(&mut self) -> SidecarStatus {
+        if self.status == SidecarStatus::Running
+            && self
+                .process
+                .as_mut()
+                .is_none_or(|process| process.has_exited())
+        {
+            println!("[Sidecar] pocket-tts exited unexpectedly.");
+            self.process = None;
+            self.status = SidecarStatus::Errored;
+        }
+        self.status
+    }
+
+    /// `Stopped`/`Errored` → `Starting`; returns the current status if a launch
+    /// should not happen
+    fn begin_start(&mut self) -> Option<SidecarStatus> {
+        match self.reap() {
+            SidecarStatus::Stopped | SidecarStatus::Errored => {
+                self.status = SidecarStatus::Starting;
+                None
+            }
+            status => Some(status),
+        }
+    }
+
+    /// `Starting` → `Running`/`Errored`. If a stop arrived during the launch the
+    /// new process is handed back to be shut down.
+    fn finish_start(
+        &mut self,
+        launched: Launched,
+    ) -> Result<(SidecarStatus, Option<Box<dyn SidecarProcess>>), String> {
+        let starting = self.status == SidecarStatus::Starting;
+        match launched {
+            Ok(process) if starting => {
+                self.process = Some(process);
+                self.status = SidecarStatus::Running;
+                Ok((SidecarStatus::Running, None))
+            }
+            Ok(process) => Ok((self.status, Some(process))),
+            Err(e) => {
+                if starting {
+                    self.status = SidecarStatus::Errored;
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// `Running`/`Errored` → `Stopping` when there is a process to shut down,
+    /// otherwise straight to `Stopped`. A stop during `Starting` is picked up
+    /// by `finish_start`.
+    fn begin_stop(&mut self) -> Result<Box<dyn SidecarProcess>, SidecarStatus> {
+        if self.status == SidecarStatus::Stopping {
+            return Err(SidecarStatus::Stopping);
+        }
+        if let Some(process) = self.process.take() {
+            self.status = SidecarStatus::Stopping;
+            Ok(process)
+        } else {
+            self.status = SidecarStatus::Stopped;
+            Err(SidecarStatus::Stopped)
+        }
+    }
+}
+
+/// Sidecar state behind a single async lock. The lock is never held across a
+/// launch or a shutdown wait, so status queries stay responsive. Launches and
+/// shutdowns run on the blocking pool, since spawning and signalling block.
+pub struct SidecarState {
+    inner: Mutex<SidecarInner>,
+    shutdown_timeout: Duration,
 }
 
 impl SidecarState {
     pub fn new() -> Self {
+        Self::with_shutdown_timeout(SHUTDOWN_TIMEOUT)
+    }
+
+    pub fn with_shutdown_timeout(shutdown_timeout: Duration) -> Self {
         Self {
-            status: Mutex::new(SidecarStatus::Stopped),
-            child: Mutex::new(None),
+            inner: Mutex::new(SidecarInner {
+                status: SidecarStatus::Stopped,
+                process: None,
+            }),
+            shutdown_timeout,
+        }
+    }
+
+    pub async fn status(&self) -> SidecarStatus {
+        self.inner.lock().await.reap()
+    }
+
+    pub async fn start(&self, launcher: Arc<dyn SidecarLauncher>) -> Result<SidecarStatus, String> {
+        let active = self.inner.lock().await.begin_start();
+        if let Some(status) = active {
+            return Ok(status);
+        }
+
+        println!("[Sidecar] Starting pocket-tts sidecar...");
+        let outcome = blocking(move || launcher.launch())
+            .await
+            .and_then(|launched| launched);
+        if let Err(e) = &outcome {
+            println!("[Sidecar] {e}");
+        }
+
+        let (status, orphan) = self.inner.lock().await.finish_start(outcome)?;
+        match orphan {
+            Some(process) => {
+                println!("[Sidecar] Stop requested during start, shutting down.");
+                shutdown(process, self.shutdown_timeout).await;
+            }
+            None => println!("[Sidecar] pocket-tts process spawned."),
         }
+        Ok(status)
+    }
+
+    pub async fn stop(&self) -> SidecarStatus {
+        let pending = self.inner.lock().await.begin_stop();
+        let process = match pending {
+            Ok(process) => process,
+            Err(status) => return status,
+        };
+
+        println!("[Sidecar] Stopping pocket-tts sidecar...");
+        shutdown(process, self.shutdown_timeout).await;
+
+        self.inner.lock().await.status = SidecarStatus::Stopped;
+        println!("[Sidecar] pocket-tts sidecar stopped.");
+        SidecarStatus::Stopped
     }
 }
 
-#[tauri::command]
-pub async fn get_pocket_status(state: State<'_, SidecarState>) -> Result<SidecarStatus, String> {
-    Ok(*state.status.lock().unwrap())
+impl Default for SidecarState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-#[tauri::command]
-pub async fn start_pocket_sidecar(
-    app: AppHandle,
-    state: State<'_, SidecarState>,
-) -> Result<SidecarStatus, String> {
-    start_pocket_sidecar_internal(&app, &state)
+/// Runs process work (spawning, `kill`, waiting) on the blocking pool
+async fn blocking<T: Send + 'static>(
+    work: impl FnOnce() -> T + Send + 'static,
+) -> Result<T, String> {
+    tauri::async_runtime::spawn_blocking(work)
+        .await
+        .map_err(|e| e.to_string())
 }
 
-pub fn start_pocket_sidecar_internal(
-    app: &AppHandle,
-    state: &SidecarState,
-) -> Result<SidecarStatus, String> {
-    let mut status = state.status.lock().unwrap();
+/// Shuts a process down without blocking the async runtime
+async fn shutdown(process: Box<dyn SidecarProcess>, timeout: Duration) {
+    if let Err(e) = blocking(move || terminate_or_kill(process, timeout)).await {
+        println!("[Sidecar] Failed to shut down pocket-tts: {e}");
+    }
+}
+
+/// Sends SIGTERM and waits up to `timeout` for the process to exit, then kills it
+fn terminate_or_kill(mut process: Box<dyn SidecarProcess>, timeout: Duration) {
+    if let Err(e) = process.terminate() {
+        println!("[Sidecar] Failed to terminate pocket-tts: {e}");
+    } else {
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            if process.has_exited() {
+                return;
+            }
+            std::thread::sleep(EXIT_POLL_INTERVAL);
+        }
+        println!("[Sidecar] pocket-tts did not exit in time, killing.");
+    }
+    if let Err(e) = process.kill() {
+        println!("[Sidecar] Failed to kill pocket-tts: {e}");
+    }
+}
 
-    if *status == SidecarStatus::Running || *status == SidecarStatus::Starting {
-        return Ok(*status);
+#[cfg(unix)]
+fn send_sigterm(pid: u32) -> io::Result<()> {
+    let status = Command::new("kill")
+        .args(["-TERM", &pid.to_string()])
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "kill -TERM {pid} failed: {status}"
+        )))
     }
+}
 
-    *status = SidecarStatus::Starting;
-    println!("[Sidecar] Starting pocket-tts sidecar...");
+#[cfg(not(unix))]
+fn send_sigterm(_pid: u32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "graceful termination is not supported on this platform",
+    ))
+}
 
-    let sidecar = app
-        .shell()
-        .sidecar("pocket-tts")
-        .map_err(|e| format!("Failed to create sidecar: {e}"));
+/// The bundled `pocket-tts` binary launched through the shell plugin
+struct BundledSidecar {
+    child: Option<CommandChild>,
+    exited: Arc<AtomicBool>,
+}
 
-    let sidecar = match sidecar {
-        Ok(sidecar) => sidecar,
-        Err(e) => {
-            println!("[Sidecar] {e}");
-            return spawn_dev_server(state);
-        }
+impl SidecarProcess for BundledSidecar {
+    fn has_exited(&mut self) -> bool {
+        self.child.is_none() || self.exited.load(Ordering::SeqCst)
     }
-        .args(["--host", "127.0.0.1", "--port", "5123", "--preload"]);
 
-    let (mut rx, child) = sidecar
-        .spawn()
-        .map_err(|e| format!("Failed to spawn sidecar: {e}"))?;
+    fn terminate(&mut self) -> io::Result<()> {
+        self.child
+            .as_ref()
+            .map_or(Ok(()), |child| send_sigterm(child.pid()))
+    }
 
-    tauri::async_runtime::spawn(async move {
-        while let Some(event) = rx.recv().await {
-            match event {
-                tauri_plugin_shell::process::CommandEvent::Stdout(line) => {
-                    print!("{}", String::from_utf8_lossy(&line));
-                }
-                tauri_plugin_shell::process::CommandEvent::Stderr(line) => {
-                    eprint!("{}", String::from_utf8_lossy(&line));
+    fn kill(&mut self) -> io::Result<()> {
+        self.child
+            .take()
+            .map_or(Ok(()), |child| child.kill().map_err(io::Error::other))
+    }
+}
+
+/// The `uv run python server.py` dev fallback
+struct DevServer {
+    child: Child,
+}
+
+impl SidecarProcess for DevServer {
+    fn has_exited(&mut self) -> bool {
+        !matches!(self.child.try_wait(), Ok(None))
+    }
+
+    fn terminate(&mut self) -> io::Result<()> {
+        send_sigterm(self.child.id())
+    }
+
+    fn kill(&mut self) -> io::Result<()> {
+        self.child.kill()?;
+        self.child.wait().map(|_| ())
+    }
+}
+
+/// Launches the bundled sidecar, falling back to the local python server in dev
+pub struct PocketLauncher {
+    app: AppHandle,
+}
+
+impl PocketLauncher {
+    pub const fn new(app: AppHandle) -> Self {
+        Self { app }
+    }
+
+    fn spawn_sidecar(&self) -> Result<Box<dyn SidecarProcess>, String> {
+        let sidecar = self
+            .app
+            .shell()
+            .sidecar("pocket-tts")
+            .map_err(|e| format!("Failed to create sidecar: {e}"))?
+            .args(["--host", "127.0.0.1", "--port", "5123", "--preload"]);
+
+        let (mut rx, child) = sidecar
+            .spawn()
+            .map_err(|e| format!("Failed to spawn sidecar: {e}"))?;
+
+        let exited = Arc::new(AtomicBool::new(false));
+        let exited_flag = Arc::clone(&exited);
+        tauri::async_runtime::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                match event {
+                    CommandEvent::Stdout(line) => {
+                        print!("{}", String::from_utf8_lossy(&line));
+                    }
+                    CommandEvent::Stderr(line) => {
+                        eprint!("{}", String::from_utf8_lossy(&line));
+                    }
+                    CommandEvent::Terminated(_) => {
+                        exited_flag.store(true, Ordering::SeqCst);
+                    }
+                    _ => {}
                 }
-                _ => {}
             }
-        }
-    });
+            exited_flag.store(true, Ordering::SeqCst);
+        });
 
-    let mut child_guard = state.child.lock().unwrap();
-    *child_guard = Some(PocketChild::Sidecar(child));
-    *status = SidecarStatus::Running;
+        Ok(Box::new(BundledSidecar {
+            child: Some(child),
+            exited,
+        }))
+    }
+}
 
-    println!("[Sidecar] pocket-tts sidecar process spawned.");
-    Ok(*status)
+impl SidecarLauncher for PocketLauncher {
+    fn launch(&self) -> Result<Box<dyn SidecarProcess>, String> {
+        match self.spawn_sidecar() {
+            Ok(process) => Ok(process),
+            Err(e) => {
+                println!("[Sidecar] {e}");
+                spawn_dev_server()
+            }
+        }
+    }
 }
 
-fn spawn_dev_server(state: &SidecarState) -> Result<SidecarStatus, String> {
-    let mut status = state.status.lock().unwrap();
+fn spawn_dev_server() -> Result<Box<dyn SidecarProcess>, String> {
     println!("[Sidecar] Attempting to start Pocket TTS via local python server...");
 
     let cwd = std::env::current_dir().map_err(|e| format!("Failed to read cwd: {e}"))?;
     let base = if cwd.ends_with("src-tauri") {
-        cwd.parent().map(|p| p.to_path_buf()).unwrap_or(cwd)
+        cwd.parent()
+            .map(std::path::Path::to_path_buf)
+            .unwrap_or(cwd)
     } else {
         cwd
     };
     let server_dir = base.join("conductor").join("pocket-tts");
     let server_path = server_dir.join("server.py");
     if !server_path.exists() {
-        *status = SidecarStatus::Errored;
         return Err(format!(
             "Pocket TTS sidecar not found and server.py missing at {}",
             server_path.display()
@@ -120,12 +376,7 @@ fn spawn_dev_server(state: &SidecarState) -> Result<SidecarStatus, String> {
     }
 
     let child = spawn_uv_run_server(&server_dir)?;
-
-    let mut child_guard = state.child.lock().unwrap();
-    *child_guard = Some(PocketChild::Process(child));
-    *status = SidecarStatus::Running;
-    println!("[Sidecar] Pocket TTS python server spawned.");
-    Ok(*status)
+    Ok(Box::new(DevServer { child }))
 }
 
 fn spawn_uv_run_server(server_dir: &std::path::Path) -> Result<Child, String> {
@@ -143,40 +394,201 @@ fn spawn_uv_run_server(server_dir: &std::path::Path) -> Result<Child, String> {
         .stdout(Stdio::inherit())
         .stderr(Stdio::inherit());
 
-    command.spawn().map_err(|e| format!("Failed to spawn uv run: {e}"))
+    command
+        .spawn()
+        .map_err(|e| format!("Failed to spawn uv run: {e}"))
 }
 
+#[tauri::command]
+pub async fn get_pocket_status(state: State<'_, SidecarState>) -> Result<SidecarStatus, String> {
+    Ok(state.status().await)
+}
+
+#[tauri::command]
+pub async fn start_pocket_sidecar(
+    app: AppHandle,
+    state: State<'_, SidecarState>,
+) -> Result<SidecarStatus, String> {
+    state.start(Arc::new(PocketLauncher::new(app))).await
+}
 
 #[tauri::command]
 pub async fn stop_pocket_sidecar(state: State<'_, SidecarState>) -> Result<SidecarStatus, String> {
-    let mut status = state.status.lock().unwrap();
-    let mut child_guard = state.child.lock().unwrap();
+    Ok(state.stop().await)
+}
 
-    if let Some(child) = child_guard.take() {
-        println!("[Sidecar] Stopping pocket-tts sidecar...");
-        match child {
-            PocketChild::Sidecar(child) => {
-                let _ = child.kill();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex as StdMutex;
+
+    /// Shared view of a fake process so tests can crash it and inspect signals
+    #[derive(Clone, Default)]
+    struct FakeHandle {
+        exited: Arc<AtomicBool>,
+        signals: Arc<StdMutex<Vec<&'static str>>>,
+    }
+
+    impl FakeHandle {
+        fn signals(&self) -> Vec<&'static str> {
+            self.signals.lock().map(|s| s.clone()).unwrap_or_default()
+        }
+
+        fn record(&self, signal: &'static str) {
+            if let Ok(mut signals) = self.signals.lock() {
+                signals.push(signal);
             }
-            PocketChild::Process(mut child) => {
-                let _ = child.kill();
+        }
+    }
+
+    struct FakeProcess {
+        handle: FakeHandle,
+        ignores_term: bool,
+    }
+
+    impl SidecarProcess for FakeProcess {
+        fn has_exited(&mut self) -> bool {
+            self.handle.exited.load(Ordering::SeqCst)
+        }
+
+        fn terminate(&mut self) -> io::Result<()> {
+            self.handle.record("term");
+            if !self.ignores_term {
+                self.handle.exited.store(true, Ordering::SeqCst);
             }
+            Ok(())
+        }
+
+        fn kill(&mut self) -> io::Result<()> {
+            self.handle.record("kill");
+            self.handle.exited.store(true, Ordering::SeqCst);
+            Ok(())
         }
     }
 
-    *status = SidecarStatus::Stopped;
-    println!("[Sidecar] pocket-tts sidecar stopped.");
-    Ok(*status)
-}
+    #[derive(Default)]
+    struct FakeLauncher {
+        handle: FakeHandle,
+        ignores_term: bool,
+        fail: bool,
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    impl SidecarLauncher for FakeLauncher {
+        fn launch(&self) -> Result<Box<dyn SidecarProcess>, String> {
+            if self.fail {
+                return Err("no server".to_string());
+            }
+            self.handle.exited.store(false, Ordering::SeqCst);
+            Ok(Box::new(FakeProcess {
+                handle: self.handle.clone(),
+                ignores_term: self.ignores_term,
+            }))
+        }
+    }
+
+    fn state() -> SidecarState {
+        SidecarState::with_shutdown_timeout(Duration::from_millis(200))
+    }
+
+    #[tokio::test]
+    async fn test_sidecar_state_initialization() {
+        assert_eq!(state().status().await, SidecarStatus::Stopped);
+    }
+
+    #[tokio::test]
+    async fn test_start_then_graceful_stop() {
+        let state = state();
+        let launcher = Arc::new(FakeLauncher::default());
+
+        assert_eq!(
+            state.start(launcher.clone()).await,
+            Ok(SidecarStatus::Running)
+        );
+        assert_eq!(
+            state.start(launcher.clone()).await,
+            Ok(SidecarStatus::Running)
+        );
+        assert_eq!(state.status().await, SidecarStatus::Running);
+
+        assert_eq!(state.stop().await, SidecarStatus::Stopped);
+        assert_eq!(state.status().await, SidecarStatus::Stopped);
+        assert_eq!(launcher.handle.signals(), vec!["term"]);
+    }
+
+    #[tokio::test]
+    async fn test_stop_kills_after_timeout() {
+        let state = state();
+        let launcher = Arc::new(FakeLauncher {
+            ignores_term: true,
+            ..FakeLauncher::default()
+        });
+
+        state.start(launcher.clone()).await.unwrap();
+        assert_eq!(state.stop().await, SidecarStatus::Stopped);
+        assert_eq!(launcher.handle.signals(), vec!["term", "kill"]);
+    }
+
+    #[tokio::test]
+    async fn test_crash_is_reported_and_restartable() {
+        let state = state();
+        let launcher = Arc::new(FakeLauncher::default());
+
+        state.start(launcher.clone()).await.unwrap();
+        launcher.handle.exited.store(true, Ordering::SeqCst);
+        assert_eq!(state.status().await, SidecarStatus::Errored);
+
+        assert_eq!(
+            state.start(launcher.clone()).await,
+            Ok(SidecarStatus::Running)
+        );
+        assert_eq!(state.status().await, SidecarStatus::Running);
+    }
+
+    #[tokio::test]
+    async fn test_failed_launch_errors() {
+        let state = state();
+        let launcher = Arc::new(FakeLauncher {
+            fail: true,
+            ..FakeLauncher::default()
+        });
+
+        assert!(state.start(launcher.clone()).await.is_err());
+        assert_eq!(state.status().await, SidecarStatus::Errored);
+        assert_eq!(state.stop().await, SidecarStatus::Stopped);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_stop_during_start_shuts_down_new_process() {
+        struct StoppingLauncher {
+            state: Arc<SidecarState>,
+            inner: FakeLauncher,
+        }
+
+        impl SidecarLauncher for StoppingLauncher {
+            fn launch(&self) -> Result<Box<dyn SidecarProcess>, String> {
+                // A stop arrives from another task while the process is being spawned
+                let (tx, rx) = std::sync::mpsc::channel();
+                let state = Arc::clone(&self.state);
+                tokio::spawn(async move { tx.send(state.stop().await) });
+                let stopped = rx
+                    .recv_timeout(Duration::from_secs(5))
+                    .map_err(|e| e.to_string())?;
+                assert_eq!(stopped, SidecarStatus::Stopped);
+                self.inner.launch()
+            }
+        }
+
+        let state = Arc::new(state());
+        let launcher = Arc::new(StoppingLauncher {
+            state: Arc::clone(&state),
+            inner: FakeLauncher::default(),
+        });
 
-    #[test]
-    fn test_sidecar_state_initialization() {
-        let state = SidecarState::new();
-        assert_eq!(*state.status.lock().unwrap(), SidecarStatus::Stopped);
-        assert!(state.child.lock().unwrap().is_none());
+        assert_eq!(
+            state.start(launcher.clone()).await,
+            Ok(SidecarStatus::Stopped)
+        );
+        assert_eq!(state.status().await, SidecarStatus::Stopped);
+        assert_eq!(launcher.inner.handle.signals(), vec!["term"]);
     }
 }
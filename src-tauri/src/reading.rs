@@ -0,0 +1,225 @@
+//! Reading sessions and statistics
+//!
+//! The reader opens a session when a book is shown, sends a heartbeat with its
+//! position every so often and ends the session when the book is closed. Each
+//! heartbeat extends the session to now and adds the words between the
+//! previous and new position. A heartbeat after a long pause splits the session
+//! instead, so a book left open overnight doesn't count as hours of reading.
+//! All statistics are aggregated from the session table.
+
+use crate::db::postgres::DbError;
+use crate::db::{self, FinishedBook, ReadingDay, ReadingSession};
+use crate::locator::{self, Locator, LocatorInput};
+use crate::text::{char_slice, BookText};
+use crate::types::TypeValidationError;
+use chrono::NaiveDate;
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+
+/// A heartbeat this long after the previous one starts a new session
+pub const IDLE_TIMEOUT_SECS: i64 = 300;
+
+/// Faster than this, the words between two positions were skipped, not read
+const MAX_WORDS_PER_MINUTE: i64 = 1200;
+
+/// Reaching this share of a book in a session counts as finishing it
+pub const FINISHED_PROGRESSION: f64 = 0.98;
+
+#[derive(Debug, Error)]
+pub enum SessionError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+
+    #[error(transparent)]
+    Locator(#[from] TypeValidationError),
+
+    #[error("Reading session {0} has already ended")]
+    Ended(i64),
+}
+
+/// Words in the text between two char offsets; none when moving backwards
+pub fn words_between(text: &BookText, from: usize, to: usize) -> usize {
+    if to <= from {
+        return 0;
+    }
+    char_slice(&text.text, from, to).split_whitespace().count()
+}
+
+/// Words read moving from one position to another over `elapsed_secs`. Jumps
+/// (the table of contents, a search result) are too fast to be reading and
+/// count as nothing.
+pub fn words_covered(text: &BookText, from: &Locator, to: &Locator, elapsed_secs: i64) -> i64 {
+    let words =
+        i64::try_from(words_between(text, from.offset(text), to.offset(text))).unwrap_or(i64::MAX);
+    let limit = MAX_WORDS_PER_MINUTE * elapsed_secs.max(1) / 60;
+    if words > limit {
+        0
+    } else {
+        words
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Streaks {
+    /// Consecutive days up to today, or up to yesterday when today has no
+    /// reading yet
+    pub current: u32,
+    pub longest: u32,
+}
+
+/// Streaks of consecutive reading days; `days` must be sorted and distinct
+pub fn streaks(days: &[NaiveDate], today: NaiveDate) -> Streaks {
+    let mut result = Streaks::default();
+    let mut run = 0;
+    let mut previous: Option<NaiveDate> = None;
+    for &day in days {
+        run = match previous {
+            Some(prev) if prev.succ_opt() == Some(day) => run + 1,
+            _ => 1,
+        };
+        result.longest = result.longest.max(run);
+        previous = Some(day);
+    }
+    let yesterday = today.pred_opt();
+    if previous.is_some_and(|last| last == today || Some(last) == yesterday) {
+        result.current = run;
+    }
+    result
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn words_per_minute(words: i64, seconds: i64) -> Option<f64> {
+    (seconds > 0).then(|| words as f64 * 60.0 / seconds as f64)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadingStats {
+    /// Days with reading in the range, oldest first
+    pub days: Vec<ReadingDay>,
+    pub total_seconds: i64,
+    pub total_words: i64,
+    pub words_per_minute: Option<f64>,
+    pub books_finished: Vec<FinishedBook>,
+    /// Over all sessions, regardless of the range
+    pub streaks: Streaks,
+}
+
+/// Applies a heartbeat (`end == false`) or the end of a session to `session`.
+/// Returns the session now in progress, which is a new one when the previous
+/// heartbeat was more than `IDLE_TIMEOUT_SECS` ago. Without a position the
+/// session keeps its last one.
+pub async fn record_progress(
+    pool: &Pool<Postgres>,
+    text: &BookText,
+    session: &ReadingSession,
+    position: Option<&LocatorInput>,
+    end: bool,
+) -> Result<ReadingSession, SessionError> {
+    let id = session.id.get();
+    if session.ended {
+        return Err(SessionError::Ended(id));
+    }
+    let locator = position.map_or_else(
+        || Ok(session.end_locator.clone()),
+        |input| locator::resolve(text, input),
+    )?;
+
+    if session.idle_secs > IDLE_TIMEOUT_SECS {
+        // The session ends at its last heartbeat; the pause isn't reading
+        let closed = db::end_reading_session(pool, id).await?;
+        if end {
+            return Ok(closed);
+        }
+        println!(
+            "[Reading] Session {id} idle for {}s, starting a new one",
+            session.idle_secs
+        );
+        return Ok(db::start_reading_session(pool, session.book_id.get(), &locator).await?);
+    }
+
+    let words = words_covered(text, &session.end_locator, &locator, session.idle_secs);
+    Ok(db::extend_reading_session(pool, id, &locator, words, end).await?)
+}
+
+/// Reading statistics for the days `from..=to` (either end open) in the given
+/// IANA time zone. Sessions count towards the day they started on.
+pub async fn reading_stats(
+    pool: &Pool<Postgres>,
+    timezone: &str,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Result<ReadingStats, DbError> {
+    let days = db::list_reading_days(pool, timezone, from, to).await?;
+    let books_finished =
+        db::list_finished_books(pool, timezone, from, to, FINISHED_PROGRESSION).await?;
+    let reading_dates = db::list_reading_dates(pool, timezone).await?;
+    let today = db::local_today(pool, timezone).await?;
+
+    let total_seconds = days.iter().map(|d| d.seconds).sum();
+    let total_words = days.iter().map(|d| d.words).sum();
+    Ok(ReadingStats {
+        days,
+        total_seconds,
+        total_words,
+        words_per_minute: words_per_minute(total_words, total_seconds),
+        books_finished,
+        streaks: streaks(&reading_dates, today),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::html_to_book_text;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_words_covered_ignores_jumps() {
+        let text = html_to_book_text(
+            "<html><body><p>It was the best of times, it was the worst of times, it was \
+             the age of wisdom, it was the age of foolishness, it was the epoch of belief, \
+             it was the epoch of incredulity.</p></body></html>",
+        );
+        let at = |offset| Locator::at_offset(&text, offset, None);
+        let start = at(0);
+        let middle = at(26);
+        let end = at(text.char_len());
+
+        assert_eq!(words_between(&text, 0, 26), 6);
+        assert_eq!(words_covered(&text, &start, &middle, 30), 6);
+        assert_eq!(words_covered(&text, &middle, &start, 30), 0);
+        assert_eq!(words_covered(&text, &start, &end, 60), 36);
+        // Thirty-six words in a second is a jump
+        assert_eq!(words_covered(&text, &start, &end, 1), 0);
+    }
+
+    #[test]
+    fn test_streaks() {
+        let days = [
+            date("2024-05-01"),
+            date("2024-05-02"),
+            date("2024-05-03"),
+            date("2024-05-10"),
+            date("2024-05-11"),
+        ];
+        assert_eq!(
+            streaks(&days, date("2024-05-12")),
+            Streaks {
+                current: 2,
+                longest: 3
+            }
+        );
+        assert_eq!(streaks(&days, date("2024-05-13")).current, 0);
+        assert_eq!(streaks(&[], date("2024-05-13")), Streaks::default());
+    }
+
+    #[test]
+    fn test_words_per_minute() {
+        assert!(words_per_minute(500, 120).is_some_and(|wpm| (wpm - 250.0).abs() < f64::EPSILON));
+        assert_eq!(words_per_minute(10, 0), None);
+    }
+}
@@ -0,0 +1,906 @@
+//! Sync of reading positions, bookmarks, highlights and chats between devices
+//!
+//! Every synced record has a key that is the same on all devices — the
+//! Gutenberg ID for a book's position, a `sync_key` UUID for everything else —
+//! and a change time per field, kept by database triggers. Trashing a record
+//! is an ordinary field change; purging it leaves a tombstone. A sync pulls
+//! what other devices pushed since our cursor, merges it field by field (the
+//! later change wins), then pushes our own changes since the last sync.
+//!
+//! Remotes are either an HTTP endpoint or a shared folder (a network drive or
+//! a synced directory):
+//!
+//! - `POST {url}/push` takes a [`SyncBatch`] as JSON.
+//! - `GET {url}/pull?device={id}&since={cursor}` returns a [`SyncPull`] with
+//!   the records other devices pushed after `cursor` (empty for everything).
+//!   The cursor is opaque to the client.
+//! - A folder holds one directory per device with a JSON file per push; the
+//!   cursor remembers the last file read from each device.
+//!
+//! Records refer to parents by their sync keys, never by local IDs, and books
+//! are matched by Gutenberg ID. Records for books or parents not in this
+//! library are kept aside and retried on every sync until they can be applied.
+//!
+//! Highlight tags are not synced: a tag is a row of the `highlight_tag` join
+//! table with no key of its own, so it has no field-level change times to
+//! merge. Each device keeps its own tags.
+
+use crate::db::postgres::DbError;
+use crate::db::{self, SyncRemoteState};
+use chrono::{DateTime, Utc};
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{Pool, Postgres};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::fs;
+
+#[derive(Debug, Error)]
+pub enum SyncError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Invalid sync remote '{0}': expected an http(s) URL or an absolute folder path")]
+    InvalidRemote(String),
+
+    #[error("No sync table for entity '{0}'")]
+    UnknownEntity(&'static str),
+}
+
+/// Kinds of synced records, in the order parents must be applied before children
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncEntity {
+    Position,
+    Bookmark,
+    Highlight,
+    HighlightMessage,
+    Thread,
+    Message,
+}
+
+impl SyncEntity {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Position => "position",
+            Self::Bookmark => "bookmark",
+            Self::Highlight => "highlight",
+            Self::HighlightMessage => "highlight_message",
+            Self::Thread => "thread",
+            Self::Message => "message",
+        }
+    }
+
+    pub fn table(self) -> Result<&'static SyncTable, SyncError> {
+        SYNC_TABLES
+            .iter()
+            .find(|table| table.entity == self)
+            .ok_or(SyncError::UnknownEntity(self.as_str()))
+    }
+}
+
+/// A column referring to another record, synced as that record's key
+#[derive(Debug)]
+pub struct SyncParent {
+    pub column: &'static str,
+    pub table: &'static str,
+    /// Column of the parent that identifies it across devices
+    pub key: &'static str,
+}
+
+/// How a synced entity is stored
+#[derive(Debug)]
+pub struct SyncTable {
+    pub entity: SyncEntity,
+    pub table: &'static str,
+    /// Key column; `None` when the record is keyed by its first parent
+    pub key: Option<&'static str>,
+    pub parents: &'static [SyncParent],
+    pub fields: &'static [&'static str],
+    /// Change time for fields the triggers haven't stamped yet
+    pub stamp: &'static str,
+}
+
+impl SyncTable {
+    /// Fields and parent columns, as they appear in a record
+    pub fn columns(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.fields
+            .iter()
+            .copied()
+            .chain(self.parents.iter().map(|parent| parent.column))
+    }
+}
+
+const BOOK: SyncParent = SyncParent {
+    column: "book_id",
+    table: "book",
+    key: "gutenberg_id",
+};
+
+pub const SYNC_TABLES: [SyncTable; 6] = [
+    SyncTable {
+        entity: SyncEntity::Position,
+        table: "book_position",
+        key: None,
+        parents: &[BOOK],
+        fields: &[
+            "cfi",
+            "chapter_index",
+            "chapter_title",
+            "chapter_offset",
+            "progression",
+            "quote",
+        ],
+        stamp: "updated_at",
+    },
+    SyncTable {
+        entity: SyncEntity::Bookmark,
+        table: "bookmark",
+        key: Some("sync_key"),
+        parents: &[BOOK],
+        fields: &["locator", "label", "color", "created_at"],
+        stamp: "updated_at",
+    },
+    SyncTable {
+        entity: SyncEntity::Highlight,
+        table: "highlight",
+        key: Some("sync_key"),
+        parents: &[BOOK],
+        fields: &[
+            "start_path",
+            "start_offset",
+            "end_path",
+            "end_offset",
+            "text",
+            "note",
+            "color",
+            "style",
+            "quote_prefix",
+            "quote_suffix",
+            "text_start",
+            "text_end",
+            "created_at",
+            "deleted_at",
+        ],
+        stamp: "updated_at",
+    },
+    SyncTable {
+        entity: SyncEntity::HighlightMessage,
+        table: "highlight_message",
+        key: Some("sync_key"),
+        parents: &[SyncParent {
+            column: "highlight_id",
+            table: "highlight",
+            key: "sync_key",
+        }],
+        fields: &["role", "content", "created_at"],
+        stamp: "created_at",
+    },
+    SyncTable {
+        entity: SyncEntity::Thread,
+        table: "book_chat_thread",
+        key: Some("sync_key"),
+        parents: &[BOOK],
+        fields: &["title", "last_cfi", "created_at", "deleted_at"],
+        stamp: "updated_at",
+    },
+    SyncTable {
+        entity: SyncEntity::Message,
+        table: "book_message",
+        key: Some("sync_key"),
+        parents: &[
+            BOOK,
+            SyncParent {
+                column: "thread_id",
+                table: "book_chat_thread",
+                key: "sync_key",
+            },
+        ],
+        fields: &[
+            "role",
+            "content",
+            "reasoning_summary",
+            "context_map",
+            "created_at",
+            "deleted_at",
+        ],
+        stamp: "created_at",
+    },
+];
+
+// ============================================================================
+// Wire format
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldValue {
+    pub value: Value,
+    pub changed_at: DateTime<Utc>,
+}
+
+/// A record's current state, or its tombstone when `deleted_at` is set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncRecord {
+    pub entity: SyncEntity,
+    pub key: String,
+    #[serde(default)]
+    pub fields: BTreeMap<String, FieldValue>,
+    #[serde(default)]
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncBatch {
+    pub device: String,
+    pub records: Vec<SyncRecord>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncPull {
+    pub records: Vec<SyncRecord>,
+    pub cursor: String,
+}
+
+// ============================================================================
+// Merging
+// ============================================================================
+
+/// A record as stored here: no fields when it doesn't exist
+#[derive(Debug, Clone, Default)]
+pub struct LocalRecord {
+    pub fields: BTreeMap<String, FieldValue>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    Local,
+    Remote,
+}
+
+/// Both devices changed the same thing since the last sync.
+/// `field` is `None` when one side deleted the record; a `None` value means
+/// that side's record is deleted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SyncConflict {
+    pub entity: SyncEntity,
+    pub key: String,
+    pub field: Option<String>,
+    pub local: Option<Value>,
+    pub remote: Option<Value>,
+    pub kept: Resolution,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeAction {
+    Keep,
+    /// Write these fields, creating the record if it doesn't exist here
+    Write(BTreeMap<String, FieldValue>),
+    Delete(DateTime<Utc>),
+}
+
+fn record_value(fields: &BTreeMap<String, FieldValue>) -> Value {
+    Value::Object(
+        fields
+            .iter()
+            .map(|(name, field)| (name.clone(), field.value.clone()))
+            .collect(),
+    )
+}
+
+fn latest_change(fields: &BTreeMap<String, FieldValue>) -> Option<DateTime<Utc>> {
+    fields.values().map(|field| field.changed_at).max()
+}
+
+/// Whether the remote value wins; equal times fall back to comparing values
+/// so every device settles on the same one
+fn remote_wins(ours: &FieldValue, theirs: &FieldValue) -> bool {
+    let rendered = |field: &FieldValue| field.value.to_string();
+    theirs.changed_at > ours.changed_at
+        || (theirs.changed_at == ours.changed_at && rendered(theirs) > rendered(ours))
+}
+
+/// Merges a pulled record into the local one, field by field with the later
+/// change winning. Changes on both sides since `last_sync` are reported as
+/// conflicts, whichever side was kept.
+pub fn merge_record(
+    local: &LocalRecord,
+    remote: &SyncRecord,
+    last_sync: Option<DateTime<Utc>>,
+) -> (MergeAction, Vec<SyncConflict>) {
+    let since_sync = |at: DateTime<Utc>| last_sync.is_none_or(|last| at > last);
+    let mut conflicts = Vec::new();
+    let mut report = |field: Option<&str>, ours: Option<Value>, theirs: Option<Value>, kept| {
+        conflicts.push(SyncConflict {
+            entity: remote.entity,
+            key: remote.key.clone(),
+            field: field.map(str::to_string),
+            local: ours,
+            remote: theirs,
+            kept,
+        });
+    };
+
+    if let Some(deleted_at) = remote.deleted_at {
+        let Some(edited_at) = latest_change(&local.fields) else {
+            return (MergeAction::Keep, conflicts);
+        };
+        if edited_at <= deleted_at {
+            return (MergeAction::Delete(deleted_at), conflicts);
+        }
+        if since_sync(edited_at) {
+            report(
+                None,
+                Some(record_value(&local.fields)),
+                None,
+                Resolution::Local,
+            );
+        }
+        return (MergeAction::Keep, conflicts);
+    }
+
+    if local.fields.is_empty() {
+        if let Some(deleted_at) = local.deleted_at {
+            if latest_change(&remote.fields).is_none_or(|edited_at| edited_at <= deleted_at) {
+                return (MergeAction::Keep, conflicts);
+            }
+            if since_sync(deleted_at) {
+                report(
+                    None,
+                    None,
+                    Some(record_value(&remote.fields)),
+                    Resolution::Remote,
+                );
+            }
+        }
+        return (MergeAction::Write(remote.fields.clone()), conflicts);
+    }
+
+    let mut patch = BTreeMap::new();
+    for (name, theirs) in &remote.fields {
+        let Some(ours) = local.fields.get(name) else {
+            patch.insert(name.clone(), theirs.clone());
+            continue;
+        };
+        if ours.value == theirs.value {
+            continue;
+        }
+        let wins = remote_wins(ours, theirs);
+        if since_sync(ours.changed_at) && since_sync(theirs.changed_at) {
+            let kept = if wins {
+                Resolution::Remote
+            } else {
+                Resolution::Local
+            };
+            report(
+                Some(name),
+                Some(ours.value.clone()),
+                Some(theirs.value.clone()),
+                kept,
+            );
+        }
+        if wins {
+            patch.insert(name.clone(), theirs.clone());
+        }
+    }
+    let action = if patch.is_empty() {
+        MergeAction::Keep
+    } else {
+        MergeAction::Write(patch)
+    };
+    (action, conflicts)
+}
+
+// ============================================================================
+// Remotes
+// ============================================================================
+
+#[derive(Debug, Clone)]
+pub enum SyncRemote {
+    Http { url: Url, token: Option<String> },
+    Folder(PathBuf),
+}
+
+impl SyncRemote {
+    /// Parses the `sync_remote` setting; `token` is sent as a bearer token
+    pub fn parse(value: &str, token: Option<String>) -> Result<Self, SyncError> {
+        let value = value.trim();
+        if value.starts_with("http://") || value.starts_with("https://") {
+            // A trailing slash makes `push` and `pull` resolve under the path
+            let mut url =
+                Url::parse(value).map_err(|_| SyncError::InvalidRemote(value.to_string()))?;
+            if !url.path().ends_with('/') {
+                url.set_path(&format!("{}/", url.path()));
+            }
+            let token = token.filter(|token| !token.trim().is_empty());
+            return Ok(Self::Http { url, token });
+        }
+        let path = PathBuf::from(value);
+        if value.is_empty() || !path.is_absolute() {
+            return Err(SyncError::InvalidRemote(value.to_string()));
+        }
+        Ok(Self::Folder(path))
+    }
+
+    /// Identifies the remote in the sync state table
+    pub fn id(&self) -> String {
+        match self {
+            Self::Http { url, .. } => url.to_string(),
+            Self::Folder(path) => path.display().to_string(),
+        }
+    }
+
+    pub async fn push(&self, client: &Client, batch: &SyncBatch) -> Result<(), SyncError> {
+        match self {
+            Self::Http { url, token } => {
+                let mut request = client.post(endpoint(url, "push")?).json(batch);
+                if let Some(token) = token {
+                    request = request.bearer_auth(token);
+                }
+                request.send().await?.error_for_status()?;
+            }
+            Self::Folder(root) => {
+                let dir = root.join(&batch.device);
+                fs::create_dir_all(&dir).await?;
+                // Zero-padded so file names sort in push order
+                let name = format!("{:020}.json", Utc::now().timestamp_micros());
+                let partial = dir.join(format!("{name}.part"));
+                fs::write(&partial, serde_json::to_vec(batch)?).await?;
+                fs::rename(partial, dir.join(name)).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn pull(
+        &self,
+        client: &Client,
+        device: &str,
+        cursor: Option<&str>,
+    ) -> Result<SyncPull, SyncError> {
+        match self {
+            Self::Http { url, token } => {
+                let mut request = client
+                    .get(endpoint(url, "pull")?)
+                    .query(&[("device", device), ("since", cursor.unwrap_or_default())]);
+                if let Some(token) = token {
+                    request = request.bearer_auth(token);
+                }
+                Ok(request.send().await?.error_for_status()?.json().await?)
+            }
+            Self::Folder(root) => pull_folder(root, device, cursor).await,
+        }
+    }
+}
+
+/// HTTP client shared by every sync, kept in managed state
+#[derive(Debug, Clone)]
+pub struct SyncClient {
+    client: Client,
+}
+
+impl SyncClient {
+    pub fn new() -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_mins(1))
+            .build()
+            .unwrap_or_default();
+        Self { client }
+    }
+}
+
+impl Default for SyncClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn endpoint(url: &Url, path: &str) -> Result<Url, SyncError> {
+    url.join(path)
+        .map_err(|_| SyncError::InvalidRemote(url.to_string()))
+}
+
+/// Reads other devices' pushes newer than the cursor, a JSON map of device
+/// to the last file read
+async fn pull_folder(
+    root: &Path,
+    device: &str,
+    cursor: Option<&str>,
+) -> Result<SyncPull, SyncError> {
+    let mut read: BTreeMap<String, String> = match cursor {
+        Some(cursor) if !cursor.is_empty() => serde_json::from_str(cursor)?,
+        _ => BTreeMap::new(),
+    };
+    let mut records = Vec::new();
+    if fs::metadata(root).await.is_ok_and(|meta| meta.is_dir()) {
+        let mut devices = fs::read_dir(root).await?;
+        while let Some(entry) = devices.next_entry().await? {
+            let other = entry.file_name().to_string_lossy().into_owned();
+            if other == device || !entry.file_type().await?.is_dir() {
+                continue;
+            }
+            let mut files = Vec::new();
+            let mut pushes = fs::read_dir(entry.path()).await?;
+            while let Some(file) = pushes.next_entry().await? {
+                let name = file.file_name().to_string_lossy().into_owned();
+                let unread = Path::new(&name)
+                    .extension()
+                    .is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+                    && read
+                        .get(&other)
+                        .is_none_or(|last| name.as_str() > last.as_str());
+                if unread {
+                    files.push(name);
+                }
+            }
+            files.sort();
+            for name in files {
+                let batch: SyncBatch =
+                    serde_json::from_slice(&fs::read(entry.path().join(&name)).await?)?;
+                records.extend(batch.records);
+                read.insert(other.clone(), name);
+            }
+        }
+    }
+    Ok(SyncPull {
+        records,
+        cursor: serde_json::to_string(&read)?,
+    })
+}
+
+// ============================================================================
+// Sync
+// ============================================================================
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncReport {
+    /// Records received from other devices
+    pub pulled: usize,
+    /// Pulled records that changed something here
+    pub applied: usize,
+    /// Records waiting for a book or parent not in this library
+    pub skipped: usize,
+    pub pushed: usize,
+    pub conflicts: Vec<SyncConflict>,
+}
+
+/// Records left waiting by earlier syncs followed by newly pulled ones, one
+/// per key with the latest pull winning, parents before children
+fn with_pending(pending: Vec<SyncRecord>, pulled: Vec<SyncRecord>) -> Vec<SyncRecord> {
+    let mut latest = BTreeMap::new();
+    for record in pending.into_iter().chain(pulled) {
+        latest.insert((record.entity, record.key.clone()), record);
+    }
+    latest.into_values().collect()
+}
+
+/// Pulls and merges other devices' changes, then pushes ours
+pub async fn sync(
+    pool: &Pool<Postgres>,
+    SyncClient { client }: &SyncClient,
+    remote: &SyncRemote,
+) -> Result<SyncReport, SyncError> {
+    let device = db::sync_device_id(pool).await?;
+    let remote_id = remote.id();
+    let SyncRemoteState { cursor, synced_at } = db::get_sync_remote(pool, &remote_id).await?;
+    // Change times come from the database clock, so the cursor must too
+    let started_at = db::database_now(pool).await?;
+    let mut report = SyncReport::default();
+
+    let pull = remote.pull(client, &device, cursor.as_deref()).await?;
+    report.pulled = pull.records.len();
+    let pending = db::list_sync_pending(pool, &remote_id).await?;
+    let mut waiting = Vec::new();
+    for record in with_pending(pending, pull.records) {
+        let table = record.entity.table()?;
+        let local = db::load_sync_record(pool, table, &record.key).await?;
+        let (action, conflicts) = merge_record(&local, &record, synced_at);
+        report.conflicts.extend(conflicts);
+        match action {
+            MergeAction::Keep => {}
+            MergeAction::Write(fields) => {
+                if db::apply_sync_fields(pool, table, &record.key, &fields, &remote_id).await? {
+                    report.applied += 1;
+                } else {
+                    waiting.push(record);
+                }
+            }
+            MergeAction::Delete(deleted_at) => {
+                db::apply_sync_delete(pool, table, &record.key, deleted_at, &remote_id).await?;
+                report.applied += 1;
+            }
+        }
+    }
+
+    let mut records = Vec::new();
+    for table in &SYNC_TABLES {
+        records.extend(db::list_sync_changes(pool, table, synced_at).await?);
+    }
+    if !records.is_empty() {
+        report.pushed = records.len();
+        remote.push(client, &SyncBatch { device, records }).await?;
+    }
+
+    report.skipped = waiting.len();
+    db::save_sync_pending(pool, &remote_id, &waiting).await?;
+    db::save_sync_remote(pool, &remote_id, &pull.cursor, started_at).await?;
+    if !report.conflicts.is_empty() {
+        println!(
+            "[Sync] {} conflicting change(s) with {remote_id}",
+            report.conflicts.len()
+        );
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + seconds, 0).unwrap()
+    }
+
+    fn fields(values: &[(&str, Value, i64)]) -> BTreeMap<String, FieldValue> {
+        values
+            .iter()
+            .map(|(name, value, seconds)| {
+                let field = FieldValue {
+                    value: value.clone(),
+                    changed_at: at(*seconds),
+                };
+                ((*name).to_string(), field)
+            })
+            .collect()
+    }
+
+    fn highlight(key: &str, note: &str) -> SyncRecord {
+        SyncRecord {
+            entity: SyncEntity::Highlight,
+            key: key.to_string(),
+            fields: fields(&[("note", Value::from(note), 10)]),
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn test_merges_fields_by_last_writer() {
+        let local = LocalRecord {
+            fields: fields(&[
+                ("note", Value::from("mine"), 30),
+                ("color", Value::from("yellow"), 5),
+                ("text", Value::from("same"), 5),
+            ]),
+            deleted_at: None,
+        };
+        let remote = SyncRecord {
+            entity: SyncEntity::Highlight,
+            key: "h1".to_string(),
+            fields: fields(&[
+                ("note", Value::from("theirs"), 20),
+                ("color", Value::from("blue"), 25),
+                ("text", Value::from("same"), 25),
+            ]),
+            deleted_at: None,
+        };
+
+        let (action, conflicts) = merge_record(&local, &remote, Some(at(15)));
+        let MergeAction::Write(patch) = action else {
+            panic!("expected a write, got {action:?}");
+        };
+        assert_eq!(patch.keys().collect::<Vec<_>>(), ["color"]);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].field.as_deref(), Some("note"));
+        assert_eq!(conflicts[0].kept, Resolution::Local);
+
+        // Only the remote changed the note since the last sync: no conflict
+        let (_, conflicts) = merge_record(&local, &remote, Some(at(25)));
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn test_merges_deletes_against_edits() {
+        let local = LocalRecord {
+            fields: fields(&[("note", Value::from("edited"), 30)]),
+            deleted_at: None,
+        };
+        let mut deleted = highlight("h1", "");
+        deleted.fields.clear();
+
+        deleted.deleted_at = Some(at(40));
+        assert_eq!(
+            merge_record(&local, &deleted, Some(at(0))),
+            (MergeAction::Delete(at(40)), Vec::new())
+        );
+
+        // Edited after the other device deleted it: kept, and reported
+        deleted.deleted_at = Some(at(20));
+        let (action, conflicts) = merge_record(&local, &deleted, Some(at(0)));
+        assert_eq!(action, MergeAction::Keep);
+        assert_eq!(conflicts[0].field, None);
+        assert_eq!(conflicts[0].remote, None);
+
+        // Deleted here after the remote's last edit: stays deleted
+        let gone = LocalRecord {
+            fields: BTreeMap::new(),
+            deleted_at: Some(at(50)),
+        };
+        assert_eq!(
+            merge_record(&gone, &highlight("h1", "x"), None),
+            (MergeAction::Keep, Vec::new())
+        );
+        let (action, _) = merge_record(&LocalRecord::default(), &highlight("h2", "x"), None);
+        assert!(matches!(action, MergeAction::Write(fields) if fields.len() == 1));
+    }
+
+    #[test]
+    fn test_retries_pending_records_before_new_pulls() {
+        let mut message = highlight("m1", "");
+        message.entity = SyncEntity::HighlightMessage;
+        let pending = vec![message, highlight("h1", "old")];
+        let pulled = vec![highlight("h1", "new"), highlight("h2", "other")];
+
+        let records = with_pending(pending, pulled);
+        let order: Vec<_> = records
+            .iter()
+            .map(|record| (record.entity, record.key.as_str()))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                (SyncEntity::Highlight, "h1"),
+                (SyncEntity::Highlight, "h2"),
+                (SyncEntity::HighlightMessage, "m1"),
+            ]
+        );
+        assert_eq!(records[0].fields["note"].value, Value::from("new"));
+    }
+
+    #[test]
+    fn test_parses_remotes() {
+        let remote =
+            SyncRemote::parse("https://sync.example.com/reader", Some(" ".into())).unwrap();
+        let SyncRemote::Http { url, token } = &remote else {
+            panic!("expected an HTTP remote");
+        };
+        assert_eq!(
+            endpoint(url, "push").unwrap().as_str(),
+            "https://sync.example.com/reader/push"
+        );
+        assert!(token.is_none());
+        assert!(matches!(
+            SyncRemote::parse("/mnt/share/reader", None),
+            Ok(SyncRemote::Folder(_))
+        ));
+        assert!(SyncRemote::parse("relative/dir", None).is_err());
+        assert!(SyncRemote::parse("", None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_exchanges_through_a_folder() {
+        let dir = std::env::temp_dir().join(format!("sync-test-{}", std::process::id()));
+        let remote = SyncRemote::Folder(dir.clone());
+        let client = Client::new();
+
+        let batch = SyncBatch {
+            device: "a".to_string(),
+            records: vec![highlight("h1", "first")],
+        };
+        remote.push(&client, &batch).await.unwrap();
+
+        let own = remote.pull(&client, "a", None).await.unwrap();
+        assert!(own.records.is_empty());
+        let pull = remote.pull(&client, "b", None).await.unwrap();
+        assert_eq!(pull.records, batch.records);
+        let again = remote.pull(&client, "b", Some(&pull.cursor)).await.unwrap();
+        assert!(again.records.is_empty());
+
+        fs::remove_dir_all(dir).await.unwrap();
+    }
+
+    /// Stand-in for a sync server: an append-only log of pushes, pulled by
+    /// position in the log
+    async fn serve(log: Arc<Mutex<Vec<SyncBatch>>>) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        let base = url.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 4096];
+                let (head, body) = loop {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request).into_owned();
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let length = head
+                            .lines()
+                            .find_map(|line| {
+                                line.to_ascii_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|value| value.trim().parse::<usize>().unwrap())
+                            })
+                            .unwrap_or(0);
+                        if body.len() >= length {
+                            break (head.to_string(), body.to_string());
+                        }
+                    }
+                };
+                let target = head.split(' ').nth(1).unwrap().to_string();
+                let target = base.join(&target).unwrap();
+                let response = if target.path() == "/push" {
+                    log.lock()
+                        .unwrap()
+                        .push(serde_json::from_str(&body).unwrap());
+                    String::new()
+                } else {
+                    let query: BTreeMap<_, _> = target.query_pairs().into_owned().collect();
+                    let since = query["since"].parse().unwrap_or(0);
+                    let pull = {
+                        let log = log.lock().unwrap();
+                        SyncPull {
+                            records: log
+                                .iter()
+                                .skip(since)
+                                .filter(|batch| batch.device != query["device"])
+                                .flat_map(|batch| batch.records.clone())
+                                .collect(),
+                            cursor: log.len().to_string(),
+                        }
+                    };
+                    serde_json::to_string(&pull).unwrap()
+                };
+                let reply = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{response}",
+                    response.len()
+                );
+                stream.write_all(reply.as_bytes()).await.unwrap();
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_exchanges_through_a_server() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let url = serve(log.clone()).await;
+        let remote = SyncRemote::parse(url.as_str(), Some("secret".into())).unwrap();
+        let client = Client::new();
+
+        let batch = SyncBatch {
+            device: "a".to_string(),
+            records: vec![highlight("h1", "first"), highlight("h2", "second")],
+        };
+        remote.push(&client, &batch).await.unwrap();
+        assert_eq!(log.lock().unwrap().len(), 1);
+
+        let pull = remote.pull(&client, "b", None).await.unwrap();
+        assert_eq!(pull.records, batch.records);
+        assert_eq!(pull.cursor, "1");
+        assert!(remote
+            .pull(&client, "a", None)
+            .await
+            .unwrap()
+            .records
+            .is_empty());
+        let again = remote.pull(&client, "b", Some(&pull.cursor)).await.unwrap();
+        assert!(again.records.is_empty());
+    }
+}
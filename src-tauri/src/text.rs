@@ -0,0 +1,573 @@
+//! Plain-text view of extracted book HTML
+//!
+//! The reader renders the HTML produced by `books::extract_mobi_to_content`, but
+//! several backend features (narration, locators, anchoring) need the same content
+//! as plain text with a chapter outline. Offsets in this module are in `char`s.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// A heading found in the book, used as a chapter boundary
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Chapter {
+    pub index: usize,
+    pub title: String,
+    /// Heading level (1-6); fallback headings detected in plain text use 2
+    pub level: u8,
+    /// Char offset of the heading in `BookText::text`
+    pub start: usize,
+    /// Char offset where the next chapter starts (or the end of the text)
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BookText {
+    pub text: String,
+    pub chapters: Vec<Chapter>,
+    char_len: usize,
+}
+
+impl BookText {
+    /// Total length of `text` in chars
+    pub const fn char_len(&self) -> usize {
+        self.char_len
+    }
+
+    /// The chapter containing the given char offset
+    pub fn chapter_at(&self, offset: usize) -> Option<&Chapter> {
+        self.chapters
+            .iter()
+            .rev()
+            .find(|chapter| chapter.start <= offset)
+    }
+
+    /// Text of the chapter with the given index
+    pub fn chapter_text(&self, index: usize) -> Option<&str> {
+        let chapter = self.chapters.get(index)?;
+        Some(char_slice(&self.text, chapter.start, chapter.end))
+    }
+}
+
+/// Slices `s` by char offsets, clamping to the string length
+pub fn char_slice(s: &str, start: usize, end: usize) -> &str {
+    let byte_at = |n: usize| s.char_indices().nth(n).map_or(s.len(), |(i, _)| i);
+    let start_byte = byte_at(start);
+    let end_byte = byte_at(end.max(start));
+    &s[start_byte..end_byte]
+}
+
+// ============================================================================
+// CACHE
+// ============================================================================
+
+/// How many books' text `BookTextCache` keeps
+const CACHED_BOOKS: usize = 4;
+
+/// Managed state keeping the text of the last few books read, so frequent
+/// calls like position saves don't re-parse the whole book. Entries are keyed
+/// by the book's content hash, so a new edition is parsed again.
+#[derive(Debug, Default)]
+pub struct BookTextCache {
+    books: Mutex<VecDeque<CachedText>>,
+}
+
+#[derive(Debug)]
+struct CachedText {
+    book_id: i64,
+    content_hash: Option<String>,
+    text: Arc<BookText>,
+}
+
+impl BookTextCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The cached text of a book, if it was cached for the same content
+    pub async fn get(&self, book_id: i64, content_hash: Option<&str>) -> Option<Arc<BookText>> {
+        self.books
+            .lock()
+            .await
+            .iter()
+            .find(|cached| {
+                cached.book_id == book_id && cached.content_hash.as_deref() == content_hash
+            })
+            .map(|cached| Arc::clone(&cached.text))
+    }
+
+    /// Caches a book's text, dropping the least recently cached book when full
+    pub async fn insert(&self, book_id: i64, content_hash: Option<String>, text: Arc<BookText>) {
+        let mut books = self.books.lock().await;
+        books.retain(|cached| cached.book_id != book_id);
+        if books.len() >= CACHED_BOOKS {
+            books.pop_front();
+        }
+        books.push_back(CachedText {
+            book_id,
+            content_hash,
+            text,
+        });
+    }
+}
+
+// ============================================================================
+// HTML TO TEXT
+// ============================================================================
+
+const SKIPPED_ELEMENTS: &[&str] = &["head", "script", "style", "title"];
+
+const BLOCK_ELEMENTS: &[&str] = &[
+    "p",
+    "div",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "li",
+    "ul",
+    "ol",
+    "tr",
+    "table",
+    "blockquote",
+    "pre",
+    "section",
+    "hr",
+    "body",
+    "dl",
+    "dt",
+    "dd",
+];
+
+struct TextBuilder {
+    text: String,
+    char_len: usize,
+    pending_space: bool,
+}
+
+impl TextBuilder {
+    const fn new() -> Self {
+        Self {
+            text: String::new(),
+            char_len: 0,
+            pending_space: false,
+        }
+    }
+
+    fn push_char(&mut self, ch: char) {
+        self.text.push(ch);
+        self.char_len += 1;
+    }
+
+    fn push_text(&mut self, s: &str, preserve_whitespace: bool) {
+        for ch in s.chars() {
+            if preserve_whitespace {
+                if ch == '\r' {
+                    continue;
+                }
+                self.push_char(ch);
+                continue;
+            }
+            if ch.is_whitespace() && ch != '\u{a0}' {
+                self.pending_space = true;
+                continue;
+            }
+            if self.pending_space && !self.text.is_empty() && !self.text.ends_with('\n') {
+                self.push_char(' ');
+            }
+            self.pending_space = false;
+            self.push_char(ch);
+        }
+    }
+
+    fn line_break(&mut self) {
+        self.pending_space = false;
+        self.trim_trailing_spaces();
+        if !self.text.is_empty() && !self.text.ends_with('\n') {
+            self.push_char('\n');
+        }
+    }
+
+    fn paragraph_break(&mut self) {
+        self.line_break();
+        if !self.text.is_empty() && !self.text.ends_with("\n\n") {
+            self.push_char('\n');
+        }
+    }
+
+    fn trim_trailing_spaces(&mut self) {
+        while self.text.ends_with(' ') {
+            self.text.pop();
+            self.char_len -= 1;
+        }
+    }
+}
+
+/// Converts extracted book HTML to plain text with a chapter outline
+///
+/// Chapters come from `<h1>`–`<h3>` headings; books without headings (for example
+/// MOBI text wrapped in `<pre>`) fall back to lines such as `ACT I` or `CHAPTER 3`.
+#[allow(clippy::too_many_lines)]
+pub fn html_to_book_text(html: &str) -> BookText {
+    let mut out = TextBuilder::new();
+    let mut chapters: Vec<Chapter> = Vec::new();
+    let mut skip_depth: Option<String> = None;
+    let mut pre_depth = 0usize;
+    let mut heading: Option<(u8, usize, String)> = None;
+
+    let mut rest = html;
+    while !rest.is_empty() {
+        let Some(lt) = rest.find('<') else {
+            push_decoded(
+                &mut out,
+                &mut heading,
+                rest,
+                pre_depth > 0,
+                skip_depth.is_some(),
+            );
+            break;
+        };
+        if lt > 0 {
+            push_decoded(
+                &mut out,
+                &mut heading,
+                &rest[..lt],
+                pre_depth > 0,
+                skip_depth.is_some(),
+            );
+        }
+        rest = &rest[lt..];
+
+        if rest.starts_with("<!--") {
+            rest = rest.find("-->").map_or("", |end| &rest[end + 3..]);
+            continue;
+        }
+
+        let Some(gt) = rest.find('>') else {
+            break;
+        };
+        let tag = &rest[1..gt];
+        rest = &rest[gt + 1..];
+
+        let (closing, name) = parse_tag_name(tag);
+        if name.is_empty() {
+            continue;
+        }
+
+        if let Some(ref skipped) = skip_depth {
+            if closing && name == *skipped {
+                skip_depth = None;
+            }
+            continue;
+        }
+        if !closing && SKIPPED_ELEMENTS.contains(&name.as_str()) && !tag.ends_with('/') {
+            skip_depth = Some(name);
+            continue;
+        }
+
+        if name == "br" {
+            out.line_break();
+            if let Some((_, _, ref mut title)) = heading {
+                title.push(' ');
+            }
+            continue;
+        }
+
+        if name == "pre" {
+            if closing {
+                pre_depth = pre_depth.saturating_sub(1);
+            } else {
+                pre_depth += 1;
+            }
+        }
+
+        if let Some(level) = heading_level(&name) {
+            if closing {
+                if let Some((level, start, title)) = heading.take() {
+                    let title = collapse_whitespace(&title);
+                    if !title.is_empty() {
+                        chapters.push(Chapter {
+                            index: chapters.len(),
+                            title,
+                            level,
+                            start,
+                            end: 0,
+                        });
+                    }
+                }
+                out.paragraph_break();
+            } else {
+                out.paragraph_break();
+                heading = Some((level, out.char_len, String::new()));
+            }
+            continue;
+        }
+
+        if BLOCK_ELEMENTS.contains(&name.as_str()) {
+            if matches!(name.as_str(), "p" | "div" | "blockquote" | "pre" | "hr") {
+                out.paragraph_break();
+            } else {
+                out.line_break();
+            }
+        }
+    }
+
+    out.trim_trailing_spaces();
+    while out.text.ends_with('\n') {
+        out.text.pop();
+        out.char_len -= 1;
+    }
+
+    if chapters.is_empty() {
+        chapters = detect_plain_text_headings(&out.text);
+    }
+    let total = out.char_len;
+    let starts: Vec<usize> = chapters.iter().map(|c| c.start).collect();
+    for (i, chapter) in chapters.iter_mut().enumerate() {
+        chapter.end = starts
+            .get(i + 1)
+            .copied()
+            .unwrap_or(total)
+            .max(chapter.start);
+    }
+
+    BookText {
+        text: out.text,
+        chapters,
+        char_len: total,
+    }
+}
+
+fn push_decoded(
+    out: &mut TextBuilder,
+    heading: &mut Option<(u8, usize, String)>,
+    raw: &str,
+    preserve_whitespace: bool,
+    skipped: bool,
+) {
+    if skipped {
+        return;
+    }
+    let decoded = decode_entities(raw);
+    if let Some((_, _, ref mut title)) = heading {
+        title.push_str(&decoded);
+    }
+    out.push_text(&decoded, preserve_whitespace);
+}
+
+fn parse_tag_name(tag: &str) -> (bool, String) {
+    let (closing, body) = tag
+        .strip_prefix('/')
+        .map_or((false, tag), |stripped| (true, stripped));
+    let name: String = body
+        .chars()
+        .take_while(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    (closing, name)
+}
+
+fn heading_level(name: &str) -> Option<u8> {
+    match name {
+        "h1" => Some(1),
+        "h2" => Some(2),
+        "h3" => Some(3),
+        _ => None,
+    }
+}
+
+pub fn collapse_whitespace(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+const PLAIN_HEADING_WORDS: &[&str] = &[
+    "ACT", "SCENE", "CHAPTER", "BOOK", "PART", "CANTO", "PROLOGUE", "EPILOGUE",
+];
+
+fn detect_plain_text_headings(text: &str) -> Vec<Chapter> {
+    let mut chapters = Vec::new();
+    let mut offset = 0usize;
+    for line in text.split('\n') {
+        let trimmed = line.trim();
+        let first_word = trimmed
+            .split(|c: char| c.is_whitespace() || c == '.')
+            .next()
+            .unwrap_or("");
+        if trimmed.chars().count() <= 80 && PLAIN_HEADING_WORDS.contains(&first_word) {
+            let leading = line.chars().take_while(|c| c.is_whitespace()).count();
+            chapters.push(Chapter {
+                index: chapters.len(),
+                title: collapse_whitespace(trimmed),
+                level: 2,
+                start: offset + leading,
+                end: 0,
+            });
+        }
+        offset += line.chars().count() + 1;
+    }
+    chapters
+}
+
+// ============================================================================
+// ENTITIES
+// ============================================================================
+
+/// Decodes HTML character references (named, decimal and hex)
+pub fn decode_entities(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let end = rest
+            .char_indices()
+            .take(12)
+            .find(|&(_, c)| c == ';')
+            .map(|(i, _)| i);
+        let decoded = end.and_then(|end| decode_entity(&rest[1..end]).map(|ch| (ch, end)));
+        if let Some((ch, end)) = decoded {
+            out.push(ch);
+            rest = &rest[end + 1..];
+        } else {
+            out.push('&');
+            rest = &rest[1..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+    if let Some(num) = name.strip_prefix('#') {
+        let code = if let Some(hex) = num.strip_prefix('x').or_else(|| num.strip_prefix('X')) {
+            u32::from_str_radix(hex, 16).ok()?
+        } else {
+            num.parse::<u32>().ok()?
+        };
+        return char::from_u32(code);
+    }
+    let ch = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "mdash" => '\u{2014}',
+        "ndash" => '\u{2013}',
+        "lsquo" => '\u{2018}',
+        "rsquo" => '\u{2019}',
+        "ldquo" => '\u{201c}',
+        "rdquo" => '\u{201d}',
+        "hellip" => '\u{2026}',
+        "aelig" => '\u{e6}',
+        "AElig" => '\u{c6}',
+        "oelig" => '\u{153}',
+        "OElig" => '\u{152}',
+        "eacute" => '\u{e9}',
+        "egrave" => '\u{e8}',
+        "agrave" => '\u{e0}',
+        "uuml" => '\u{fc}',
+        "ouml" => '\u{f6}',
+        "auml" => '\u{e4}',
+        "ccedil" => '\u{e7}',
+        "pound" => '\u{a3}',
+        "sect" => '\u{a7}',
+        "para" => '\u{b6}',
+        "copy" => '\u{a9}',
+        "middot" => '\u{b7}',
+        "shy" => '\u{ad}',
+        _ => return None,
+    };
+    Some(ch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAY: &str = r#"<html><head><title>Hamlet</title><style>p { x: y; }</style></head>
+<body>
+<h2><a id="sceneI_8.1"></a><b>ACT I</b></h2>
+<h3><b>SCENE I. Elsinore. A platform
+ before the Castle.</b></h3>
+<p class="drama">
+BARNARDO.<br>
+Who&rsquo;s there?<br>
+</p>
+<!-- a comment -->
+<h3>SCENE II. A room of state.</h3>
+<p>Enter &amp; exit.</p>
+</body></html>"#;
+
+    #[test]
+    fn test_html_to_book_text_extracts_chapters() {
+        let book = html_to_book_text(PLAY);
+        let titles: Vec<&str> = book.chapters.iter().map(|c| c.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            vec![
+                "ACT I",
+                "SCENE I. Elsinore. A platform before the Castle.",
+                "SCENE II. A room of state."
+            ]
+        );
+        assert!(!book.text.contains("Hamlet"));
+        assert!(!book.text.contains("x: y"));
+        assert!(book.text.contains("BARNARDO.\nWho\u{2019}s there?"));
+        assert!(book.chapter_text(2).unwrap().contains("Enter & exit."));
+        assert_eq!(book.chapters[2].end, book.char_len());
+    }
+
+    #[test]
+    fn test_chapter_offsets_point_at_headings() {
+        let book = html_to_book_text(PLAY);
+        for chapter in &book.chapters {
+            let slice = char_slice(&book.text, chapter.start, chapter.end);
+            assert!(slice.starts_with(chapter.title.split(' ').next().unwrap()));
+        }
+        let in_scene = book.chapters[1].start + 5;
+        assert_eq!(book.chapter_at(in_scene).unwrap().index, 1);
+    }
+
+    #[test]
+    fn test_plain_text_fallback_headings() {
+        let html =
+            "<html><body><pre>ACT I.\n\nSome words.\n\nACT II.\nMore words.</pre></body></html>";
+        let book = html_to_book_text(html);
+        assert_eq!(book.chapters.len(), 2);
+        assert_eq!(book.chapters[1].title, "ACT II.");
+        assert!(book.chapter_text(0).unwrap().contains("Some words."));
+    }
+
+    #[test]
+    fn test_decode_entities() {
+        assert_eq!(
+            decode_entities("a &amp; b &#8217; &#x41; &bogus; &"),
+            "a & b \u{2019} A &bogus; &"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cache_keys_by_content_hash() {
+        let cache = BookTextCache::new();
+        cache
+            .insert(1, Some("a".to_string()), Arc::new(html_to_book_text(PLAY)))
+            .await;
+        assert!(cache.get(1, Some("a")).await.is_some());
+        assert!(cache.get(1, Some("b")).await.is_none());
+        assert!(cache.get(2, Some("a")).await.is_none());
+
+        for id in (2..).take(CACHED_BOOKS) {
+            cache.insert(id, None, Arc::default()).await;
+        }
+        assert!(cache.get(1, Some("a")).await.is_none());
+        assert!(cache.get(2, None).await.is_some());
+    }
+}
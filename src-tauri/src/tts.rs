@@ -0,0 +1,125 @@
+//! HTTP client for the local Pocket TTS server
+//!
+//! Both the bundled sidecar and the `uv run` dev fallback started by `pocket.rs`
+//! listen on the same address and expose `/tts`, `/voices` and `/health`.
+
+use crate::wav::{self, WavAudio, WavError};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use thiserror::Error;
+
+pub const POCKET_TTS_URL: &str = "http://127.0.0.1:5123";
+pub const DEFAULT_VOICE: &str = "alba";
+
+#[derive(Debug, Error)]
+pub enum TtsError {
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Invalid audio payload: {0}")]
+    Decode(#[from] data_encoding::DecodeError),
+
+    #[error(transparent)]
+    Wav(#[from] WavError),
+
+    #[error("TTS server error: {0}")]
+    Server(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TtsVoice {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub language: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct VoicesResponse {
+    voices: Vec<TtsVoice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SpeechResponse {
+    audio_base64: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct SpeechRequest<'a> {
+    text: &'a str,
+    speaker: &'a str,
+}
+
+#[derive(Debug, Clone)]
+pub struct TtsClient {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+impl TtsClient {
+    pub fn new() -> Self {
+        Self::with_base_url(POCKET_TTS_URL)
+    }
+
+    pub fn with_base_url(base_url: &str) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_mins(5))
+            .build()
+            .unwrap_or_default();
+        Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub async fn is_healthy(&self) -> bool {
+        self.client
+            .get(format!("{}/health", self.base_url))
+            .timeout(Duration::from_secs(5))
+            .send()
+            .await
+            .is_ok_and(|resp| resp.status().is_success())
+    }
+
+    pub async fn voices(&self) -> Result<Vec<TtsVoice>, TtsError> {
+        let resp = self
+            .client
+            .get(format!("{}/voices", self.base_url))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(resp.json::<VoicesResponse>().await?.voices)
+    }
+
+    /// Narrates `text` with `voice` and returns the decoded WAV segment
+    pub async fn synthesize(&self, text: &str, voice: &str) -> Result<WavAudio, TtsError> {
+        let resp = self
+            .client
+            .post(format!("{}/tts", self.base_url))
+            .json(&SpeechRequest {
+                text,
+                speaker: voice,
+            })
+            .send()
+            .await?;
+        let status = resp.status();
+        let body = resp.json::<SpeechResponse>().await?;
+        if let Some(error) = body.error {
+            return Err(TtsError::Server(error));
+        }
+        let audio = body
+            .audio_base64
+            .ok_or_else(|| TtsError::Server(format!("HTTP {status} without audio")))?;
+        let bytes = data_encoding::BASE64.decode(audio.as_bytes())?;
+        Ok(wav::parse_wav(&bytes)?)
+    }
+}
+
+impl Default for TtsClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -120,6 +120,132 @@ impl fmt::Display for MessageId {
     }
 }
 
+/// TTS lexicon entry ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct LexiconEntryId(i64);
+
+impl LexiconEntryId {
+    pub const fn new(id: i64) -> Self {
+        Self(id)
+    }
+
+    pub const fn get(self) -> i64 {
+        self.0
+    }
+}
+
+impl fmt::Display for LexiconEntryId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LexiconEntryId({})", self.0)
+    }
+}
+
+/// User-defined catalog ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CatalogId(i64);
+
+impl CatalogId {
+    pub const fn new(id: i64) -> Self {
+        Self(id)
+    }
+
+    pub const fn get(self) -> i64 {
+        self.0
+    }
+}
+
+impl fmt::Display for CatalogId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CatalogId({})", self.0)
+    }
+}
+
+/// Library collection ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CollectionId(i64);
+
+impl CollectionId {
+    pub const fn new(id: i64) -> Self {
+        Self(id)
+    }
+
+    pub const fn get(self) -> i64 {
+        self.0
+    }
+}
+
+impl fmt::Display for CollectionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CollectionId({})", self.0)
+    }
+}
+
+/// Reading session ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SessionId(i64);
+
+impl SessionId {
+    pub const fn new(id: i64) -> Self {
+        Self(id)
+    }
+
+    pub const fn get(self) -> i64 {
+        self.0
+    }
+}
+
+impl fmt::Display for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SessionId({})", self.0)
+    }
+}
+
+/// Position history entry ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PositionEntryId(i64);
+
+impl PositionEntryId {
+    pub const fn new(id: i64) -> Self {
+        Self(id)
+    }
+
+    pub const fn get(self) -> i64 {
+        self.0
+    }
+}
+
+impl fmt::Display for PositionEntryId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PositionEntryId({})", self.0)
+    }
+}
+
+/// Bookmark ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct BookmarkId(i64);
+
+impl BookmarkId {
+    pub const fn new(id: i64) -> Self {
+        Self(id)
+    }
+
+    pub const fn get(self) -> i64 {
+        self.0
+    }
+}
+
+impl fmt::Display for BookmarkId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BookmarkId({})", self.0)
+    }
+}
+
 // ============================================================================
 // ROLE ENUM - Make invalid roles unrepresentable
 // ============================================================================
@@ -161,6 +287,184 @@ impl std::str::FromStr for MessageRole {
     }
 }
 
+// ============================================================================
+// READING STATUS ENUM
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadingStatus {
+    ToRead,
+    Reading,
+    Finished,
+    Abandoned,
+}
+
+impl ReadingStatus {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::ToRead => "to_read",
+            Self::Reading => "reading",
+            Self::Finished => "finished",
+            Self::Abandoned => "abandoned",
+        }
+    }
+}
+
+impl fmt::Display for ReadingStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for ReadingStatus {
+    type Err = TypeValidationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "to_read" => Ok(Self::ToRead),
+            "reading" => Ok(Self::Reading),
+            "finished" => Ok(Self::Finished),
+            "abandoned" => Ok(Self::Abandoned),
+            _ => Err(TypeValidationError::InvalidReadingStatus(s.to_string())),
+        }
+    }
+}
+
+// ============================================================================
+// HIGHLIGHT STYLE ENUM
+// ============================================================================
+
+/// How the reader draws a highlight
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HighlightStyle {
+    #[default]
+    Highlight,
+    Underline,
+    Strikethrough,
+}
+
+impl HighlightStyle {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Highlight => "highlight",
+            Self::Underline => "underline",
+            Self::Strikethrough => "strikethrough",
+        }
+    }
+}
+
+impl fmt::Display for HighlightStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for HighlightStyle {
+    type Err = TypeValidationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "highlight" => Ok(Self::Highlight),
+            "underline" => Ok(Self::Underline),
+            "strikethrough" => Ok(Self::Strikethrough),
+            _ => Err(TypeValidationError::InvalidHighlightStyle(s.to_string())),
+        }
+    }
+}
+
+// ============================================================================
+// POSITION CHANGE ENUM
+// ============================================================================
+
+/// How the reader left a position recorded in the history
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PositionChange {
+    /// Read on past it
+    Read,
+    /// Navigated away, e.g. through the table of contents or a citation
+    Jump,
+    /// Restored an earlier position from the history
+    Restore,
+}
+
+impl PositionChange {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Jump => "jump",
+            Self::Restore => "restore",
+        }
+    }
+}
+
+impl fmt::Display for PositionChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for PositionChange {
+    type Err = TypeValidationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read" => Ok(Self::Read),
+            "jump" => Ok(Self::Jump),
+            "restore" => Ok(Self::Restore),
+            _ => Err(TypeValidationError::InvalidPositionChange(s.to_string())),
+        }
+    }
+}
+
+// ============================================================================
+// TRASH KIND ENUM
+// ============================================================================
+
+/// What a trash entry holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrashKind {
+    Book,
+    Highlight,
+    Thread,
+    /// Chat messages trashed by one delete
+    Messages,
+}
+
+impl TrashKind {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Book => "book",
+            Self::Highlight => "highlight",
+            Self::Thread => "thread",
+            Self::Messages => "messages",
+        }
+    }
+}
+
+impl fmt::Display for TrashKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for TrashKind {
+    type Err = TypeValidationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "book" => Ok(Self::Book),
+            "highlight" => Ok(Self::Highlight),
+            "thread" => Ok(Self::Thread),
+            "messages" => Ok(Self::Messages),
+            _ => Err(TypeValidationError::InvalidTrashKind(s.to_string())),
+        }
+    }
+}
+
 // ============================================================================
 // CFI (Canonical Fragment Identifier) - Validated newtype
 // ============================================================================
@@ -264,6 +568,78 @@ impl Url {
     }
 }
 
+// ============================================================================
+// Tag - Normalized free-form book tag
+// ============================================================================
+
+/// A book tag: trimmed, lowercased, inner whitespace collapsed to one space
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Tag(String);
+
+impl Tag {
+    pub const fn new_unchecked(s: String) -> Self {
+        Self(s)
+    }
+
+    pub fn new(s: &str) -> Result<Self, TypeValidationError> {
+        let tag = s
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+        if tag.is_empty() {
+            return Err(TypeValidationError::EmptyTag);
+        }
+        Ok(Self(tag))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+// ============================================================================
+// Color - Validated hex color
+// ============================================================================
+
+/// A `#rrggbb` color; `#rgb` is expanded and hex digits are lowercased
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Color(String);
+
+impl Color {
+    pub const fn new_unchecked(s: String) -> Self {
+        Self(s)
+    }
+
+    pub fn new(s: &str) -> Result<Self, TypeValidationError> {
+        let invalid = || TypeValidationError::InvalidColor(s.to_string());
+        let hex = s.trim().strip_prefix('#').ok_or_else(invalid)?;
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let hex = match hex.len() {
+            3 => hex.chars().flat_map(|c| [c, c]).collect(),
+            6 => hex.to_string(),
+            _ => return Err(invalid()),
+        };
+        Ok(Self(format!("#{}", hex.to_ascii_lowercase())))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
 // ============================================================================
 // Setting Key enum - Make invalid keys unrepresentable
 // ============================================================================
@@ -275,6 +651,20 @@ pub enum SettingKey {
     PocketVoiceId,
     DefaultModel,
     DefaultVoice,
+    TtsNarratorVoice,
+    TtsStageDirections,
+    TtsSpeakerLabels,
+    GutendexOffline,
+    CatalogSource,
+    GutendexBaseUrl,
+    GutenbergMirror,
+    /// Sync endpoint URL or shared folder path
+    SyncRemote,
+    SyncToken,
+    /// Generated on first sync; identifies this device's changes
+    SyncDeviceId,
+    /// Days trashed items are kept before being purged
+    TrashRetentionDays,
     // Add new settings here as enum variants
 }
 
@@ -285,15 +675,45 @@ impl SettingKey {
             Self::PocketVoiceId => "pocket_voice_id",
             Self::DefaultModel => "default_model",
             Self::DefaultVoice => "default_voice",
+            Self::TtsNarratorVoice => "tts_narrator_voice",
+            Self::TtsStageDirections => "tts_stage_directions",
+            Self::TtsSpeakerLabels => "tts_speaker_labels",
+            Self::GutendexOffline => "gutendex_offline",
+            Self::CatalogSource => "catalog_source",
+            Self::GutendexBaseUrl => "gutendex_base_url",
+            Self::GutenbergMirror => "gutenberg_mirror",
+            Self::SyncRemote => "sync_remote",
+            Self::SyncToken => "sync_token",
+            Self::SyncDeviceId => "sync_device_id",
+            Self::TrashRetentionDays => "trash_retention_days",
         }
     }
 
+    /// Credentials and this device's identity, left out of library backups
+    pub const fn is_secret(self) -> bool {
+        matches!(
+            self,
+            Self::OpenaiApiKey | Self::SyncToken | Self::SyncDeviceId
+        )
+    }
+
     pub const fn all() -> &'static [Self] {
         &[
             Self::OpenaiApiKey,
             Self::PocketVoiceId,
             Self::DefaultModel,
             Self::DefaultVoice,
+            Self::TtsNarratorVoice,
+            Self::TtsStageDirections,
+            Self::TtsSpeakerLabels,
+            Self::GutendexOffline,
+            Self::CatalogSource,
+            Self::GutendexBaseUrl,
+            Self::GutenbergMirror,
+            Self::SyncRemote,
+            Self::SyncToken,
+            Self::SyncDeviceId,
+            Self::TrashRetentionDays,
         ]
     }
 }
@@ -313,6 +733,17 @@ impl std::str::FromStr for SettingKey {
             "pocket_voice_id" => Ok(Self::PocketVoiceId),
             "default_model" => Ok(Self::DefaultModel),
             "default_voice" => Ok(Self::DefaultVoice),
+            "tts_narrator_voice" => Ok(Self::TtsNarratorVoice),
+            "tts_stage_directions" => Ok(Self::TtsStageDirections),
+            "tts_speaker_labels" => Ok(Self::TtsSpeakerLabels),
+            "gutendex_offline" => Ok(Self::GutendexOffline),
+            "catalog_source" => Ok(Self::CatalogSource),
+            "gutendex_base_url" => Ok(Self::GutendexBaseUrl),
+            "gutenberg_mirror" => Ok(Self::GutenbergMirror),
+            "sync_remote" => Ok(Self::SyncRemote),
+            "sync_token" => Ok(Self::SyncToken),
+            "sync_device_id" => Ok(Self::SyncDeviceId),
+            "trash_retention_days" => Ok(Self::TrashRetentionDays),
             _ => Err(TypeValidationError::InvalidSettingKey(s.to_string())),
         }
     }
@@ -398,8 +829,32 @@ pub enum TypeValidationError {
     #[error("Invalid URL scheme: {0} (must start with http:// or https://)")]
     InvalidUrlScheme(String),
 
+    #[error("Invalid reading status: {0} (expected: to_read, reading, finished, or abandoned)")]
+    InvalidReadingStatus(String),
+
+    #[error("Empty tag")]
+    EmptyTag,
+
+    #[error("Invalid highlight style: {0} (expected: highlight, underline, or strikethrough)")]
+    InvalidHighlightStyle(String),
+
+    #[error("Invalid color: {0} (expected #rgb or #rrggbb)")]
+    InvalidColor(String),
+
+    #[error("Invalid position change: {0} (expected: read, jump, or restore)")]
+    InvalidPositionChange(String),
+
+    #[error("Invalid chapter index: {0} (the book has {1} chapters)")]
+    InvalidChapterIndex(usize, usize),
+
+    #[error("Invalid trash kind: {0} (expected: book, highlight, thread, or messages)")]
+    InvalidTrashKind(String),
+
     #[error("Invalid setting key: {0}")]
     InvalidSettingKey(String),
+
+    #[error("Invalid setting value: {0}")]
+    InvalidSettingValue(String),
 }
 
 // ============================================================================
@@ -437,6 +892,77 @@ mod tests {
         assert!("invalid".parse::<MessageRole>().is_err());
     }
 
+    #[test]
+    fn test_reading_status_round_trip() {
+        for status in [
+            ReadingStatus::ToRead,
+            ReadingStatus::Reading,
+            ReadingStatus::Finished,
+            ReadingStatus::Abandoned,
+        ] {
+            assert_eq!(status.as_str().parse::<ReadingStatus>().unwrap(), status);
+        }
+        assert!("done".parse::<ReadingStatus>().is_err());
+    }
+
+    #[test]
+    fn test_color_validation() {
+        assert_eq!(Color::new("#FA0").unwrap().as_str(), "#ffaa00");
+        assert_eq!(Color::new(" #1e90FF ").unwrap().as_str(), "#1e90ff");
+        assert!(Color::new("1e90ff").is_err());
+        assert!(Color::new("#12345").is_err());
+        assert!(Color::new("#ggg").is_err());
+    }
+
+    #[test]
+    fn test_highlight_style_round_trip() {
+        for style in [
+            HighlightStyle::Highlight,
+            HighlightStyle::Underline,
+            HighlightStyle::Strikethrough,
+        ] {
+            assert_eq!(style.as_str().parse::<HighlightStyle>().unwrap(), style);
+        }
+        assert!("bold".parse::<HighlightStyle>().is_err());
+    }
+
+    #[test]
+    fn test_position_change_round_trip() {
+        for change in [
+            PositionChange::Read,
+            PositionChange::Jump,
+            PositionChange::Restore,
+        ] {
+            assert_eq!(change.as_str().parse::<PositionChange>().unwrap(), change);
+        }
+        assert!("skip".parse::<PositionChange>().is_err());
+    }
+
+    #[test]
+    fn test_trash_kind_round_trip() {
+        for kind in [
+            TrashKind::Book,
+            TrashKind::Highlight,
+            TrashKind::Thread,
+            TrashKind::Messages,
+        ] {
+            assert_eq!(kind.as_str().parse::<TrashKind>().unwrap(), kind);
+        }
+        assert!("bookmark".parse::<TrashKind>().is_err());
+    }
+
+    #[test]
+    fn test_tag_normalization() {
+        assert_eq!(
+            Tag::new("  Science   Fiction ").unwrap().as_str(),
+            "science fiction"
+        );
+        assert!(matches!(
+            Tag::new(" \t "),
+            Err(TypeValidationError::EmptyTag)
+        ));
+    }
+
     #[test]
     fn test_cfi_validation() {
         assert!(Cfi::new("epubcfi(/6/4[chap01ref]!/4/2)".to_string()).is_ok());
@@ -0,0 +1,253 @@
+//! Library refresh: re-checks downloaded books against their source
+//!
+//! Each book remembers the URL its MOBI came from, the server's `ETag` and
+//! `Last-Modified`, and a SHA-256 of the file. A check sends one conditional
+//! request per book; when a new edition arrives it is re-extracted in place and
+//! highlights whose text moved or vanished are reported, since the reader
+//! anchors highlights by DOM path and offset. Highlights, bookmarks and the
+//! reading position are then re-anchored in the new text (see `anchoring`).
+
+use crate::anchoring;
+use crate::books::{self, BooksError, MobiFetch, Validators};
+use crate::db::postgres::DbError;
+use crate::db::{self, BookSource, Highlight, HighlightFilter};
+use crate::mirror::GutenbergMirror;
+use crate::text::{collapse_whitespace, html_to_book_text};
+use crate::types::{BookId, HighlightId};
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use tauri::{AppHandle, Emitter};
+
+pub const PROGRESS_EVENT: &str = "library-update-progress";
+
+/// How a highlight's quote relates to the new edition's text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnchorStatus {
+    /// Still present, but the text before it changed
+    Moved,
+    /// No longer present anywhere in the book
+    Missing,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StaleHighlight {
+    pub highlight_id: HighlightId,
+    pub text: String,
+    pub status: AnchorStatus,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdatedBook {
+    pub book_id: BookId,
+    pub title: String,
+    /// Highlights that may need re-anchoring
+    pub highlights: Vec<StaleHighlight>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedCheck {
+    pub book_id: BookId,
+    pub title: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UpdateReport {
+    pub checked: usize,
+    pub unchanged: usize,
+    pub updated: Vec<UpdatedBook>,
+    pub failed: Vec<FailedCheck>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct UpdateProgress<'a> {
+    checked: usize,
+    total: usize,
+    current: &'a str,
+}
+
+/// Gutenberg's MOBI link, for books downloaded before sources were recorded
+fn default_source_url(gutenberg_id: i64) -> String {
+    format!("https://www.gutenberg.org/ebooks/{gutenberg_id}.kf8.images")
+}
+
+/// Highlights whose quote no longer follows the same text. Anchors are DOM
+/// paths, so a change anywhere earlier in the book can shift them.
+pub fn stale_highlights(
+    old_html: &str,
+    new_html: &str,
+    highlights: &[Highlight],
+) -> Vec<StaleHighlight> {
+    let old_text = collapse_whitespace(&html_to_book_text(old_html).text);
+    let new_text = collapse_whitespace(&html_to_book_text(new_html).text);
+
+    highlights
+        .iter()
+        .filter_map(|highlight| {
+            let quote = collapse_whitespace(&highlight.text);
+            if quote.is_empty() {
+                return None;
+            }
+            let status = match (old_text.find(&quote), new_text.find(&quote)) {
+                (_, None) => AnchorStatus::Missing,
+                (Some(old), Some(new)) if old_text[..old] == new_text[..new] => return None,
+                _ => AnchorStatus::Moved,
+            };
+            Some(StaleHighlight {
+                highlight_id: highlight.id,
+                text: highlight.text.clone(),
+                status,
+            })
+        })
+        .collect()
+}
+
+/// Checks one book; `Some` when a new edition was stored
+async fn check_book(
+    app_handle: &AppHandle,
+    pool: &Pool<Postgres>,
+    mirror: &GutenbergMirror,
+    source: &BookSource,
+) -> Result<Option<UpdatedBook>, BooksError> {
+    let book_id = source.book_id.get();
+    let gutenberg_id = source.gutenberg_id.get();
+    let url = source
+        .source_url
+        .clone()
+        .unwrap_or_else(|| default_source_url(gutenberg_id));
+    let validators = Validators {
+        etag: source.etag.clone(),
+        last_modified: source.last_modified.clone(),
+    };
+
+    let (bytes, validators) = match books::fetch_mobi(&url, mirror, &validators).await? {
+        MobiFetch::NotModified => {
+            db::touch_book_checked(pool, book_id).await?;
+            return Ok(None);
+        }
+        MobiFetch::Downloaded { bytes, validators } => (bytes, validators),
+    };
+    let hash = books::content_hash(&bytes);
+    let record_source = || {
+        db::set_book_source(
+            pool,
+            book_id,
+            &url,
+            validators.etag.as_deref(),
+            validators.last_modified.as_deref(),
+            &hash,
+        )
+    };
+    let stored_hash = match &source.content_hash {
+        Some(stored) => Some(stored.clone()),
+        // Downloaded before hashes were recorded: hash the stored file
+        None => db::get_book(pool, book_id)
+            .await?
+            .mobi_data
+            .as_deref()
+            .map(books::content_hash),
+    };
+    if stored_hash.as_deref() == Some(hash.as_str()) {
+        record_source().await?;
+        return Ok(None);
+    }
+
+    println!(
+        "[Updates] New edition of book {gutenberg_id} ({})",
+        source.title
+    );
+    let old_html = db::get_book(pool, book_id).await?.html_content;
+    let (html, first_image_index) =
+        books::extract_in_background(app_handle, gutenberg_id, bytes.clone()).await?;
+    db::update_book_content(pool, book_id, &bytes, &html, first_image_index).await?;
+    record_source().await?;
+
+    let highlights = db::list_highlights(pool, book_id, &HighlightFilter::default()).await?;
+    anchoring::reanchor_book(pool, book_id, &html_to_book_text(&html)).await?;
+
+    Ok(Some(UpdatedBook {
+        book_id: source.book_id,
+        title: source.title.clone(),
+        highlights: stale_highlights(old_html.as_deref().unwrap_or_default(), &html, &highlights),
+    }))
+}
+
+/// Checks every library book for a newer edition. Books are checked one at a
+/// time; failures are reported per book rather than aborting the run.
+pub async fn check_for_updates(
+    app_handle: &AppHandle,
+    pool: &Pool<Postgres>,
+    mirror: &GutenbergMirror,
+) -> Result<UpdateReport, DbError> {
+    let sources = db::list_book_sources(pool).await?;
+    let mut report = UpdateReport::default();
+
+    for source in &sources {
+        let _ = app_handle.emit(
+            PROGRESS_EVENT,
+            UpdateProgress {
+                checked: report.checked,
+                total: sources.len(),
+                current: &source.title,
+            },
+        );
+        report.checked += 1;
+        match check_book(app_handle, pool, mirror, source).await {
+            Ok(Some(updated)) => report.updated.push(updated),
+            Ok(None) => report.unchanged += 1,
+            Err(e) => report.failed.push(FailedCheck {
+                book_id: source.book_id,
+                title: source.title.clone(),
+                error: e.to_string(),
+            }),
+        }
+    }
+
+    println!(
+        "[Updates] Checked {} books: {} updated, {} failed",
+        report.checked,
+        report.updated.len(),
+        report.failed.len()
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale_highlights() {
+        let old = "<html><body><p>Preface.</p><p>To be, or not to be.</p>\
+                   <p>Alas, poor Yorick.</p></body></html>";
+        let new = "<html><body><p>A new preface.</p><p>To be, or not to be.</p>\
+                   <p>Alas, poor Yorick!</p></body></html>";
+        let highlights = [
+            Highlight::fixture(1, 1, "To be, or  not to be"),
+            Highlight::fixture(2, 1, "Alas, poor Yorick."),
+            Highlight::fixture(3, 1, "Preface."),
+        ];
+
+        let stale = stale_highlights(old, new, &highlights);
+        let statuses: Vec<(i64, AnchorStatus)> = stale
+            .iter()
+            .map(|s| (s.highlight_id.get(), s.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (1, AnchorStatus::Moved),
+                (2, AnchorStatus::Missing),
+                (3, AnchorStatus::Missing)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unchanged_prefix_keeps_highlight() {
+        let old = "<html><body><p>To be, or not to be.</p><p>Old ending.</p></body></html>";
+        let new = "<html><body><p>To be, or not to be.</p><p>New ending.</p></body></html>";
+        assert!(stale_highlights(old, new, &[Highlight::fixture(1, 1, "or not to be")]).is_empty());
+    }
+}
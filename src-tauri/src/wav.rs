@@ -0,0 +1,397 @@
+//! Minimal RIFF/WAVE support for stitching narrated segments together
+//!
+//! The TTS sidecar returns one PCM WAV per request. `WavWriter` streams those
+//! segments into a single file and records chapter markers as a `cue ` chunk with
+//! matching `LIST`/`adtl` labels, which most audio players and editors understand.
+
+use std::io::{Seek, SeekFrom, Write};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum WavError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid WAV data: {0}")]
+    Invalid(String),
+
+    #[error("Segment format {found:?} does not match output format {expected:?}")]
+    FormatMismatch {
+        expected: WavFormat,
+        found: WavFormat,
+    },
+
+    #[error("Audio exceeds the 4 GiB WAV size limit")]
+    TooLarge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WavFormat {
+    pub audio_format: u16,
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub bits_per_sample: u16,
+}
+
+impl WavFormat {
+    pub const fn block_align(self) -> u16 {
+        self.channels * (self.bits_per_sample / 8)
+    }
+
+    pub const fn byte_rate(self) -> u32 {
+        self.sample_rate * self.block_align() as u32
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WavAudio {
+    pub format: WavFormat,
+    pub data: Vec<u8>,
+}
+
+impl WavAudio {
+    pub fn frames(&self) -> u64 {
+        let align = u64::from(self.format.block_align().max(1));
+        self.data.len() as u64 / align
+    }
+
+    /// Generates `seconds` of silence in the given format
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    pub fn silence(format: WavFormat, seconds: f32) -> Self {
+        let frames = (format.sample_rate as f32 * seconds).max(0.0) as usize;
+        Self {
+            format,
+            data: vec![0; frames * format.block_align() as usize],
+        }
+    }
+}
+
+#[inline]
+fn le_u16(b: &[u8], off: usize) -> Option<u16> {
+    b.get(off..off + 2)
+        .map(|s| u16::from_le_bytes([s[0], s[1]]))
+}
+
+#[inline]
+fn le_u32(b: &[u8], off: usize) -> Option<u32> {
+    b.get(off..off + 4)
+        .map(|s| u32::from_le_bytes([s[0], s[1], s[2], s[3]]))
+}
+
+/// Parses a RIFF/WAVE byte buffer into its format and raw sample data
+pub fn parse_wav(bytes: &[u8]) -> Result<WavAudio, WavError> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(WavError::Invalid("missing RIFF/WAVE header".to_string()));
+    }
+
+    let mut format: Option<WavFormat> = None;
+    let mut data: Option<&[u8]> = None;
+    let mut off = 12;
+    while off + 8 <= bytes.len() {
+        let id = &bytes[off..off + 4];
+        let size = le_u32(bytes, off + 4).unwrap_or(0) as usize;
+        let body_start = off + 8;
+        let body_end = (body_start + size).min(bytes.len());
+        let body = &bytes[body_start..body_end];
+
+        match id {
+            b"fmt " => {
+                let parsed = WavFormat {
+                    audio_format: le_u16(body, 0).unwrap_or(0),
+                    channels: le_u16(body, 2).unwrap_or(0),
+                    sample_rate: le_u32(body, 4).unwrap_or(0),
+                    bits_per_sample: le_u16(body, 14).unwrap_or(0),
+                };
+                if parsed.channels == 0 || parsed.bits_per_sample == 0 {
+                    return Err(WavError::Invalid("malformed fmt chunk".to_string()));
+                }
+                format = Some(parsed);
+            }
+            b"data" => data = Some(body),
+            _ => {}
+        }
+
+        off = body_start + size + (size & 1);
+    }
+
+    let format = format.ok_or_else(|| WavError::Invalid("missing fmt chunk".to_string()))?;
+    let data = data.ok_or_else(|| WavError::Invalid("missing data chunk".to_string()))?;
+    Ok(WavAudio {
+        format,
+        data: data.to_vec(),
+    })
+}
+
+/// A labelled position in the output, expressed in sample frames
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CueMarker {
+    pub frame: u32,
+    pub label: String,
+}
+
+/// Streams WAV segments into a single file, then appends chapter markers
+pub struct WavWriter<W: Write + Seek> {
+    inner: W,
+    format: WavFormat,
+    data_len: u64,
+    markers: Vec<CueMarker>,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut inner: W, format: WavFormat) -> Result<Self, WavError> {
+        inner.write_all(b"RIFF")?;
+        inner.write_all(&0u32.to_le_bytes())?;
+        inner.write_all(b"WAVE")?;
+        inner.write_all(b"fmt ")?;
+        inner.write_all(&16u32.to_le_bytes())?;
+        inner.write_all(&format.audio_format.to_le_bytes())?;
+        inner.write_all(&format.channels.to_le_bytes())?;
+        inner.write_all(&format.sample_rate.to_le_bytes())?;
+        inner.write_all(&format.byte_rate().to_le_bytes())?;
+        inner.write_all(&format.block_align().to_le_bytes())?;
+        inner.write_all(&format.bits_per_sample.to_le_bytes())?;
+        inner.write_all(b"data")?;
+        inner.write_all(&0u32.to_le_bytes())?;
+        Ok(Self {
+            inner,
+            format,
+            data_len: 0,
+            markers: Vec::new(),
+        })
+    }
+
+    pub const fn format(&self) -> WavFormat {
+        self.format
+    }
+
+    /// Current position in sample frames
+    pub fn frames(&self) -> u64 {
+        self.data_len / u64::from(self.format.block_align().max(1))
+    }
+
+    /// Records a chapter marker at the current position
+    pub fn add_marker(&mut self, label: &str) -> Result<(), WavError> {
+        let frame = u32::try_from(self.frames()).map_err(|_| WavError::TooLarge)?;
+        self.markers.push(CueMarker {
+            frame,
+            label: label.to_string(),
+        });
+        Ok(())
+    }
+
+    pub fn append(&mut self, audio: &WavAudio) -> Result<(), WavError> {
+        if audio.format != self.format {
+            return Err(WavError::FormatMismatch {
+                expected: self.format,
+                found: audio.format,
+            });
+        }
+        let align = self.format.block_align().max(1) as usize;
+        let whole = audio.data.len() - audio.data.len() % align;
+        if self.data_len + whole as u64 > u64::from(u32::MAX) - 1024 {
+            return Err(WavError::TooLarge);
+        }
+        self.inner.write_all(&audio.data[..whole])?;
+        self.data_len += whole as u64;
+        Ok(())
+    }
+
+    /// Writes the marker chunks, patches the header sizes and returns the writer
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn finish(mut self) -> Result<(W, Vec<CueMarker>), WavError> {
+        if self.data_len & 1 == 1 {
+            self.inner.write_all(&[0])?;
+        }
+
+        if !self.markers.is_empty() {
+            self.write_cue_chunk()?;
+            self.write_label_list()?;
+        }
+
+        let end = self.inner.stream_position()?;
+        let riff_size = u32::try_from(end - 8).map_err(|_| WavError::TooLarge)?;
+        self.inner.seek(SeekFrom::Start(4))?;
+        self.inner.write_all(&riff_size.to_le_bytes())?;
+        self.inner.seek(SeekFrom::Start(40))?;
+        self.inner
+            .write_all(&(self.data_len as u32).to_le_bytes())?;
+        self.inner.seek(SeekFrom::Start(end))?;
+        self.inner.flush()?;
+        Ok((self.inner, self.markers))
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn write_cue_chunk(&mut self) -> Result<(), WavError> {
+        let count = self.markers.len() as u32;
+        self.inner.write_all(b"cue ")?;
+        self.inner.write_all(&(4 + count * 24).to_le_bytes())?;
+        self.inner.write_all(&count.to_le_bytes())?;
+        for (i, marker) in self.markers.iter().enumerate() {
+            let id = i as u32 + 1;
+            self.inner.write_all(&id.to_le_bytes())?;
+            self.inner.write_all(&marker.frame.to_le_bytes())?;
+            self.inner.write_all(b"data")?;
+            self.inner.write_all(&0u32.to_le_bytes())?;
+            self.inner.write_all(&0u32.to_le_bytes())?;
+            self.inner.write_all(&marker.frame.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn write_label_list(&mut self) -> Result<(), WavError> {
+        let mut body: Vec<u8> = b"adtl".to_vec();
+        for (i, marker) in self.markers.iter().enumerate() {
+            let mut text = marker.label.as_bytes().to_vec();
+            text.push(0);
+            let size = 4 + text.len() as u32;
+            body.extend_from_slice(b"labl");
+            body.extend_from_slice(&size.to_le_bytes());
+            body.extend_from_slice(&(i as u32 + 1).to_le_bytes());
+            body.extend_from_slice(&text);
+            if size & 1 == 1 {
+                body.push(0);
+            }
+        }
+        self.inner.write_all(b"LIST")?;
+        self.inner.write_all(&(body.len() as u32).to_le_bytes())?;
+        self.inner.write_all(&body)?;
+        Ok(())
+    }
+}
+
+/// Reads the `cue `/`labl` markers back out of a WAV file
+pub fn read_markers(bytes: &[u8]) -> Vec<CueMarker> {
+    let mut frames: Vec<(u32, u32)> = Vec::new();
+    let mut labels: std::collections::HashMap<u32, String> = std::collections::HashMap::new();
+    let mut off = 12;
+    while off + 8 <= bytes.len() {
+        let id = &bytes[off..off + 4];
+        let size = le_u32(bytes, off + 4).unwrap_or(0) as usize;
+        let body = &bytes[off + 8..(off + 8 + size).min(bytes.len())];
+        if id == b"cue " {
+            let count = le_u32(body, 0).unwrap_or(0) as usize;
+            for i in 0..count {
+                let base = 4 + i * 24;
+                if let (Some(cue_id), Some(frame)) = (le_u32(body, base), le_u32(body, base + 20)) {
+                    frames.push((cue_id, frame));
+                }
+            }
+        } else if id == b"LIST" && body.starts_with(b"adtl") {
+            let mut sub = 4;
+            while sub + 8 <= body.len() {
+                let sub_size = le_u32(body, sub + 4).unwrap_or(0) as usize;
+                let sub_body = &body[sub + 8..(sub + 8 + sub_size).min(body.len())];
+                if &body[sub..sub + 4] == b"labl" {
+                    if let Some(cue_id) = le_u32(sub_body, 0) {
+                        let text = sub_body.get(4..).unwrap_or_default();
+                        let text = text.split(|&b| b == 0).next().unwrap_or_default();
+                        labels.insert(cue_id, String::from_utf8_lossy(text).to_string());
+                    }
+                }
+                sub += 8 + sub_size + (sub_size & 1);
+            }
+        }
+        off += 8 + size + (size & 1);
+    }
+
+    frames
+        .into_iter()
+        .map(|(cue_id, frame)| CueMarker {
+            frame,
+            label: labels.remove(&cue_id).unwrap_or_default(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const MONO_16: WavFormat = WavFormat {
+        audio_format: 1,
+        channels: 1,
+        sample_rate: 24000,
+        bits_per_sample: 16,
+    };
+
+    fn segment(samples: &[i16]) -> Vec<u8> {
+        let mut writer = WavWriter::new(Cursor::new(Vec::new()), MONO_16).unwrap();
+        let data = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        writer
+            .append(&WavAudio {
+                format: MONO_16,
+                data,
+            })
+            .unwrap();
+        writer.finish().unwrap().0.into_inner()
+    }
+
+    #[test]
+    fn test_parse_roundtrip() {
+        let bytes = segment(&[1, -2, 3]);
+        let audio = parse_wav(&bytes).unwrap();
+        assert_eq!(audio.format, MONO_16);
+        assert_eq!(audio.frames(), 3);
+        assert_eq!(audio.data, vec![1, 0, 254, 255, 3, 0]);
+    }
+
+    #[test]
+    fn test_concat_with_markers() {
+        let first = parse_wav(&segment(&[1, 2, 3, 4])).unwrap();
+        let second = parse_wav(&segment(&[5, 6])).unwrap();
+
+        let mut writer = WavWriter::new(Cursor::new(Vec::new()), MONO_16).unwrap();
+        writer.add_marker("ACT I").unwrap();
+        writer.append(&first).unwrap();
+        writer.add_marker("ACT II").unwrap();
+        writer.append(&second).unwrap();
+        let (cursor, markers) = writer.finish().unwrap();
+        let bytes = cursor.into_inner();
+
+        assert_eq!(markers.len(), 2);
+        let combined = parse_wav(&bytes).unwrap();
+        assert_eq!(combined.frames(), 6);
+        assert_eq!(
+            read_markers(&bytes),
+            vec![
+                CueMarker {
+                    frame: 0,
+                    label: "ACT I".to_string()
+                },
+                CueMarker {
+                    frame: 4,
+                    label: "ACT II".to_string()
+                },
+            ]
+        );
+        let riff_size = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+        assert_eq!(riff_size, bytes.len() - 8);
+    }
+
+    #[test]
+    fn test_format_mismatch_is_rejected() {
+        let mut writer = WavWriter::new(Cursor::new(Vec::new()), MONO_16).unwrap();
+        let stereo = WavAudio::silence(
+            WavFormat {
+                channels: 2,
+                ..MONO_16
+            },
+            0.1,
+        );
+        assert!(matches!(
+            writer.append(&stereo),
+            Err(WavError::FormatMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_rejects_non_wav() {
+        assert!(parse_wav(b"not a wav file").is_err());
+    }
+}
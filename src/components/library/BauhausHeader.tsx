@@ -1,15 +1,9 @@
 import { Loader2, Search, X } from 'lucide-react'
 import { Button } from '@/components/ui/button'
 import { Input } from '@/components/ui/input'
-import type { CatalogEntry } from '../../lib/gutenberg'
+import { CATALOG_GROUPS, type CatalogEntry, DEFAULT_CATALOG_KEY } from '../../lib/gutenberg'
 
-const FEATURED_COLLECTIONS = [
-  { key: 'collection-all', label: 'All Books' },
-  { key: 'collection-shakespeare', label: 'Shakespeare' },
-  { key: 'collection-greek-tragedy', label: 'Greek Tragedy' },
-  { key: 'collection-greek-epic', label: 'Greek Epic' },
-  { key: 'collection-roman-drama', label: 'Roman Drama' },
-]
+const FEATURED_COLLECTION_COUNT = 5
 
 interface BauhausHeaderProps {
   catalogQuery: string
@@ -17,6 +11,7 @@ interface BauhausHeaderProps {
   handleSearch: (q: string) => void
   catalogQ: { isFetching: boolean }
   activeCatalog: CatalogEntry
+  collections?: CatalogEntry[]
   catalogSearch: string
   setCatalogKey: (key: string) => void
   searchInputRef: React.RefObject<HTMLInputElement | null>
@@ -28,6 +23,7 @@ export function BauhausHeader({
   handleSearch,
   catalogQ,
   activeCatalog,
+  collections = CATALOG_GROUPS[0].items,
   catalogSearch,
   setCatalogKey,
   searchInputRef,
@@ -94,7 +90,7 @@ export function BauhausHeader({
 
           {/* Horizontal Collections Menu */}
           <nav className="flex flex-wrap items-center gap-6 border-l-4 border-amber-500 pl-6">
-            {FEATURED_COLLECTIONS.map((fc) => {
+            {collections.slice(0, FEATURED_COLLECTION_COUNT).map((fc) => {
               const isActive = activeCatalog.key === fc.key
               return (
                 <button
@@ -102,7 +98,7 @@ export function BauhausHeader({
                   key={fc.key}
                   onClick={() =>
                     setCatalogKey(
-                      isActive && fc.key !== 'collection-all' ? 'collection-all' : fc.key,
+                      isActive && fc.key !== DEFAULT_CATALOG_KEY ? DEFAULT_CATALOG_KEY : fc.key,
                     )
                   }
                   className={`font-mono text-[10px] font-bold uppercase tracking-[0.2em] transition-[color,text-decoration-color] hover:text-amber-500 ${
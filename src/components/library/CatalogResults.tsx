@@ -166,6 +166,7 @@ export function CatalogResults({
       publicationYear: null,
       coverUrl: cover,
       mobiUrl,
+      languages: selectedBook.languages,
     })
     setPaused(false)
     runQueue()
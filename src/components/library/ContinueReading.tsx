@@ -2,6 +2,7 @@ import { Link } from '@tanstack/react-router'
 import type { Book } from '@/lib/tauri/types'
 import { BookOpen, Sparkles } from 'lucide-react'
 import { ScrollArea } from '@/components/ui/scroll-area'
+import { useBookCovers } from '@/hooks/library/useBookCovers'
 
 interface ContinueReadingProps {
   booksInProgress: Book[]
@@ -9,6 +10,7 @@ interface ContinueReadingProps {
 }
 
 export function ContinueReading({ booksInProgress, progressByBookId }: ContinueReadingProps) {
+  const covers = useBookCovers(booksInProgress)
   if (booksInProgress.length === 0) return null
 
   const count = booksInProgress.length
@@ -31,6 +33,7 @@ export function ContinueReading({ booksInProgress, progressByBookId }: ContinueR
         <div className="flex flex-col gap-8 pb-4">
           {booksInProgress.map((b) => {
             const progress = Math.min(100, progressByBookId.get(b.id) || 0)
+            const cover = covers.get(b.id)
             return (
               <Link
                 key={b.id}
@@ -39,9 +42,9 @@ export function ContinueReading({ booksInProgress, progressByBookId }: ContinueR
                 className="group flex flex-row items-start gap-4 focus-visible:ring-2 focus-visible:ring-amber-500 focus-visible:ring-offset-2 outline-none"
               >
                 <div className="relative h-28 w-20 flex-shrink-0 bg-stone-100 shadow-sm transition-transform group-hover:scale-105 dark:bg-stone-800">
-                  {b.cover_url ? (
+                  {cover ? (
                     <img
-                      src={b.cover_url}
+                      src={cover}
                       alt={b.title}
                       width={80}
                       height={112}
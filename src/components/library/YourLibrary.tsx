@@ -3,6 +3,7 @@ import type { UseQueryResult } from '@tanstack/react-query'
 import type { Book } from '@/lib/tauri/types'
 import { Button } from '@/components/ui/button'
 import { Input } from '@/components/ui/input'
+import { useBookCovers } from '@/hooks/library/useBookCovers'
 import { BookCardMinimal } from './BookCardMinimal'
 import { LibraryEmptyState } from './LibraryEmptyState'
 import { LibraryGrid, LibrarySkeleton } from './LibraryGrid'
@@ -25,6 +26,7 @@ export function YourLibrary({
   deleteBook,
 }: YourLibraryProps) {
   const totalBooks = (booksQ.data ?? []).length
+  const covers = useBookCovers(filteredBooks)
 
   return (
     <section className="space-y-8">
@@ -80,7 +82,7 @@ export function YourLibrary({
               gutenbergId={b.gutenberg_id}
               title={b.title}
               authors={b.authors}
-              coverUrl={b.cover_url}
+              coverUrl={covers.get(b.id) ?? null}
               progress={progressByBookId.get(b.id)}
               isLocal={true}
               onDelete={deleteBook}
@@ -5,6 +5,7 @@ import {
   MessageSquare,
   MessageSquarePlus,
   Trash2,
+  Unlink,
   X,
 } from 'lucide-react'
 import { useState } from 'react'
@@ -126,6 +127,15 @@ export default function HighlightsList({
                     p. {page}
                   </span>
                 )}
+                {highlight.orphaned && (
+                  <span
+                    className="flex items-center gap-1 text-[10px] text-[#E02E2E] font-bold uppercase tracking-wider"
+                    title="This passage wasn't found after the book's text changed"
+                  >
+                    <Unlink className="h-3 w-3" />
+                    Not in text
+                  </span>
+                )}
               </div>
 
               {/* Inline note editing when selected */}
@@ -5,12 +5,13 @@ import { Button } from '@/components/ui/button'
 import { cn } from '@/lib/utils'
 import { CATALOG_GROUPS, type CatalogEntry } from '../../lib/gutenberg'
 
-const QUICK_COLLECTIONS = [
-  { key: 'collection-all', icon: BookOpen, label: 'All Books' },
-  { key: 'collection-shakespeare', icon: Feather, label: 'Shakespeare' },
-  { key: 'collection-greek-tragedy', icon: Globe, label: 'Greek Tragedy' },
-  { key: 'collection-greek-epic', icon: Sparkles, label: 'Greek Epics' },
-]
+const QUICK_COLLECTION_COUNT = 4
+
+const COLLECTION_ICONS: Record<string, typeof BookOpen> = {
+  shakespeare: Feather,
+  'greek-tragedy': Globe,
+  'greek-epic': Sparkles,
+}
 
 const CATEGORY_SHORTCUTS = [
   { key: 'category-literature-adventure', label: 'Adventure' },
@@ -25,9 +26,14 @@ interface CollectionsMenuProps {
   catalogKey: string
   setCatalogKey: (key: string) => void
   activeCatalog?: CatalogEntry
+  collections?: CatalogEntry[]
 }
 
-export const CollectionsMenu: React.FC<CollectionsMenuProps> = ({ catalogKey, setCatalogKey }) => {
+export const CollectionsMenu: React.FC<CollectionsMenuProps> = ({
+  catalogKey,
+  setCatalogKey,
+  collections = CATALOG_GROUPS[0].items,
+}) => {
   const [isExpanded, setIsExpanded] = useState(false)
 
   const handleSelect = (key: string) => {
@@ -39,7 +45,8 @@ export const CollectionsMenu: React.FC<CollectionsMenuProps> = ({ catalogKey, se
     <div className="absolute bottom-6 left-1/2 -translate-x-1/2 z-30 pointer-events-auto">
       {/* Compact pill bar */}
       <div className="flex items-center gap-1 bg-stone-950/80 backdrop-blur-xl rounded-full p-1 border border-white/5">
-        {QUICK_COLLECTIONS.map(({ key, icon: Icon, label }) => {
+        {collections.slice(0, QUICK_COLLECTION_COUNT).map(({ key, label, ...entry }) => {
+          const Icon = COLLECTION_ICONS[entry.catalogKey] ?? BookOpen
           const isActive = catalogKey === key
           return (
             <Button
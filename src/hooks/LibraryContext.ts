@@ -30,6 +30,8 @@ export interface LibraryContextType {
   catalogPageUrl: string | null
   setCatalogPageUrl: (url: string | null) => void
   catalogQ: UseQueryResult<GutendexResponse, Error>
+  /** Saved catalogs, shown as collections */
+  collections: CatalogEntry[]
   activeCatalog: CatalogEntry
   catalogSearch: string
   canQueryCatalog: boolean
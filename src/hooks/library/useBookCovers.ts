@@ -0,0 +1,24 @@
+import { useQueries } from '@tanstack/react-query'
+import { getBookCover } from '@/lib/tauri'
+import type { Book } from '@/lib/tauri/types'
+
+/**
+ * Locally cached cover thumbnails for library books, or null while they load.
+ * Outside Tauri there are no cached covers, so the catalog's cover URL is used.
+ */
+export function useBookCovers(books: Book[]): Map<number, string | null> {
+  const coverQs = useQueries({
+    queries: books.map((book) => ({
+      queryKey: ['bookCover', book.id, 'thumbnail'],
+      queryFn: () => getBookCover(book.id, true),
+      staleTime: Infinity,
+    })),
+  })
+
+  const covers = new Map<number, string | null>()
+  books.forEach((book, i) => {
+    const cover = coverQs[i]?.data
+    covers.set(book.id, cover === null ? book.cover_url : (cover ?? null))
+  })
+  return covers
+}
@@ -2,9 +2,9 @@ import { useQuery } from '@tanstack/react-query'
 import { useNavigate, useRouterState } from '@tanstack/react-router'
 import React from 'react'
 import {
-  CATALOG_BY_KEY,
-  CATALOG_GROUPS,
+  buildCatalogGroups,
   type CatalogEntry,
+  catalogsByKey,
   DEFAULT_CATALOG_KEY,
 } from '@/lib/gutenberg'
 import {
@@ -14,7 +14,7 @@ import {
   type SortOption,
   sortResults,
 } from '@/lib/gutenbergUtils'
-import { gutendexCatalogPage } from '@/lib/tauri'
+import { gutendexCatalogPage, listCatalogs } from '@/lib/tauri'
 
 export function useCatalogSearch() {
   const routerState = useRouterState()
@@ -66,9 +66,13 @@ export function useCatalogSearch() {
     setRecentSearches(getRecentSearches())
   }, [])
 
+  const catalogsQ = useQuery({ queryKey: ['catalogs'], queryFn: listCatalogs })
+  const catalogGroups = React.useMemo(() => buildCatalogGroups(catalogsQ.data), [catalogsQ.data])
+  const collections = catalogGroups[0].items
+
   const activeCatalog = React.useMemo<CatalogEntry>(() => {
-    return CATALOG_BY_KEY.get(catalogKey) ?? CATALOG_GROUPS[0].items[0]!
-  }, [catalogKey])
+    return catalogsByKey(catalogGroups).get(catalogKey) ?? collections[0]!
+  }, [catalogGroups, collections, catalogKey])
 
   const catalogSearch = catalogQuery.trim()
   const catalogTopic = activeCatalog.kind === 'category' ? (activeCatalog.topic ?? null) : null
@@ -149,6 +153,7 @@ export function useCatalogSearch() {
     catalogPageUrl,
     setCatalogPageUrl,
     catalogQ,
+    collections,
     activeCatalog,
     catalogSearch,
     canQueryCatalog,
@@ -11,6 +11,7 @@ export type DownloadTask = {
   publicationYear: number | null
   coverUrl: string | null
   mobiUrl: string
+  languages?: string[]
   status: DownloadStatus
   attempts: number
   error: string | null
@@ -100,6 +101,7 @@ export function useDownloadQueue(localGutenbergIds: Set<number>) {
             publicationYear: next.publicationYear,
             coverUrl: next.coverUrl,
             mobiUrl: next.mobiUrl,
+            languages: next.languages,
           })
 
           await qc.invalidateQueries({ queryKey: ['books'] })
@@ -180,6 +182,7 @@ export function useDownloadQueue(localGutenbergIds: Set<number>) {
               publicationYear: null,
               coverUrl: coverUrl(b),
               mobiUrl,
+              languages: b.languages,
             })
             seen.add(b.id)
             enqueued += 1
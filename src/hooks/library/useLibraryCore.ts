@@ -1,6 +1,6 @@
 import { useQuery, useQueryClient } from '@tanstack/react-query'
 import { useMemo, useState } from 'react'
-import { hardDeleteBook, listBooks } from '@/lib/tauri'
+import { trashBook, listBooks } from '@/lib/tauri'
 
 export function useLibraryCore() {
   const qc = useQueryClient()
@@ -42,7 +42,7 @@ export function useLibraryCore() {
 
   async function deleteBook(id: number) {
     try {
-      await hardDeleteBook(id)
+      await trashBook(id)
       await qc.invalidateQueries({ queryKey: ['books'] })
     } catch (e) {
       const msg = e instanceof Error ? e.message : String(e)
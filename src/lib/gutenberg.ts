@@ -1,3 +1,5 @@
+import type { Catalog } from './tauri/types'
+
 export type CatalogKind = 'all' | 'collection' | 'category'
 
 export type CatalogEntry = {
@@ -22,7 +24,8 @@ function slug(value: string): string {
     .replace(/^-+|-+$/g, '')
 }
 
-const COLLECTIONS: CatalogEntry[] = [
+// Shown until the saved catalogs load, and outside Tauri
+const DEFAULT_COLLECTIONS: CatalogEntry[] = [
   {
     key: 'collection-popular',
     label: 'Most Popular',
@@ -198,13 +201,47 @@ const CATEGORY_CATALOG_GROUPS: CatalogGroup[] = CATEGORY_GROUPS.map((group) => (
   })),
 }))
 
-export const CATALOG_GROUPS: CatalogGroup[] = [
-  { label: 'Collections', items: COLLECTIONS },
-  ...CATEGORY_CATALOG_GROUPS,
-]
+export const DEFAULT_CATALOG_KEY = 'collection-popular'
 
-export const CATALOG_BY_KEY = new Map(
-  CATALOG_GROUPS.flatMap((group) => group.items).map((item) => [item.key, item]),
-)
+function describeCatalog(catalog: Catalog): string {
+  const { search, topic } = catalog.query
+  if (search && topic) return `Books matching "${search}" in ${topic}.`
+  if (search) return `Books matching "${search}".`
+  if (topic) return `Books about ${topic}.`
+  return 'Books on Project Gutenberg.'
+}
 
-export const DEFAULT_CATALOG_KEY = 'collection-popular'
+/**
+ * Collection entries for the saved catalogs. The "all" catalog is always
+ * offered as Most Popular, since categories browse it too.
+ */
+export function catalogCollections(catalogs: Catalog[]): CatalogEntry[] {
+  const defaults = new Map(DEFAULT_COLLECTIONS.map((entry) => [entry.catalogKey, entry]))
+  const popular = defaults.get('all')!
+  return [
+    popular,
+    ...catalogs
+      .filter((catalog) => catalog.key !== popular.catalogKey)
+      .map((catalog) => ({
+        key: `collection-${catalog.key}`,
+        label: catalog.name,
+        description: defaults.get(catalog.key)?.description ?? describeCatalog(catalog),
+        kind: 'collection' as const,
+        catalogKey: catalog.key,
+      })),
+  ]
+}
+
+/** Catalog groups with the saved catalogs as collections, or the defaults */
+export function buildCatalogGroups(catalogs?: Catalog[] | null): CatalogGroup[] {
+  const collections = catalogs?.length ? catalogCollections(catalogs) : DEFAULT_COLLECTIONS
+  return [{ label: 'Collections', items: collections }, ...CATEGORY_CATALOG_GROUPS]
+}
+
+export function catalogsByKey(groups: CatalogGroup[]): Map<string, CatalogEntry> {
+  return new Map(groups.flatMap((group) => group.items).map((item) => [item.key, item]))
+}
+
+export const CATALOG_GROUPS: CatalogGroup[] = buildCatalogGroups()
+
+export const CATALOG_BY_KEY = catalogsByKey(CATALOG_GROUPS)
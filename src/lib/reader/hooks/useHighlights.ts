@@ -4,6 +4,7 @@ import type { PendingHighlight, StagedSnippet } from '@/lib/readerTypes'
 import {
   applyHighlightToRange,
   clearExistingHighlights,
+  findQuoteRange,
   findTextRange,
   resolveNodePath,
 } from '@/lib/readerUtils'
@@ -172,24 +173,48 @@ export function useHighlights(options: UseHighlightsOptions): UseHighlightsResul
       const highlights = highlightsQ.data ?? []
       for (const highlight of highlights as any[]) {
         try {
-          const startPath = JSON.parse(highlight.start_path)
-          const endPath = JSON.parse(highlight.end_path)
-          const startNode = resolveNodePath(root, startPath)
-          const endNode = resolveNodePath(root, endPath)
-          if (!startNode || !endNode) continue
-          const range = doc.createRange()
-          range.setStart(startNode, highlight.start_offset)
-          range.setEnd(endNode, highlight.end_offset)
-          applyHighlightToRange(
-            range,
-            highlight.id,
-            'readerHighlight',
-            highlight.start_offset,
-            highlight.end_offset,
-            startNode,
-            endNode,
-            root,
-          )
+          // Orphaned highlights couldn't be placed in the current text and
+          // only show in the highlights list
+          if (highlight.orphaned) continue
+          // The text selectors are kept up to date when the book's HTML
+          // changes, so prefer them over the DOM path
+          const quoteRange =
+            highlight.text_start != null
+              ? findQuoteRange(
+                  root,
+                  highlight.text,
+                  highlight.quote_prefix ?? '',
+                  highlight.quote_suffix ?? '',
+                  highlight.text_start,
+                )
+              : null
+          if (quoteRange) {
+            applyHighlightToRange(quoteRange, highlight.id)
+          } else if (highlight.start_path) {
+            const startPath = JSON.parse(highlight.start_path)
+            const endPath = JSON.parse(highlight.end_path)
+            const startNode = resolveNodePath(root, startPath)
+            const endNode = resolveNodePath(root, endPath)
+            if (!startNode || !endNode) continue
+            const range = doc.createRange()
+            range.setStart(startNode, highlight.start_offset)
+            range.setEnd(endNode, highlight.end_offset)
+            applyHighlightToRange(
+              range,
+              highlight.id,
+              'readerHighlight',
+              highlight.start_offset,
+              highlight.end_offset,
+              startNode,
+              endNode,
+              root,
+            )
+          } else {
+            // Highlights without selectors or a DOM range only have their text
+            const range = findTextRange(root, highlight.text)
+            if (!range) continue
+            applyHighlightToRange(range, highlight.id)
+          }
           if (activeId === highlight.id) {
             const activeEls = doc.querySelectorAll(
               `span.readerHighlight[data-highlight-id="${highlight.id}"]`,
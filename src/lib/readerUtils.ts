@@ -303,6 +303,79 @@ export function findTextRange(
   return null
 }
 
+/**
+ * Find a highlight's quote from its text selectors. Every occurrence is scored
+ * by how much of the surrounding prefix/suffix matches, then by closeness to
+ * the char offset it was last anchored at.
+ */
+export function findQuoteRange(
+  root: HTMLElement,
+  quote: string,
+  prefix: string,
+  suffix: string,
+  hint?: number | null,
+): Range | null {
+  const normalizedQuote = ultraNormalize(quote)
+  if (!normalizedQuote) return null
+
+  const doc = root.ownerDocument
+  const walker = doc.createTreeWalker(root, NodeFilter.SHOW_TEXT)
+  const streamParts: string[] = []
+  const charMap: Array<{ node: Text; offset: number; fullIndex: number }> = []
+  let fullIndex = 0
+
+  while (walker.nextNode()) {
+    const node = walker.currentNode as Text
+    const nodeText = node.nodeValue || ''
+    for (let i = 0; i < nodeText.length; i++, fullIndex++) {
+      const char = nodeText[i].toLowerCase()
+      if (/[a-z0-9]/.test(char)) {
+        streamParts.push(char)
+        charMap.push({ node, offset: i, fullIndex })
+      }
+    }
+  }
+
+  const stream = streamParts.join('')
+  const normalizedPrefix = ultraNormalize(prefix)
+  const normalizedSuffix = ultraNormalize(suffix)
+  const contextScore = (start: number, end: number) => {
+    let score = 0
+    while (
+      score < normalizedPrefix.length &&
+      stream[start - score - 1] === normalizedPrefix[normalizedPrefix.length - score - 1]
+    ) {
+      score++
+    }
+    let after = 0
+    while (after < normalizedSuffix.length && stream[end + after] === normalizedSuffix[after]) {
+      after++
+    }
+    return score + after
+  }
+
+  let best: { start: number; score: number; distance: number } | null = null
+  for (
+    let start = stream.indexOf(normalizedQuote);
+    start !== -1;
+    start = stream.indexOf(normalizedQuote, start + 1)
+  ) {
+    const score = contextScore(start, start + normalizedQuote.length)
+    const distance = hint == null ? 0 : Math.abs(charMap[start].fullIndex - hint)
+    if (!best || score > best.score || (score === best.score && distance < best.distance)) {
+      best = { start, score, distance }
+    }
+  }
+  if (!best) return null
+
+  const startInfo = charMap[best.start]
+  const endInfo = charMap[best.start + normalizedQuote.length - 1]
+  const range = doc.createRange()
+  range.setStart(startInfo.node, startInfo.offset)
+  range.setEnd(endInfo.node, endInfo.offset + 1)
+  return range
+}
+
 // ============================================================================
 // Content Cleaning
 // ============================================================================
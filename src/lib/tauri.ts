@@ -1,8 +1,13 @@
+export * from './tauri/backup'
+export * from './tauri/bookmarks'
 export * from './tauri/books'
 export * from './tauri/chat'
 export * from './tauri/core'
 export * from './tauri/gutenberg'
 export * from './tauri/highlights'
 export * from './tauri/settings'
+export * from './tauri/sync'
+export * from './tauri/trash'
+export * from './tauri/tts'
 export * from './tauri/types'
 export * from './tauri/webStorage'
@@ -0,0 +1,25 @@
+import { invoke, isTauri } from './core'
+import type { LibraryBackupSummary, LibraryRestoreReport } from './types'
+
+export async function exportLibrary(): Promise<LibraryBackupSummary | null> {
+  if (!isTauri) return null
+  const { save } = await import('@tauri-apps/plugin-dialog')
+  const date = new Date().toISOString().slice(0, 10)
+  const outputPath = await save({
+    defaultPath: `library-${date}.zip`,
+    filters: [{ name: 'Library backup', extensions: ['zip'] }],
+  })
+  if (!outputPath) return null
+  return await invoke('export_library', { outputPath })
+}
+
+export async function importLibrary(): Promise<LibraryRestoreReport | null> {
+  if (!isTauri) return null
+  const { open } = await import('@tauri-apps/plugin-dialog')
+  const path = await open({
+    multiple: false,
+    filters: [{ name: 'Library backup', extensions: ['zip'] }],
+  })
+  if (!path) return null
+  return await invoke('import_library', { path })
+}
@@ -0,0 +1,36 @@
+import { invoke } from './core'
+import type { Bookmark, LocatorInput } from './types'
+
+export async function listBookmarks(bookId: number): Promise<Bookmark[]> {
+  return (await invoke<Bookmark[]>('list_bookmarks', { bookId })) ?? []
+}
+
+export async function createBookmark(params: {
+  bookId: number
+  position: LocatorInput
+  label?: string | null
+  color?: string | null
+}): Promise<Bookmark> {
+  return await invoke('create_bookmark', {
+    bookId: params.bookId,
+    position: params.position,
+    label: params.label ?? null,
+    color: params.color ?? null,
+  })
+}
+
+export async function updateBookmark(params: {
+  bookmarkId: number
+  label?: string | null
+  color?: string | null
+}): Promise<Bookmark> {
+  return await invoke('update_bookmark', {
+    bookmarkId: params.bookmarkId,
+    label: params.label ?? null,
+    color: params.color ?? null,
+  })
+}
+
+export async function deleteBookmark(bookmarkId: number): Promise<void> {
+  await invoke('delete_bookmark', { bookmarkId })
+}
@@ -1,5 +1,22 @@
 import { invoke, isTauri } from './core'
-import type { Book, BookPosition } from './types'
+import type {
+  Book,
+  BookFilter,
+  BookListFilter,
+  BookPage,
+  BookPosition,
+  BookQuerySort,
+  BookSummary,
+  Collection,
+  LibraryUpdateReport,
+  Locator,
+  LocatorInput,
+  PositionEntry,
+  ReadingSession,
+  ReadingStats,
+  ReadingStatus,
+  TagCount,
+} from './types'
 import { getWebBooks, saveWebBooks } from './webStorage'
 
 export async function dbInit(): Promise<void> {
@@ -23,21 +40,66 @@ export async function getBook(bookId: number): Promise<Book> {
   return await invoke('get_book', { bookId })
 }
 
-export async function hardDeleteBook(bookId: number): Promise<void> {
+/** Moves a book to the trash; in the browser build it's removed outright */
+export async function trashBook(bookId: number): Promise<void> {
   if (!isTauri) {
     const books = getWebBooks()
     saveWebBooks(books.filter((x) => x.id !== bookId))
     return
   }
-  await invoke('hard_delete_book', { bookId })
+  await invoke('trash_book', { bookId })
 }
 
 export async function getBookPosition(bookId: number): Promise<BookPosition | null> {
   return await invoke('get_book_position', { bookId })
 }
 
-export async function setBookPosition(params: { bookId: number; cfi: string }): Promise<void> {
-  await invoke('set_book_position', { bookId: params.bookId, cfi: params.cfi })
+export async function setBookPosition(params: { bookId: number; position: LocatorInput }): Promise<Locator> {
+  return await invoke('set_book_position', { bookId: params.bookId, position: params.position })
+}
+
+export async function listPositionHistory(bookId: number): Promise<PositionEntry[]> {
+  return await invoke('list_position_history', { bookId })
+}
+
+export async function restoreBookPosition(entryId: number): Promise<Locator> {
+  return await invoke('restore_book_position', { entryId })
+}
+
+export async function jumpBack(bookId: number): Promise<Locator | null> {
+  return await invoke('jump_back', { bookId })
+}
+
+export async function startReadingSession(params: { bookId: number; position: LocatorInput }): Promise<ReadingSession> {
+  return await invoke('start_reading_session', { bookId: params.bookId, position: params.position })
+}
+
+export async function readingSessionHeartbeat(params: {
+  sessionId: number
+  position: LocatorInput
+}): Promise<ReadingSession> {
+  return await invoke('reading_session_heartbeat', { sessionId: params.sessionId, position: params.position })
+}
+
+export async function endReadingSession(params: {
+  sessionId: number
+  position?: LocatorInput | null
+}): Promise<ReadingSession> {
+  return await invoke('end_reading_session', { sessionId: params.sessionId, position: params.position ?? null })
+}
+
+export async function listReadingSessions(bookId: number, limit?: number): Promise<ReadingSession[]> {
+  return await invoke('list_reading_sessions', { bookId, limit: limit ?? null })
+}
+
+export async function getReadingStats(
+  params: { from?: string | null; to?: string | null; timezone?: string | null } = {},
+): Promise<ReadingStats> {
+  return await invoke('get_reading_stats', {
+    from: params.from ?? null,
+    to: params.to ?? null,
+    timezone: params.timezone ?? Intl.DateTimeFormat().resolvedOptions().timeZone,
+  })
 }
 
 export async function getBookImageData(bookId: number, relativeIndex: number): Promise<string> {
@@ -46,3 +108,90 @@ export async function getBookImageData(bookId: number, relativeIndex: number): P
   }
   return 'data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mP8/5+hHgAHggJ/PchI7wAAAABJRU5ErkJggg=='
 }
+
+export async function getBookCover(bookId: number, thumbnail = false): Promise<string | null> {
+  if (isTauri) {
+    return await invoke('get_book_cover', { bookId, thumbnail })
+  }
+  return null
+}
+
+export async function checkForUpdates(): Promise<LibraryUpdateReport> {
+  return await invoke('check_for_updates')
+}
+
+export async function listBooksFiltered(filter: BookListFilter): Promise<BookSummary[]> {
+  return await invoke('list_books_filtered', { filter })
+}
+
+export async function queryBooks(params: {
+  filter?: BookFilter
+  sort?: BookQuerySort
+  descending?: boolean
+  cursor?: string | null
+  limit?: number
+}): Promise<BookPage> {
+  return await invoke('query_books', {
+    filter: params.filter ?? {},
+    sort: params.sort ?? null,
+    descending: params.descending ?? null,
+    cursor: params.cursor ?? null,
+    limit: params.limit ?? null,
+  })
+}
+
+export async function setBookStatus(bookId: number, status: ReadingStatus | null): Promise<void> {
+  await invoke('set_book_status', { bookId, status })
+}
+
+export async function listCollections(): Promise<Collection[]> {
+  return await invoke('list_collections')
+}
+
+export async function listBookCollections(bookId: number): Promise<Collection[]> {
+  return await invoke('list_book_collections', { bookId })
+}
+
+export async function createCollection(name: string): Promise<Collection> {
+  return await invoke('create_collection', { name })
+}
+
+export async function renameCollection(collectionId: number, name: string): Promise<Collection> {
+  return await invoke('rename_collection', { collectionId, name })
+}
+
+export async function deleteCollection(collectionId: number): Promise<void> {
+  await invoke('delete_collection', { collectionId })
+}
+
+export async function addBookToCollection(collectionId: number, bookId: number): Promise<void> {
+  await invoke('add_book_to_collection', { collectionId, bookId })
+}
+
+export async function removeBookFromCollection(collectionId: number, bookId: number): Promise<void> {
+  await invoke('remove_book_from_collection', { collectionId, bookId })
+}
+
+export async function listTags(): Promise<TagCount[]> {
+  return await invoke('list_tags')
+}
+
+export async function listBookTags(bookId: number): Promise<string[]> {
+  return await invoke('list_book_tags', { bookId })
+}
+
+export async function addBookTag(bookId: number, tag: string): Promise<string> {
+  return await invoke('add_book_tag', { bookId, tag })
+}
+
+export async function removeBookTag(bookId: number, tag: string): Promise<void> {
+  await invoke('remove_book_tag', { bookId, tag })
+}
+
+export async function renameTag(from: string, to: string): Promise<string> {
+  return await invoke('rename_tag', { from, to })
+}
+
+export async function deleteTag(tag: string): Promise<void> {
+  await invoke('delete_tag', { tag })
+}
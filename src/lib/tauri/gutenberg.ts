@@ -1,6 +1,13 @@
 import { invoke as tauriInvoke } from '@tauri-apps/api/core'
 import { invoke, isTauri } from './core'
-import type { Book, GutendexResponse } from './types'
+import type {
+  Book,
+  Catalog,
+  CatalogDownloadRequest,
+  CatalogDownloadSummary,
+  CatalogQuery,
+  GutendexResponse,
+} from './types'
 import { getWebBooks, saveWebBooks } from './webStorage'
 
 export async function gutendexShakespearePage(pageUrl?: string | null): Promise<GutendexResponse> {
@@ -9,11 +16,40 @@ export async function gutendexShakespearePage(pageUrl?: string | null): Promise<
   })
 }
 
+export async function listCatalogs(): Promise<Catalog[]> {
+  return (await invoke<Catalog[]>('list_catalogs')) ?? []
+}
+
+export async function createCatalog(params: {
+  name: string
+  key?: string | null
+  query: CatalogQuery
+}): Promise<Catalog> {
+  return await invoke('create_catalog', {
+    name: params.name,
+    key: params.key ?? null,
+    query: params.query,
+  })
+}
+
+export async function updateCatalog(params: {
+  catalogId: number
+  name: string
+  query: CatalogQuery
+}): Promise<Catalog> {
+  return await invoke('update_catalog', params)
+}
+
+export async function deleteCatalog(catalogId: number): Promise<void> {
+  await invoke('delete_catalog', { catalogId })
+}
+
 export async function gutendexCatalogPage(params: {
   catalogKey: string
   pageUrl?: string | null
   searchQuery?: string | null
   topic?: string | null
+  query?: CatalogQuery | null
 }): Promise<GutendexResponse> {
   if (isTauri) {
     return await tauriInvoke('gutendex_catalog_page', {
@@ -21,6 +57,7 @@ export async function gutendexCatalogPage(params: {
       pageUrl: params.pageUrl ?? null,
       searchQuery: params.searchQuery ?? null,
       topic: params.topic ?? null,
+      query: params.query ?? null,
     })
   }
 
@@ -73,6 +110,7 @@ export async function downloadGutenbergMobi(params: {
   publicationYear: number | null
   coverUrl: string | null
   mobiUrl: string
+  languages?: string[]
 }): Promise<number> {
   if (isTauri) {
     return await tauriInvoke('download_gutenberg_mobi', {
@@ -82,6 +120,7 @@ export async function downloadGutenbergMobi(params: {
       publicationYear: params.publicationYear,
       coverUrl: params.coverUrl,
       mobiUrl: params.mobiUrl,
+      languages: params.languages ?? null,
     })
   }
 
@@ -110,6 +149,16 @@ export async function downloadGutenbergMobi(params: {
   return newId
 }
 
+export async function downloadCatalog(
+  request: CatalogDownloadRequest,
+): Promise<CatalogDownloadSummary> {
+  return await invoke('download_catalog', { request })
+}
+
+export async function cancelCatalogDownload(): Promise<boolean> {
+  return await invoke('cancel_catalog_download')
+}
+
 export async function getBookHtml(bookId: number): Promise<string> {
   if (isTauri) {
     return await invoke('get_book_html', { bookId })
@@ -1,8 +1,89 @@
-import { invoke } from './core'
-import type { Highlight, HighlightMessage } from './types'
+import { invoke, isTauri } from './core'
+import type {
+  AnnotationImportReport,
+  AnnotationSource,
+  Highlight,
+  HighlightExportFormat,
+  HighlightExportSummary,
+  HighlightFilter,
+  HighlightMessage,
+  HighlightSort,
+  HighlightStyle,
+  HighlightTagCount,
+  LibraryHighlight,
+  ReanchorReport,
+} from './types'
 
-export async function listHighlights(bookId: number): Promise<Highlight[]> {
-  return (await invoke<Highlight[]>('list_highlights', { bookId })) ?? []
+export async function listHighlights(bookId: number, filter?: HighlightFilter): Promise<Highlight[]> {
+  return (await invoke<Highlight[]>('list_highlights', { bookId, filter: filter ?? null })) ?? []
+}
+
+export async function listAllHighlights(
+  params: {
+    filter?: HighlightFilter
+    sort?: HighlightSort
+    descending?: boolean
+    limit?: number
+    offset?: number
+  } = {},
+): Promise<LibraryHighlight[]> {
+  return (
+    (await invoke<LibraryHighlight[]>('list_all_highlights', {
+      filter: params.filter ?? null,
+      sort: params.sort ?? null,
+      descending: params.descending ?? null,
+      limit: params.limit ?? null,
+      offset: params.offset ?? null,
+    })) ?? []
+  )
+}
+
+const EXPORT_EXTENSIONS: Record<HighlightExportFormat, { name: string; extension: string }> = {
+  markdown: { name: 'Markdown', extension: 'md' },
+  json: { name: 'JSON', extension: 'json' },
+  csv: { name: 'CSV', extension: 'csv' },
+  anki: { name: 'Anki deck (text)', extension: 'txt' },
+}
+
+export async function exportHighlights(params: {
+  format: HighlightExportFormat
+  bookId?: number | null
+  filter?: HighlightFilter
+  defaultName?: string
+}): Promise<HighlightExportSummary | null> {
+  if (!isTauri) return null
+  const { save } = await import('@tauri-apps/plugin-dialog')
+  const { name, extension } = EXPORT_EXTENSIONS[params.format]
+  const outputPath = await save({
+    defaultPath: `${params.defaultName ?? 'highlights'}.${extension}`,
+    filters: [{ name, extensions: [extension] }],
+  })
+  if (!outputPath) return null
+
+  return await invoke('export_highlights', {
+    bookId: params.bookId ?? null,
+    filter: params.filter ?? null,
+    format: params.format,
+    outputPath,
+  })
+}
+
+export async function importAnnotations(
+  params: { source?: AnnotationSource; bookId?: number | null } = {},
+): Promise<AnnotationImportReport | null> {
+  if (!isTauri) return null
+  const { open } = await import('@tauri-apps/plugin-dialog')
+  const path = await open({
+    multiple: false,
+    filters: [{ name: 'Kindle clippings or CSV', extensions: ['txt', 'csv'] }],
+  })
+  if (!path) return null
+
+  return await invoke('import_annotations', {
+    path,
+    source: params.source ?? null,
+    bookId: params.bookId ?? null,
+  })
 }
 
 export async function createHighlight(params: {
@@ -13,6 +94,11 @@ export async function createHighlight(params: {
   endOffset: number
   text: string
   note?: string | null
+  color?: string | null
+  style?: HighlightStyle | null
+  tags?: string[] | null
+  quotePrefix?: string | null
+  quoteSuffix?: string | null
 }): Promise<Highlight> {
   return await invoke('create_highlight', {
     bookId: params.bookId,
@@ -22,6 +108,11 @@ export async function createHighlight(params: {
     endOffset: params.endOffset,
     text: params.text,
     note: params.note ?? null,
+    color: params.color ?? null,
+    style: params.style ?? null,
+    tags: params.tags ?? null,
+    quotePrefix: params.quotePrefix ?? null,
+    quoteSuffix: params.quoteSuffix ?? null,
   })
 }
 
@@ -35,6 +126,34 @@ export async function updateHighlightNote(params: {
   })
 }
 
+export async function setHighlightStyle(params: {
+  highlightId: number
+  color?: string | null
+  style?: HighlightStyle | null
+}): Promise<Highlight> {
+  return await invoke('set_highlight_style', {
+    highlightId: params.highlightId,
+    color: params.color ?? null,
+    style: params.style ?? null,
+  })
+}
+
+export async function listHighlightTags(): Promise<HighlightTagCount[]> {
+  return (await invoke<HighlightTagCount[]>('list_highlight_tags')) ?? []
+}
+
+export async function addHighlightTag(highlightId: number, tag: string): Promise<Highlight> {
+  return await invoke('add_highlight_tag', { highlightId, tag })
+}
+
+export async function removeHighlightTag(highlightId: number, tag: string): Promise<Highlight> {
+  return await invoke('remove_highlight_tag', { highlightId, tag })
+}
+
+export async function reanchorHighlights(bookId: number): Promise<ReanchorReport> {
+  return await invoke('reanchor_highlights', { bookId })
+}
+
 export async function deleteHighlight(highlightId: number): Promise<void> {
   await invoke('delete_highlight', { highlightId })
 }
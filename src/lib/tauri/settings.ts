@@ -36,7 +36,7 @@ export async function getSetting(key: string): Promise<string | null> {
   return null
 }
 
-export type SidecarStatus = 'stopped' | 'starting' | 'running' | 'errored'
+export type SidecarStatus = 'stopped' | 'starting' | 'running' | 'stopping' | 'errored'
 
 export async function startPocketSidecar(): Promise<SidecarStatus> {
   return await invoke<SidecarStatus>('start_pocket_sidecar')
@@ -0,0 +1,7 @@
+import { invoke, isTauri } from './core'
+import type { SyncReport } from './types'
+
+export async function syncNow(): Promise<SyncReport | null> {
+  if (!isTauri) return null
+  return await invoke('sync_now')
+}
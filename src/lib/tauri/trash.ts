@@ -0,0 +1,19 @@
+import { invoke, isTauri } from './core'
+import type { PurgedTrash, TrashItem, TrashKind } from './types'
+
+export async function listTrash(): Promise<TrashItem[]> {
+  if (!isTauri) return []
+  return await invoke('list_trash')
+}
+
+export async function restoreFromTrash(kind: TrashKind, id: number): Promise<void> {
+  await invoke('restore_from_trash', { kind, id })
+}
+
+export async function purgeFromTrash(kind: TrashKind, id: number): Promise<PurgedTrash> {
+  return await invoke('purge_from_trash', { kind, id })
+}
+
+export async function emptyTrash(): Promise<PurgedTrash> {
+  return await invoke('empty_trash')
+}
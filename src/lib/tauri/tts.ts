@@ -0,0 +1,30 @@
+import { invoke, isTauri } from './core'
+import type { LexiconEntry, SpeechPart } from './types'
+
+export async function normalizeTtsText(text: string): Promise<SpeechPart[] | null> {
+  if (!isTauri) return null
+  return await invoke('normalize_tts_text', { text })
+}
+
+export async function listTtsLexicon(): Promise<LexiconEntry[]> {
+  return (await invoke<LexiconEntry[]>('list_tts_lexicon')) ?? []
+}
+
+export async function createTtsLexiconEntry(params: {
+  term: string
+  replacement: string
+}): Promise<LexiconEntry> {
+  return await invoke('create_tts_lexicon_entry', params)
+}
+
+export async function updateTtsLexiconEntry(params: {
+  entryId: number
+  term: string
+  replacement: string
+}): Promise<LexiconEntry> {
+  return await invoke('update_tts_lexicon_entry', params)
+}
+
+export async function deleteTtsLexiconEntry(entryId: number): Promise<void> {
+  await invoke('delete_tts_lexicon_entry', { entryId })
+}
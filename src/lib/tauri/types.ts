@@ -10,13 +10,154 @@ export type Book = {
   html_content?: string | null
   first_image_index: number | null
   created_at: string
+  status?: ReadingStatus | null
+  status_updated_at?: string | null
+  started_at?: string | null
+  finished_at?: string | null
+  last_opened_at?: string | null
 }
 
-export type BookPosition = {
-  cfi: string
+export type ReadingStatus = 'to_read' | 'reading' | 'finished' | 'abandoned'
+
+export type Collection = {
+  id: number
+  name: string
+  position: number
+  book_count: number
+  created_at: string
   updated_at: string
 }
 
+export type TagCount = {
+  tag: string
+  book_count: number
+}
+
+export type BookFilter = {
+  author?: string | null
+  title?: string | null
+  year_from?: number | null
+  year_to?: number | null
+  language?: string | null
+  has_highlights?: boolean | null
+  has_chats?: boolean | null
+  status?: ReadingStatus | null
+}
+
+export type BookQuerySort = 'title' | 'author' | 'publication_year' | 'date_added' | 'last_read'
+
+export type BookSummary = {
+  id: number
+  gutenberg_id: number
+  title: string
+  authors: string
+  publication_year: number | null
+  cover_url: string | null
+  languages: string[]
+  status: ReadingStatus | null
+  created_at: string
+  last_read_at: string | null
+  highlight_count: number
+  thread_count: number
+  progression: number | null
+  chapter_title: string | null
+}
+
+export type BookPage = {
+  books: BookSummary[]
+  next_cursor: string | null
+}
+
+export type BookSort = 'title' | 'author' | 'date_added' | 'last_opened' | 'status'
+
+export type BookListFilter = {
+  collection_id?: number | null
+  tag?: string | null
+  status?: ReadingStatus | null
+  author?: string | null
+  sort?: BookSort
+  descending?: boolean
+}
+
+export type LocatorInput = {
+  chapter_index?: number | null
+  chapter_offset?: number | null
+  progression?: number | null
+  quote?: string | null
+  cfi?: string | null
+}
+
+export type Locator = {
+  chapter_index: number | null
+  chapter_title: string | null
+  chapter_offset: number
+  progression: number
+  quote: string
+  cfi: string | null
+}
+
+export type BookPosition = Locator & {
+  updated_at: string
+}
+
+export type Bookmark = Locator & {
+  id: number
+  book_id: number
+  label: string | null
+  color: string | null
+  created_at: string
+  updated_at: string
+}
+
+export type PositionChange = 'read' | 'jump' | 'restore'
+
+export type PositionEntry = Locator & {
+  id: number
+  book_id: number
+  change: PositionChange
+  created_at: string
+}
+
+export type ReadingSession = {
+  id: number
+  book_id: number
+  started_at: string
+  ended_at: string
+  start_locator: Locator
+  end_locator: Locator
+  words_read: number
+  duration_secs: number
+  ended: boolean
+}
+
+export type ReadingDay = {
+  day: string
+  seconds: number
+  words: number
+  words_per_minute: number | null
+  book_count: number
+}
+
+export type ReadingStats = {
+  days: ReadingDay[]
+  total_seconds: number
+  total_words: number
+  words_per_minute: number | null
+  books_finished: { book_id: number; title: string; finished_on: string }[]
+  streaks: { current: number; longest: number }
+}
+
+export type LibraryUpdateReport = {
+  checked: number
+  unchanged: number
+  updated: {
+    book_id: number
+    title: string
+    highlights: { highlight_id: number; text: string; status: 'moved' | 'missing' }[]
+  }[]
+  failed: { book_id: number; title: string; error: string }[]
+}
+
 export type GutendexAuthor = {
   name: string
   birth_year: number | null
@@ -27,6 +168,7 @@ export type GutendexBook = {
   id: number
   title: string
   authors: GutendexAuthor[]
+  translators?: GutendexAuthor[]
   copyright: boolean | null
   formats: Record<string, string>
   download_count?: number
@@ -34,6 +176,51 @@ export type GutendexBook = {
   subjects?: string[]
   bookshelves?: string[]
   languages?: string[]
+  media_type?: string | null
+}
+
+export type CatalogSort = 'popular' | 'ascending' | 'descending'
+
+export type CatalogQuery = {
+  search?: string | null
+  topic?: string | null
+  languages?: string[]
+  author_year_start?: number | null
+  author_year_end?: number | null
+  copyright?: boolean | null
+  ids?: number[]
+  mime_type?: string | null
+  sort?: CatalogSort | null
+}
+
+export type Catalog = {
+  id: number
+  key: string
+  name: string
+  query: CatalogQuery
+  position: number
+  created_at: string
+  updated_at: string
+}
+
+export type CatalogDownloadRequest = {
+  catalog_key: string
+  query?: CatalogQuery
+  languages?: string[]
+  limit?: number | null
+}
+
+export type CatalogDownloadSummary = {
+  scanned: number
+  added: { gutenberg_id: number; book_id: number; title: string }[]
+  skipped: {
+    gutenberg_id: number
+    title: string
+    reason: 'in_library' | 'language' | 'no_mobi'
+  }[]
+  failed: { gutenberg_id: number; title: string; error: string }[]
+  cancelled: boolean
+  error: string | null
 }
 
 export type GutendexResponse = {
@@ -58,7 +245,67 @@ export type Highlight = {
   text: string
   note: string | null
   created_at: string
-  updated_at: string
+  updated_at: string  color?: string | null
+  style?: HighlightStyle
+  tags?: string[]
+  quote_prefix?: string
+  quote_suffix?: string
+  text_start?: number | null
+  text_end?: number | null
+  orphaned?: boolean
+}
+
+export type ReanchorReport = {
+  anchored: number
+  moved: number[]
+  orphaned: number[]
+}
+
+export type HighlightStyle = 'highlight' | 'underline' | 'strikethrough'
+
+export type HighlightFilter = {
+  color?: string | null
+  tag?: string | null
+  style?: HighlightStyle | null
+}
+
+export type HighlightSort = 'date' | 'book'
+
+export type LibraryHighlight = Highlight & {
+  book_title: string
+  book_authors: string
+}
+
+export type HighlightExportFormat = 'markdown' | 'json' | 'csv' | 'anki'
+
+export type HighlightExportSummary = {
+  path: string
+  books: number
+  highlights: number
+}
+
+export type AnnotationSource = 'kindle' | 'csv'
+
+export type UnmatchedClipping = {
+  title: string
+  authors: string
+  text: string
+  note: string | null
+  location: number | null
+  tags: string[]
+  color: string | null
+  reason: 'no_quote' | 'book_not_found' | 'text_unavailable' | 'quote_not_found'
+}
+
+export type AnnotationImportReport = {
+  imported: number
+  duplicates: number
+  unmatched: UnmatchedClipping[]
+}
+
+export type HighlightTagCount = {
+  tag: string
+  highlight_count: number
 }
 
 export type HighlightMessage = {
@@ -98,3 +345,74 @@ export type ChatResult = {
   content: string
   reasoning_summary: string | null
 }
+
+export type LibraryBackupSummary = {
+  path: string
+  books: number
+  highlights: number
+}
+
+export type LibraryRestoreReport = {
+  books_added: number
+  books_merged: number
+  highlights_added: number
+  settings_added: number
+}
+
+export type LexiconEntry = {
+  id: number
+  term: string
+  replacement: string
+  created_at: string
+  updated_at: string
+}
+
+export type SpeechPart = {
+  kind: 'speech' | 'stage_direction'
+  text: string
+}
+
+export type SyncEntity =
+  | 'position'
+  | 'bookmark'
+  | 'highlight'
+  | 'highlight_message'
+  | 'thread'
+  | 'message'
+
+/** Both devices changed the same thing; `field` is null when one side deleted the record */
+export type SyncConflict = {
+  entity: SyncEntity
+  key: string
+  field: string | null
+  local: unknown
+  remote: unknown
+  kept: 'local' | 'remote'
+}
+
+export type SyncReport = {
+  pulled: number
+  applied: number
+  skipped: number
+  pushed: number
+  conflicts: SyncConflict[]
+}
+
+export type TrashKind = 'book' | 'highlight' | 'thread' | 'messages'
+
+export type TrashItem = {
+  kind: TrashKind
+  /** Book, highlight or thread id; the trash batch for messages */
+  id: number
+  book_id: number
+  book_title: string
+  label: string
+  deleted_at: string
+}
+
+export type PurgedTrash = {
+  books: number
+  highlights: number
+  threads: number
+  messages: number
+}
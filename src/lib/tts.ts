@@ -1,5 +1,6 @@
 import { pocketTTSService } from './pocket-tts'
 import { getSetting } from './tauri/settings'
+import { normalizeTtsText } from './tauri/tts'
 
 export interface Voice {
   voice_id: string
@@ -45,6 +46,19 @@ export function estimateWordTimings(text: string, duration: number): WordTiming[
   return timings
 }
 
+/**
+ * Applies the pronunciation lexicon and narration settings to text before it
+ * is spoken, falling back to the text as is outside Tauri.
+ */
+export async function prepareSpeechText(text: string): Promise<string> {
+  const parts = await normalizeTtsText(text)
+  if (!parts) return text
+  return parts
+    .map((part) => part.text.trim())
+    .filter((part) => part.length > 0)
+    .join(' ')
+}
+
 export type PlaybackState = 'idle' | 'playing' | 'paused' | 'buffering' | 'error'
 export type EndReason = 'ended' | 'stopped' | 'replaced' | 'error' | 'unknown'
 
@@ -212,8 +226,9 @@ export class AudioPlayer {
         console.log(`[AudioPlayer] No voiceId found, falling back to: ${finalVoiceId}`)
       }
 
+      const speechText = await prepareSpeechText(text)
       console.log(`[AudioPlayer] Calling Pocket TTS service with voiceId: ${finalVoiceId}`)
-      const response = await pocketTTSService.textToSpeech(text, finalVoiceId)
+      const response = await pocketTTSService.textToSpeech(speechText, finalVoiceId)
 
       const binaryString = atob(response.audio_base64)
       const bytes = new Uint8Array(binaryString.length)
@@ -29,6 +29,7 @@ export default function LibraryPage() {
     setPaused,
     bulkScan,
     catalogQ,
+    collections,
     activeCatalog,
     catalogSearch,
     canQueryCatalog,
@@ -64,6 +65,7 @@ export default function LibraryPage() {
           handleSearch={handleSearch}
           catalogQ={catalogQ}
           activeCatalog={activeCatalog}
+          collections={collections}
           catalogSearch={catalogSearch}
           setCatalogKey={setCatalogKey}
           searchInputRef={searchInputRef}
@@ -368,6 +368,7 @@ const ThreeDLibraryPage: React.FC = () => {
     catalogKey,
     setCatalogKey,
     activeCatalog,
+    collections,
     localGutenbergIds,
     progressByBookId,
     enqueue,
@@ -952,6 +953,7 @@ const ThreeDLibraryPage: React.FC = () => {
           catalogKey={catalogKey}
           setCatalogKey={setCatalogKey}
           activeCatalog={activeCatalog}
+          collections={collections}
         />
       </div>
 
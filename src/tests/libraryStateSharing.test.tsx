@@ -26,7 +26,7 @@ describe('Library State Sharing', () => {
     spies.push(
       spyOn(tauri, 'gutendexCatalogPage').mockResolvedValue({ results: [], count: 0 } as any),
     )
-    spies.push(spyOn(tauri, 'hardDeleteBook').mockResolvedValue(undefined as any))
+    spies.push(spyOn(tauri, 'trashBook').mockResolvedValue(undefined as any))
     spies.push(spyOn(tauri, 'downloadGutenbergMobi').mockResolvedValue(undefined as any))
     spies.push(spyOn(tauri, 'dbInit').mockResolvedValue(undefined as any))
   })